@@ -0,0 +1,23 @@
+//! throws whatever at the repository url parser, which gets every package's
+//! `repository.url` from the registry, as it is. see the readme for running it.
+//! what it finds goes in `fuzz/corpus/repo_url/`, and the urls it starts from
+//! are in `fuzz/seeds/repo_url/`
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../src/repo_url.rs"]
+mod repo_url;
+
+fuzz_target!(|url: &str| {
+	if let Some((host, owner, name)) = repo_url::parse(url) {
+		// what the rest of the bot counts on, for making api urls and keys from them
+		assert!(!host.is_empty() && !owner.is_empty() && !name.is_empty(), "{url:?} parsed to an empty part");
+		assert!(!name.contains('/'), "{url:?} parsed to a name with a / in it");
+		// only gitlab has subgroups
+		if host == "github.com" || host == "www.github.com" {
+			assert!(!owner.contains('/'), "{url:?} parsed to a github owner with a / in it");
+		}
+	}
+});
@@ -0,0 +1,52 @@
+//! a plain text file of everything the bot's done to get in touch with
+//! maintainers (issues, emails, follow-ups, and closing issues), and what they
+//! answered, a line each, oldest first, like `2022-11-03 14:02 filed issue
+//! https://github.com/... for package x`. it's only ever added to, so it's a
+//! record of every bit of outreach there's ever been, for grepping, whatever
+//! happens to the database or the logs. times are utc. it's `log.activity`,
+//! which is `<db_path>.activity.log` unless it's set
+
+use crate::db::DatabaseThing;
+use chrono::Utc;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Mutex;
+
+lazy_static! {
+	/// where it's written, see [`init`]
+	static ref PATH: Mutex<Option<String>> = Mutex::new(None);
+}
+
+/// writes whatever's [`record`]ed from here on to `path`, if there is one
+pub fn init(path: Option<String>) {
+	*PATH.lock().unwrap() = path;
+}
+
+/// adds a line saying what was done to the file, unless `db` is read only
+/// (for dry runs), since then nothing was really done
+pub fn record(db: &DatabaseThing, what: &str) {
+	if db.is_read_only() { return }
+	let path = PATH.lock().unwrap();
+	let path = match &*path {
+		Some(path) => { path }
+		None => { return }
+	};
+
+	let line = format!("{} {}\n", Utc::now().format("%Y-%m-%d %H:%M"), what.replace('\n', " "));
+	let written = OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)
+		.and_then(|mut file| file.write_all(line.as_bytes()));
+	if let Err(e) = written {
+		warn!("error writing to the activity log {path}: {e}");
+	}
+}
+
+/// `package x`, or `packages x, y`
+pub fn packages(names: &[String]) -> String {
+	match names.len() {
+		1 => { format!("package {}", names[0]) }
+		_ => { format!("packages {}", names.join(", ")) }
+	}
+}
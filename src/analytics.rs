@@ -0,0 +1,469 @@
+//! how maintainers answer: approval, decline, and no response rates for every
+//! package that's been asked (by issue or email), overall and by download
+//! count, how long ago they were asked, and whether a follow-up was posted, to
+//! go by when deciding what to do about the ones that never answer. and how
+//! long it takes maintainers to first reply to an issue, for deciding how long
+//! to wait before following up. and what the last few runs did with the
+//! packages they went through, and how long that took. with `template_variants`,
+//! the rates for each of them too, to see which wording works best. and, for
+//! every package (asked or not, and the ones without a repository), how much of
+//! the registry's downloads are covered by maintainers saying yes so far
+
+use crate::db::{ DatabaseThing, Package, PackageState };
+use crate::forge::RepoLocation;
+use crate::runs::{ Action, RunReport };
+use crate::status::{ self, OutputFormat };
+use crate::Result;
+use chrono::{ DateTime, Duration, Utc };
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// where asking a package's maintainer got to
+#[derive(Clone, Copy)]
+enum Outcome {
+	Approved,
+	Declined,
+	/// someone answered on the issue, but hasn't said yes or no
+	Replied,
+	NoResponse
+}
+
+/// a package whose maintainer was asked
+struct Asked {
+	downloads: u32,
+	asked_at: DateTime<Utc>,
+	followed_up: bool,
+	outcome: Outcome,
+	/// whether they were asked with an issue, rather than by email
+	by_issue: bool,
+	/// how long after being asked the first reply on the issue was
+	replied_after: Option<Duration>,
+	/// which of `template_variants` the issue was from
+	template_variant: Option<String>
+}
+
+/// how many packages in a bucket ended up where
+#[derive(Default, Serialize)]
+struct Counts {
+	packages: usize,
+	approved: usize,
+	declined: usize,
+	replied: usize,
+	no_response: usize
+}
+
+impl Counts {
+	fn add(&mut self, outcome: Outcome) {
+		self.packages += 1;
+		match outcome {
+			Outcome::Approved => { self.approved += 1 }
+			Outcome::Declined => { self.declined += 1 }
+			Outcome::Replied => { self.replied += 1 }
+			Outcome::NoResponse => { self.no_response += 1 }
+		}
+	}
+
+	fn rate(&self, count: usize) -> String {
+		format!("{:.0}%", count as f64 * 100.0 / self.packages as f64)
+	}
+}
+
+/// where a package got to, whether it was asked or not
+#[derive(Clone, Copy)]
+enum Reach {
+	/// asked, and waiting on an answer
+	Filed,
+	Approved,
+	AlreadyMigrated,
+	Declined,
+	/// nowhere to ask, or a repository that isn't on a forge the bot knows
+	NoRepository,
+	NotAsked
+}
+
+/// some packages, and their downloads
+#[derive(Default, Serialize)]
+struct Volume {
+	packages: usize,
+	downloads: u64
+}
+
+impl Volume {
+	fn add(&mut self, downloads: u32) {
+		self.packages += 1;
+		self.downloads += downloads as u64;
+	}
+}
+
+/// every package in a bucket, by where it got to
+#[derive(Default, Serialize)]
+struct Coverage {
+	packages: usize,
+	downloads: u64,
+	filed: Volume,
+	approved: Volume,
+	already_migrated: Volume,
+	declined: Volume,
+	no_repository: Volume,
+	not_asked: Volume
+}
+
+impl Coverage {
+	fn add(&mut self, reach: Reach, downloads: u32) {
+		self.packages += 1;
+		self.downloads += downloads as u64;
+		match reach {
+			Reach::Filed => { &mut self.filed }
+			Reach::Approved => { &mut self.approved }
+			Reach::AlreadyMigrated => { &mut self.already_migrated }
+			Reach::Declined => { &mut self.declined }
+			Reach::NoRepository => { &mut self.no_repository }
+			Reach::NotAsked => { &mut self.not_asked }
+		}.add(downloads);
+	}
+
+	/// what share of the downloads are from packages that can move to pulsar,
+	/// because they were approved or are there already
+	fn covered(&self) -> String {
+		self.share(self.approved.downloads + self.already_migrated.downloads)
+	}
+
+	fn share(&self, downloads: u64) -> String {
+		match self.downloads {
+			0 => { "-".into() }
+			total => { format!("{:.1}%", downloads as f64 * 100.0 / total as f64) }
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct CoverageBucket {
+	bucket: String,
+	#[serde(flatten)]
+	coverage: Coverage
+}
+
+#[derive(Serialize)]
+struct CoverageJson {
+	overall: Coverage,
+	by_downloads: Vec<CoverageBucket>
+}
+
+#[derive(Serialize)]
+struct Bucket {
+	bucket: String,
+	#[serde(flatten)]
+	counts: Counts
+}
+
+#[derive(Serialize)]
+struct AnalyticsJson {
+	overall: Counts,
+	by_downloads: Vec<Bucket>,
+	by_age: Vec<Bucket>,
+	by_follow_up: Vec<Bucket>,
+	/// empty if there aren't any issues from `template_variants`
+	by_template_variant: Vec<Bucket>,
+	first_reply: FirstReply,
+	/// every package, not only the ones that were asked
+	coverage: CoverageJson,
+	runs: RunsJson
+}
+
+/// what the kept runs did, from their [`RunReport`]s
+#[derive(Serialize)]
+struct RunsJson {
+	runs: usize,
+	actions: Vec<ActionCounts>
+}
+
+#[derive(Serialize)]
+struct ActionCounts {
+	action: Action,
+	packages: usize,
+	median_seconds: Option<f64>
+}
+
+impl RunsJson {
+	fn new(runs: &[RunReport]) -> Self {
+		let actions = Action::ALL.iter()
+			.map(|action| {
+				let mut took = runs.iter()
+					.flat_map(|run| &run.packages)
+					.filter(|result| result.action == *action)
+					.map(|result| result.took_ms)
+					.collect::<Vec<_>>();
+				took.sort_unstable();
+				ActionCounts {
+					action: *action,
+					packages: took.len(),
+					median_seconds: took.get(took.len().saturating_sub(1) / 2).map(|ms| *ms as f64 / 1000.0)
+				}
+			})
+			.collect();
+		Self { runs: runs.len(), actions }
+	}
+}
+
+/// how long it took for issues to get their first reply
+#[derive(Serialize)]
+struct FirstReply {
+	replied: usize,
+	/// issues nobody has replied to yet
+	waiting: usize,
+	median_hours: Option<f64>,
+	p90_hours: Option<f64>
+}
+
+impl FirstReply {
+	fn new(asked: &[Asked]) -> Self {
+		let mut times = asked.iter().filter_map(|package| package.replied_after).collect::<Vec<_>>();
+		times.sort();
+		let hours = |percentile: f64| {
+			// nearest rank
+			let rank = (percentile * times.len() as f64).ceil() as usize;
+			times.get(rank.max(1) - 1).map(|time| time.num_minutes() as f64 / 60.0)
+		};
+		Self {
+			replied: times.len(),
+			waiting: asked.iter()
+				.filter(|package| package.by_issue && package.replied_after.is_none() && matches!(package.outcome, Outcome::NoResponse))
+				.count(),
+			median_hours: hours(0.5),
+			p90_hours: hours(0.9)
+		}
+	}
+}
+
+fn format_hours(hours: f64) -> String {
+	match hours < 24.0 {
+		true => { format!("{hours:.1} hours") }
+		false => { format!("{:.1} days", hours / 24.0) }
+	}
+}
+
+const DOWNLOAD_BUCKETS: &[(u32, &str)] = &[
+	(0, "under 100 downloads"),
+	(100, "100 to 999 downloads"),
+	(1_000, "1,000 to 9,999 downloads"),
+	(10_000, "10,000+ downloads")
+];
+
+/// (at least this many days ago, bucket)
+const AGE_BUCKETS: &[(i64, &str)] = &[
+	(0, "asked under a week ago"),
+	(7, "asked 1 to 4 weeks ago"),
+	(28, "asked 1 to 3 months ago"),
+	(91, "asked over 3 months ago")
+];
+
+/// prints the rates, as a table or (with `--format json`) the counts they're from,
+/// along with what the kept `runs` did
+pub fn run(db: &DatabaseThing, runs: &[RunReport], format: OutputFormat) -> Result {
+	print!("{}", render(db, runs, format)?);
+	Ok(())
+}
+
+/// what [`run`] prints
+pub fn render(db: &DatabaseThing, runs: &[RunReport], format: OutputFormat) -> Result<String> {
+	let mut out = String::new();
+	let now = Utc::now();
+	let asked = db.packages().iter().filter_map(asked).collect::<Vec<_>>();
+
+	let mut overall = Counts::default();
+	let mut by_downloads = buckets(DOWNLOAD_BUCKETS);
+	let mut by_age = buckets(AGE_BUCKETS);
+	let mut by_follow_up = vec![
+		Bucket { bucket: "followed up".into(), counts: Counts::default() },
+		Bucket { bucket: "not followed up".into(), counts: Counts::default() }
+	];
+	let mut by_template_variant = BTreeMap::<&str, Counts>::new();
+	for package in &asked {
+		overall.add(package.outcome);
+		let downloads = DOWNLOAD_BUCKETS.iter().rposition(|(min, _)| package.downloads >= *min).unwrap_or(0);
+		by_downloads[downloads].counts.add(package.outcome);
+		let days = (now - package.asked_at).num_days();
+		let age = AGE_BUCKETS.iter().rposition(|(min, _)| days >= *min).unwrap_or(0);
+		by_age[age].counts.add(package.outcome);
+		by_follow_up[if package.followed_up { 0 } else { 1 }].counts.add(package.outcome);
+		if let Some(variant) = &package.template_variant {
+			by_template_variant.entry(variant).or_default().add(package.outcome);
+		}
+	}
+	let by_template_variant = by_template_variant.into_iter()
+		.map(|(variant, counts)| Bucket { bucket: format!("template variant {variant}"), counts })
+		.collect::<Vec<_>>();
+
+	let first_reply = FirstReply::new(&asked);
+	let coverage = coverage(db);
+	let runs = RunsJson::new(runs);
+	if format == OutputFormat::Json {
+		let analytics = AnalyticsJson { overall, by_downloads, by_age, by_follow_up, by_template_variant, first_reply, coverage, runs };
+		writeln!(out, "{}", serde_json::to_string_pretty(&analytics)?)?;
+		return Ok(out)
+	}
+
+	if asked.is_empty() {
+		writeln!(out, "no maintainers have been asked yet")?;
+		write_coverage(&mut out, &coverage)?;
+		write_runs(&mut out, &runs)?;
+		return Ok(out)
+	}
+
+	let overall = Bucket { bucket: "overall".into(), counts: overall };
+	// empty buckets don't have rates
+	let rows = std::iter::once(&overall).chain(&by_downloads).chain(&by_age).chain(&by_follow_up).chain(&by_template_variant)
+		.filter(|bucket| bucket.counts.packages > 0)
+		.map(|bucket| {
+			let counts = &bucket.counts;
+			[
+				bucket.bucket.clone(),
+				counts.packages.to_string(),
+				counts.rate(counts.approved),
+				counts.rate(counts.declined),
+				counts.rate(counts.replied),
+				counts.rate(counts.no_response)
+			]
+		})
+		.collect::<Vec<_>>();
+	out.push_str(&status::table(["", "packages", "approved", "declined", "replied", "no response"], &rows));
+	writeln!(out)?;
+	writeln!(out, "replied is someone answering on the issue without saying yes or no yet")?;
+
+	writeln!(out)?;
+	writeln!(out, "first reply on an issue: {} replied to, {} still waiting", first_reply.replied, first_reply.waiting)?;
+	if let (Some(median), Some(p90)) = (first_reply.median_hours, first_reply.p90_hours) {
+		writeln!(out, "   median {}, 90% within {}", format_hours(median), format_hours(p90))?;
+	}
+
+	write_coverage(&mut out, &coverage)?;
+	write_runs(&mut out, &runs)?;
+	Ok(out)
+}
+
+fn write_coverage(out: &mut String, coverage: &CoverageJson) -> std::fmt::Result {
+	let overall = &coverage.overall;
+	if overall.packages == 0 { return Ok(()) }
+	writeln!(out)?;
+	writeln!(out, "every package, by downloads:")?;
+	let overall_row = ("overall", overall);
+	let rows = std::iter::once(overall_row)
+		.chain(coverage.by_downloads.iter().map(|bucket| (bucket.bucket.as_str(), &bucket.coverage)))
+		.filter(|(_, coverage)| coverage.packages > 0)
+		.map(|(bucket, coverage)| [
+			bucket.to_string(),
+			coverage.packages.to_string(),
+			coverage.downloads.to_string(),
+			coverage.filed.packages.to_string(),
+			coverage.approved.packages.to_string(),
+			coverage.already_migrated.packages.to_string(),
+			coverage.declined.packages.to_string(),
+			coverage.no_repository.packages.to_string(),
+			coverage.not_asked.packages.to_string(),
+			coverage.covered()
+		])
+		.collect::<Vec<_>>();
+	out.push_str(&status::table(
+		["", "packages", "downloads", "filed", "approved", "already migrated", "declined", "no repo", "not asked", "covered"],
+		&rows
+	));
+	writeln!(out)?;
+	writeln!(out, "covered is the share of downloads from packages that were approved or are on pulsar already")?;
+	writeln!(
+		out,
+		"of every download, {} are waiting on an answer, {} were declined, and {} have no repository to ask",
+		overall.share(overall.filed.downloads), overall.share(overall.declined.downloads), overall.share(overall.no_repository.downloads)
+	)
+}
+
+/// every package in the database, and the ones the registry has without a
+/// repository, by where they got to
+fn coverage(db: &DatabaseThing) -> CoverageJson {
+	let mut overall = Coverage::default();
+	let mut by_downloads = DOWNLOAD_BUCKETS.iter()
+		.map(|(_, bucket)| CoverageBucket { bucket: bucket.to_string(), coverage: Coverage::default() })
+		.collect::<Vec<_>>();
+	let packages = db.packages().into_iter().map(|package| (reach(&package), package.info.downloads));
+	let without_repository = db.registry_sync_state().without_repository.into_values()
+		.map(|downloads| (Reach::NoRepository, downloads));
+	for (reach, downloads) in packages.chain(without_repository) {
+		overall.add(reach, downloads);
+		let bucket = DOWNLOAD_BUCKETS.iter().rposition(|(min, _)| downloads >= *min).unwrap_or(0);
+		by_downloads[bucket].coverage.add(reach, downloads);
+	}
+	CoverageJson { overall, by_downloads }
+}
+
+fn reach(package: &Package) -> Reach {
+	match &package.state {
+		PackageState::MaintainerApproved(_) | PackageState::Migrated(_) => { Reach::Approved }
+		PackageState::AlreadyMigrated(_) => { Reach::AlreadyMigrated }
+		PackageState::ConsentRevoked(_) | PackageState::OptedOut(_) | PackageState::DeclinedByOwnerPolicy(_) => { Reach::Declined }
+		PackageState::IssueFiled(_) | PackageState::ContactedByEmail(_) | PackageState::IssueFilingInProgress(_) => { Reach::Filed }
+		_ if RepoLocation::parse(&package.info.repository.url).is_none() => { Reach::NoRepository }
+		_ => { Reach::NotAsked }
+	}
+}
+
+fn write_runs(out: &mut String, runs: &RunsJson) -> std::fmt::Result {
+	if runs.runs == 0 { return Ok(()) }
+	writeln!(out)?;
+	writeln!(out, "the last {} runs:", runs.runs)?;
+	let rows = runs.actions.iter()
+		.filter(|counts| counts.packages > 0)
+		.map(|counts| [
+			counts.action.name().to_string(),
+			counts.packages.to_string(),
+			counts.median_seconds.map(|seconds| format!("{seconds:.1}s")).unwrap_or_default()
+		])
+		.collect::<Vec<_>>();
+	out.push_str(&status::table(["", "packages", "median time"], &rows));
+	Ok(())
+}
+
+fn buckets<T>(buckets: &[(T, &'static str)]) -> Vec<Bucket> {
+	buckets.iter().map(|(_, bucket)| Bucket { bucket: bucket.to_string(), counts: Counts::default() }).collect()
+}
+
+/// how asking `package`'s maintainer went, if they were asked and it's gotten
+/// somewhere (packages that were asked and then blocked, or reset, aren't counted)
+fn asked(package: &Package) -> Option<Asked> {
+	let states = || package.history.iter().map(|transition| &transition.state).chain([&package.state]);
+	let asked_at = states().find_map(|state| match state {
+		PackageState::IssueFiled(filed) => { Some(filed.filed_at) }
+		PackageState::ContactedByEmail(contacted) => { Some(contacted.contacted_at) }
+		PackageState::MaintainerApproved(approved) => { Some(approved.contacted_at) }
+		PackageState::Migrated(migrated) => { Some(migrated.approval.contacted_at) }
+		PackageState::ConsentRevoked(revoked) => { Some(revoked.approval.contacted_at) }
+		_ => { None }
+	})?;
+	let followed_up = states().any(|state| match state {
+		PackageState::IssueFiled(filed) => { filed.reminders.iter().any(|reminder| reminder.sent_at.is_some()) }
+		_ => { false }
+	});
+	let by_issue = states().any(|state| matches!(state, PackageState::IssueFiled(_)));
+	let template_variant = states().find_map(|state| match state {
+		PackageState::IssueFiled(filed) => { filed.template_variant.clone() }
+		_ => { None }
+	});
+	// approving counts as replying, for approvals from before first replies were kept
+	let approved_at = match &package.state {
+		PackageState::MaintainerApproved(approved) => { Some(approved) }
+		PackageState::Migrated(migrated) => { Some(&migrated.approval) }
+		PackageState::ConsentRevoked(revoked) => { Some(&revoked.approval) }
+		_ => { None }
+	}.filter(|approval| approval.issue_url.is_some()).map(|approval| approval.approved_at);
+	let replied_after = [package.first_reply_at, approved_at].into_iter().flatten().min()
+		.filter(|_| by_issue)
+		.map(|replied_at| replied_at - asked_at)
+		.filter(|after| *after >= Duration::zero());
+	let outcome = match &package.state {
+		PackageState::MaintainerApproved(_) | PackageState::Migrated(_) => { Outcome::Approved }
+		PackageState::ConsentRevoked(_) | PackageState::OptedOut(_) => { Outcome::Declined }
+		PackageState::IssueFiled(filed) if filed.replied_at.is_some() => { Outcome::Replied }
+		PackageState::IssueFiled(_) | PackageState::ContactedByEmail(_) => { Outcome::NoResponse }
+		_ => { return None }
+	};
+	Some(Asked { downloads: package.info.downloads, asked_at, followed_up, outcome, by_issue, replied_after, template_variant })
+}
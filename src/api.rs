@@ -0,0 +1,404 @@
+//! a read-only json api on `api.listen`, for other pulsar services (and
+//! dashboards) to ask how the migration is going without reading `state.ron`:
+//!
+//! - `GET /packages`, like `list --format json`, filtered with `?state=`,
+//!   `owner=`, `min_downloads=`, `min_stars=`, `search=`, and `sort=`, and paged
+//!   with `limit=` (100 unless it's given) and `cursor=` (see [`Cursor`]), or
+//!   `offset=`
+//! - `GET /packages/{name}`, like `show --format json`
+//! - `GET /stats`, how many packages are in each state and how filing is going
+//! - `GET /events`, every package changing state as it happens, as server-sent
+//!   events (`event: state`, with the change as json, like `webhook.url` gets),
+//!   for following a big run live
+//! - `GET /feed.atom`, an atom feed of the issues filed and approvals recorded
+//!   lately (see [`crate::feed`]), which `api.public_feed` serves to anyone
+//!
+//! and on `/`, a page with all that for people: how many packages are in each
+//! state, the most downloaded ones still waiting for an answer, the issues
+//! filed last, and looking up a package, for anyone in the community wanting to
+//! know how it's going without a terminal
+//!
+//! package states and maintainers' replies aren't for everyone, so everything
+//! but the page needs one of `api.tokens`, as `Authorization: Bearer <token>`
+//! (the page asks for it), unless `api.public` is on
+
+use crate::db::{ DatabaseThing, Package, PackageState, RunRecord, StateChange };
+use crate::feed;
+use crate::http::{ self, Request };
+use crate::queue::QueueOrder;
+use crate::status::{ self, ListFilter, ListJson, OutputFormat, ShowJson };
+use crate::secrets::Secret;
+use crate::Result;
+use chrono::{ DateTime, Duration, Utc };
+use ring::digest::{ self, Digest };
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{ TcpListener, TcpStream };
+use tokio::sync::{ broadcast, mpsc };
+use tokio::time;
+
+/// the page on `/`, which gets everything on it from the api
+const DASHBOARD: &str = include_str!("../resources/dashboard.html");
+
+/// packages on a page of `/packages`, unless `limit` says otherwise
+const DEFAULT_LIMIT: usize = 100;
+
+/// issues in `/stats`' `recent_filings`
+const RECENT_FILINGS: usize = 10;
+
+/// nothing that's asked for has a body
+const MAX_REQUEST: usize = 16 * 1024;
+
+/// state changes kept for `/events` streams that are behind, before they miss some
+const EVENTS_BUFFERED: usize = 1024;
+
+/// seconds between comments on quiet `/events` streams, so proxies don't close them
+/// (and closed ones are noticed)
+const KEEP_ALIVE_SECS: u64 = 15;
+
+const FEED: &str = "/feed.atom";
+
+/// so dashboards on other sites can use it from the browser
+const CORS: &[(&str, &str)] = &[
+	("Access-Control-Allow-Origin", "*"),
+	("Access-Control-Allow-Headers", "Authorization"),
+	("Access-Control-Allow-Methods", "GET")
+];
+
+#[derive(Clone, PartialEq)]
+pub struct ApiConfig {
+	/// address to listen on, like `127.0.0.1:9187`
+	pub listen: String,
+	/// any of these work as a bearer token
+	pub tokens: Vec<Secret>,
+	/// so it can be served to anyone, without tokens
+	pub public: bool,
+	/// so `/feed.atom` can be, since everything in it is on the issues anyway
+	pub public_feed: bool
+}
+
+struct Server {
+	db: DatabaseThing,
+	/// of the tokens, so checking one takes the same time however much of it is right
+	tokens: Vec<Digest>,
+	public: bool,
+	public_feed: bool,
+	/// every state change, for `/events`
+	changes: broadcast::Sender<StateChange>
+}
+
+#[derive(Serialize)]
+struct PackagesJson {
+	/// how many match, across every page
+	total: usize,
+	offset: usize,
+	packages: Vec<ListJson>,
+	/// for `cursor=`, to get the page after this one, if there is one
+	next_cursor: Option<String>
+}
+
+/// where a page of `/packages` ended: the last package's name, and what it was
+/// sorted by. the next page starts after wherever that would be, so packages
+/// being added, or changing state, while paging through doesn't skip or repeat
+/// any, like an offset would
+struct Cursor {
+	/// downloads or stars, or 0 by name
+	key: u32,
+	name: String
+}
+
+impl Cursor {
+	fn new(package: &Package, order: QueueOrder) -> Self {
+		Self { key: Self::key(package, order), name: package.info.name.clone() }
+	}
+
+	fn key(package: &Package, order: QueueOrder) -> u32 {
+		match order {
+			QueueOrder::Downloads => { package.info.downloads }
+			QueueOrder::Stars => { package.info.stargazers_count }
+			QueueOrder::Alphabetical | QueueOrder::Random => { 0 }
+		}
+	}
+
+	/// whether the cursor comes before `package`, in the order [`queue::sort`] puts them in
+	fn before(&self, package: &Package, order: QueueOrder) -> bool {
+		(Reverse(self.key), self.name.as_str()) < (Reverse(Self::key(package, order)), package.info.name.as_str())
+	}
+
+	/// hex, so it doesn't need escaping in urls, and isn't mistaken for something to edit
+	fn encode(&self) -> String {
+		http::encode_hex(format!("{}:{}", self.key, self.name).as_bytes())
+	}
+
+	fn decode(cursor: &str) -> Option<Self> {
+		let cursor = String::from_utf8(http::decode_hex(cursor)?).ok()?;
+		let (key, name) = cursor.split_once(':')?;
+		Some(Self { key: key.parse().ok()?, name: name.into() })
+	}
+}
+
+#[derive(Serialize)]
+struct StatsJson {
+	packages: usize,
+	/// by [`crate::db::PackageState::kind`]
+	states: BTreeMap<&'static str, usize>,
+	issues_filed_last_day: usize,
+	issues_filed_last_week: usize,
+	last_filed_at: Option<DateTime<Utc>>,
+	last_sync: Option<DateTime<Utc>>,
+	last_run: Option<RunRecord>,
+	/// newest first
+	recent_filings: Vec<FilingJson>
+}
+
+#[derive(Serialize)]
+struct FilingJson {
+	/// more than one, if they share a repository
+	packages: Vec<String>,
+	issue_url: String,
+	filed_at: DateTime<Utc>
+}
+
+#[derive(Serialize)]
+struct ErrorJson {
+	error: String
+}
+
+/// starts serving the api, in the background for as long as the bot runs
+pub async fn serve(db: &DatabaseThing, config: &ApiConfig) -> Result {
+	let listen = &config.listen;
+	if config.tokens.is_empty() && !config.public && !config.public_feed {
+		return Err("api.tokens isn't set, and api.public is off, so nobody could use the api".into())
+	}
+	let listener = TcpListener::bind(listen).await
+		.map_err(|e| format!("error listening for the api on {listen}: {e}"))?;
+	info!("serving the api on http://{listen}/");
+
+	// passed on to however many `/events` streams there are
+	let (changes, _) = broadcast::channel(EVENTS_BUFFERED);
+	let (tx, mut rx) = mpsc::unbounded_channel::<StateChange>();
+	db.add_state_changes(tx);
+	let forward = changes.clone();
+	tokio::spawn(async move {
+		while let Some(change) = rx.recv().await {
+			// nobody listening is fine
+			let _ = forward.send(change);
+		}
+	});
+
+	let server = Arc::new(Server {
+		db: db.clone(),
+		tokens: config.tokens.iter().map(|token| digest::digest(&digest::SHA256, token.expose().as_bytes())).collect(),
+		public: config.public,
+		public_feed: config.public_feed,
+		changes
+	});
+	tokio::spawn(async move {
+		loop {
+			let stream = match listener.accept().await {
+				Ok((stream, _)) => { stream }
+				Err(e) => {
+					warn!("error accepting an api connection: {e}");
+					continue
+				}
+			};
+			let server = server.clone();
+			tokio::spawn(async move {
+				if let Err(e) = server.respond(stream).await {
+					debug!("error answering an api request: {e}");
+				}
+			});
+		}
+	});
+
+	Ok(())
+}
+
+impl Server {
+	async fn respond(&self, mut stream: TcpStream) -> Result {
+		let request = Request::read(&mut stream, MAX_REQUEST).await?;
+		match (request.method.as_str(), request.path.as_str()) {
+			// there's nothing on it until it has a token
+			("GET", "/") => { return http::respond(&mut stream, "200 OK", "text/html; charset=utf-8", &[], DASHBOARD).await }
+			// browsers ask before sending an Authorization header to another site
+			("OPTIONS", _) => { return http::respond(&mut stream, "204 No Content", "text/plain", CORS, "").await }
+			_ => {}
+		}
+
+		let mut headers = CORS.to_vec();
+		let answer = match self.authorize(&request) {
+			Ok(()) if request.method == "GET" && request.path.trim_end_matches('/') == "/events" => { return self.events(stream).await }
+			Ok(()) if request.method == "GET" && request.path == FEED => {
+				let feed = feed::atom(&self.db, request.header("host"))?;
+				return http::respond(&mut stream, "200 OK", "application/atom+xml; charset=utf-8", &headers, &feed).await
+			}
+			Ok(()) => { answer(&self.db, &request) }
+			Err(error) => {
+				headers.push(("WWW-Authenticate", "Bearer"));
+				Err(("401 Unauthorized", error.into()))
+			}
+		};
+		let (status, body) = match answer {
+			Ok(body) => { ("200 OK", body) }
+			Err((status, error)) => { (status, serde_json::to_string(&ErrorJson { error })?) }
+		};
+		http::respond(&mut stream, status, "application/json", &headers, &body).await
+	}
+
+	/// streams state changes until the connection closes
+	async fn events(&self, mut stream: TcpStream) -> Result {
+		let mut changes = self.changes.subscribe();
+		http::respond_streaming(&mut stream, "text/event-stream", CORS).await?;
+		// so it's clear straight away that it's working
+		stream.write_all(b": listening for state changes\n\n").await?;
+
+		let every = StdDuration::from_secs(KEEP_ALIVE_SECS);
+		let mut keep_alive = time::interval_at(time::Instant::now() + every, every);
+		loop {
+			let event = tokio::select! {
+				change = changes.recv() => {
+					match change {
+						Ok(change) => { format!("event: state\ndata: {}\n\n", serde_json::to_string(&change)?) }
+						Err(broadcast::error::RecvError::Lagged(missed)) => { format!("event: missed\ndata: {missed}\n\n") }
+						Err(broadcast::error::RecvError::Closed) => { return Ok(()) }
+					}
+				}
+				_ = keep_alive.tick() => { ": still here\n\n".to_string() }
+			};
+			// errors once whoever was listening has gone
+			stream.write_all(event.as_bytes()).await?;
+		}
+	}
+
+	/// checks the request has one of `api.tokens`
+	fn authorize(&self, request: &Request) -> std::result::Result<(), &'static str> {
+		if self.public || (self.public_feed && request.path == FEED) { return Ok(()) }
+		let token = request.header("authorization")
+			.and_then(|authorization| authorization.strip_prefix("Bearer "))
+			.ok_or("this needs an api token, as Authorization: Bearer <token>")?;
+		let token = digest::digest(&digest::SHA256, token.trim().as_bytes());
+		match self.tokens.iter().any(|allowed| allowed.as_ref() == token.as_ref()) {
+			true => { Ok(()) }
+			false => { Err("that api token isn't one of api.tokens") }
+		}
+	}
+}
+
+type Answer = std::result::Result<String, (&'static str, String)>;
+
+fn answer(db: &DatabaseThing, request: &Request) -> Answer {
+	if request.method != "GET" {
+		return Err(("405 Method Not Allowed", "the api is read-only, only GET works".into()))
+	}
+	let path = request.path.trim_end_matches('/');
+	match path {
+		"/packages" => { packages(db, request) }
+		"/stats" => { json(&stats(db)) }
+		_ => {
+			let name = match path.strip_prefix("/packages/") {
+				Some(name) => { percent_encoding::percent_decode_str(name).decode_utf8_lossy().into_owned() }
+				None => { return Err(("404 Not Found", format!("nothing at {path}, try /packages, /packages/{{name}}, /stats, /events, or /feed.atom"))) }
+			};
+			match db.package(&name) {
+				Some(package) => { json(&ShowJson::new(&package)) }
+				None => { Err(("404 Not Found", format!("package {name} is not in the database"))) }
+			}
+		}
+	}
+}
+
+fn packages(db: &DatabaseThing, request: &Request) -> Answer {
+	let mut filter = ListFilter { state: None, min_downloads: 0, min_stars: 0, search: None, owner: None, sort: None, limit: None, format: OutputFormat::Json };
+	let mut limit = DEFAULT_LIMIT;
+	let mut offset = None;
+	let mut cursor = None;
+	for (name, value) in request.query_pairs() {
+		match name.as_str() {
+			"state" => { filter.state = Some(value) }
+			"search" => { filter.search = Some(value) }
+			"owner" => { filter.owner = Some(value) }
+			"min_downloads" => { filter.min_downloads = number(&name, &value)? }
+			"min_stars" => { filter.min_stars = number(&name, &value)? }
+			"sort" => { filter.sort = Some(QueueOrder::from_str(&value).map_err(|e| ("400 Bad Request", format!("sort: {e}")))?) }
+			"limit" => { limit = number(&name, &value)? }
+			"offset" => { offset = Some(number(&name, &value)?) }
+			"cursor" => { cursor = Some(Cursor::decode(&value).ok_or(("400 Bad Request", format!("cursor: {value} isn't a cursor from next_cursor")))?) }
+			_ => { return Err(("400 Bad Request", format!("unknown parameter {name}"))) }
+		}
+	}
+	// cursors need an order that's the same every time. by name, unless it's something else
+	let order = filter.sort.unwrap_or(QueueOrder::Alphabetical);
+	if order == QueueOrder::Random && cursor.is_some() {
+		return Err(("400 Bad Request", "random order can't be paged with a cursor, it's different every time".into()))
+	}
+	if offset.is_some() && cursor.is_some() {
+		return Err(("400 Bad Request", "give offset or cursor, not both".into()))
+	}
+	filter.sort = Some(order);
+
+	let packages = status::matching(db, &filter).map_err(|e| ("400 Bad Request", e.to_string()))?;
+	let offset = match (&cursor, offset) {
+		(Some(cursor), _) => { packages.iter().take_while(|package| !cursor.before(package, order)).count() }
+		(None, offset) => { offset.unwrap_or(0) }
+	};
+	let page = packages.iter().skip(offset).take(limit).collect::<Vec<_>>();
+	let next_cursor = match page.last() {
+		Some(last) if offset + page.len() < packages.len() && order != QueueOrder::Random => { Some(Cursor::new(last, order).encode()) }
+		_ => { None }
+	};
+	json(&PackagesJson {
+		total: packages.len(),
+		offset,
+		packages: page.into_iter().map(ListJson::new).collect(),
+		next_cursor
+	})
+}
+
+fn number<T: FromStr>(name: &str, value: &str) -> std::result::Result<T, (&'static str, String)> {
+	value.parse().map_err(|_| ("400 Bad Request", format!("{name}: {value} isn't a number")))
+}
+
+fn stats(db: &DatabaseThing) -> StatsJson {
+	let packages = db.packages();
+	let mut states = BTreeMap::new();
+	// by issue, since packages sharing a repository share one
+	let mut filings = BTreeMap::<&str, FilingJson>::new();
+	for package in packages.iter() {
+		*states.entry(package.state.kind()).or_default() += 1;
+		let filed = package.history.iter().map(|transition| &transition.state).chain([&package.state])
+			.filter_map(|state| match state {
+				PackageState::IssueFiled(filed) => { Some(filed) }
+				_ => { None }
+			});
+		for filed in filed {
+			let filing = filings.entry(&filed.issue_url)
+				.or_insert_with(|| FilingJson { packages: Vec::new(), issue_url: filed.issue_url.clone(), filed_at: filed.filed_at });
+			if !filing.packages.contains(&package.info.name) {
+				filing.packages.push(package.info.name.clone());
+			}
+		}
+	}
+	let mut recent_filings = filings.into_values().collect::<Vec<_>>();
+	recent_filings.sort_by_key(|filing| std::cmp::Reverse(filing.filed_at));
+	recent_filings.truncate(RECENT_FILINGS);
+
+	StatsJson {
+		packages: packages.len(),
+		states,
+		issues_filed_last_day: db.issues_filed_last_day(),
+		issues_filed_last_week: db.issues_filed_since(Utc::now() - Duration::days(7)),
+		last_filed_at: db.last_filing(),
+		last_sync: db.registry_sync_state().last_sync,
+		last_run: db.recent_runs().pop(),
+		recent_filings
+	}
+}
+
+fn json<T: Serialize>(value: &T) -> Answer {
+	serde_json::to_string(value).map_err(|e| ("500 Internal Server Error", e.to_string()))
+}
@@ -0,0 +1,122 @@
+//! everything there is to show for the migration, in one `.tar.gz`, for `archive`:
+//! the database, the kept run reports, the reports written to `reports.dir`, the
+//! activity log, and analytics (as text and json), with a `manifest.json` of
+//! every file in it, its size, and its sha256, so once the migration's over it
+//! can be kept, or published for anyone to see what was done. the database has
+//! maintainers' email addresses in it, if any were emailed
+
+use crate::analytics;
+use crate::config::Config;
+use crate::db::DatabaseThing;
+use crate::http;
+use crate::runs;
+use crate::status::OutputFormat;
+use crate::Result;
+use chrono::{ DateTime, Utc };
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use ring::digest;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+use tokio::fs;
+
+#[derive(Serialize)]
+struct Manifest {
+	created_at: DateTime<Utc>,
+	/// of the bot
+	version: &'static str,
+	/// how many packages are in each state
+	packages: BTreeMap<&'static str, usize>,
+	/// besides this one
+	files: Vec<ManifestFile>
+}
+
+#[derive(Serialize)]
+struct ManifestFile {
+	/// in the archive, under its directory
+	path: String,
+	bytes: usize,
+	sha256: String
+}
+
+/// writes the archive to `output`, or `pulsar-migration-<date>.tar.gz`. it's
+/// all in a directory of that name, so it doesn't spill out when it's extracted
+pub async fn run(db: &DatabaseThing, config: &Config, output: Option<String>) -> Result {
+	let created_at = Utc::now();
+	let name = format!("pulsar-migration-{}", created_at.format("%Y-%m-%d"));
+	let output = output.unwrap_or_else(|| format!("{name}.tar.gz"));
+
+	// (path in the archive, contents)
+	let mut files = vec![(file_name(&config.db_path), read(&config.db_path).await?)];
+	let runs_path = runs::path(&config.db_path);
+	if Path::new(&runs_path).exists() {
+		files.push(("runs.json".into(), read(&runs_path).await?));
+	}
+	if let Some(dir) = config.report_dir.as_deref().filter(|dir| Path::new(dir).is_dir()) {
+		let mut entries = fs::read_dir(dir).await
+			.map_err(|e| format!("error reading report directory {dir}: {e}"))?;
+		let mut reports = Vec::new();
+		while let Some(entry) = entries.next_entry().await.map_err(|e| format!("error reading report directory {dir}: {e}"))? {
+			if entry.file_type().await.map_or(false, |kind| kind.is_file()) {
+				reports.push(entry.path());
+			}
+		}
+		reports.sort();
+		for report in reports {
+			let path = report.to_string_lossy().into_owned();
+			files.push((format!("reports/{}", file_name(&path)), read(&path).await?));
+		}
+	}
+	if let Some(activity_log) = config.activity_log.as_deref().filter(|path| Path::new(path).exists()) {
+		files.push(("activity.log".into(), read(activity_log).await?));
+	}
+	let runs = runs::load(&config.db_path).await?;
+	files.push(("analytics.txt".into(), analytics::render(db, &runs, OutputFormat::Text)?.into_bytes()));
+	files.push(("analytics.json".into(), analytics::render(db, &runs, OutputFormat::Json)?.into_bytes()));
+
+	let mut packages = BTreeMap::new();
+	for package in db.packages() {
+		*packages.entry(package.state.kind()).or_default() += 1;
+	}
+	let manifest = Manifest {
+		created_at,
+		version: env!("CARGO_PKG_VERSION"),
+		packages,
+		files: files.iter()
+			.map(|(path, data)| ManifestFile {
+				path: path.clone(),
+				bytes: data.len(),
+				sha256: http::encode_hex(digest::digest(&digest::SHA256, data).as_ref())
+			})
+			.collect()
+	};
+	let manifest = serde_json::to_string_pretty(&manifest)?;
+
+	let write = || -> std::io::Result<()> {
+		let mut archive = tar::Builder::new(GzEncoder::new(File::create(&output)?, Compression::default()));
+		let manifest = ("manifest.json".to_string(), manifest.into_bytes());
+		for (path, data) in std::iter::once(&manifest).chain(&files) {
+			let mut header = tar::Header::new_gnu();
+			header.set_size(data.len() as u64);
+			header.set_mode(0o644);
+			header.set_mtime(created_at.timestamp() as u64);
+			header.set_cksum();
+			archive.append_data(&mut header, format!("{name}/{path}"), &data[..])?;
+		}
+		archive.into_inner()?.finish()?;
+		Ok(())
+	};
+	write().map_err(|e| format!("error writing archive {output}: {e}"))?;
+	info!("archived {} files to {output}", files.len() + 1);
+	Ok(())
+}
+
+async fn read(path: &str) -> Result<Vec<u8>> {
+	Ok(fs::read(path).await.map_err(|e| format!("error reading {path}: {e}"))?)
+}
+
+fn file_name(path: &str) -> String {
+	Path::new(path).file_name().map_or_else(|| path.into(), |name| name.to_string_lossy().into_owned())
+}
@@ -0,0 +1,53 @@
+//! stopping creating anything on a forge host when everything tried on it keeps
+//! failing, like after the token's been revoked or the bot's been blocked for
+//! abuse. after `filing.breaker_failures` failures in a row on a host, nothing
+//! is filed (or posted) on it for `filing.breaker_cooldown` minutes, across
+//! runs, and the operator is told, rather than every package left failing the
+//! same way. the packages it would have been are left as they are, so they're
+//! picked up once it's over, or sooner if `retry` is run
+
+use crate::config::Config;
+use crate::db::{ DatabaseThing, TrippedBreaker };
+use crate::error::Class;
+use crate::notify;
+use crate::Error;
+use chrono::{ Duration, Utc };
+use std::collections::HashMap;
+
+/// failures in a row on each host, for a run
+#[derive(Default)]
+pub struct Breakers {
+	failures: HashMap<String, u32>
+}
+
+impl Breakers {
+	/// records something tried on `host` failing, tripping its breaker if that's
+	/// enough in a row. repositories not existing says nothing about the host, so
+	/// that's not counted. returns whether it tripped
+	pub async fn failed(&mut self, db: &DatabaseThing, config: &Config, host: &str, error: &Error) -> bool {
+		if config.breaker_failures == 0 || error.class() == Class::Terminal { return false }
+
+		let failures = self.failures.entry(host.to_string()).or_default();
+		*failures += 1;
+		if *failures < config.breaker_failures { return false }
+
+		let failures = std::mem::take(failures);
+		let now = Utc::now();
+		let until = now + Duration::minutes(config.breaker_cooldown as i64);
+		db.set_breaker(host, Some(TrippedBreaker { tripped_at: now, until, failures, last_error: error.to_string() }));
+		db.flush();
+
+		error!("the last {failures} things tried on {host} all failed, not filing anything on it until {until} (the last error: {error})");
+		let text = format!(
+			"**stopped filing on {host}**: the last {failures} things tried on it all failed, so nothing is filed on it until {until}. \
+			the last error: {error}. once it's sorted out, `retry` carries on straight away"
+		);
+		notify::send(config, &text).await;
+		true
+	}
+
+	/// records something being created on `host`, so its failures aren't in a row anymore
+	pub fn succeeded(&mut self, host: &str) {
+		self.failures.remove(host);
+	}
+}
@@ -0,0 +1,215 @@
+//! letting several copies of the bot work through the same packages without
+//! stepping on each other, by claiming repositories in a directory they share.
+//! claims are leases that run out if the worker holding them stops renewing
+//! them (because it died), so someone else can pick the work up
+
+use crate::config::Config;
+use crate::Result;
+use chrono::{ DateTime, Duration, Utc };
+use rand::Rng;
+use serde::{ Deserialize, Serialize };
+use std::collections::HashSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, Mutex };
+use tokio::task::JoinHandle;
+
+#[derive(Deserialize, Serialize)]
+struct Claim {
+	worker: String,
+	expires_at: DateTime<Utc>,
+	/// the work is done, so it's never picked up again
+	done: bool
+}
+
+pub struct Claims {
+	dir: PathBuf,
+	worker: String,
+	lease: Duration,
+	/// claims this worker holds, renewed in the background until they're done or released
+	held: Arc<Mutex<HashSet<PathBuf>>>,
+	heartbeat: JoinHandle<()>
+}
+
+impl Claims {
+	/// `None` if there's no `claims.dir`, so there's only one worker
+	pub fn new(config: &Config) -> Result<Option<Self>> {
+		let dir = match &config.claims_dir {
+			Some(dir) => { PathBuf::from(dir) }
+			None => { return Ok(None) }
+		};
+		fs::create_dir_all(&dir)
+			.map_err(|e| format!("error creating claims directory {}: {e}", dir.display()))?;
+
+		let worker = config.worker_id.clone();
+		let lease = Duration::seconds(config.claim_lease as i64);
+		let held = Arc::new(Mutex::new(HashSet::<PathBuf>::new()));
+
+		// renewing a few times per lease, so a slow write doesn't lose it
+		let heartbeat = {
+			let (worker, held) = (worker.clone(), held.clone());
+			let every = std::time::Duration::from_secs((config.claim_lease / 3).max(1));
+			tokio::spawn(async move {
+				loop {
+					tokio::time::sleep(every).await;
+					// locked the whole time, so a claim can't be finished partway through renewing it
+					let mut held = held.lock().unwrap();
+					let mut lost = Vec::new();
+					for path in held.iter() {
+						// if renewing fell behind, another worker could have taken it over, and it's theirs now
+						match read_claim(path) {
+							Ok(Some(claim)) if claim.worker == worker && !claim.done => {}
+							Ok(_) => {
+								warn!("lost claim {}, another worker took it over", path.display());
+								lost.push(path.clone());
+								continue
+							}
+							Err(e) => {
+								error!("error renewing claim {}: {e}", path.display());
+								continue
+							}
+						}
+						let claim = Claim { worker: worker.clone(), expires_at: Utc::now() + lease, done: false };
+						if let Err(e) = write_claim(path, &claim) {
+							error!("error renewing claim {}: {e}", path.display());
+						}
+					}
+					for path in lost {
+						held.remove(&path);
+					}
+				}
+			})
+		};
+
+		info!("claiming work in {} as worker {worker}", dir.display());
+		Ok(Some(Self { dir, worker, lease, held, heartbeat }))
+	}
+
+	/// claims `key` for this worker. false if another worker has it, or it's been done already
+	pub fn try_claim(&self, key: &str) -> Result<bool> {
+		let path = self.path(key);
+
+		if let Some(existing) = read_claim(&path)? {
+			if self.taken(&existing) { return Ok(false) }
+
+			// ours already, or the worker that had it stopped renewing it. it's moved
+			// out of the way rather than removed, since only one worker's move can work
+			let stale = temp_path(&path, "stale");
+			match fs::rename(&path, &stale) {
+				Ok(()) => {}
+				// someone else took it over first
+				Err(e) if e.kind() == ErrorKind::NotFound => { return Ok(false) }
+				Err(e) => { return Err(format!("error moving claim {}: {e}", path.display()).into()) }
+			}
+			// which could have been after they'd already made it theirs again
+			let moved = read_claim(&stale)?;
+			if moved.as_ref().map_or(false, |moved| self.taken(moved)) {
+				// put back, unless yet another worker has claimed it since
+				match fs::hard_link(&stale, &path) {
+					Ok(()) => {}
+					Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+					Err(e) => { error!("error putting claim {} back: {e}", path.display()) }
+				}
+				let _ = fs::remove_file(&stale);
+				return Ok(false)
+			}
+			let _ = fs::remove_file(&stale);
+
+			if existing.worker != self.worker {
+				info!("taking over {key} from worker {}, its claim ran out", existing.worker);
+			}
+		}
+
+		// linking it into place fails if it's there already, so only one worker gets it
+		let claim = Claim { worker: self.worker.clone(), expires_at: Utc::now() + self.lease, done: false };
+		let temp = write_temp(&path, &claim)?;
+		let linked = fs::hard_link(&temp, &path);
+		let _ = fs::remove_file(&temp);
+		match linked {
+			Ok(()) => {}
+			// someone else got there first
+			Err(e) if e.kind() == ErrorKind::AlreadyExists => { return Ok(false) }
+			Err(e) => { return Err(format!("error creating claim {}: {e}", path.display()).into()) }
+		}
+
+		self.held.lock().unwrap().insert(path);
+		Ok(true)
+	}
+
+	/// marks `key` as done, so no worker picks it up again
+	pub fn complete(&self, key: &str) -> Result {
+		let path = self.path(key);
+		let mut held = self.held.lock().unwrap();
+		held.remove(&path);
+		let claim = Claim { worker: self.worker.clone(), expires_at: Utc::now(), done: true };
+		write_claim(&path, &claim)
+	}
+
+	/// gives `key` up without it being done, so another worker can try it
+	pub fn release(&self, key: &str) -> Result {
+		let path = self.path(key);
+		let mut held = self.held.lock().unwrap();
+		held.remove(&path);
+		match fs::remove_file(&path) {
+			Err(e) if e.kind() != ErrorKind::NotFound => {
+				Err(format!("error removing claim {}: {e}", path.display()).into())
+			}
+			_ => { Ok(()) }
+		}
+	}
+
+	/// done, or another worker's and still being renewed
+	fn taken(&self, claim: &Claim) -> bool {
+		claim.done || (claim.worker != self.worker && claim.expires_at > Utc::now())
+	}
+
+	fn path(&self, key: &str) -> PathBuf {
+		let name = key.chars()
+			.map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+			.collect::<String>();
+		self.dir.join(format!("{name}.claim"))
+	}
+}
+
+impl Drop for Claims {
+	fn drop(&mut self) {
+		self.heartbeat.abort();
+	}
+}
+
+/// `None` if no one's claimed it
+fn read_claim(path: &Path) -> Result<Option<Claim>> {
+	let claim = match fs::read_to_string(path) {
+		Ok(claim) => { claim }
+		Err(e) if e.kind() == ErrorKind::NotFound => { return Ok(None) }
+		Err(e) => { return Err(format!("error reading claim {}: {e}", path.display()).into()) }
+	};
+	serde_json::from_str(&claim)
+		.map(Some)
+		.map_err(|e| format!("error parsing claim {}: {e}", path.display()).into())
+}
+
+/// written somewhere else then moved over it, so other workers never see it half written
+fn write_claim(path: &Path, claim: &Claim) -> Result {
+	let temp = write_temp(path, claim)?;
+	fs::rename(&temp, path).map_err(|e| {
+		let _ = fs::remove_file(&temp);
+		format!("error writing claim {}: {e}", path.display()).into()
+	})
+}
+
+fn write_temp(path: &Path, claim: &Claim) -> Result<PathBuf> {
+	let temp = temp_path(path, "tmp");
+	fs::write(&temp, serde_json::to_string(claim)?)
+		.map_err(|e| format!("error writing claim {}: {e}", temp.display()))?;
+	Ok(temp)
+}
+
+/// next to `path`, and not the same as any other worker's. not from [`crate::random`],
+/// since workers run with the same `--seed` would pick the same ones
+fn temp_path(path: &Path, kind: &str) -> PathBuf {
+	let mut name = path.as_os_str().to_owned();
+	name.push(format!(".{}-{:016x}.{kind}", std::process::id(), rand::thread_rng().gen::<u64>()));
+	PathBuf::from(name)
+}
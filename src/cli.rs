@@ -1,31 +1,781 @@
 //! stuff used by the CLI
 
-use crate::db::{ self, DatabaseThing };
-use crate::Result;
-use clap::{ Parser, Subcommand };
+use crate::activity;
+use crate::breaker::Breakers;
+use crate::claims::Claims;
+use crate::config::{ self, Config };
+use crate::consent;
+use crate::control::RunControl;
+use crate::db::{ self, DatabaseThing, PackageState };
+use crate::diff::{ DiffRecorder, RunDiff };
+use crate::digest;
+use crate::eligibility::{ self, DormantPolicy, Eligibility };
+use crate::filing;
+use crate::email::{ self, Mailer };
+use crate::error::Context;
+use crate::forge::{ self, ContactRoute, Forges, RepoLocation };
+use crate::graph::GraphFormat;
+use crate::lists::PackageList;
+use crate::logging::LogFormat;
+use crate::notify;
+use crate::offline::{ Connectivity, Outage };
+use crate::preflight::{ self, Preflight };
+use crate::progress;
+use crate::pulsar::PulsarClient;
+use crate::queue::{ self, QueueOrder };
+use crate::reconcile;
+use crate::reminders;
+use crate::report;
+use crate::responses;
+use crate::retry;
+use crate::runs::{ Action, RunReport };
+use crate::sandbox;
+use crate::staging;
+use crate::templates::Vars;
+use crate::status::{ self, OutputFormat };
+use crate::systemd::{ Activity, Notifier };
+use crate::registry::{ self, RegistryClient };
+use crate::usage::{ self, Stage };
+use crate::{ Error, Result };
+use chrono::{ DateTime, Local, NaiveDate, Utc };
+use clap::{ Args, CommandFactory, Parser, Subcommand };
+use clap_complete::Shell;
+use futures::stream::{ self, StreamExt };
+use std::collections::{ BTreeMap, HashMap, HashSet };
+use std::path::{ Path, PathBuf };
+use std::str::FromStr;
+use std::time::{ Duration, SystemTime };
+use tokio::time::Instant;
 use tokio::fs;
+use tracing::instrument;
 
+/// what a run usually looks like, at the end of `--help`
+const EXAMPLES: &str = "\
+getting started:
+    pulsar-migrator-issue-bot doctor               check the config, tokens, and registry
+    pulsar-migrator-issue-bot sync                 fetch packages from the registry
+    pulsar-migrator-issue-bot plan                 see who the next run would contact
+    pulsar-migrator-issue-bot start --dry-run      see exactly what it would send
+    pulsar-migrator-issue-bot start                post reminders, then file issues
+
+every command has its own --help. `completions <shell>` prints tab completions for
+bash, zsh, fish, elvish, or powershell.";
+
+/// asks the owners of atom.io packages for permission to move them to the
+/// pulsar package registry, by filing issues on their repositories (or emailing
+/// them), and keeps track of the answers
 #[derive(Parser)]
-#[clap(version)]
+#[clap(version, after_help = EXAMPLES)]
 pub struct Cli {
+	/// config file to use (default: config.toml, if there is one)
+	#[clap(long, short, global = true, value_name = "FILE", env = config::CONFIG_ENV)]
+	pub config: Option<String>,
+	/// profile from the config to use, like `staging` or `prod` (default: PULSAR_BOT_PROFILE)
+	#[clap(long, global = true, value_name = "NAME")]
+	pub profile: Option<String>,
+	/// print more about what's going on, `-vv` for every http request too
+	#[clap(long, short, global = true, action = clap::ArgAction::Count)]
+	pub verbose: u8,
+	/// only print errors and things that need looking at (and what was asked for, like `list`)
+	#[clap(long, short, global = true, conflicts_with = "verbose")]
+	pub quiet: bool,
+	/// `text`, or `json` for log collectors, with what package (and so on) every line is about
+	#[clap(long, global = true, value_name = "FORMAT", default_value = "text", env = config::LOG_FORMAT_ENV, value_parser = LogFormat::from_str)]
+	pub log_format: LogFormat,
+	/// save what the registry, forges, and pulsar backend answer to this (empty)
+	/// directory, for `--replay` to answer with later
+	#[clap(long, global = true, value_name = "DIR", conflicts_with = "replay")]
+	pub record: Option<String>,
+	/// answer every request to the registry, forges, and pulsar backend with
+	/// what was saved with `--record`, instead of making it
+	#[clap(long, global = true, value_name = "DIR")]
+	pub replay: Option<String>,
+	/// seed for everything picked at random (like `queue.order = "random"`), so
+	/// it's picked the same way every time
+	#[clap(long, global = true, value_name = "N")]
+	pub seed: Option<u64>,
+	/// file every issue in a repository of its own in this github organisation
+	/// (made when it's needed) instead, with a database of its own, for trying
+	/// out a whole run before any maintainer sees it
+	#[clap(long, global = true, value_name = "ORG")]
+	pub sandbox: Option<String>,
 	#[clap(subcommand)]
 	pub command: Subcommands
 }
 
 #[derive(Subcommand)]
 pub enum Subcommands {
+	/// adds packages from json files of package data (one package, or a list
+	/// of them) to the database
 	ReadPackageData {
 		files: Vec<String>
 	},
-	Start
+	/// fetches packages from the atom.io registry, adding the ones that aren't known yet.
+	/// after the first sync, only packages added or updated since the last sync are fetched
+	#[clap(alias = "sync-registry")]
+	Sync {
+		/// walk the entire registry, even if it's been synced before
+		#[clap(long)]
+		full: bool,
+		/// load packages from an offline dump of the registry instead of the live api.
+		/// can be a json file, a directory of json files, or a tar(.gz) archive of them
+		#[clap(long, value_name = "PATH")]
+		from_dump: Option<String>,
+		/// only sync these packages, fetched one by one
+		#[clap(long = "package", value_name = "NAME")]
+		packages: Vec<String>
+	},
+	/// contacts every pending package that's eligible
+	#[clap(after_help = "\
+examples:
+    file --dry-run --max-issues 5     print the first 5 issues, without filing them
+    file --only owner:someone         contact just one owner's packages
+    file --draft review.json          write the issues to a file to `publish` later
+    file --dry-run --diff plan.json   write what would change, then check a real run with --check-diff plan.json
+    file --dashboard                  watch the run, and pause or stop it from there")]
+	File {
+		#[clap(flatten)]
+		args: FileArgs
+	},
+	/// goes through the issues that have been filed: posts reminders on the ones
+	/// nobody replied to, checks approved ones for permission being taken back,
+	/// and registers approved packages with the pulsar backend
+	Poll {
+		/// print the reminders that would be posted, without posting them or saving anything
+		#[clap(long)]
+		dry_run: bool
+	},
+	/// checks the filed issues for replies, recording answers (and that someone
+	/// replied) without posting anything, so it can run more often than `poll`.
+	/// keeps to `responses.max_issues` and `responses.min_rate_limit`
+	PollResponses,
+	/// handles the github webhook deliveries that were received but never
+	/// handled (because that failed, or the bot was stopped part way through)
+	ReplayWebhooks {
+		/// print the deliveries waiting to be handled, without handling them
+		#[clap(long)]
+		dry_run: bool
+	},
+	/// `poll`, then `file`, which is everything a scheduled run needs to do
+	Start {
+		#[clap(flatten)]
+		args: FileArgs
+	},
+	/// asks a few questions (where the github token is, how many issues to file,
+	/// and so on), checking the answers as it goes, and writes a config to start
+	/// from to `--config` (or config.toml)
+	Init,
+	/// checks that the config loads, the database loads, the github tokens work
+	/// and can file issues, and the registry responds, without changing anything
+	Doctor,
+	/// loads the config and checks its settings make sense together, printing
+	/// where anything wrong is, and fails if the bot wouldn't run with it. nothing
+	/// else is checked (see `doctor` for that), so it works before deploying
+	ValidateConfig,
+	/// prints how many packages are in each state, without changing anything
+	Status {
+		/// `text`, or `json` for scripts
+		#[clap(long, value_name = "FORMAT", default_value = "text", value_parser = OutputFormat::from_str)]
+		format: OutputFormat
+	},
+	/// resets packages that failed to be contacted and contacts them straight away,
+	/// for when they failed because of something that's been fixed since
+	Retry {
+		/// `RetryPending`, or `NeedsReview` or `Blocked` for the ones there because contacting failed
+		#[clap(long, default_value = "RetryPending")]
+		state: String,
+		/// print the issues and emails that would be sent, without sending or saving anything
+		#[clap(long)]
+		dry_run: bool,
+		/// only retry these packages
+		packages: Vec<String>
+	},
+	/// prints every package, or the ones matching the filters, as a table
+	List {
+		/// like `IssueFiled` or `NeedsReview`
+		#[clap(long)]
+		state: Option<String>,
+		/// only packages with at least this many downloads
+		#[clap(long, value_name = "N", default_value = "0")]
+		min_downloads: u32,
+		/// only packages with at least this many stars
+		#[clap(long, value_name = "N", default_value = "0")]
+		min_stars: u32,
+		/// only packages with this in their name, in any case
+		#[clap(long, value_name = "TEXT")]
+		search: Option<String>,
+		/// only packages whose repository belongs to this user or organisation, in any case
+		#[clap(long, value_name = "OWNER")]
+		owner: Option<String>,
+		/// `downloads`, `stars`, `alphabetical`, or `random`, like `queue.order`
+		#[clap(long, value_name = "ORDER", value_parser = QueueOrder::from_str)]
+		sort: Option<QueueOrder>,
+		/// only print the first this many
+		#[clap(long, short = 'n', value_name = "N")]
+		limit: Option<usize>,
+		/// `text`, or `json` for scripts
+		#[clap(long, value_name = "FORMAT", default_value = "text", value_parser = OutputFormat::from_str)]
+		format: OutputFormat
+	},
+	/// prints everything known about one package: its state and every state it
+	/// was in before, its repository and issue, the last comments seen on the
+	/// issue, and any follow-ups scheduled
+	Show {
+		package: String,
+		/// `text`, or `json` for scripts
+		#[clap(long, value_name = "FORMAT", default_value = "text", value_parser = OutputFormat::from_str)]
+		format: OutputFormat
+	},
+	/// prints approval, decline, and no response rates for the packages whose
+	/// maintainers were asked, overall and by downloads, how long ago they were
+	/// asked, and whether a follow-up was posted
+	Analytics {
+		/// `text`, or `json` for scripts
+		#[clap(long, value_name = "FORMAT", default_value = "text", value_parser = OutputFormat::from_str)]
+		format: OutputFormat
+	},
+	/// prints what the last run (that wasn't a dry run) did with every package it
+	/// went through, how long each took, and what went wrong with the ones that failed
+	LastRun {
+		/// `text`, or `json` for scripts
+		#[clap(long, value_name = "FORMAT", default_value = "text", value_parser = OutputFormat::from_str)]
+		format: OutputFormat
+	},
+	/// files the issues approved in a draft made with `file --draft`
+	Publish {
+		draft: String
+	},
+	/// prints the issue (or email) that would be sent for a package, exactly as
+	/// it would be sent, without contacting anyone
+	Preview {
+		package: String
+	},
+	/// prints what the next run would do, and why packages before that are skipped,
+	/// without changing anything. the pulsar backend and repositories aren't checked,
+	/// `file --dry-run` does that
+	Plan {
+		/// how many issues and emails to show
+		#[clap(long, short = 'n', value_name = "N", default_value = "10")]
+		count: usize
+	},
+	/// records that a maintainer gave permission for their package to be migrated
+	Approve {
+		package: String,
+		/// link to the comment permission was given in (or some other reference to it, for emails)
+		#[clap(long, value_name = "URL")]
+		approval_url: String,
+		/// when permission was given, if it wasn't just now
+		#[clap(long, value_name = "TIMESTAMP", value_parser = parse_timestamp)]
+		at: Option<DateTime<Utc>>
+	},
+	/// records that a maintainer took back their permission
+	Revoke {
+		package: String,
+		/// link to where permission was taken back (or some other reference to it, for emails)
+		#[clap(long, value_name = "URL")]
+		revocation_url: String,
+		/// when permission was taken back, if it wasn't just now
+		#[clap(long, value_name = "TIMESTAMP", value_parser = parse_timestamp)]
+		at: Option<DateTime<Utc>>
+	},
+	/// sets a package's state by hand, for things that happened outside the bot,
+	/// like permission given by email, or the repository being dealt with by hand
+	Mark {
+		package: String,
+		/// like `MaintainerApproved`, `Migrated`, `Blocked`, or `New`
+		state: String,
+		/// why, kept in the package's history (and used as the reason, or the
+		/// approval reference, for states that have one)
+		#[clap(long)]
+		note: Option<String>
+	},
+	/// deletes the personal details stored about a maintainer's packages (names,
+	/// email addresses, comments on their issues, emails written to them),
+	/// keeping their states, for maintainers who ask for their data to be deleted
+	Forget {
+		/// the forge login of the owner, for every package they publish or whose repository they own
+		#[clap(long, value_name = "LOGIN")]
+		owner: String,
+		/// print what would be deleted, without deleting it
+		#[clap(long)]
+		dry_run: bool
+	},
+	/// writes every approved package as json, for importing into the pulsar backend
+	#[clap(alias = "export-consent")]
+	Export {
+		/// file to write to, instead of printing it
+		#[clap(long, short, value_name = "FILE")]
+		output: Option<String>,
+		/// only packages whose repository has this license (like MIT, in any case),
+		/// `none` for ones without one, or `unknown` for ones that weren't checked.
+		/// can be given more than once
+		#[clap(long = "license", value_name = "LICENSE")]
+		licenses: Vec<String>
+	},
+	/// writes every owner's repositories and their packages, and which are asked
+	/// together in one issue, as a graph, for seeing monorepos and owners with a lot
+	/// of packages. most packages first
+	Graph {
+		/// `dot` for graphviz (like `graph | dot -Tsvg > graph.svg`), or `json`
+		#[clap(long, value_name = "FORMAT", default_value = "dot", value_parser = GraphFormat::from_str)]
+		format: GraphFormat,
+		/// file to write to, instead of printing it
+		#[clap(long, short, value_name = "FILE")]
+		output: Option<String>,
+		/// only packages in this state, like `New`
+		#[clap(long)]
+		state: Option<String>,
+		/// only this user or organisation's repositories, in any case
+		#[clap(long, value_name = "OWNER")]
+		owner: Option<String>,
+		/// only owners with at least this many packages
+		#[clap(long, value_name = "N", default_value = "1")]
+		min_packages: usize
+	},
+	/// writes the database, run reports, activity log, and analytics to one
+	/// `.tar.gz`, with a manifest of the files and their checksums, for keeping
+	/// (or publishing) once the migration's over
+	Archive {
+		/// file to write to, instead of `pulsar-migration-<date>.tar.gz`
+		#[clap(long, short, value_name = "FILE")]
+		output: Option<String>
+	},
+	/// closes the issues filed since a given time with an apology, and puts
+	/// their packages back to new, for when something went wrong with a run
+	Rollback {
+		/// like `2022-08-01` or `2022-08-01T12:00:00Z`
+		#[clap(long, value_name = "TIMESTAMP", value_parser = parse_timestamp)]
+		since: DateTime<Utc>,
+		/// print the issues that would be closed, without closing them
+		#[clap(long)]
+		dry_run: bool
+	},
+	/// records issues that were filed by hand (like before the bot existed) as
+	/// `IssueFiled`, for the packages on their repositories, so they're polled and
+	/// followed up like the ones the bot filed
+	Import {
+		/// urls of the issues
+		issues: Vec<String>,
+		/// look for them on the repositories of every package this user or
+		/// organisation owns, by title (`issue_title`, `previous_issue_titles`, and `--title`)
+		#[clap(long, value_name = "OWNER")]
+		owner: Option<String>,
+		/// another title to look for with `--owner`, where `{{package}}` (and so on) matches anything
+		#[clap(long = "title", value_name = "TITLE")]
+		titles: Vec<String>,
+		/// print what would be recorded, without saving anything
+		#[clap(long)]
+		dry_run: bool
+	},
+	/// checks that the issues in the database match the ones on the forges,
+	/// fixing what's drifted where it can
+	Reconcile {
+		/// only check this many filed issues and pending repositories, picked at random
+		#[clap(long, value_name = "N")]
+		sample: Option<usize>,
+		/// print what would be fixed, without saving anything
+		#[clap(long)]
+		dry_run: bool
+	},
+	/// emails the digest (see `digest` in the config) if it's due, for running
+	/// from cron. `daemon` sends it by itself
+	Digest {
+		/// send it even if it's not due yet
+		#[clap(long)]
+		now: bool,
+		/// print it, without sending it
+		#[clap(long)]
+		dry_run: bool
+	},
+	/// keeps running, syncing the registry, checking filed issues for replies, and
+	/// contacting a batch of packages on a schedule (and sending the digest, if there is one)
+	Daemon {
+		/// how often to contact a batch of packages, like `30m`, `6h`, or `1d`
+		#[clap(long, value_name = "INTERVAL", default_value = "6h", value_parser = parse_interval)]
+		batch_every: Duration,
+		/// how often to check filed issues for replies (like `poll-responses`),
+		/// post reminders, and register approved packages (like `poll`)
+		#[clap(long, value_name = "INTERVAL", default_value = "1h", value_parser = parse_interval)]
+		poll_every: Duration,
+		/// how often to sync the registry
+		#[clap(long, value_name = "INTERVAL", default_value = "1d", value_parser = parse_interval)]
+		sync_every: Duration,
+		/// how many issues to file per batch
+		#[clap(long, value_name = "N")]
+		max_issues: Option<usize>
+	},
+	/// prints tab completions for a shell, to save where it loads them from, like
+	/// `completions bash > /etc/bash_completion.d/pulsar-migrator-issue-bot`, or
+	/// `completions zsh > "${fpath[1]}/_pulsar-migrator-issue-bot"`
+	Completions {
+		#[clap(arg_enum, value_name = "SHELL")]
+		shell: Shell
+	}
+}
+
+impl Subcommands {
+	/// whether it only looks at the database. these never move a corrupt one
+	/// aside and start over, since that's for the commands that save it
+	pub fn reads_only(&self) -> bool {
+		use Subcommands::*;
+		match self {
+			Status { .. } | List { .. } | Analytics { .. } | LastRun { .. } | Show { .. } | Preview { .. } | Plan { .. } | Export { .. } | Graph { .. } | Archive { .. } => { true }
+			File { args } | Start { args } => { args.dry_run || args.draft.is_some() }
+			ReplayWebhooks { dry_run } | Import { dry_run, .. } | Reconcile { dry_run, .. } => { *dry_run }
+			_ => { false }
+		}
+	}
+}
+
+/// prints completions for `shell` to stdout
+pub fn print_completions(shell: Shell) {
+	let mut command = Cli::command();
+	let name = command.get_name().to_string();
+	clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+}
+
+#[derive(Args)]
+pub struct FileArgs {
+	/// do all the checks, and print the issues and emails that would be sent,
+	/// without sending anything or saving anything to the database
+	#[clap(long)]
+	dry_run: bool,
+	/// show every issue and email before it's sent, and ask whether to send it
+	#[clap(long)]
+	interactive: bool,
+	/// show how the run is going in a dashboard that can pause, skip, and stop it,
+	/// with everything it'd print going to this file instead (default: dashboard.log)
+	#[clap(
+		long,
+		value_name = "LOG_FILE",
+		min_values = 0,
+		default_missing_value = "dashboard.log",
+		conflicts_with = "interactive"
+	)]
+	pub dashboard: Option<String>,
+	/// stop after filing this many issues
+	#[clap(long, value_name = "N")]
+	max_issues: Option<usize>,
+	/// only sync and contact these packages, or packages from these owners (`package:` or `owner:` to only match one)
+	#[clap(long, value_name = "PACKAGE_OR_OWNER")]
+	only: Vec<String>,
+	/// like --dry-run, but also writes the issues that would be filed to this file,
+	/// for someone to review and then `publish`
+	#[clap(long, value_name = "FILE")]
+	draft: Option<String>,
+	/// write what the run changed (or would have, for dry runs) to this file: the
+	/// packages whose state changed, and the issues filed and emails sent
+	#[clap(long, value_name = "FILE")]
+	diff: Option<String>,
+	/// compare what the run does with a `--diff` file from a dry run, and log
+	/// anything it did differently
+	#[clap(long, value_name = "FILE")]
+	check_diff: Option<String>,
+	/// stop starting on new packages when this is nearly up, like `50m`, for running under a time limit
+	#[clap(long, value_name = "INTERVAL", value_parser = parse_interval)]
+	max_runtime: Option<Duration>,
+	/// stop the run at the first error it's not known why it happened (rather
+	/// than retrying it later), printing everything about it, for early on
+	#[clap(long)]
+	strict: bool
+}
+
+impl FileArgs {
+	pub fn into_options(self) -> RunOptions {
+		RunOptions {
+			dry_run: self.dry_run || self.draft.is_some(),
+			interactive: self.interactive,
+			max_issues: self.max_issues,
+			max_runtime: self.max_runtime,
+			only: (!self.only.is_empty()).then(|| PackageList::parse(&self.only.join("\n"))),
+			draft: self.draft,
+			diff: self.diff,
+			check_diff: self.check_diff,
+			strict: self.strict
+		}
+	}
+}
+
+/// parses intervals like `90s`, `30m`, `6h`, `1d`, or a plain number of seconds
+fn parse_interval(s: &str) -> std::result::Result<Duration, String> {
+	let s = s.trim();
+	let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+		Some(i) => { s.split_at(i) }
+		None => { (s, "s") }
+	};
+
+	let number = number.parse::<u64>()
+		.map_err(|e| format!("invalid interval {s}: {e}"))?;
+	let multiplier = match unit.trim() {
+		"s" => { 1 }
+		"m" => { 60 }
+		"h" => { 60 * 60 }
+		"d" => { 24 * 60 * 60 }
+		unit => { return Err(format!("invalid interval {s}: unknown unit {unit} (expected s, m, h, or d)")) }
+	};
+
+	let secs = number.checked_mul(multiplier)
+		.ok_or_else(|| format!("invalid interval {s}: interval too long"))?;
+	Ok(Duration::from_secs(secs))
+}
+
+/// parses rfc 3339 timestamps, or plain dates (taken as midnight utc)
+fn parse_timestamp(s: &str) -> std::result::Result<DateTime<Utc>, String> {
+	let s = s.trim();
+	if let Ok(timestamp) = DateTime::parse_from_rfc3339(s) {
+		return Ok(timestamp.with_timezone(&Utc))
+	}
+
+	let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+		.map_err(|e| format!("invalid timestamp {s}: {e} (expected something like 2022-08-01 or 2022-08-01T12:00:00Z)"))?;
+	Ok(DateTime::from_utc(date.and_hms(0, 0, 0), Utc))
+}
+
+/// how often the daemon checks if its config file changed, while it's waiting
+const CONFIG_CHECK_EVERY: Duration = Duration::from_secs(5);
+
+/// how often a run logs the [`status::budget_line`], so it's clear why it's waiting if it is
+const BUDGET_LOG_EVERY: Duration = Duration::from_secs(10 * 60);
+
+/// how often [`daemon`] does each thing
+pub struct Schedule {
+	pub sync_every: Duration,
+	/// checking filed issues, see [`responses::poll`] and [`poll`]
+	pub poll_every: Duration,
+	pub batch_every: Duration
+}
+
+/// runs forever (until stopped), syncing the registry, checking filed issues,
+/// and contacting packages, each on its own [`Schedule`]. the db stays loaded the
+/// whole time, and is saved after everything it does. the config is reloaded
+/// when its file changes, see [`ConfigWatch`]
+pub async fn daemon(
+	db: &DatabaseThing,
+	forges: &mut Forges,
+	mailer: &mut Mailer,
+	config: ConfigWatch,
+	schedule: &Schedule,
+	options: &RunOptions
+) -> Result {
+	let mut config = config;
+	let mut control = RunControl::new();
+	let mut next_sync = Instant::now();
+	let mut next_poll = Instant::now();
+	let mut next_batch = resume_batch_at(db).unwrap_or_else(Instant::now);
+	let systemd = Notifier::start(db, control.handle());
+
+	while !control.stopping() {
+		config.reload_if_changed(forges, mailer);
+
+		if Instant::now() >= next_sync {
+			info!("syncing registry");
+			systemd.set(Activity::Syncing);
+			if let Err(e) = sync_registry(db, forges.config(), false, None, Vec::new()).await {
+				error!("error syncing registry: {e}");
+			}
+			db.flush();
+			next_sync = Instant::now() + schedule.sync_every;
+		}
+		if control.stopping() { break }
+
+		// before a batch, so following up doesn't get starved by --max-issues
+		if Instant::now() >= next_poll {
+			info!("checking filed issues");
+			systemd.set(Activity::Polling);
+			if let Err(e) = responses::poll(db, forges).await {
+				error!("error checking issues for replies: {e}");
+			}
+			if let Err(e) = poll(db, forges, options.dry_run).await {
+				error!("error polling issues: {e}");
+			}
+			db.flush();
+			next_poll = Instant::now() + schedule.poll_every;
+		}
+		if control.stopping() { break }
+
+		if Instant::now() >= next_batch {
+			info!("contacting a batch of packages");
+			systemd.set(Activity::Contacting);
+			if let Err(e) = file(db, forges, mailer, &mut control, options).await {
+				error!("error contacting packages: {e}");
+			}
+			db.flush();
+			next_batch = Instant::now() + schedule.batch_every;
+			if let Some(resume_at) = resume_batch_at(db).filter(|resume_at| *resume_at < next_batch) {
+				info!("carrying on with the batch in {}s, when the rate limit resets", resume_at.saturating_duration_since(Instant::now()).as_secs());
+				next_batch = resume_at;
+			}
+		}
+
+		let next = next_sync.min(next_poll).min(next_batch);
+		status::prepare_budget(forges);
+		info!("{}", status::budget_line(db, forges).await);
+		let sleep = next.saturating_duration_since(Instant::now());
+		info!("sleeping for {}s", sleep.as_secs());
+		systemd.set(Activity::Sleeping(Local::now() + chrono::Duration::from_std(sleep).unwrap_or_else(|_| chrono::Duration::zero())));
+		while Instant::now() < next && !control.stopping() {
+			send_digest_if_due(db, forges.config(), mailer).await;
+			control.sleep_until(next.min(Instant::now() + CONFIG_CHECK_EVERY)).await;
+			config.reload_if_changed(forges, mailer);
+		}
+	}
+
+	systemd.set(Activity::Stopping);
+	info!("daemon stopped");
+	Ok(())
+}
+
+/// when a batch that stopped because a host ran out of api requests can be
+/// carried on with, see [`db::Batch::resume_at`]
+fn resume_batch_at(db: &DatabaseThing) -> Option<Instant> {
+	let resume_at = db.batch()?.resume_at?;
+	Some(Instant::now() + (resume_at - Utc::now()).to_std().unwrap_or_default())
+}
+
+async fn send_digest_if_due(db: &DatabaseThing, config: &Config, mailer: &Mailer) {
+	if !config.digest.as_ref().map_or(false, |digest| digest::is_due(db, digest)) { return }
+	if let Err(e) = digest::send_if_due(db, config, mailer, false, false).await {
+		error!("error sending the digest: {e}");
+	}
+}
+
+/// the config file the daemon was started with. when it (or one of the
+/// templates) changes, the new one is used from then on, except for what can't
+/// change without a restart (see [`Config::keep_restart_only`]), which is left
+/// how it was
+pub struct ConfigWatch {
+	path: Option<String>,
+	profile: Option<String>,
+	/// see [`Config::template_files`]
+	template_files: Vec<PathBuf>,
+	/// the config file's, then each of the template files'
+	modified: Vec<Option<SystemTime>>
+}
+
+impl ConfigWatch {
+	pub fn new(path: Option<&str>, profile: Option<&str>, config: &Config) -> Self {
+		let mut watch = Self {
+			path: path.map(String::from),
+			profile: profile.map(String::from),
+			template_files: config.template_files().cloned().collect(),
+			modified: Vec::new()
+		};
+		watch.modified = watch.modified();
+		watch
+	}
+
+	fn path(&self) -> &str {
+		self.path.as_deref().unwrap_or(config::DEFAULT_PATH)
+	}
+
+	fn modified(&self) -> Vec<Option<SystemTime>> {
+		std::iter::once(Path::new(self.path())).chain(self.template_files.iter().map(PathBuf::as_path))
+			.map(|path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok())
+			.collect()
+	}
+
+	fn reload_if_changed(&mut self, forges: &mut Forges, mailer: &mut Mailer) {
+		let modified = self.modified();
+		if modified == self.modified { return }
+		// only the templates changing is reloading the config too, since it's what loads them
+		let changed = match modified.first() == self.modified.first() {
+			true => { "the templates" }
+			false => { self.path() }
+		}.to_string();
+		self.modified = modified;
+
+		// a broken config is left for someone to fix, the one that's loaded is fine to keep going with
+		let mut config = match Config::load(self.path.as_deref(), self.profile.as_deref()) {
+			Ok(config) => { config }
+			Err(e) => {
+				error!("{changed} changed, but can't be used, carrying on with what was loaded before: {e}");
+				return
+			}
+		};
+		let kept = forges.config().keep_restart_only(&mut config);
+		if !kept.is_empty() {
+			warn!("{} changed {}, which needs a restart to use. everything else was reloaded", self.path(), kept.join(", "));
+		} else {
+			info!("reloaded {changed}");
+		}
+		// templates_dir (or template_variants) changing means different files to watch
+		self.template_files = config.template_files().cloned().collect();
+		self.modified = self.modified();
+
+		mailer.set_templates(config.templates.clone());
+		forges.set_config(config);
+	}
+}
+
+/// options for a [`file`] run
+pub struct RunOptions {
+	pub dry_run: bool,
+	pub interactive: bool,
+	pub max_issues: Option<usize>,
+	pub max_runtime: Option<Duration>,
+	/// just these packages, synced from the registry first. retry waits and repository cooldowns don't apply to them
+	pub only: Option<PackageList>,
+	/// see [`staging`]. implies `dry_run`
+	pub draft: Option<String>,
+	/// see [`diff`]
+	pub diff: Option<String>,
+	pub check_diff: Option<String>,
+	/// stop at the first [`Error::is_unexpected`] one, see [`strict_stop`]
+	pub strict: bool
+}
+
+/// asks the operator about each package before contacting it, for interactive runs
+#[derive(Default)]
+struct Confirmer {
+	/// owners the operator said to skip for the rest of the run
+	skipped_owners: HashSet<String>,
+	/// the operator wants to stop
+	quit: bool
+}
+
+impl Confirmer {
+	fn skips(&self, owner: &str) -> bool {
+		self.skipped_owners.contains(&owner.to_lowercase())
+	}
+
+	/// whether to go ahead with contacting `owner`
+	async fn confirm(&mut self, owner: &str) -> Result<bool> {
+		loop {
+			println!("send? [y]es, [n]o, [s]kip everything from {owner}, [q]uit");
+			let (read, answer) = tokio::task::spawn_blocking(|| {
+				let mut line = String::new();
+				std::io::stdin().read_line(&mut line).map(|read| (read, line))
+			}).await??;
+
+			// eof counts as quitting
+			if read == 0 {
+				self.quit = true;
+				return Ok(false)
+			}
+
+			match &*answer.trim().to_lowercase() {
+				"y" | "yes" => { return Ok(true) }
+				"n" | "no" => { return Ok(false) }
+				"s" | "skip" => {
+					self.skipped_owners.insert(owner.to_lowercase());
+					return Ok(false)
+				}
+				"q" | "quit" => {
+					self.quit = true;
+					return Ok(false)
+				}
+				_ => {}
+			}
+		}
+	}
 }
 
-pub async fn read_package_data(db: DatabaseThing, files: Vec<String>) -> Result {
+pub async fn read_package_data(db: DatabaseThing, config: &Config, files: Vec<String>) -> Result {
 	for filename in files.into_iter() {
 		let file_contents = match fs::read(&filename).await {
 			Ok(file) => { file }
 			Err(err) => {
-				println!("error opening file {filename}: {err}");
+				error!("error opening file {filename}: {err}");
 				continue
 			}
 		};
@@ -33,7 +783,7 @@ pub async fn read_package_data(db: DatabaseThing, files: Vec<String>) -> Result
 		let file_str = match String::from_utf8(file_contents) {
 			Ok(file) => { file }
 			Err(err) => {
-				println!("err parsing file {filename} as utf8: {err}");
+				error!("err parsing file {filename} as utf8: {err}");
 				continue
 			}
 		};
@@ -42,51 +792,1150 @@ pub async fn read_package_data(db: DatabaseThing, files: Vec<String>) -> Result
 		match package {
 			Ok(packages) => {
 				for package in packages {
-					db.add_package(&package);
+					import_package(&db, config, package, &filename);
 				}
 			}
 			Err(err) => {
 				let package = serde_json::from_str::<db::PackageNew>(&file_str);
 				match package {
-					Ok(package) => {
-						if db.contains_package(&package.name) {
-							println!("package {} was not added: package with same name already addeed", package.name);
-							continue
-						}
+					Ok(package) => { import_package(&db, config, package, &filename) }
+					Err(err2) => { error!("package had errors!\nerr 1: {err}\nerr 2: {err2}") }
+				}
+			}
+		}
+	}
 
-						if package.repository.r#type != "git" {
-							println!(
-								"package {} was not added: repository does not appear to be a git repository, so it cannot be a github repo, and filing issues on non-github repositories not supported (yet?)",
-								package.name
-							);
-							println!("filename: {filename}");
-							continue
-						}
+	Ok(())
+}
 
-						if !package.repository.url.contains("github.com") {
-							println!(
-								"package {} was not added: repository does not appear to be a github repository, filing issues on non-github repositories not supported (yet?)",
-								package.name
-							);
-							println!("filename: {filename}");
-							continue
-						}
+#[instrument(name = "sync", skip_all, fields(full = full))]
+pub async fn sync_registry(
+	db: &DatabaseThing,
+	config: &Config,
+	full: bool,
+	from_dump: Option<String>,
+	packages: Vec<String>
+) -> Result {
+	if let Some(last_sync) = db.registry_sync_state().last_sync {
+		info!("last registry sync: {last_sync}");
+	}
+
+	let stats = match from_dump {
+		Some(path) => { registry::sync_from_dump(db, config, &path)? }
+		None if config.simulation.packages > 0 => { registry::sync_simulated(db, config)? }
+		None if !packages.is_empty() => {
+			RegistryClient::new(config)?.sync_packages(db, &packages).await?
+		}
+		None => {
+			RegistryClient::new(config)?.sync(db, !full).await?
+		}
+	};
+
+	info!(
+		"registry sync done ({}): {} pages, {} packages seen, {} added, {} updated, {} renamed, {} with refreshed download or star counts, {} skipped because they have no repository, {} themes excluded, {} blocklisted or not on the allowlist",
+		match (stats.from_dump, stats.incremental, stats.resumed) {
+			(true, _, _) => { "from dump" }
+			(_, true, true) => { "incremental, resumed" }
+			(_, true, false) => { "incremental" }
+			(_, false, true) => { "full, resumed" }
+			(_, false, false) => { "full" }
+		},
+		stats.pages,
+		stats.seen,
+		stats.added,
+		stats.updated,
+		stats.renamed,
+		stats.counts_refreshed,
+		stats.no_repository,
+		stats.themes_excluded,
+		stats.not_allowed
+	);
+
+	let shared = db.shared_repositories();
+	if !shared.is_empty() {
+		info!("{} repositories have more than one package in them, they'll get one issue each for all of their packages:", shared.len());
+		for (repo, packages) in shared.iter() {
+			info!("   {repo}: {}", packages.join(", "));
+		}
+	}
+
+	Ok(())
+}
+
+fn import_package(db: &DatabaseThing, config: &Config, package: db::PackageNew, filename: &str) {
+	let package = package.with_owner();
+	if db.contains_package(&package.name) {
+		info!("package {} was not added: package with same name already addeed", package.name);
+		return
+	}
+
+	let forge = RepoLocation::parse(&package.repository.url)
+		.and_then(|repo| repo.forge(config));
+	if forge.is_none() {
+		match package.contact_email() {
+			Some(_) => {
+				info!(
+					"package {}: repository {} is not on a supported forge (github or gitlab), will be contacted by email instead",
+					package.name,
+					package.repository.url
+				);
+			}
+			None => {
+				info!(
+					"package {}: repository {} is not on a supported forge (github or gitlab), and there is no author email to contact instead",
+					package.name,
+					package.repository.url
+				);
+			}
+		}
+		debug!("filename: {filename}");
+	}
+
+	let res = db.add_package(&package);
+	match res {
+		Ok(()) => { info!("package {} was added", package.name) }
+		Err(e) => { error!("package {} was not added: {e}", package.name) }
+	}
+}
 
-						let res = db.add_package(&package);
-						match res {
-							Ok(()) => { println!("package {} was added", package.name) }
-							Err(e) => { println!("package {} was not added: {e}", package.name) }
+/// makes sure nothing gets saved in a dry run
+fn begin_dry_run(db: &DatabaseThing) {
+	if db.is_read_only() { return }
+	info!("dry run, nothing will be filed, emailed, posted, or saved");
+	db.set_read_only();
+}
+
+/// [`poll`], then [`file`]
+pub async fn start(
+	db: &DatabaseThing,
+	forges: &mut Forges,
+	mailer: &Mailer,
+	control: &mut RunControl,
+	options: &RunOptions
+) -> Result<String> {
+	// before anything new, so following up doesn't get starved by --max-issues
+	poll(db, forges, options.dry_run).await?;
+	file(db, forges, mailer, control, options).await
+}
+
+/// keeps up with the issues that have been filed already
+#[instrument(name = "poll", skip_all)]
+pub async fn poll(db: &DatabaseThing, forges: &mut Forges, dry_run: bool) -> Result {
+	usage::in_stage(Stage::Polling, poll_issues(db, forges, dry_run)).await
+}
+
+async fn poll_issues(db: &DatabaseThing, forges: &mut Forges, dry_run: bool) -> Result {
+	if dry_run { begin_dry_run(db) }
+
+	if dry_run || forges.config().filing_window.contains(Utc::now()) {
+		reminders::send_due(db, forges, dry_run).await?;
+	} else {
+		info!("outside the filing window, not posting reminders");
+	}
+
+	// before registering, so nothing that was just taken back gets registered
+	consent::check_revocations(db, forges).await?;
+	if !dry_run {
+		consent::register_approved(db, forges.config()).await?;
+	}
+
+	Ok(())
+}
+
+/// see [`Subcommands::Retry`]
+pub async fn retry(
+	db: &DatabaseThing,
+	forges: &mut Forges,
+	mailer: &Mailer,
+	control: &mut RunControl,
+	state: &str,
+	packages: &[String],
+	dry_run: bool
+) -> Result {
+	if dry_run { begin_dry_run(db) }
+
+	let names = retry::reset(db, state, packages)?;
+	if !dry_run {
+		// whatever tripped them is presumably sorted out, if things are being retried
+		for (host, _) in db.breakers() {
+			info!("carrying on filing on {host}");
+			db.set_breaker(&host, None);
+		}
+	}
+	if names.is_empty() {
+		info!("nothing to retry");
+		return Ok(())
+	}
+	info!("retrying {} packages", names.len());
+
+	let only = names.iter().map(|name| format!("package:{name}")).collect::<Vec<_>>().join("\n");
+	let options = RunOptions {
+		dry_run,
+		interactive: false,
+		max_issues: None,
+		max_runtime: None,
+		only: Some(PackageList::parse(&only)),
+		draft: None,
+		diff: None,
+		check_diff: None,
+		strict: false
+	};
+	file(db, forges, mailer, control, &options).await?;
+	Ok(())
+}
+
+/// returns the summary of the run that gets printed at the end
+#[instrument(name = "file", skip_all)]
+pub async fn file(
+	db: &DatabaseThing,
+	forges: &mut Forges,
+	mailer: &Mailer,
+	control: &mut RunControl,
+	options: &RunOptions
+) -> Result<String> {
+	let started_at = Utc::now();
+	// a bit is kept spare for finishing the package it's on and saving
+	let deadline = options.max_runtime
+		.map(|max_runtime| Instant::now() + max_runtime - (max_runtime / 10).min(Duration::from_secs(60)));
+
+	if options.dry_run { begin_dry_run(db) }
+
+	// read first, so a run isn't done only to find out it can't be checked
+	let expected_diff = match &options.check_diff {
+		Some(path) => { Some(RunDiff::read(path).await?) }
+		None => { None }
+	};
+	let mut recorder = (options.diff.is_some() || expected_diff.is_some()).then(|| DiffRecorder::start(db));
+
+	let mut confirmer = options.interactive.then(Confirmer::default);
+
+	// fetched every run, since it's shared and can change at any time. if it
+	// can't be fetched, it's not safe to contact anyone
+	let opt_out = match &forges.config().opt_out_list {
+		Some(location) => { Some(PackageList::fetch(location).await?) }
+		None => { None }
+	};
+
+	let window = forges.config().filing_window.clone();
+
+	if let Some(only) = &options.only {
+		// the registry might know about things the db doesn't yet
+		let mut names = only.package_names().map(String::from).collect::<HashSet<_>>();
+		names.extend(db.packages().into_iter()
+			.filter(|package| only.matches(&package.info))
+			.map(|package| package.info.name));
+		let names = names.into_iter().collect::<Vec<_>>();
+
+		match RegistryClient::new(forges.config())?.sync_packages(db, &names).await {
+			Ok(stats) => { info!("synced {} packages from the registry ({} added, {} updated)", stats.seen, stats.added, stats.updated) }
+			Err(e) => { error!("error syncing packages from the registry, carrying on with what's in the database: {e}") }
+		}
+	}
+
+	// before looking for pending packages, since any that weren't filed go back to being pending
+	reconcile::settle_filings_in_progress(db, forges).await?;
+
+	let pulsar = if forges.config().check_pulsar_backend {
+		Some(PulsarClient::new(forges.config())?)
+	} else {
+		None
+	};
+
+	let mut packages = Vec::new();
+	let mut deferred = 0;
+	let mut skipped_deprecated = 0;
+	let mut needs_review = 0;
+	let mut blocked = 0;
+	let mut waiting_to_retry = 0;
+	let mut retrying = HashSet::new();
+	for package in db.pending_packages() {
+		if options.only.as_ref().map_or(false, |only| !only.matches(&package.info)) { continue }
+
+		if let PackageState::RetryPending(retry) = &package.state {
+			if retry.retry_at > Utc::now() && options.only.is_none() {
+				waiting_to_retry += 1;
+				continue
+			}
+		}
+
+		match eligibility::check(&package.info, forges.config()) {
+			Eligibility::Eligible => {
+				match package.state {
+					PackageState::Deferred(_) => { db.set_state(&package.info.name, PackageState::New)? }
+					PackageState::RetryPending(_) => { retrying.insert(package.info.name.clone()); }
+					_ => {}
+				}
+				packages.push(package.info);
+			}
+			Eligibility::Deferred(reason) => {
+				deferred += 1;
+				let already_deferred = matches!(&package.state, PackageState::Deferred(d) if d.reason == reason);
+				if !already_deferred {
+					db.set_state(&package.info.name, PackageState::Deferred(db::PackageDeferred {
+						reason,
+						deferred_at: Utc::now()
+					}))?;
+				}
+			}
+			Eligibility::NeedsReview(reason) => {
+				needs_review += 1;
+				warn!("package {} needs review: {reason}", package.info.name);
+				db.set_state(&package.info.name, PackageState::NeedsReview(db::PackageNeedsReview {
+					reason,
+					flagged_at: Utc::now(),
+					contact_failure: None
+				}))?;
+			}
+			Eligibility::Blocked => {
+				blocked += 1;
+				debug!("skipping package {}: on the blocklist", package.info.name);
+				db.set_state(&package.info.name, PackageState::Blocked(db::PackageSkipped {
+					reason: "on the blocklist".into(),
+					skipped_at: Utc::now(),
+					contact_failure: None
+				}))?;
+			}
+			Eligibility::SkipDeprecated(reason) => {
+				skipped_deprecated += 1;
+				debug!("skipping package {}: {reason}", package.info.name);
+				db.set_state(&package.info.name, PackageState::SkippedDeprecated(db::PackageSkipped {
+					reason,
+					skipped_at: Utc::now(),
+					contact_failure: None
+				}))?;
+			}
+		}
+	}
+	db.flush();
+	// dormant packages are a queue of their own, for once there's nothing else
+	if forges.config().dormant_policy == DormantPolicy::Last && packages.is_empty() && waiting_to_retry == 0 {
+		packages.extend(db.packages().into_iter()
+			.filter(|package| matches!(package.state, PackageState::Dormant(_)))
+			.filter(|package| options.only.as_ref().map_or(true, |only| only.matches(&package.info)))
+			.filter(|package| matches!(eligibility::check(&package.info, forges.config()), Eligibility::Eligible))
+			.map(|package| package.info));
+		if !packages.is_empty() {
+			info!("there's nothing else left to contact, so contacting the {} dormant packages", packages.len());
+		}
+	}
+	queue::sort(&mut packages, forges.config().queue_order);
+	// retries go before new work, so failures get another go even if a run is capped
+	packages.sort_by_key(|package| !retrying.contains(&package.name));
+
+	// a run for a few packages isn't picking up where another left off
+	if let Some(batch) = db.batch().filter(|_| options.only.is_none()) {
+		recover_batch(db, &batch, &mut packages);
+	}
+
+	info!("deferred packages (not eligible yet): {deferred}");
+	info!("newly skipped deprecated or unpublished packages: {skipped_deprecated}");
+	info!("newly flagged for review: {needs_review}");
+	info!("newly blocked: {blocked}");
+	info!("being retried: {}", retrying.len());
+	info!("waiting to be retried later: {waiting_to_retry}");
+	print_capability_report(&packages, forges.config());
+
+	if options.only.is_none() {
+		db.set_batch(Some(db::Batch {
+			started_at: Utc::now(),
+			packages: packages.iter().map(|package| package.name.clone()).collect(),
+			progress: 0,
+			resume_at: None
+		}));
+		db.flush();
+	}
+
+	// groups are looked up ahead of time, a few at once, since that's only
+	// reading. contacting them is done one at a time, in order
+	let mut groups = queue::group_by_repository(packages);
+	if let Some(opt_out) = &opt_out {
+		for group in groups.iter_mut() {
+			for package in group.iter().filter(|package| opt_out.matches(package)) {
+				info!("skipping package {}: its owner opted out of being contacted", package.name);
+				db.set_state(&package.name, PackageState::OptedOut(db::PackageSkipped {
+					reason: "owner is on the opt out list".into(),
+					skipped_at: Utc::now(),
+					contact_failure: None
+				}))?;
+			}
+			group.retain(|package| !opt_out.matches(package));
+		}
+		db.flush();
+	}
+	if forges.config().skip_declined_owners {
+		for group in groups.iter_mut() {
+			let repo = match group.first().and_then(|package| RepoLocation::parse(&package.repository.url)) {
+				Some(repo) => { repo }
+				None => { continue }
+			};
+			let declined = db.declined_packages_of(&repo);
+			if declined.is_empty() { continue }
+			for package in group.drain(..) {
+				info!("skipping package {}: its owner declined for {}", package.name, declined.join(", "));
+				db.set_state(&package.name, PackageState::DeclinedByOwnerPolicy(db::PackageSkipped {
+					reason: format!("{} declined for {}", repo.owner, declined.join(", ")),
+					skipped_at: Utc::now(),
+					contact_failure: None
+				}))?;
+			}
+		}
+		db.flush();
+	}
+
+	for group in groups.iter_mut() {
+		if let Some(ContactRoute::Forge(repo, _)) = group.first().map(|package| ContactRoute::for_package(package, forges.config())) {
+			if let Some(until) = db.repo_cooldown(&repo).filter(|_| options.only.is_none()) {
+				info!("skipping {repo}: something failed on it recently, it's left alone until {until}");
+				group.clear();
+			}
+		}
+	}
+
+	// clients need to be created before looking things up, since that's done concurrently
+	for group in groups.iter() {
+		if let Some(ContactRoute::Forge(repo, _)) = group.first().map(|package| ContactRoute::for_package(package, forges.config())) {
+			let _ = forges.client_for(&repo);
+		}
+	}
+	if let Some(canary) = forges.config().canary_repo.clone() {
+		let _ = forges.client_for(&canary);
+	}
+	if forges.config().sandbox.is_some() {
+		let _ = forges.client_for_host(sandbox::HOST);
+	}
+	status::prepare_budget(forges);
+	let forges = &*forges;
+
+	let total = groups.iter().map(Vec::len).sum();
+	control.report(|progress| progress.total = total);
+	// asking about every package needs the terminal to itself
+	let bar = match confirmer {
+		Some(_) => { progress::Bar::hidden() }
+		None => { progress::Bar::new("contacting", Some(total as u64)) }
+	};
+
+	let lookup_concurrency = forges.config().lookup_concurrency;
+	let mut lookups = stream::iter(groups)
+		.map(|group| look_up(db, forges, pulsar.as_ref(), group))
+		.buffered(lookup_concurrency);
+
+	let mut summary = RunSummary { dry_run: options.dry_run, ..Default::default() };
+	let mut results = RunReport::new(options.dry_run);
+	let mut done = 0;
+	let mut canaries_left = forges.config().canary_filings;
+	let mut filed_last_day = db.issues_filed_last_day();
+	let claims = Claims::new(forges.config())?;
+	let mut draft = options.draft.as_ref().map(|_| staging::Draft::new());
+	let mut next_filing = None::<Instant>;
+	let mut told_failure_rate = false;
+	let mut next_budget_log = Instant::now();
+	let mut connectivity = Connectivity::default();
+	let mut breakers = Breakers::default();
+	let mut strict_error = None;
+	'groups: while let Some(lookup) = lookups.next().await {
+		if Instant::now() >= next_budget_log {
+			info!("{}", status::budget_line(db, forges).await);
+			next_budget_log = Instant::now() + BUDGET_LOG_EVERY;
+		}
+		if options.only.is_none() {
+			db.update_batch(|batch| batch.progress = done);
+		}
+		control.report(|progress| {
+			progress.current = None;
+			progress.done = done;
+			progress.issues_filed = summary.issues_filed;
+			progress.failed = summary.failed;
+		});
+		bar.set_position(done);
+		bar.set_message(format!("packages, {} filed, {} emailed, {} failed", summary.issues_filed, summary.emailed, summary.failed));
+
+		let tried = summary.issues_filed + summary.emailed + summary.failed;
+		if !options.dry_run && !told_failure_rate && notify::failure_rate_high(forges.config(), tried, summary.failed) {
+			told_failure_rate = true;
+			let text = format!("**a lot of packages are failing**: {} of the {tried} tried so far this run failed, see the logs", summary.failed);
+			notify::send(forges.config(), &text).await;
+		}
+
+		if options.max_issues.map_or(false, |max| summary.issues_filed >= max) {
+			info!("filed {} issues, stopping (--max-issues)", summary.issues_filed);
+			summary.stopped_early = true;
+			break
+		}
+		let daily_limit = forges.config().daily_issue_limit;
+		if daily_limit > 0 && filed_last_day >= daily_limit {
+			info!("filed {filed_last_day} issues in the last 24 hours, stopping (filing.daily_limit)");
+			summary.stopped_early = true;
+			break
+		}
+		if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+			info!("nearly out of time, stopping (--max-runtime)");
+			summary.stopped_early = true;
+			break
+		}
+		control.wait_if_paused(db).await;
+		if control.stopping() {
+			summary.stopped_early = true;
+			break
+		}
+		// everything before this is fine to do whenever, only contacting is kept to the window
+		if !options.dry_run && !window.contains(Utc::now()) {
+			info!("outside the filing window, stopping");
+			summary.stopped_early = true;
+			break
+		}
+		if confirmer.as_ref().map_or(false, |confirmer| confirmer.quit) { break }
+
+		let (repo, pending, preflight, looked_up_at) = match lookup? {
+			Lookup::Empty => { continue }
+			Lookup::NotForge(group) => {
+				done += group.len();
+				// not filing issues, so there's nothing to share between packages in the same repository
+				for package in group.iter() {
+					control.report(|progress| progress.current = Some(format!("{} (by email)", package.name)));
+					let package_started = Instant::now();
+					let name = std::slice::from_ref(&package.name);
+					match contact_without_forge(db, mailer, pulsar.as_ref(), package, forges.config(), options, confirmer.as_mut()).await? {
+						Outcome::Contacted => {
+							summary.emailed += 1;
+							results.record(name, Action::Emailed, package_started.elapsed());
+							if let Some(recorder) = &mut recorder { recorder.emailed(&package.name) }
 						}
+						Outcome::Failed(e) => {
+							summary.failed += 1;
+							results.failed(name, &e, package_started.elapsed());
+							if let Some(e) = strict_stop(options, &e, &format!("emailing {}", package.name)) {
+								strict_error = Some(e);
+								summary.stopped_early = true;
+								break 'groups
+							}
+						}
+						Outcome::Skipped(reason) => { results.with_reason(name, Action::Skipped, &reason, package_started.elapsed()) }
 					}
-					Err(err2) => { println!("package had errors!\nerr 1: {err}\nerr 2: {err2}") }
 				}
+				continue
 			}
+			Lookup::Forge { repo, group_len, pending, backend_failed, preflight, looked_up_at } => {
+				done += group_len;
+				for (name, e) in backend_failed {
+					let name = std::slice::from_ref(&name);
+					retry::record_failure(db, forges.config(), name, &e)?;
+					summary.failed += 1;
+					results.failed(name, &e, Duration::ZERO);
+					if let Some(e) = strict_stop(options, &e, &format!("checking the pulsar backend for {}", name[0])) {
+						strict_error = Some(e);
+						summary.stopped_early = true;
+						break 'groups
+					}
+				}
+				(repo, pending, preflight, looked_up_at)
+			}
+		};
+		if pending.is_empty() { continue }
+
+		let names = pending.iter()
+			.map(|package| package.name.clone())
+			.collect::<Vec<_>>();
+		if confirmer.as_ref().map_or(false, |confirmer| confirmer.skips(&repo.owner)) {
+			info!("skipping {repo}: skipping everything from {}", repo.owner);
+			results.with_reason(&names, Action::Skipped, &format!("skipping everything from {}", repo.owner), Duration::ZERO);
+			continue
 		}
+
+		let names_str = names.join(", ");
+		if let Some(breaker) = db.breaker(&repo.host).filter(|_| !options.dry_run) {
+			info!("skipping {repo}: too much has failed on {} lately, nothing is filed on it until {}", repo.host, breaker.until);
+			results.with_reason(&names, Action::Skipped, &format!("nothing is filed on {} until {}", repo.host, breaker.until), Duration::ZERO);
+			continue
+		}
+		control.report(|progress| progress.current = Some(format!("{names_str} on {repo}")));
+		// not counting the lookup, since that's done ahead of time alongside others
+		let mut group_started = Instant::now();
+
+		let client = match forges.client(&repo) {
+			Ok(client) => { client }
+			Err(e) => {
+				error!(package = %names_str, owner = %repo.owner, "skipping package {names_str}: {e}");
+				summary.failed += 1;
+				results.failed(&names, &e, group_started.elapsed());
+				if let Some(e) = strict_stop(options, &e, &format!("getting a client for {names_str} on {repo}")) {
+					strict_error = Some(e);
+					summary.stopped_early = true;
+					break
+				}
+				continue
+			}
+		};
+
+		// looked up ahead of time, so it may have been while the network was down
+		let mut preflight = preflight;
+		let mut started = looked_up_at;
+		while let Err(e) = &preflight {
+			match connectivity.wait_out(control, forges.config(), &repo.host, e, started).await {
+				Outage::NotOne => { break }
+				Outage::Over => {
+					started = Instant::now();
+					let dormant_after_years = dormant_after_years(db, forges.config(), &pending);
+					preflight = usage::in_stage(Stage::Preflight, preflight::check(db, &pending, client, &repo, dormant_after_years)).await;
+				}
+				Outage::Stopped => {
+					summary.stopped_early = true;
+					break 'groups
+				}
+			}
+		}
+
+		match preflight {
+			Ok(Preflight::Ok) => {}
+			Ok(Preflight::NeedsReview(reason)) => {
+				warn!(package = %names_str, owner = %repo.owner, "package {names_str} needs review: {reason}");
+				for name in names.iter() {
+					db.set_state(name, PackageState::NeedsReview(db::PackageNeedsReview {
+						reason: reason.clone(),
+						flagged_at: Utc::now(),
+						contact_failure: None
+					}))?;
+				}
+				db.flush();
+				results.with_reason(&names, Action::NeedsReview, &reason, group_started.elapsed());
+				continue
+			}
+			Ok(Preflight::Dormant(reason)) => {
+				info!(package = %names_str, owner = %repo.owner, "skipping package {names_str}: {reason}");
+				for name in names.iter() {
+					db.set_state(name, PackageState::Dormant(db::PackageSkipped {
+						reason: reason.clone(),
+						skipped_at: Utc::now(),
+						contact_failure: None
+					}))?;
+				}
+				db.flush();
+				results.with_reason(&names, Action::Skipped, &reason, group_started.elapsed());
+				continue
+			}
+			Err(e) => {
+				error!(package = %names_str, owner = %repo.owner, "skipping package {names_str}: {e}");
+				summary.failed += 1;
+				results.failed(&names, &e, group_started.elapsed());
+				if !options.dry_run {
+					retry::cool_down(db, forges.config(), &repo, &e);
+					retry::record_failure(db, forges.config(), &names, &e)?;
+					breakers.failed(db, forges.config(), &repo.host, &e).await;
+				}
+				if let Some(e) = strict_stop(options, &e, &format!("checking {names_str} on {repo}")) {
+					strict_error = Some(e);
+					summary.stopped_early = true;
+					break
+				}
+				continue
+			}
+		}
+
+		let (templates, picked) = forges.config().pick_templates(&repo, &pending);
+		let (title, body) = match templates.issue(&Vars::issue(&repo, &pending).with_declined(db.declined_packages_of(&repo))) {
+			Ok(content) => { content }
+			Err(e) => {
+				error!(package = %names_str, owner = %repo.owner, "skipping package {names_str}: {e}");
+				summary.failed += 1;
+				results.failed(&names, &e, group_started.elapsed());
+				if !options.dry_run {
+					retry::record_failure(db, forges.config(), &names, &e)?;
+				}
+				continue
+			}
+		};
+		if options.dry_run || confirmer.is_some() {
+			for package in pending.iter() {
+				println!("package {} ({} downloads, {} stars)", package.name, package.downloads, package.stargazers_count);
+			}
+			if let Some(variant) = &picked.variant {
+				println!("(from template variant {variant})");
+			}
+			if let Some(locale) = &picked.locale {
+				println!("(in {locale})");
+			}
+			println!("issue on {repo}:\n{title}\n\n{body}\n");
+		}
+		if let Some(draft) = &mut draft {
+			draft.proposals.push(staging::Proposal {
+				repository: repo.clone(),
+				packages: names.clone(),
+				title: title.clone(),
+				body: body.clone(),
+				template_variant: picked.variant.clone(),
+				locale: picked.locale.clone(),
+				approved: false
+			});
+		}
+		if options.dry_run {
+			summary.issues_filed += 1;
+			filed_last_day += 1;
+			if let Some(recorder) = &mut recorder { recorder.filed(&repo, &names, &title) }
+			results.record(&names, Action::Filed, group_started.elapsed());
+			continue
+		}
+		if let Some(confirmer) = &mut confirmer {
+			if !confirmer.confirm(&repo.owner).await? {
+				results.with_reason(&names, Action::Skipped, "not confirmed", group_started.elapsed());
+				continue
+			}
+		}
+
+		// from here on it's where the issue's really filed, see [`sandbox`]
+		let (repo, client) = match sandbox::redirect(forges, repo, client) {
+			Ok(redirected) => { redirected }
+			Err(e) => {
+				error!(package = %names_str, "skipping package {names_str}: {e}");
+				summary.failed += 1;
+				results.failed(&names, &e, group_started.elapsed());
+				continue
+			}
+		};
+
+		let host_cooldown = retry::host_cooldown(db, client, &repo.host).await;
+		// rather than sitting there until it resets, which can be most of an hour
+		if let Some(cooldown) = host_cooldown.as_ref().filter(|cooldown| cooldown.exhausted && cooldown.until > Utc::now()) {
+			info!("{} has run out of api requests until {}, stopping. the rest of the batch is carried on with then", repo.host, cooldown.until);
+			if options.only.is_none() {
+				db.update_batch(|batch| batch.resume_at = Some(cooldown.until));
+			}
+			summary.stopped_early = true;
+			break
+		}
+		let host_cooldown = host_cooldown.map(|cooldown| {
+			info!("{} is rate limiting the bot, waiting until {}", repo.host, cooldown.until);
+			Instant::now() + (cooldown.until - Utc::now()).to_std().unwrap_or_default()
+		});
+		if let Some(wait_until) = next_filing.into_iter().chain(host_cooldown).max().filter(|wait_until| *wait_until > Instant::now()) {
+			debug!("waiting {}s before filing the next issue", wait_until.saturating_duration_since(Instant::now()).as_secs());
+			control.report(|progress| progress.waiting_until = Some(wait_until));
+			control.sleep_until(wait_until).await;
+			control.report(|progress| progress.waiting_until = None);
+			if control.stopping() {
+				summary.stopped_early = true;
+				break
+			}
+			if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+				info!("nearly out of time, stopping (--max-runtime)");
+				summary.stopped_early = true;
+				break
+			}
+			group_started = Instant::now();
+		}
+
+		if control.take_skip() {
+			info!("skipping {repo}: skipped by the operator, it's left for another run");
+			results.with_reason(&names, Action::Skipped, "skipped by the operator", group_started.elapsed());
+			continue
+		}
+
+		let claim_key = repo.key();
+		if let Some(claims) = &claims {
+			if !claims.try_claim(&claim_key)? {
+				info!("skipping {repo}: another worker is on it, or already did it");
+				results.with_reason(&names, Action::Skipped, "another worker is on it, or already did it", group_started.elapsed());
+				continue
+			}
+		}
+
+		if let Some(canary) = forges.config().canary_repo.as_ref().filter(|_| canaries_left > 0) {
+			// if this doesn't work, the real one wouldn't have either, so stop before a maintainer sees it
+			let issue_url = match forges.client(canary) {
+				Ok(canary_client) => { usage::in_stage(Stage::Filing, canary_client.create_canary_issue(canary, &repo, &title, &body)).await }
+				Err(e) => { Err(e) }
+			};
+			let issue_url = match issue_url {
+				Ok(issue_url) => { issue_url }
+				Err(e) => {
+					error!(package = %names_str, owner = %repo.owner, "error filing canary issue for package {names_str} on {canary}: {e}");
+					if let Some(claims) = &claims { claims.release(&claim_key)? }
+					summary.failed += 1;
+					results.failed(&names, &e, group_started.elapsed());
+					strict_error = Some(Error::Other(format!("error filing canary issue for package {names_str} on {canary}, stopping: {e}")));
+					summary.stopped_early = true;
+					break 'groups
+				}
+			};
+			info!(package = %names_str, owner = %repo.owner, "filed canary issue for package {names_str}: {issue_url}");
+			canaries_left -= 1;
+		}
+
+		let issue = || filing::Issue { title: &title, body: &body, template_variant: picked.variant.as_deref(), locale: picked.locale.as_deref() };
+		let mut started = Instant::now();
+		let mut filed = usage::in_stage(Stage::Filing, filing::file_issue(db, forges.config(), client, &repo, &names, issue())).await;
+		// filing checks for an issue first, so trying again doesn't make a second one
+		while let Err(e) = &filed {
+			match connectivity.wait_out(control, forges.config(), &repo.host, e, started).await {
+				Outage::NotOne => { break }
+				Outage::Over => {
+					started = Instant::now();
+					filed = usage::in_stage(Stage::Filing, filing::file_issue(db, forges.config(), client, &repo, &names, issue())).await;
+				}
+				Outage::Stopped => {
+					if let Some(claims) = &claims { claims.release(&claim_key)? }
+					retry::record_failure(db, forges.config(), &names, e)?;
+					summary.failed += 1;
+					results.failed(&names, e, group_started.elapsed());
+					summary.stopped_early = true;
+					break 'groups
+				}
+			}
+		}
+		let filed = match filed {
+			Ok(filed) => { filed }
+			Err(e) => {
+				error!(package = %names_str, owner = %repo.owner, "error filing issue for package {names_str} on {repo}: {e}");
+				summary.failed += 1;
+				results.failed(&names, &e, group_started.elapsed());
+				if let Some(claims) = &claims { claims.release(&claim_key)? }
+				retry::cool_down(db, forges.config(), &repo, &e);
+				retry::record_failure(db, forges.config(), &names, &e)?;
+				breakers.failed(db, forges.config(), &repo.host, &e).await;
+				if let Some(e) = strict_stop(options, &e, &format!("filing an issue for {names_str} on {repo}")) {
+					strict_error = Some(e);
+					summary.stopped_early = true;
+					break
+				}
+				continue
+			}
+		};
+		breakers.succeeded(&repo.host);
+		results.filed(&names, &filed.issue_url, filed.already_filed, group_started.elapsed());
+		if !filed.already_filed {
+			summary.issues_filed += 1;
+			filed_last_day += 1;
+			if let Some(recorder) = &mut recorder { recorder.filed(&repo, &names, &title) }
+			next_filing = Some(Instant::now() + filing::next_delay(forges.config()));
+		}
+		if let Some(claims) = &claims { claims.complete(&claim_key)? }
 	}
 
-	Ok(())
+	// unless it stopped for a rate limit, when it's kept for the next run to carry on with
+	if options.only.is_none() && db.batch().map_or(true, |batch| batch.resume_at.is_none()) {
+		db.set_batch(None);
+	}
+	db.flush();
+	control.report(|progress| {
+		progress.current = None;
+		progress.done = done;
+		progress.issues_filed = summary.issues_filed;
+		progress.failed = summary.failed;
+	});
+	drop(bar);
+	summary.processed = done;
+	results.finish(summary.stopped_early);
+	let condensed = report::finish(db, forges.config(), &summary, &results, started_at).await?;
+	if !options.dry_run {
+		if let Err(e) = results.save(&forges.config().db_path).await {
+			warn!("{e}");
+		}
+		db.record_run(db::RunRecord {
+			started_at,
+			finished_at: Utc::now(),
+			processed: summary.processed,
+			issues_filed: summary.issues_filed,
+			emailed: summary.emailed,
+			failed: summary.failed
+		});
+		db.flush();
+	}
+
+	if let (Some(draft), Some(path)) = (draft, &options.draft) {
+		draft.write(path).await?;
+	}
+	if let Some(recorder) = recorder {
+		let diff = recorder.finish(db, options.dry_run);
+		if let Some(path) = &options.diff {
+			diff.write(path).await?;
+		}
+		if let (Some(expected), Some(path)) = (&expected_diff, &options.check_diff) {
+			diff.check_against(expected, path);
+		}
+	}
+
+	match strict_error {
+		Some(e) => { Err(e) }
+		None => { Ok(condensed) }
+	}
+}
+
+/// with `--strict`, the error to stop the run with if `e` (from `what`) is one
+/// it's not known why it happened, so it's looked into rather than retried. it's
+/// been handled like any other by then, so the package is retried once it's sorted out
+fn strict_stop(options: &RunOptions, e: &Error, what: &str) -> Option<Error> {
+	if !options.strict || !e.is_unexpected() { return None }
+	Some(Error::Other(format!("stopping (--strict), unexpected error {what}: {}", e.full_context())))
+}
+
+/// picks up after a run that crashed or stopped for a rate limit partway
+/// through, by putting the packages it hadn't got to yet at the front of the queue
+fn recover_batch(db: &DatabaseThing, batch: &db::Batch, packages: &mut [db::PackageNew]) {
+	info!(
+		"the last run (started {}) {}, it got through {} of {} packages",
+		batch.started_at,
+		match batch.resume_at {
+			Some(_) => { "stopped when it ran out of api requests" }
+			None => { "didn't finish" }
+		},
+		batch.progress,
+		batch.packages.len()
+	);
+
+	queue::resume_first(packages, batch);
+
+	db.set_batch(None);
+	db.flush();
+}
+
+/// the part of contacting a group of packages (from [`queue::group_by_repository`])
+/// that only reads things, so it can be done ahead of time
+enum Lookup {
+	/// every package in the group was skipped
+	Empty,
+	/// not going to get an issue filed, so they're contacted one by one
+	NotForge(Vec<db::PackageNew>),
+	Forge {
+		repo: RepoLocation,
+		group_len: usize,
+		/// packages that aren't on the pulsar backend already
+		pending: Vec<db::PackageNew>,
+		/// packages it couldn't be checked for, by name
+		backend_failed: Vec<(String, Error)>,
+		preflight: Result<Preflight>,
+		/// when the preflight checks were started
+		looked_up_at: Instant
+	}
+}
+
+/// [`Config::dormant_after_years`], unless `packages` are dormant already, and
+/// being contacted since there's nothing else left to
+fn dormant_after_years(db: &DatabaseThing, config: &Config, packages: &[db::PackageNew]) -> u32 {
+	let dormant = packages.iter()
+		.any(|package| db.package(&package.name).map_or(false, |package| matches!(package.state, PackageState::Dormant(_))));
+	if dormant { 0 } else { config.dormant_after_years }
+}
+
+#[instrument(skip_all, fields(package = %group.iter().map(|package| package.name.as_str()).collect::<Vec<_>>().join(", ")))]
+async fn look_up(
+	db: &DatabaseThing,
+	forges: &Forges,
+	pulsar: Option<&PulsarClient>,
+	group: Vec<db::PackageNew>
+) -> Result<Lookup> {
+	let repo = match group.first().map(|package| ContactRoute::for_package(package, forges.config())) {
+		Some(ContactRoute::Forge(repo, _)) => { repo }
+		Some(_) => { return Ok(Lookup::NotForge(group)) }
+		None => { return Ok(Lookup::Empty) }
+	};
+
+	let group_len = group.len();
+	let mut pending = Vec::new();
+	let mut backend_failed = Vec::new();
+	for package in group {
+		match already_migrated(db, pulsar, &package).await {
+			Ok(true) => {}
+			Ok(false) => { pending.push(package) }
+			Err(e) => { backend_failed.push((package.name, e)) }
+		}
+	}
+
+	let looked_up_at = Instant::now();
+	let preflight = match forges.client(&repo) {
+		Ok(client) if !pending.is_empty() => {
+			let dormant_after_years = dormant_after_years(db, forges.config(), &pending);
+			usage::in_stage(Stage::Preflight, preflight::check(db, &pending, client, &repo, dormant_after_years)).await
+		}
+		// reported when the lookup is used
+		_ => { Ok(Preflight::Ok) }
+	};
+
+	Ok(Lookup::Forge { repo, group_len, pending, backend_failed, preflight, looked_up_at })
+}
+
+/// what happened when trying to contact a package
+enum Outcome {
+	Contacted,
+	/// something went wrong, it's still pending
+	Failed(Error),
+	/// and why
+	Skipped(String)
+}
+
+/// what a run did, for its [`report`]
+#[derive(Default)]
+pub struct RunSummary {
+	pub dry_run: bool,
+	/// packages gone through, contacted or not
+	pub processed: usize,
+	/// or would have been, for dry runs
+	pub issues_filed: usize,
+	pub emailed: usize,
+	pub failed: usize,
+	pub stopped_early: bool
+}
+
+
+/// handles a package that isn't going to get an issue filed
+#[instrument(skip_all, fields(package = %package.name))]
+async fn contact_without_forge(
+	db: &DatabaseThing,
+	mailer: &Mailer,
+	pulsar: Option<&PulsarClient>,
+	package: &db::PackageNew,
+	config: &Config,
+	options: &RunOptions,
+	confirmer: Option<&mut Confirmer>
+) -> Result<Outcome> {
+	match ContactRoute::for_package(package, config) {
+		ContactRoute::Forge(..) => { unreachable!("packages on a forge get an issue filed") }
+		ContactRoute::ForgeDisabled(_, forge) => {
+			info!("skipping package {}: forge {forge} is disabled", package.name);
+			return Ok(Outcome::Skipped(format!("forge {forge} is disabled")))
+		}
+		ContactRoute::Email(email) => {
+			// skipping owners by their login if there is one, so it's the same as for issues
+			let owner = package.owner.as_ref()
+				.and_then(|owner| owner.login.clone())
+				.unwrap_or_else(|| email.clone());
+			if let Some(confirmer) = &confirmer {
+				if confirmer.quit { return Ok(Outcome::Skipped("quit".into())) }
+				if confirmer.skips(&owner) {
+					info!("skipping package {}: skipping everything from {owner}", package.name);
+					return Ok(Outcome::Skipped(format!("skipping everything from {owner}")))
+				}
+			}
+
+			match already_migrated(db, pulsar, package).await {
+				Ok(true) => { return Ok(Outcome::Skipped("already on the pulsar backend".into())) }
+				Ok(false) => {}
+				Err(e) => {
+					retry::record_failure(db, config, std::slice::from_ref(&package.name), &e)?;
+					return Ok(Outcome::Failed(e))
+				}
+			}
+
+			if options.dry_run || confirmer.is_some() {
+				let (subject, body) = config.templates.email(&Vars::email(package))?;
+				println!("package {} ({} downloads, {} stars)", package.name, package.downloads, package.stargazers_count);
+				println!("email to {email}:\n{subject}\n\n{body}\n");
+			}
+			// counted as contacted, like issues are in dry runs
+			if options.dry_run { return Ok(Outcome::Contacted) }
+			if let Some(confirmer) = confirmer {
+				if !confirmer.confirm(&owner).await? { return Ok(Outcome::Skipped("not confirmed".into())) }
+			}
+
+			return email_package(db, mailer, config, package, email).await
+		}
+		ContactRoute::Unsupported => {
+			info!(
+				"skipping package {}: repository {} is not on a supported forge, and it can't be contacted by email (no author email, or email fallback is disabled)",
+				package.name,
+				package.repository.url
+			);
+			Ok(Outcome::Skipped("not on a supported forge, and can't be emailed".into()))
+		}
+	}
+}
+
+/// checks if a package is on the pulsar backend already, marking it in the db if it is.
+/// if the check fails, it's an error for the package, which is retried like any other
+async fn already_migrated(db: &DatabaseThing, pulsar: Option<&PulsarClient>, package: &db::PackageNew) -> Result<bool> {
+	let pulsar = match pulsar {
+		Some(pulsar) => { pulsar }
+		None => { return Ok(false) }
+	};
+
+	match pulsar.package_exists(&package.name).await {
+		Ok(false) => { Ok(false) }
+		Ok(true) => {
+			info!("skipping package {}: already on the pulsar backend", package.name);
+			db.set_state(&package.name, PackageState::AlreadyMigrated(db::PackageAlreadyMigrated {
+				found_at: Utc::now()
+			}))?;
+			db.flush();
+			Ok(true)
+		}
+		Err(e) => {
+			error!(package = %package.name, "error checking the pulsar backend for package {}: {e}", package.name);
+			Err(e).context("error checking the pulsar backend")
+		}
+	}
 }
 
-pub async fn start(db: DatabaseThing) {
+/// prints how many of the pending packages are going to be handled by
+/// each forge, so it's clear what a run is actually going to cover
+fn print_capability_report(packages: &[db::PackageNew], config: &Config) {
+	let mut forges = BTreeMap::<String, usize>::new();
+	let mut disabled = BTreeMap::<String, usize>::new();
+	let mut missing_token = BTreeMap::<String, usize>::new();
+	let mut email = 0;
+	let mut unsupported = 0;
+
+	for package in packages {
+		match ContactRoute::for_package(package, config) {
+			ContactRoute::Forge(repo, forge) => {
+				if config.token_for(&repo.host, forge).is_some() || config.simulate_forges.is_some() {
+					*forges.entry(format!("{forge} ({})", repo.host)).or_default() += 1;
+				} else {
+					*missing_token.entry(format!("{forge} ({})", repo.host)).or_default() += 1;
+				}
+			}
+			ContactRoute::ForgeDisabled(_, forge) => { *disabled.entry(forge.to_string()).or_default() += 1 }
+			ContactRoute::Email(_) => { email += 1 }
+			ContactRoute::Unsupported => { unsupported += 1 }
+		}
+	}
+
+	info!("pending packages: {}", packages.len());
+	for (forge, count) in forges.iter() {
+		info!("   {forge}: {count}");
+	}
+	for (forge, count) in missing_token.iter() {
+		info!("   {forge}, no token configured (skipped): {count}");
+	}
+	for (forge, count) in disabled.iter() {
+		info!("   {forge}, disabled (skipped): {count}");
+	}
+	if config.email_fallback {
+		info!("   email: {email}");
+	} else {
+		info!("   email: disabled");
+	}
+	info!("   unsupported host (skipped): {unsupported}");
+}
+
+/// fallback for packages that aren't on a supported forge
+async fn email_package(
+	db: &DatabaseThing,
+	mailer: &Mailer,
+	config: &Config,
+	package: &db::PackageNew,
+	email: String
+) -> Result<Outcome> {
+	let generated = match mailer.send_permission_request(package, &email).await {
+		Ok(generated) => { generated }
+		Err(e) => {
+			error!("error emailing {email} for package {}: {e}", package.name);
+			retry::record_failure(db, config, std::slice::from_ref(&package.name), &e)?;
+			return Ok(Outcome::Failed(e))
+		}
+	};
+
+	if generated.sent {
+		info!("emailed {email} for package {}", package.name);
+		activity::record(db, &format!("emailed the maintainer of package {}", package.name));
+	} else {
+		info!("generated email for package {} at {}, it needs to be sent manually to {email}", package.name, generated.file);
+		activity::record(db, &format!("wrote an email to the maintainer of package {} to {}, to be sent by hand", package.name, generated.file));
+	}
+
+	db.set_state(&package.name, PackageState::ContactedByEmail(db::PackageContactedByEmail {
+		email,
+		email_file: generated.file,
+		sent: generated.sent,
+		contacted_at: Utc::now()
+	}))?;
+	db.flush();
 
+	Ok(Outcome::Contacted)
 }
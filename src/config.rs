@@ -0,0 +1,1398 @@
+//! configuration, read from `config.toml` (see `config.example.toml`). secrets
+//! like tokens are read from files, the keyring, or environment variables (and
+//! `.env` file if present) named in it, see [`crate::secrets`], so the file
+//! itself can be shared. anything in it can
+//! be overridden with `PULSAR_BOT_` environment variables too, see [`ENV_PREFIX`],
+//! or by a profile in it, see [`PROFILE_ENV`]
+
+use crate::api::ApiConfig;
+use crate::db::{ Durability, PackageNew };
+use crate::digest::{ DigestConfig, DigestEvery };
+use crate::eligibility::DormantPolicy;
+use crate::forge::{ self, ForgeAuth, ForgeKind, RepoLocation };
+use crate::lists::PackageList;
+use crate::locale::LocaleConfig;
+use crate::logging::{ LogFile, LogRotation };
+use crate::events::GithubWebhooksConfig;
+use crate::fixtures;
+use crate::github::GithubClient;
+use crate::gitlab::GitlabClient;
+use crate::notify::{ Chat, NotifyConfig };
+use crate::queue::QueueOrder;
+use crate::random;
+use crate::secrets::{ Secret, SecretSource };
+use crate::simulated::{ Popularity, SimulationConfig };
+use crate::templates::{ IssueTitles, Templates };
+use crate::window::{ self, FilingWindow };
+use crate::{ Error, Result };
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+use std::collections::{ BTreeMap, HashMap, HashSet };
+use std::env::var;
+use std::fs;
+use std::path::{ Path, PathBuf };
+
+/// where the config is read from when `--config` isn't given
+pub const DEFAULT_PATH: &str = "config.toml";
+
+/// environment variables starting with this override what's in the config,
+/// with `__` between sections and keys, like `PULSAR_BOT_FILING__DAILY_LIMIT=20`
+/// or `PULSAR_BOT_DB_PATH=/data/state.ron`
+pub const ENV_PREFIX: &str = "PULSAR_BOT_";
+
+/// picks a profile, like `--profile`. profiles are `[profiles.<name>]` tables
+/// with anything from the rest of the file in them, which replace what's there.
+/// if a file has profiles, one of them has to be picked, so a run never ends up
+/// with settings that were only half meant for it
+pub const PROFILE_ENV: &str = "PULSAR_BOT_PROFILE";
+
+/// like `--config` and `--log-format`, so a container can be set up with nothing
+/// but environment variables. they're for the command line, so they aren't overrides
+pub const CONFIG_ENV: &str = "PULSAR_BOT_CONFIG";
+pub const LOG_FORMAT_ENV: &str = "PULSAR_BOT_LOG_FORMAT";
+
+/// shorter `github_webhooks.secret`s get a warning
+const WEBHOOK_SECRET_MIN_LENGTH: usize = 16;
+
+#[derive(Clone)]
+pub struct Config {
+	/// the profile picked, see [`PROFILE_ENV`]
+	pub profile: Option<String>,
+	/// where it was loaded from, for errors: the file, and the profile and
+	/// environment variables if there were any
+	pub source: String,
+	/// file the database is saved in
+	pub db_path: String,
+	/// when saving it waits for it to be on the disk
+	pub db_durability: Durability,
+	/// seconds a run gets to stop after SIGTERM (or ctrl-c), before the
+	/// database is saved and the bot stops anyway, see [`crate::control`]
+	pub stop_timeout: u64,
+	/// what gets posted and sent
+	pub templates: Templates,
+	/// other wordings of the templates, by name. each issue gets one of them at
+	/// random if there are any, which is kept with it, so how they did can be
+	/// compared in `analytics`
+	pub template_variants: BTreeMap<String, Templates>,
+	/// templates in other languages, and who gets them, see [`crate::locale`]
+	pub locales: LocaleConfig,
+	/// every title the bot's issues could have, for finding ones it filed already
+	pub issue_titles: IssueTitles,
+	/// token used for github.com
+	pub github_token: Option<Secret>,
+	/// token used for gitlab.com
+	pub gitlab_token: Option<Secret>,
+	/// hosts that are known to run a specific forge, for self hosted
+	/// instances on custom domains that can't be guessed from the hostname
+	pub forge_hosts: HashMap<String, ForgeKind>,
+	/// tokens for specific hosts, takes priority over `github_token` and `gitlab_token`
+	pub forge_tokens: HashMap<String, Secret>,
+	/// how the tokens for specific hosts are sent, for the ones that aren't the usual way
+	pub forge_auth: HashMap<String, ForgeAuth>,
+	/// whether the token for every forge is checked before a run contacts anything
+	pub check_tokens: bool,
+	/// forges that packages shouldn't be filed on, packages on them are left alone
+	pub disabled_forges: HashSet<ForgeKind>,
+	/// whether to email authors of packages that aren't on a supported forge
+	pub email_fallback: bool,
+	/// packages with less downloads than this are deferred rather than contacted
+	pub min_downloads: u32,
+	/// packages with less stars than this are deferred rather than contacted
+	pub min_stars: u32,
+	/// whether packages whose repository owner doesn't look like the author get flagged for review
+	pub ownership_check: bool,
+	/// packages and owners that are never contacted
+	pub blocklist: PackageList,
+	/// if set, only these packages and owners are contacted
+	pub allowlist: Option<PackageList>,
+	/// file or url of a list of owners who asked to never be contacted by any
+	/// pulsar bot, fetched at the start of every run
+	pub opt_out_list: Option<String>,
+	/// whether an owner saying no for one package (or taking back their permission)
+	/// means none of their other packages are asked about either
+	pub skip_declined_owners: bool,
+	/// packages whose repository hasn't been pushed to in this many years are
+	/// marked dormant when they're checked before filing, 0 to not
+	pub dormant_after_years: u32,
+	/// whether dormant packages are never contacted, or contacted after everything else
+	pub dormant_policy: DormantPolicy,
+	/// order packages are contacted in
+	pub queue_order: QueueOrder,
+	/// whether themes are synced from the registry and contacted
+	pub include_themes: bool,
+	/// base url of the atom.io package registry api
+	pub registry_url: String,
+	/// other registries with the same api to fall back to if `registry_url` keeps failing
+	pub registry_mirrors: Vec<String>,
+	/// how many times to retry a registry request that was rate limited, errored, or timed out
+	pub registry_max_retries: u32,
+	/// registry request timeout, in seconds
+	pub registry_timeout: u64,
+	/// directory to cache registry responses in, `None` to not cache
+	pub registry_cache_dir: Option<String>,
+	/// how long (in seconds) cached registry responses are used without checking if they changed
+	pub registry_cache_max_age: u64,
+	/// how often (in seconds) incremental registry syncs walk the whole registry to refresh download and star counts, 0 to never do that
+	pub counts_refresh_interval: u64,
+	/// how many groups of packages get looked up (pre-flight checks etc) at once, ahead of filing
+	pub lookup_concurrency: usize,
+	/// how many times contacting a package is tried before it's left for someone to look at
+	pub retry_max_attempts: u32,
+	/// most issues to file in any 24 hours, across runs, 0 for no limit
+	pub daily_issue_limit: usize,
+	/// seconds to wait between filing issues, picked at random from this range every time
+	pub filing_delay: (u64, u64),
+	/// hours to leave a repository alone for after something fails on it, 0 to not
+	pub repo_cooldown: u32,
+	/// failures in a row on a forge host before nothing's filed on it for `breaker_cooldown`
+	/// minutes, 0 to never stop, see [`crate::breaker`]
+	pub breaker_failures: u32,
+	pub breaker_cooldown: u32,
+	/// days after filing an issue to post a reminder on it if nobody replied, 0 for never
+	pub follow_up_after: u32,
+	/// days after filing an issue to post the last reminder on it, 0 for never
+	pub final_reminder_after: u32,
+	/// the most issues `poll-responses` checks each run, 0 for no limit
+	pub responses_max_issues: usize,
+	/// api requests `poll-responses` leaves on a forge host, for filing
+	pub responses_min_rate_limit: u32,
+	/// when issues can be filed (and emails and reminders sent)
+	pub filing_window: FilingWindow,
+	/// for testing: if set, forges are replaced with pretend ones that fail this
+	/// fraction (0 to 1) of requests, see [`crate::simulated`]
+	pub simulate_forges: Option<f64>,
+	/// where the api of a host is, instead of where it usually is, like a mock
+	/// server for testing
+	pub forge_api_urls: HashMap<String, String>,
+	/// the proxy recording or replaying responses, for `--record` and `--replay`,
+	/// see [`Self::use_fixtures`]
+	pub fixtures: Option<String>,
+	/// the github organisation every issue is filed in instead, for `--sandbox`,
+	/// see [`Self::use_sandbox`]
+	pub sandbox: Option<String>,
+	/// a test repository the first `canary_filings` issues of every run are filed on first
+	pub canary_repo: Option<RepoLocation>,
+	pub canary_filings: usize,
+	/// base url of the pulsar package backend api
+	pub pulsar_backend_url: String,
+	/// whether to check the pulsar backend for packages that were already migrated before contacting them
+	pub check_pulsar_backend: bool,
+	/// if set, approved packages are registered with the pulsar backend using this token
+	pub pulsar_backend_token: Option<Secret>,
+	/// directory shared between workers to claim repositories in, see [`crate::claims`]
+	pub claims_dir: Option<String>,
+	/// what this worker is called in its claims
+	pub worker_id: String,
+	/// seconds a claim lasts if the worker holding it stops renewing it
+	pub claim_lease: u64,
+	/// if set, every time a package changes state it's posted here as json
+	pub webhook_url: Option<String>,
+	/// if set, github's webhook events are received, see [`crate::events`]
+	pub github_webhooks: Option<GithubWebhooksConfig>,
+	/// if set, everything that's logged is written here too
+	pub log_file: Option<LogFile>,
+	/// where what's done to contact maintainers is written, see [`crate::activity`]
+	pub activity_log: Option<String>,
+	/// address to serve prometheus metrics on, see [`crate::metrics`]
+	pub metrics_listen: Option<String>,
+	/// if set, the json api is served, see [`crate::api`]
+	pub api: Option<ApiConfig>,
+	/// directory run reports are written to, see [`crate::report`]
+	pub report_dir: Option<String>,
+	/// if set, the team's chat is told about some things, see [`crate::notify`]
+	pub notify: Option<NotifyConfig>,
+	/// if set, a digest is emailed every so often, see [`crate::digest`]
+	pub digest: Option<DigestConfig>,
+	/// if set, errors and panics are sent to sentry, see [`crate::reporting`]
+	pub sentry_dsn: Option<Secret>,
+	/// what sentry is told this is, like `production` or `staging`. if it isn't
+	/// set, it's the profile
+	pub sentry_environment: Option<String>,
+	/// if set, spans are sent to this opentelemetry collector, see [`crate::traces`]
+	pub otlp_endpoint: Option<String>,
+	/// made up packages, and how the pretend forges' maintainers answer, see [`crate::simulated`]
+	pub simulation: SimulationConfig,
+	/// directory generated emails get written to
+	pub email_outbox: String,
+	/// if set, generated emails are also sent using this server
+	pub smtp: Option<SmtpConfig>
+}
+
+/// see [`Config::check`]
+pub struct Problem {
+	/// what it's about, like `filing.delay_max`
+	pub key: &'static str,
+	pub message: String,
+	/// if it is, nothing runs with the config. otherwise it's just probably not what was meant
+	pub fatal: bool
+}
+
+#[derive(Clone, PartialEq)]
+pub struct SmtpConfig {
+	pub host: String,
+	pub port: Option<u16>,
+	pub username: String,
+	pub password: Secret,
+	/// address the emails are sent from, like `Pulsar <someone@example.com>`
+	pub from: String
+}
+
+impl Config {
+	/// reads the config from `path`, or [`DEFAULT_PATH`], with `profile` (or the
+	/// one in [`PROFILE_ENV`]) on top. everything has a default, so it's fine for
+	/// there to be no file at the default path
+	pub fn load(path: Option<&str>, profile: Option<&str>) -> Result<Self> {
+		let config = Self::load_unchecked(path, profile)?;
+		if let Some(problem) = config.check().into_iter().find(|problem| problem.fatal) {
+			return Err(Error::Config(format!("error in {}: {}: {}", config.source, problem.key, problem.message)))
+		}
+		Ok(config)
+	}
+
+	/// [`load`](Self::load), without [`check`](Self::check)ing how the settings go together
+	pub fn load_unchecked(path: Option<&str>, profile: Option<&str>) -> Result<Self> {
+		let (path, file) = match path {
+			Some(path) => {
+				let contents = fs::read_to_string(path)
+					.map_err(|e| Error::Config(format!("error reading config {path}: {e}")))?;
+				(path, contents)
+			}
+			None if Path::new(DEFAULT_PATH).exists() => {
+				let contents = fs::read_to_string(DEFAULT_PATH)
+					.map_err(|e| Error::Config(format!("error reading config {DEFAULT_PATH}: {e}")))?;
+				(DEFAULT_PATH, contents)
+			}
+			None => { (DEFAULT_PATH, String::new()) }
+		};
+
+		let mut value = toml::from_str::<toml::Value>(&file)
+			.map_err(|e| Error::Config(format!("error in {path}: {e}")))?;
+		let profile = profile.map(String::from)
+			.or_else(|| var(PROFILE_ENV).ok())
+			.filter(|profile| !profile.is_empty());
+		apply_profile(&mut value, profile.as_deref()).map_err(|e| Error::Config(format!("error in {path}: {e}")))?;
+		let overridden = apply_env_overrides(&mut value)?;
+
+		let (source, file) = if overridden.is_empty() && profile.is_none() {
+			let file = toml::from_str::<ConfigFile>(&file)
+				.map_err(|e| Error::Config(format!("error in {path}: {e}")))?;
+			(path.to_string(), file)
+		} else {
+			let mut source = path.to_string();
+			if let Some(profile) = &profile {
+				source += &format!(" (profile {profile})");
+			}
+			if !overridden.is_empty() {
+				source += &format!(" (or {})", overridden.join(", "));
+			}
+			// going through a string again, so errors say which key they're for. the
+			// line numbers are for the string, not the file, so they're left off
+			let file = toml::to_string(&value)
+				.map_err(|e| e.to_string())
+				.and_then(|value| toml::from_str::<ConfigFile>(&value).map_err(|e| e.to_string()))
+				.map_err(|e| Error::Config(format!("error in {source}: {}", e.split(" at line ").next().unwrap_or(&e))))?;
+			(source, file)
+		};
+		let config = Self::from_file(file).map_err(|e| Error::Config(format!("error in {source}: {e}")))?;
+		Ok(Self { profile, source, ..config })
+	}
+
+	/// settings that don't make sense together. each one is fine by itself, or it
+	/// wouldn't have loaded
+	pub fn check(&self) -> Vec<Problem> {
+		let mut problems = Vec::new();
+		let mut problem = |key, fatal, message: String| { problems.push(Problem { key, message, fatal }) };
+
+		let (delay_min, delay_max) = self.filing_delay;
+		if delay_max < delay_min {
+			problem("filing.delay_max", true, format!("{delay_max} is less than filing.delay_min ({delay_min})"));
+		}
+		if self.follow_up_after > 0 && self.final_reminder_after > 0 && self.final_reminder_after <= self.follow_up_after {
+			problem("reminders.final_reminder_after", true, format!(
+				"{} days is before the first reminder (reminders.follow_up_after, {} days), the last one has to come after it",
+				self.final_reminder_after,
+				self.follow_up_after
+			));
+		}
+		if self.canary_repo.is_some() && self.canary_filings == 0 {
+			problem("filing.canary_filings", false, "is 0, so filing.canary_repo is never used".into());
+		}
+		if self.registry_cache_max_age > 0 && self.counts_refresh_interval > 0 && self.registry_cache_max_age >= self.counts_refresh_interval {
+			problem("registry.cache_max_age", false, format!(
+				"cached pages are used for longer than registry.counts_refresh_interval ({}s), so refreshing the counts gets the cached ones",
+				self.counts_refresh_interval
+			));
+		}
+		if self.registry_mirrors.contains(&self.registry_url) {
+			problem("registry.mirrors", false, format!("has {} in it, which is registry.url already", self.registry_url));
+		}
+		if self.simulation.packages > 0 && self.simulate_forges.is_none() {
+			problem("simulation.packages", true, "is set, but forges.simulate isn't, so the made up packages would be filed on real forges".into());
+		}
+		if self.simulation.reply_rate > 0.0 && self.simulate_forges.is_none() {
+			problem("simulation.reply_rate", false, "is set, but forges.simulate isn't, so there aren't any pretend maintainers to reply".into());
+		}
+		if self.smtp.is_some() && !self.email_fallback {
+			problem("email.smtp", false, "is set, but email.fallback is off, so nothing is ever emailed".into());
+		}
+		if self.digest.is_some() && self.smtp.is_none() {
+			problem("digest.to", false, "is set, but email.smtp isn't, so digests are only written to email.outbox".into());
+		}
+		if self.disabled_forges.contains(&ForgeKind::GitHub) && self.disabled_forges.contains(&ForgeKind::GitLab) && !self.email_fallback {
+			problem("forges.disabled", false, "has every forge in it, and email.fallback is off, so nothing can be contacted".into());
+		}
+		for (host, auth) in self.forge_auth.iter() {
+			let kind = forge::forge_for_host(host, self);
+			if kind.map_or(true, |kind| self.token_for(host, kind).is_none()) {
+				problem("forges.auth", false, format!("has {host}, but there's no token for it"));
+			} else if *auth == ForgeAuth::OAuth && kind == Some(ForgeKind::GitHub) {
+				problem("forges.auth", false, format!("has {host} as oauth, but github takes oauth tokens like any other, so it doesn't change anything"));
+			}
+		}
+		match self.github_webhooks.as_ref().map(|github_webhooks| &github_webhooks.secret) {
+			Some(None) => { problem("github_webhooks.secret", false, "isn't set, so github_webhooks.listen is never listened on".into()) }
+			Some(Some(secret)) if secret.expose().len() < WEBHOOK_SECRET_MIN_LENGTH => {
+				problem("github_webhooks.secret", false, format!(
+					"is shorter than {WEBHOOK_SECRET_MIN_LENGTH} characters, so it could be guessed and events forged with it"
+				));
+			}
+			_ => {}
+		}
+		match &self.api {
+			Some(api) if api.tokens.is_empty() && !api.public && !api.public_feed => {
+				problem("api.tokens", false, "isn't set, and api.public is off, so api.listen is never listened on".into());
+			}
+			Some(api) if !api.tokens.is_empty() && api.public => {
+				problem("api.public", false, "is on, so api.tokens aren't needed, and anyone can read the api".into());
+			}
+			_ => {}
+		}
+
+		problems
+	}
+
+	fn from_file(file: ConfigFile) -> std::result::Result<Self, String> {
+		let ConfigFile { db_path, db_durability, stop_timeout, templates_dir, template_variants, locales, previous_issue_titles, forges, eligibility, queue, filing, reminders, responses, registry, pulsar, email, claims, webhook, github_webhooks, log, metrics, api, reports, notify, digest, sentry, otlp, simulation } = file;
+
+		let templates = Templates::load(&templates_dir.as_deref().into_iter().collect::<Vec<_>>(), &pulsar.backend_url).map_err(|e| format!("templates_dir: {e}"))?;
+		let template_variants = template_variants.into_iter()
+			.map(|(name, dir)| {
+				let variant = load_templates_over(&format!("template_variants.{name}"), &dir, templates_dir.as_deref(), &pulsar.backend_url)?;
+				Ok((name, variant))
+			})
+			.collect::<std::result::Result<BTreeMap<_, _>, String>>()?;
+		let locales = LocaleConfig {
+			templates: locales.templates.into_iter()
+				.map(|(locale, dir)| {
+					let localized = load_templates_over(&format!("locales.templates.{locale}"), &dir, templates_dir.as_deref(), &pulsar.backend_url)?;
+					Ok((locale, localized))
+				})
+				.collect::<std::result::Result<_, String>>()?,
+			packages: locales.packages,
+			owners: locales.owners.into_iter().map(|(owner, locale)| (owner.to_lowercase(), locale)).collect(),
+			detect: locales.detect
+		};
+		let issue_titles = IssueTitles::new(
+			std::iter::once(&templates)
+				.chain(template_variants.values())
+				.chain(locales.templates.values())
+				.map(|templates| templates.issue_title.as_str())
+				.chain(previous_issue_titles.iter().map(String::as_str))
+		);
+		for (key, locale) in locales.packages.iter().map(|(name, locale)| (format!("locales.packages.{name}"), locale))
+			.chain(locales.owners.iter().map(|(owner, locale)| (format!("locales.owners.{owner}"), locale)))
+		{
+			if !locales.templates.contains_key(locale) {
+				return Err(format!("{key}: there aren't any templates for {locale}, add them to locales.templates"))
+			}
+		}
+
+		let github_token = SecretSource {
+			key: "forges.github_token",
+			env: Some(&forges.github_token_env),
+			file: forges.github_token_file.as_deref(),
+			keyring: forges.github_token_keyring.as_deref()
+		}.load()?;
+		let gitlab_token = SecretSource {
+			key: "forges.gitlab_token",
+			env: Some(&forges.gitlab_token_env),
+			file: forges.gitlab_token_file.as_deref(),
+			keyring: forges.gitlab_token_keyring.as_deref()
+		}.load()?;
+		let forge_hosts = forges.hosts.into_iter()
+			.map(|(host, kind)| {
+				let kind = ForgeKind::from_name(&kind)
+					.ok_or_else(|| format!("forges.hosts: unknown forge {kind} for host {host} (expected github or gitlab)"))?;
+				Ok((host.to_lowercase(), kind))
+			})
+			.collect::<std::result::Result<_, String>>()?;
+		let mut forge_tokens = HashMap::new();
+		let token_hosts = forges.token_envs.keys()
+			.chain(forges.token_files.keys())
+			.chain(forges.token_keyrings.keys())
+			.collect::<HashSet<_>>();
+		for host in token_hosts {
+			let token = SecretSource {
+				key: &format!("forges token for {host}"),
+				env: forges.token_envs.get(host).map(String::as_str),
+				file: forges.token_files.get(host).map(String::as_str),
+				keyring: forges.token_keyrings.get(host).map(String::as_str)
+			}.load()?;
+			if let Some(token) = token {
+				forge_tokens.insert(host.to_lowercase(), token);
+			}
+		}
+		let forge_auth = forges.auth.into_iter()
+			.map(|(host, auth)| {
+				let auth = ForgeAuth::from_name(&auth)
+					.ok_or_else(|| format!("forges.auth: unknown way to send a token {auth} for host {host} (expected token or oauth)"))?;
+				Ok((host.to_lowercase(), auth))
+			})
+			.collect::<std::result::Result<_, String>>()?;
+		let disabled_forges = forges.disabled.iter()
+			.map(|forge| {
+				ForgeKind::from_name(forge)
+					.ok_or_else(|| format!("forges.disabled: unknown forge {forge} (expected github or gitlab)"))
+			})
+			.collect::<std::result::Result<_, String>>()?;
+		if let Some(rate) = forges.simulate {
+			if !(0.0..=1.0).contains(&rate) {
+				return Err(format!("forges.simulate: failure rate {rate} isn't between 0 and 1"))
+			}
+		}
+
+		let blocklist = match &eligibility.blocklist_file {
+			Some(path) => { PackageList::load(path).map_err(|e| format!("eligibility.blocklist_file: {e}"))? }
+			None => { PackageList::default() }
+		};
+		let allowlist = match &eligibility.allowlist_file {
+			Some(path) => { Some(PackageList::load(path).map_err(|e| format!("eligibility.allowlist_file: {e}"))?) }
+			None => { None }
+		};
+
+		let db_durability = db_durability.parse::<Durability>().map_err(|e| format!("db_durability: {e}"))?;
+		let queue_order = queue.order.parse::<QueueOrder>().map_err(|e| format!("queue.order: {e}"))?;
+		let dormant_policy = eligibility.dormant.parse::<DormantPolicy>().map_err(|e| format!("eligibility.dormant: {e}"))?;
+
+		let filing_window = FilingWindow {
+			days: match filing.days.as_deref().filter(|days| !days.is_empty()) {
+				Some(days) => { Some(window::parse_days(days).map_err(|e| format!("filing.days: {e}"))?) }
+				None => { None }
+			},
+			hours: match filing.hours.as_deref().filter(|hours| !hours.is_empty()) {
+				Some(hours) => { Some(window::parse_hours(hours).map_err(|e| format!("filing.hours: {e}"))?) }
+				None => { None }
+			}
+		};
+		let filing_delay_max = filing.delay_max.unwrap_or(filing.delay_min);
+		let canary_repo = match filing.canary_repo.as_deref().filter(|repo| !repo.is_empty()) {
+			Some(repo) => {
+				let parsed = RepoLocation::parse(repo)
+					.or_else(|| RepoLocation::parse(&format!("https://{repo}")))
+					.ok_or_else(|| format!("filing.canary_repo: can't tell what repository {repo} is"))?;
+				Some(parsed)
+			}
+			None => { None }
+		};
+
+		let smtp = match email.smtp {
+			Some(smtp) => {
+				let password = SecretSource {
+					key: "email.smtp.password",
+					env: Some(&smtp.password_env),
+					file: smtp.password_file.as_deref(),
+					keyring: smtp.password_keyring.as_deref()
+				}.load()?
+					.ok_or_else(|| format!("email.smtp.password_env: environment variable {} isn't set (or set password_file or password_keyring)", smtp.password_env))?;
+				Some(SmtpConfig {
+					host: smtp.host,
+					port: smtp.port,
+					username: smtp.username,
+					password,
+					from: smtp.from
+				})
+			}
+			None => { None }
+		};
+
+		let pulsar_backend_token = SecretSource {
+			key: "pulsar.token",
+			env: Some(&pulsar.token_env),
+			file: pulsar.token_file.as_deref(),
+			keyring: pulsar.token_keyring.as_deref()
+		}.load()?;
+
+		let sentry_dsn = SecretSource {
+			key: "sentry.dsn",
+			env: Some(&sentry.dsn_env),
+			file: sentry.dsn_file.as_deref(),
+			keyring: sentry.dsn_keyring.as_deref()
+		}.load()?;
+		if let Some(dsn) = &sentry_dsn {
+			// don't say what it is, there's a key in it
+			dsn.expose().parse::<sentry::types::Dsn>().map_err(|e| format!("sentry.dsn: isn't a sentry dsn: {e}"))?;
+		}
+
+		let otlp_endpoint = otlp.endpoint.filter(|endpoint| !endpoint.is_empty());
+		if let Some(endpoint) = &otlp_endpoint {
+			url::Url::parse(endpoint).map_err(|e| format!("otlp.endpoint: {endpoint} isn't a url: {e}"))?;
+		}
+
+		for (key, rate) in [("reply_rate", simulation.reply_rate), ("approval_rate", simulation.approval_rate)] {
+			if !(0.0..=1.0).contains(&rate) {
+				return Err(format!("simulation.{key}: {rate} isn't between 0 and 1"))
+			}
+		}
+		let simulation = SimulationConfig {
+			packages: simulation.packages,
+			popularity: simulation.popularity.parse::<Popularity>().map_err(|e| format!("simulation.popularity: {e}"))?,
+			max_downloads: simulation.max_downloads,
+			reply_rate: simulation.reply_rate,
+			approval_rate: simulation.approval_rate,
+			seed: simulation.seed
+		};
+
+		let github_webhooks = match github_webhooks.listen.filter(|listen| !listen.is_empty()) {
+			Some(listen) => {
+				let secret = SecretSource {
+					key: "github_webhooks.secret",
+					env: Some(&github_webhooks.secret_env),
+					file: github_webhooks.secret_file.as_deref(),
+					keyring: github_webhooks.secret_keyring.as_deref()
+				}.load()?;
+				Some(GithubWebhooksConfig { listen, secret })
+			}
+			None => { None }
+		};
+
+		let api = match api.listen.filter(|listen| !listen.is_empty()) {
+			Some(listen) => {
+				let tokens = SecretSource {
+					key: "api.tokens",
+					env: Some(&api.tokens_env),
+					file: api.tokens_file.as_deref(),
+					keyring: api.tokens_keyring.as_deref()
+				}.load()?;
+				Some(ApiConfig { listen, tokens: tokens.map(|tokens| tokens.split()).unwrap_or_default(), public: api.public, public_feed: api.public_feed })
+			}
+			None => { None }
+		};
+
+		let activity_log = match log.activity {
+			Some(path) => { Some(path).filter(|path| !path.is_empty()) }
+			None => { Some(format!("{db_path}.activity.log")) }
+		};
+		let log_file = match log.file.filter(|file| !file.is_empty()) {
+			Some(path) => {
+				let rotate = log.rotate.parse::<LogRotation>().map_err(|e| format!("log.rotate: {e}"))?;
+				Some(LogFile { path, rotate, keep: log.keep })
+			}
+			None => { None }
+		};
+
+		let notify = match notify.url.filter(|url| !url.is_empty()) {
+			Some(url) => {
+				if !(0.0..=1.0).contains(&notify.failure_rate) {
+					return Err(format!("notify.failure_rate: {} isn't between 0 and 1", notify.failure_rate))
+				}
+				Some(NotifyConfig { chat: Chat::for_url(&url), url, failure_rate: notify.failure_rate, min_downloads: notify.min_downloads })
+			}
+			None => { None }
+		};
+
+		let digest = match digest.to.is_empty() {
+			true => { None }
+			false => {
+				let every = digest.every.parse::<DigestEvery>().map_err(|e| format!("digest.every: {e}"))?;
+				Some(DigestConfig { to: digest.to, every })
+			}
+		};
+
+		let worker_id = claims.worker_id.filter(|id| !id.is_empty()).unwrap_or_else(|| {
+			let host = var("HOSTNAME").unwrap_or_else(|_| "worker".into());
+			format!("{host}-{}", std::process::id())
+		});
+
+		Ok(Self {
+			profile: None,
+			source: String::new(),
+			db_path,
+			db_durability,
+			stop_timeout,
+			templates,
+			template_variants,
+			locales,
+			issue_titles,
+			github_token,
+			gitlab_token,
+			forge_hosts,
+			forge_tokens,
+			forge_auth,
+			check_tokens: forges.check_tokens,
+			disabled_forges,
+			email_fallback: email.fallback,
+			min_downloads: eligibility.min_downloads,
+			min_stars: eligibility.min_stars,
+			ownership_check: eligibility.ownership_check,
+			blocklist,
+			allowlist,
+			opt_out_list: eligibility.opt_out_list.filter(|list| !list.is_empty()),
+			skip_declined_owners: eligibility.skip_declined_owners,
+			dormant_after_years: eligibility.dormant_after_years,
+			dormant_policy,
+			queue_order,
+			include_themes: eligibility.include_themes,
+			registry_url: registry.url,
+			registry_mirrors: registry.mirrors,
+			registry_max_retries: registry.max_retries,
+			registry_timeout: registry.timeout,
+			registry_cache_dir: Some(registry.cache_dir).filter(|dir| !dir.trim().is_empty()),
+			registry_cache_max_age: registry.cache_max_age,
+			counts_refresh_interval: registry.counts_refresh_interval,
+			lookup_concurrency: queue.lookup_concurrency.max(1),
+			retry_max_attempts: filing.retry_max_attempts,
+			daily_issue_limit: filing.daily_limit,
+			filing_delay: (filing.delay_min, filing_delay_max),
+			repo_cooldown: filing.repo_cooldown,
+			breaker_failures: filing.breaker_failures,
+			breaker_cooldown: filing.breaker_cooldown,
+			follow_up_after: reminders.follow_up_after,
+			final_reminder_after: reminders.final_reminder_after,
+			responses_max_issues: responses.max_issues,
+			responses_min_rate_limit: responses.min_rate_limit,
+			filing_window,
+			simulate_forges: forges.simulate,
+			forge_api_urls: forges.api_urls.into_iter().map(|(host, url)| (host.to_lowercase(), url)).collect(),
+			fixtures: None,
+			sandbox: None,
+			canary_repo,
+			canary_filings: filing.canary_filings,
+			pulsar_backend_url: pulsar.backend_url,
+			check_pulsar_backend: pulsar.check_backend,
+			pulsar_backend_token,
+			claims_dir: claims.dir.filter(|dir| !dir.is_empty()),
+			worker_id,
+			claim_lease: claims.lease.max(1),
+			webhook_url: webhook.url.filter(|url| !url.is_empty()),
+			github_webhooks,
+			log_file,
+			activity_log,
+			metrics_listen: metrics.listen.filter(|listen| !listen.is_empty()),
+			api,
+			report_dir: Some(reports.dir).filter(|dir| !dir.trim().is_empty()),
+			notify,
+			digest,
+			sentry_dsn,
+			sentry_environment: sentry.environment.filter(|environment| !environment.is_empty()),
+			otlp_endpoint,
+			simulation,
+			email_outbox: email.outbox,
+			smtp
+		})
+	}
+
+	/// for reloading the config while running: puts back everything in `new` that's
+	/// only read at startup (the database, credentials, and what the forges, mailer,
+	/// and webhook were set up with), and returns what was changed in it anyway
+	pub fn keep_restart_only(&self, new: &mut Config) -> Vec<&'static str> {
+		if let Some(fixtures) = &self.fixtures {
+			new.use_fixtures(fixtures.clone());
+		}
+		if let Some(org) = &self.sandbox {
+			new.use_sandbox(org.clone());
+		}
+		let mut kept = Vec::new();
+		macro_rules! keep {
+			($($field:ident: $key:literal),*) => {
+				$(
+					if new.$field != self.$field {
+						new.$field = self.$field.clone();
+						kept.push($key);
+					}
+				)*
+			};
+		}
+		keep!(
+			db_path: "db_path",
+			db_durability: "db_durability",
+			stop_timeout: "stop_timeout",
+			github_token: "forges.github_token_*",
+			gitlab_token: "forges.gitlab_token_*",
+			forge_tokens: "forges.token_*",
+			forge_auth: "forges.auth",
+			simulate_forges: "forges.simulate",
+			forge_api_urls: "forges.api_urls",
+			pulsar_backend_token: "pulsar.token_*",
+			smtp: "email.smtp",
+			email_outbox: "email.outbox",
+			webhook_url: "webhook.url",
+			github_webhooks: "github_webhooks",
+			log_file: "log",
+			activity_log: "log.activity",
+			metrics_listen: "metrics.listen",
+			api: "api",
+			notify: "notify",
+			sentry_dsn: "sentry.dsn_*",
+			sentry_environment: "sentry.environment",
+			otlp_endpoint: "otlp.endpoint",
+			simulation: "simulation"
+		);
+		kept
+	}
+
+	/// points the registry, the forges, and the pulsar backend at the proxy at
+	/// `base`, which records or replays their responses, see [`crate::fixtures`]
+	pub fn use_fixtures(&mut self, base: String) {
+		self.registry_url = fixtures::proxied(&base, &self.registry_url);
+		for mirror in self.registry_mirrors.iter_mut() {
+			*mirror = fixtures::proxied(&base, mirror);
+		}
+		self.pulsar_backend_url = fixtures::proxied(&base, &self.pulsar_backend_url);
+		for url in self.forge_api_urls.values_mut() {
+			*url = fixtures::proxied(&base, url);
+		}
+		self.fixtures = Some(base);
+	}
+
+	/// files every issue in a repository in `org` instead, with a database of its
+	/// own, and without anything else that reaches maintainers, see [`crate::sandbox`]
+	pub fn use_sandbox(&mut self, org: String) {
+		self.db_path = format!("{}.sandbox", self.db_path);
+		if let Some(activity_log) = &mut self.activity_log {
+			*activity_log = format!("{activity_log}.sandbox");
+		}
+		self.smtp = None;
+		self.pulsar_backend_token = None;
+		self.claims_dir = None;
+		self.sandbox = Some(org);
+	}
+
+	/// the templates for a new issue on `repo` for `packages`: the ones in its
+	/// [`locale`](Self::locales) if it has one, or otherwise one of
+	/// [`Self::template_variants`] if there are any
+	pub fn pick_templates(&self, repo: &RepoLocation, packages: &[PackageNew]) -> (&Templates, PickedTemplates) {
+		if let Some(locale) = self.locales.for_issue(repo, packages) {
+			return (&self.locales.templates[locale], PickedTemplates { variant: None, locale: Some(locale.into()) })
+		}
+		let names = self.template_variants.keys().collect::<Vec<_>>();
+		match random::with(|rng| names.choose(rng).copied()) {
+			Some(name) => { (&self.template_variants[name], PickedTemplates { variant: Some(name.clone()), locale: None }) }
+			None => { (&self.templates, PickedTemplates::default()) }
+		}
+	}
+
+	/// the templates an issue was filed with, for posting on it again. ones that
+	/// aren't in the config anymore are the usual templates
+	pub fn templates_for(&self, variant: Option<&str>, locale: Option<&str>) -> &Templates {
+		locale.and_then(|locale| self.locales.templates.get(locale))
+			.or_else(|| variant.and_then(|variant| self.template_variants.get(variant)))
+			.unwrap_or(&self.templates)
+	}
+
+	/// every file the templates (and their variants, and translations) could be from
+	pub fn template_files(&self) -> impl Iterator<Item = &PathBuf> {
+		self.templates.files().iter()
+			.chain(self.template_variants.values().flat_map(Templates::files))
+			.chain(self.locales.templates.values().flat_map(Templates::files))
+	}
+
+	/// where a host's api is, if it's not where its client looks for it
+	pub fn forge_api_url(&self, host: &str, kind: ForgeKind) -> Option<String> {
+		if let Some(url) = self.forge_api_urls.get(host) {
+			return Some(url.clone())
+		}
+		let fixtures = self.fixtures.as_ref()?;
+		let url = match kind {
+			ForgeKind::GitHub => { GithubClient::default_api_url(host) }
+			ForgeKind::GitLab => { GitlabClient::default_api_url(host) }
+		};
+		Some(fixtures::proxied(fixtures, &url))
+	}
+
+	/// how the token for a host is sent
+	pub fn auth_for(&self, host: &str) -> ForgeAuth {
+		self.forge_auth.get(host).copied().unwrap_or(ForgeAuth::Token)
+	}
+
+	/// every host there's a token for, with its forge, except for the forges
+	/// that are disabled
+	pub fn token_hosts(&self) -> Vec<(String, ForgeKind)> {
+		let mut hosts = self.forge_tokens.keys().cloned().collect::<Vec<_>>();
+		for (host, token) in [("github.com", &self.github_token), ("gitlab.com", &self.gitlab_token)] {
+			if token.is_some() && !self.forge_tokens.contains_key(host) {
+				hosts.push(host.into());
+			}
+		}
+		hosts.sort();
+		hosts.into_iter()
+			.filter_map(|host| forge::forge_for_host(&host, self).map(|kind| (host, kind)))
+			.filter(|(_, kind)| !self.disabled_forges.contains(kind))
+			.collect()
+	}
+
+	/// gets the token to use for a host, if there is one
+	pub fn token_for(&self, host: &str, kind: ForgeKind) -> Option<&str> {
+		if let Some(token) = self.forge_tokens.get(host) {
+			return Some(token.expose())
+		}
+
+		match (kind, host) {
+			(ForgeKind::GitHub, "github.com") => { self.github_token.as_ref().map(Secret::expose) }
+			(ForgeKind::GitLab, "gitlab.com") => { self.gitlab_token.as_ref().map(Secret::expose) }
+			_ => { None }
+		}
+	}
+}
+
+/// which templates an issue's from, see [`Config::pick_templates`]
+#[derive(Clone, Default)]
+pub struct PickedTemplates {
+	/// see [`Config::template_variants`]
+	pub variant: Option<String>,
+	/// see [`LocaleConfig::templates`]
+	pub locale: Option<String>
+}
+
+/// templates from `dir` (for `key`), with anything it doesn't have from
+/// `templates_dir`, or built in
+fn load_templates_over(key: &str, dir: &str, templates_dir: Option<&str>, pulsar_backend_url: &str) -> std::result::Result<Templates, String> {
+	// otherwise a typo would be the same as the usual templates
+	if !Path::new(dir).is_dir() {
+		return Err(format!("{key}: {dir} isn't a directory"))
+	}
+	let dirs = std::iter::once(dir).chain(templates_dir).collect::<Vec<_>>();
+	let loaded = Templates::load(&dirs, pulsar_backend_url).map_err(|e| format!("{key}: {e}"))?;
+	Ok(loaded)
+}
+
+/// takes the profiles out of `file`, and puts everything from `profile` in it
+fn apply_profile(file: &mut toml::Value, profile: Option<&str>) -> std::result::Result<(), String> {
+	let profiles = match file.as_table_mut().expect("config files are tables").remove("profiles") {
+		Some(toml::Value::Table(profiles)) => { profiles }
+		Some(_) => { return Err("profiles should be a table of profiles, like [profiles.staging]".into()) }
+		None => { Default::default() }
+	};
+	let names = profiles.keys().cloned().collect::<Vec<_>>().join(", ");
+
+	let profile = match profile {
+		Some(profile) => { profile }
+		None if profiles.is_empty() => { return Ok(()) }
+		None => { return Err(format!("it has profiles ({names}), pick one with --profile or {PROFILE_ENV}")) }
+	};
+	match profiles.get(profile) {
+		Some(toml::Value::Table(settings)) => {
+			merge(file.as_table_mut().expect("config files are tables"), settings.clone());
+			Ok(())
+		}
+		Some(_) => { Err(format!("profiles.{profile} should be a table")) }
+		None if profiles.is_empty() => { Err(format!("there's no profile {profile}, it doesn't have any")) }
+		None => { Err(format!("there's no profile {profile} (it has {names})")) }
+	}
+}
+
+/// puts everything in `from` into `into`, going into sections that are in both
+fn merge(into: &mut toml::value::Table, from: toml::value::Table) {
+	for (key, value) in from {
+		match (into.get_mut(&key), value) {
+			(Some(toml::Value::Table(into)), toml::Value::Table(from)) => { merge(into, from) }
+			(_, value) => { into.insert(key, value); }
+		}
+	}
+}
+
+/// sets everything given in [`ENV_PREFIX`] environment variables in `file`.
+/// values are read as toml if they can be (so `20`, `true`, and `["a", "b"]`
+/// work), otherwise as a string. returns the names of the variables used
+fn apply_env_overrides(file: &mut toml::Value) -> Result<Vec<String>> {
+	let mut vars = std::env::vars()
+		.filter(|(name, _)| name.starts_with(ENV_PREFIX) && name.len() > ENV_PREFIX.len())
+		.filter(|(name, _)| ![PROFILE_ENV, CONFIG_ENV, LOG_FORMAT_ENV].contains(&name.as_str()))
+		.collect::<Vec<_>>();
+	vars.sort();
+
+	for (name, raw) in vars.iter() {
+		let keys = name[ENV_PREFIX.len()..].split("__")
+			.map(str::to_lowercase)
+			.collect::<Vec<_>>();
+		let value = toml::from_str::<toml::value::Table>(&format!("value = {raw}"))
+			.ok()
+			.and_then(|mut table| table.remove("value"))
+			.unwrap_or_else(|| toml::Value::String(raw.clone()));
+
+		let (key, sections) = keys.split_last().expect("split always gives at least one");
+		let mut table = file.as_table_mut().expect("config files are tables");
+		for section in sections {
+			table = table.entry(section.clone())
+				.or_insert_with(|| toml::Value::Table(Default::default()))
+				.as_table_mut()
+				.ok_or_else(|| Error::Config(format!("error in {name}: {section} isn't a section")))?;
+		}
+		table.insert(key.clone(), value);
+	}
+
+	Ok(vars.into_iter().map(|(name, _)| name).collect())
+}
+
+// what config.toml looks like. every section and field is optional, see
+// config.example.toml for what they all do
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+	db_path: String,
+	db_durability: String,
+	stop_timeout: u64,
+	templates_dir: Option<String>,
+	template_variants: BTreeMap<String, String>,
+	locales: LocalesSection,
+	previous_issue_titles: Vec<String>,
+	forges: ForgesSection,
+	eligibility: EligibilitySection,
+	queue: QueueSection,
+	filing: FilingSection,
+	reminders: RemindersSection,
+	responses: ResponsesSection,
+	registry: RegistrySection,
+	pulsar: PulsarSection,
+	email: EmailSection,
+	claims: ClaimsSection,
+	webhook: WebhookSection,
+	github_webhooks: GithubWebhooksSection,
+	log: LogSection,
+	metrics: MetricsSection,
+	api: ApiSection,
+	reports: ReportsSection,
+	notify: NotifySection,
+	digest: DigestSection,
+	sentry: SentrySection,
+	otlp: OtlpSection,
+	simulation: SimulationSection
+}
+
+impl Default for ConfigFile {
+	fn default() -> Self {
+		Self {
+			db_path: "state.ron".into(),
+			db_durability: "batch".into(),
+			stop_timeout: 8,
+			templates_dir: None,
+			template_variants: BTreeMap::new(),
+			locales: LocalesSection::default(),
+			previous_issue_titles: Vec::new(),
+			forges: ForgesSection::default(),
+			eligibility: EligibilitySection::default(),
+			queue: QueueSection::default(),
+			filing: FilingSection::default(),
+			reminders: RemindersSection::default(),
+			responses: ResponsesSection::default(),
+			registry: RegistrySection::default(),
+			pulsar: PulsarSection::default(),
+			email: EmailSection::default(),
+			claims: ClaimsSection::default(),
+			webhook: WebhookSection::default(),
+			github_webhooks: GithubWebhooksSection::default(),
+			log: LogSection::default(),
+			metrics: MetricsSection::default(),
+			api: ApiSection::default(),
+			reports: ReportsSection::default(),
+			notify: NotifySection::default(),
+			digest: DigestSection::default(),
+			sentry: SentrySection::default(),
+			otlp: OtlpSection::default(),
+			simulation: SimulationSection::default()
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ForgesSection {
+	github_token_env: String,
+	github_token_file: Option<String>,
+	github_token_keyring: Option<String>,
+	gitlab_token_env: String,
+	gitlab_token_file: Option<String>,
+	gitlab_token_keyring: Option<String>,
+	hosts: HashMap<String, String>,
+	token_envs: HashMap<String, String>,
+	token_files: HashMap<String, String>,
+	token_keyrings: HashMap<String, String>,
+	auth: HashMap<String, String>,
+	check_tokens: bool,
+	disabled: Vec<String>,
+	simulate: Option<f64>,
+	api_urls: HashMap<String, String>
+}
+
+impl Default for ForgesSection {
+	fn default() -> Self {
+		Self {
+			github_token_env: "GITHUB_TOKEN".into(),
+			github_token_file: None,
+			github_token_keyring: None,
+			gitlab_token_env: "GITLAB_TOKEN".into(),
+			gitlab_token_file: None,
+			gitlab_token_keyring: None,
+			hosts: HashMap::new(),
+			token_envs: HashMap::new(),
+			token_files: HashMap::new(),
+			token_keyrings: HashMap::new(),
+			auth: HashMap::new(),
+			check_tokens: true,
+			disabled: Vec::new(),
+			simulate: None,
+			api_urls: HashMap::new()
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct EligibilitySection {
+	min_downloads: u32,
+	min_stars: u32,
+	ownership_check: bool,
+	include_themes: bool,
+	blocklist_file: Option<String>,
+	allowlist_file: Option<String>,
+	opt_out_list: Option<String>,
+	skip_declined_owners: bool,
+	dormant_after_years: u32,
+	dormant: String
+}
+
+impl Default for EligibilitySection {
+	fn default() -> Self {
+		Self {
+			min_downloads: 0,
+			min_stars: 0,
+			ownership_check: true,
+			include_themes: true,
+			blocklist_file: None,
+			allowlist_file: None,
+			opt_out_list: None,
+			skip_declined_owners: false,
+			dormant_after_years: 0,
+			dormant: "skip".into()
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct QueueSection {
+	order: String,
+	lookup_concurrency: usize
+}
+
+impl Default for QueueSection {
+	fn default() -> Self {
+		Self { order: "downloads".into(), lookup_concurrency: 4 }
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FilingSection {
+	days: Option<String>,
+	hours: Option<String>,
+	daily_limit: usize,
+	delay_min: u64,
+	delay_max: Option<u64>,
+	repo_cooldown: u32,
+	breaker_failures: u32,
+	breaker_cooldown: u32,
+	retry_max_attempts: u32,
+	canary_repo: Option<String>,
+	canary_filings: usize
+}
+
+impl Default for FilingSection {
+	fn default() -> Self {
+		Self {
+			days: None,
+			hours: None,
+			daily_limit: 0,
+			delay_min: 0,
+			delay_max: None,
+			repo_cooldown: 48,
+			breaker_failures: 5,
+			breaker_cooldown: 60,
+			retry_max_attempts: 5,
+			canary_repo: None,
+			canary_filings: 1
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RemindersSection {
+	follow_up_after: u32,
+	final_reminder_after: u32
+}
+
+impl Default for RemindersSection {
+	fn default() -> Self {
+		Self { follow_up_after: 14, final_reminder_after: 30 }
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ResponsesSection {
+	max_issues: usize,
+	min_rate_limit: u32
+}
+
+impl Default for ResponsesSection {
+	fn default() -> Self {
+		Self { max_issues: 0, min_rate_limit: 1000 }
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RegistrySection {
+	url: String,
+	mirrors: Vec<String>,
+	max_retries: u32,
+	timeout: u64,
+	cache_dir: String,
+	cache_max_age: u64,
+	counts_refresh_interval: u64
+}
+
+impl Default for RegistrySection {
+	fn default() -> Self {
+		Self {
+			url: "https://atom.io/api".into(),
+			mirrors: Vec::new(),
+			max_retries: 5,
+			timeout: 60,
+			cache_dir: "registry_cache".into(),
+			cache_max_age: 0,
+			counts_refresh_interval: 7 * 24 * 60 * 60
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PulsarSection {
+	backend_url: String,
+	check_backend: bool,
+	token_env: String,
+	token_file: Option<String>,
+	token_keyring: Option<String>
+}
+
+impl Default for PulsarSection {
+	fn default() -> Self {
+		Self {
+			backend_url: "https://api.pulsar-edit.dev/api".into(),
+			check_backend: true,
+			token_env: "PULSAR_BACKEND_TOKEN".into(),
+			token_file: None,
+			token_keyring: None
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct EmailSection {
+	fallback: bool,
+	outbox: String,
+	smtp: Option<SmtpSection>
+}
+
+impl Default for EmailSection {
+	fn default() -> Self {
+		Self { fallback: true, outbox: "emails".into(), smtp: None }
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SmtpSection {
+	host: String,
+	port: Option<u16>,
+	username: String,
+	#[serde(default = "default_smtp_password_env")]
+	password_env: String,
+	password_file: Option<String>,
+	password_keyring: Option<String>,
+	from: String
+}
+
+fn default_smtp_password_env() -> String {
+	"SMTP_PASSWORD".into()
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ClaimsSection {
+	dir: Option<String>,
+	worker_id: Option<String>,
+	lease: u64
+}
+
+impl Default for ClaimsSection {
+	fn default() -> Self {
+		Self { dir: None, worker_id: None, lease: 300 }
+	}
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct WebhookSection {
+	url: Option<String>
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct GithubWebhooksSection {
+	listen: Option<String>,
+	secret_env: String,
+	secret_file: Option<String>,
+	secret_keyring: Option<String>
+}
+
+impl Default for GithubWebhooksSection {
+	fn default() -> Self {
+		Self { listen: None, secret_env: "GITHUB_WEBHOOK_SECRET".into(), secret_file: None, secret_keyring: None }
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LogSection {
+	file: Option<String>,
+	rotate: String,
+	keep: usize,
+	/// next to the database if it's not set, "" to not write it
+	activity: Option<String>
+}
+
+impl Default for LogSection {
+	fn default() -> Self {
+		Self { file: None, rotate: "daily".into(), keep: 7, activity: None }
+	}
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MetricsSection {
+	listen: Option<String>
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ApiSection {
+	listen: Option<String>,
+	tokens_env: String,
+	tokens_file: Option<String>,
+	tokens_keyring: Option<String>,
+	public: bool,
+	public_feed: bool
+}
+
+impl Default for ApiSection {
+	fn default() -> Self {
+		Self { listen: None, tokens_env: "ISSUE_BOT_API_TOKENS".into(), tokens_file: None, tokens_keyring: None, public: false, public_feed: false }
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ReportsSection {
+	dir: String
+}
+
+impl Default for ReportsSection {
+	fn default() -> Self {
+		Self { dir: "reports".into() }
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct NotifySection {
+	url: Option<String>,
+	failure_rate: f64,
+	min_downloads: u32
+}
+
+impl Default for NotifySection {
+	fn default() -> Self {
+		Self { url: None, failure_rate: 0.5, min_downloads: 10_000 }
+	}
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LocalesSection {
+	templates: BTreeMap<String, String>,
+	packages: HashMap<String, String>,
+	owners: HashMap<String, String>,
+	detect: bool
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DigestSection {
+	to: Vec<String>,
+	every: String
+}
+
+impl Default for DigestSection {
+	fn default() -> Self {
+		Self { to: Vec::new(), every: "daily".into() }
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SentrySection {
+	dsn_env: String,
+	dsn_file: Option<String>,
+	dsn_keyring: Option<String>,
+	environment: Option<String>
+}
+
+impl Default for SentrySection {
+	fn default() -> Self {
+		Self { dsn_env: "SENTRY_DSN".into(), dsn_file: None, dsn_keyring: None, environment: None }
+	}
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct OtlpSection {
+	endpoint: Option<String>
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SimulationSection {
+	packages: u32,
+	popularity: String,
+	max_downloads: u32,
+	reply_rate: f64,
+	approval_rate: f64,
+	seed: u64
+}
+
+impl Default for SimulationSection {
+	fn default() -> Self {
+		Self {
+			packages: 0,
+			popularity: "zipf".into(),
+			max_downloads: 100_000,
+			reply_rate: 0.0,
+			approval_rate: 0.8,
+			seed: 0
+		}
+	}
+}
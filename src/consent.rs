@@ -0,0 +1,410 @@
+//! keeping track of the maintainers that said yes (and the ones that changed
+//! their minds), and handing them off to the pulsar backend
+
+use crate::activity;
+use crate::config::Config;
+use crate::db::{ DatabaseThing, Package, PackageApproved, PackageConsentRevoked, PackageMigrated, PackageNew, PackageSkipped, PackageState, RepoLicense };
+use crate::events;
+use crate::forge::{ self, ForgeKind, Forges, IssueComment, RepoLocation };
+use crate::pulsar::PulsarClient;
+use crate::Result;
+use chrono::{ DateTime, Utc };
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio::fs;
+use tracing::instrument;
+
+/// things a maintainer might say when taking back their permission. kept
+/// fairly specific, since every match gets the package delisted
+const REVOCATION_PHRASES: &[&str] = &[
+	"withdraw",
+	"revoke",
+	"changed my mind",
+	"change my mind",
+	"don't migrate",
+	"do not migrate",
+	"don't want it migrated",
+	"no longer want",
+	"no longer give permission",
+	"take back my permission",
+	"remove my package"
+];
+
+/// things a maintainer might say when saying no, checked before
+/// [`APPROVAL_PHRASES`] so "no, please don't" isn't taken as a yes. these, like
+/// the approvals, only count as whole words
+const DECLINE_PHRASES: &[&str] = &[
+	"nope",
+	"no thanks",
+	"no thank you",
+	"not interested",
+	"please don't",
+	"please do not",
+	"i don't want",
+	"i do not want",
+	"i'd rather not",
+	"i would rather not",
+	"i decline",
+	"opt out",
+	"don't migrate",
+	"do not migrate"
+];
+
+/// things a maintainer might say when giving permission
+const APPROVAL_PHRASES: &[&str] = &[
+	"yes",
+	"yep",
+	"yeah",
+	"go ahead",
+	"go for it",
+	"please do",
+	"feel free",
+	"of course",
+	"absolutely",
+	"sounds good",
+	"i approve",
+	"approved",
+	"permission granted",
+	"you have my permission",
+	"i give permission",
+	"i give my permission"
+];
+
+/// one approved package in the export, with everything the pulsar backend needs to import it
+#[derive(Serialize)]
+struct ConsentRecord {
+	name: String,
+	repository: String,
+	issue_url: Option<String>,
+	email: Option<String>,
+	approval_url: String,
+	contacted_at: DateTime<Utc>,
+	approved_at: DateTime<Utc>,
+	/// if permission was taken back, so the pulsar backend can delist it
+	revoked_at: Option<DateTime<Utc>>,
+	revocation_url: Option<String>,
+	/// the repository's, see [`RepoLicense`]. `None` if it doesn't have one, or
+	/// it wasn't checked
+	license: Option<String>,
+	/// when the repository was last pushed to, and the tag of its latest release,
+	/// see [`RepoActivity`](crate::db::RepoActivity). `None` if it wasn't checked
+	pushed_at: Option<DateTime<Utc>>,
+	latest_release: Option<String>
+}
+
+impl ConsentRecord {
+	fn new(package: &Package, approved: PackageApproved) -> Self {
+		Self {
+			name: package.info.name.clone(),
+			repository: package.info.repository.url.clone(),
+			license: package.license.as_ref().and_then(|license| license.license.clone()),
+			pushed_at: package.activity.as_ref().and_then(|activity| activity.pushed_at),
+			latest_release: package.activity.as_ref().and_then(|activity| activity.latest_release.as_ref()).map(|release| release.tag.clone()),
+			issue_url: approved.issue_url,
+			email: approved.email,
+			approval_url: approved.approval_url,
+			contacted_at: approved.contacted_at,
+			approved_at: approved.approved_at,
+			revoked_at: None,
+			revocation_url: None
+		}
+	}
+}
+
+/// records that permission was given for `package`, and for the packages
+/// it shares an issue with, since they were all asked at once. they're
+/// registered with the pulsar backend straight away, if that's set up
+#[instrument(skip_all, fields(package = %package))]
+pub async fn approve(db: &DatabaseThing, config: &Config, package: &str, approval_url: String, approved_at: DateTime<Utc>) -> Result {
+	let state = db.package_state(package)
+		.ok_or_else(|| format!("package {package} is not in the database"))?;
+
+	let (names, approved) = match state {
+		PackageState::IssueFiled(filed) => {
+			let mut names = filed.grouped_with.clone();
+			names.insert(0, package.into());
+			(names, PackageApproved {
+				issue_url: Some(filed.issue_url),
+				email: None,
+				contacted_at: filed.filed_at,
+				approval_url,
+				approved_at,
+				registration_error: None
+			})
+		}
+		PackageState::ContactedByEmail(contacted) => {
+			(vec![package.into()], PackageApproved {
+				issue_url: None,
+				email: Some(contacted.email),
+				contacted_at: contacted.contacted_at,
+				approval_url,
+				approved_at,
+				registration_error: None
+			})
+		}
+		state => { return Err(format!("package {package} hasn't been asked for permission, it's {}", state.name()).into()) }
+	};
+
+	for name in names.iter() {
+		db.set_state(name, PackageState::MaintainerApproved(approved.clone()))?;
+		info!("package {name} approved");
+	}
+	activity::record(db, &format!("{} approved in {}", activity::packages(&names), approved.approval_url));
+	db.flush();
+
+	register_approved(db, config).await
+}
+
+/// registers every approved package that hasn't been yet with the pulsar
+/// backend, if there's a token for it. ones that fail are tried again next time
+pub async fn register_approved(db: &DatabaseThing, config: &Config) -> Result {
+	if config.pulsar_backend_token.is_none() { return Ok(()) }
+	let pulsar = PulsarClient::new(config)?;
+
+	for package in db.packages() {
+		let mut approved = match &package.state {
+			PackageState::MaintainerApproved(approved) => { approved.clone() }
+			_ => { continue }
+		};
+		let record = ConsentRecord::new(&package, approved.clone());
+		let name = package.info.name;
+
+		match pulsar.register(&record).await {
+			Ok(backend_response) => {
+				info!("registered package {name} with the pulsar backend");
+				activity::record(db, &format!("registered package {name} with the pulsar backend"));
+				db.set_state(&name, PackageState::Migrated(PackageMigrated {
+					approval: approved,
+					migrated_at: Utc::now(),
+					backend_response
+				}))?;
+			}
+			Err(e) => {
+				error!("error registering package {name} with the pulsar backend, it'll be tried again next run: {e}");
+				approved.registration_error = Some(e.to_string());
+				db.set_state(&name, PackageState::MaintainerApproved(approved))?;
+			}
+		}
+		db.flush();
+	}
+
+	Ok(())
+}
+
+/// looks through the comments on the issues of approved (and migrated) packages
+/// for maintainers taking back their permission, and records it if they did.
+/// issues on github are left to [`crate::events`], while it's running
+pub async fn check_revocations(db: &DatabaseThing, forges: &mut Forges) -> Result {
+	let events = events::receiving();
+
+	// packages sharing an issue were approved together, so only check it once
+	let mut checked = HashMap::<String, Option<(Option<Revocation>, Vec<IssueComment>)>>::new();
+
+	for package in db.packages() {
+		let (approval, migrated_at) = match package.state {
+			PackageState::MaintainerApproved(approved) => { (approved, None) }
+			PackageState::Migrated(migrated) => { (migrated.approval, Some(migrated.migrated_at)) }
+			_ => { continue }
+		};
+		// emails can't be checked, those are done with `revoke`
+		let issue_url = match &approval.issue_url {
+			Some(issue_url) => { issue_url.clone() }
+			None => { continue }
+		};
+		let on_github = RepoLocation::parse(&package.info.repository.url)
+			.and_then(|repo| forge::forge_for_host(&repo.host, forges.config()))
+			== Some(ForgeKind::GitHub);
+		if events && on_github { continue }
+
+		if !checked.contains_key(&issue_url) {
+			let revocation = match find_revocation(forges, &package.info, &issue_url, approval.approved_at).await {
+				Ok(revocation) => { Some(revocation) }
+				Err(e) => {
+					error!("error checking {issue_url} for permission being taken back: {e}");
+					None
+				}
+			};
+			checked.insert(issue_url.clone(), revocation);
+		}
+
+		let (revocation, comments) = match &checked[&issue_url] {
+			Some(checked) => { checked }
+			None => { continue }
+		};
+		db.set_seen_comments(&package.info.name, comments);
+		if let Some((revocation_url, revoked_at)) = revocation {
+			let name = package.info.name;
+			info!("package {name}: the maintainer took back their permission in {revocation_url}");
+			activity::record(db, &format!("package {name}'s permission taken back in {revocation_url}"));
+			db.set_state(&name, PackageState::ConsentRevoked(PackageConsentRevoked {
+				approval,
+				migrated_at,
+				revocation_url: revocation_url.clone(),
+				revoked_at: *revoked_at
+			}))?;
+		}
+	}
+	db.flush();
+
+	Ok(())
+}
+
+/// the comment permission was taken back in, and when
+type Revocation = (String, DateTime<Utc>);
+
+/// the first comment on `issue_url` since approval that takes it back, if there
+/// is one, and all the comments on it
+#[instrument(skip_all, fields(package = %package.name, issue = %issue_url))]
+async fn find_revocation(
+	forges: &mut Forges,
+	package: &PackageNew,
+	issue_url: &str,
+	approved_at: DateTime<Utc>
+) -> Result<(Option<Revocation>, Vec<IssueComment>)> {
+	let repo = RepoLocation::parse(&package.repository.url)
+		.ok_or_else(|| format!("can't tell what repository {} is", package.repository.url))?;
+	let number = forge::issue_number(issue_url)
+		.ok_or_else(|| format!("can't find the issue number in {issue_url}"))?;
+
+	let activity = forges.client_for(&repo)?.issue_activity(&repo, number).await?;
+	let revocation = activity.comments.iter()
+		.find(|comment| comment.created_at > approved_at && is_revocation(&comment.body))
+		.map(|comment| (comment.url.clone(), comment.created_at));
+	Ok((revocation, activity.comments))
+}
+
+pub fn is_revocation(comment: &str) -> bool {
+	let comment = comment.to_lowercase().replace('\u{2019}', "'");
+	REVOCATION_PHRASES.iter().any(|phrase| comment.contains(phrase))
+}
+
+/// what a reply to an issue asking for permission says
+#[derive(Clone, Copy, PartialEq)]
+pub enum Answer {
+	Yes,
+	No
+}
+
+/// whether `comment` gives permission or says no, if it's clear either way
+pub fn classify(comment: &str) -> Option<Answer> {
+	let comment = comment.to_lowercase().replace('\u{2019}', "'");
+	// words, with spaces around, so "no" doesn't match "know"
+	let words = comment.split(|c: char| !c.is_alphanumeric() && c != '\'')
+		.filter(|word| !word.is_empty())
+		.collect::<Vec<_>>();
+	let words = format!(" {} ", words.join(" "));
+	let says = |phrases: &[&str]| phrases.iter().any(|phrase| words.contains(&format!(" {phrase} ")));
+
+	if says(DECLINE_PHRASES) {
+		Some(Answer::No)
+	} else if says(APPROVAL_PHRASES) {
+		Some(Answer::Yes)
+	} else {
+		None
+	}
+}
+
+/// goes through the replies on `package`'s issue (everyone's but ours, oldest
+/// first), and on the first one saying yes or no approves it (see [`approve`],
+/// which registers it with the pulsar backend too) or opts it out, along with
+/// the packages sharing its issue. an issue `closed` without a yes is a no.
+/// used both when polling issues and for github's webhooks, so it's the same
+/// either way. says what was done, if anything
+pub async fn answer_from_replies(db: &DatabaseThing, config: &Config, package: &str, comments: &[IssueComment], closed: bool) -> Result<Option<String>> {
+	let filed = match db.package_state(package) {
+		Some(PackageState::IssueFiled(filed)) => { filed }
+		_ => { return Ok(None) }
+	};
+
+	let answer = comments.iter()
+		.filter(|comment| comment.created_at > filed.filed_at)
+		.find_map(|comment| classify(&comment.body).map(|answer| (answer, comment)));
+	let (reason, declined_at) = match answer {
+		Some((Answer::Yes, comment)) => {
+			approve(db, config, package, comment.url.clone(), comment.created_at).await?;
+			return Ok(Some(format!("approved in {}", comment.url)))
+		}
+		Some((Answer::No, comment)) => { (format!("declined in {}", comment.url), comment.created_at) }
+		None if closed => { (format!("{} was closed without an answer", filed.issue_url), Utc::now()) }
+		None => { return Ok(None) }
+	};
+
+	let mut names = filed.grouped_with.clone();
+	names.insert(0, package.into());
+	for name in names.iter() {
+		db.set_state(name, PackageState::OptedOut(PackageSkipped { reason: reason.clone(), skipped_at: declined_at, contact_failure: None }))?;
+		info!("package {name} opted out, {reason}");
+	}
+	activity::record(db, &format!("{} opted out, {reason}", activity::packages(&names)));
+	db.flush();
+
+	Ok(Some(format!("opted out, {reason}")))
+}
+
+/// records that the maintainer of `package` took back their permission, for
+/// when it wasn't in a comment that [`check_revocations`] could find
+pub fn revoke(db: &DatabaseThing, package: &str, revocation_url: String, revoked_at: DateTime<Utc>) -> Result {
+	let state = db.package_state(package)
+		.ok_or_else(|| format!("package {package} is not in the database"))?;
+
+	let (approval, migrated_at) = match state {
+		PackageState::MaintainerApproved(approved) => { (approved, None) }
+		PackageState::Migrated(migrated) => { (migrated.approval, Some(migrated.migrated_at)) }
+		state => { return Err(format!("package {package} wasn't approved, it's {}", state.name()).into()) }
+	};
+
+	db.set_state(package, PackageState::ConsentRevoked(PackageConsentRevoked {
+		approval,
+		migrated_at,
+		revocation_url: revocation_url.clone(),
+		revoked_at
+	}))?;
+	db.flush();
+	info!("package {package}: permission revoked");
+	activity::record(db, &format!("package {package}'s permission taken back in {revocation_url}"));
+
+	Ok(())
+}
+
+/// writes every approved package (including ones that have been migrated
+/// already, and ones where permission was taken back) as json, to `output` or stdout
+/// for `export --license`: `wanted` is a license, or `none`, or `unknown`
+fn has_license(package: &Package, wanted: &str) -> bool {
+	match &package.license {
+		None => { wanted.eq_ignore_ascii_case("unknown") }
+		Some(RepoLicense { license: None, .. }) => { wanted.eq_ignore_ascii_case("none") }
+		Some(RepoLicense { license: Some(license), .. }) => { license.eq_ignore_ascii_case(wanted) }
+	}
+}
+
+pub async fn export(db: &DatabaseThing, output: Option<String>, licenses: &[String]) -> Result {
+	let records = db.packages().into_iter()
+		.filter(|package| licenses.is_empty() || licenses.iter().any(|wanted| has_license(package, wanted)))
+		.filter_map(|package| {
+			let approved = match package.state.clone() {
+				PackageState::MaintainerApproved(approved) => { approved }
+				PackageState::Migrated(migrated) => { migrated.approval }
+				PackageState::ConsentRevoked(revoked) => {
+					let mut record = ConsentRecord::new(&package, revoked.approval);
+					record.revoked_at = Some(revoked.revoked_at);
+					record.revocation_url = Some(revoked.revocation_url);
+					return Some(record)
+				}
+				_ => { return None }
+			};
+			Some(ConsentRecord::new(&package, approved))
+		})
+		.collect::<Vec<_>>();
+
+	let json = serde_json::to_string_pretty(&records)?;
+	match output {
+		Some(path) => {
+			fs::write(&path, json).await
+				.map_err(|e| format!("error writing {path}: {e}"))?;
+			info!("exported {} approved packages to {path}", records.len());
+		}
+		None => { println!("{json}") }
+	}
+
+	Ok(())
+}
@@ -0,0 +1,267 @@
+//! controlling a run while it's going, with signals (or the dashboard). on
+//! SIGTERM (or ctrl-c), runs stop after the package they're on, and anything
+//! else stops straight away, so a container stops within its grace period
+//! without losing anything, see [`handle_shutdown`]
+
+use crate::crash;
+use crate::db::DatabaseThing;
+use crate::github::GithubClient;
+use crate::status;
+use std::sync::{ Arc, Mutex };
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+lazy_static! {
+	/// whether the bot's been asked to stop. every run listens to it
+	static ref STOP: watch::Sender<bool> = watch::channel(false).0;
+	/// the run going on, if there is one, for [`snapshot_on_signal`]
+	static ref RUNNING: Mutex<Option<ControlHandle>> = Mutex::new(None);
+}
+
+/// logs what the bot's up to (see [`status::snapshot`]) whenever it's sent
+/// SIGUSR2, without getting in the way of what it's doing
+pub fn snapshot_on_signal(db: &DatabaseThing, github: Option<GithubClient>, daily_limit: usize) {
+	#[cfg(unix)]
+	{
+		use tokio::signal::unix::{ signal, SignalKind };
+
+		let mut signals = match signal(SignalKind::user_defined2()) {
+			Ok(signals) => { signals }
+			Err(e) => {
+				error!("error listening for SIGUSR2, it won't log what the bot's doing: {e}");
+				return
+			}
+		};
+		let db = db.clone();
+		tokio::spawn(async move {
+			while signals.recv().await.is_some() {
+				let run = RUNNING.lock().unwrap().clone();
+				for line in status::snapshot(&db, run, github.as_ref(), daily_limit).await {
+					info!("snapshot: {line}");
+				}
+			}
+		});
+	}
+}
+
+/// waits (in the background) for SIGTERM or ctrl-c, then asks the run going on
+/// to stop. if there isn't one, or it hasn't stopped in `timeout` seconds (or
+/// it's asked again), the database is saved and the bot stops anyway
+pub fn handle_shutdown(db: &DatabaseThing, timeout: u64) {
+	let db = db.clone();
+	tokio::spawn(async move {
+		if !wait_for_shutdown_signal().await { return }
+		let running = STOP.receiver_count() > 0;
+		STOP.send_replace(true);
+		if !running {
+			info!("stopping");
+			db.flush();
+			std::process::exit(143);
+		}
+		info!("stopping after the current package, or in {timeout}s (do that again to stop right away)");
+
+		tokio::select! {
+			stopping = wait_for_shutdown_signal() => {
+				if !stopping { return }
+				info!("stopping right away");
+			}
+			_ = tokio::time::sleep(Duration::from_secs(timeout)) => {
+				warn!("still not stopped after {timeout}s (stop_timeout), stopping right away");
+			}
+		}
+		db.flush();
+		std::process::exit(130);
+	});
+}
+
+/// lets an operator pause a run by sending the process SIGUSR1 (and resume
+/// it by sending it again), and stop it cleanly with ctrl-c or SIGTERM
+pub struct RunControl {
+	paused: watch::Receiver<bool>,
+	stopping: watch::Receiver<bool>,
+	skipping: watch::Receiver<bool>,
+	handle: ControlHandle
+}
+
+/// for pausing, skipping, and stopping a run from somewhere else, and seeing
+/// how it's going
+#[derive(Clone)]
+pub struct ControlHandle {
+	pause_tx: Arc<watch::Sender<bool>>,
+	skip_tx: Arc<watch::Sender<bool>>,
+	progress: Arc<Mutex<Progress>>
+}
+
+/// what a run is up to
+#[derive(Clone, Default)]
+pub struct Progress {
+	/// the packages being contacted right now
+	pub current: Option<String>,
+	/// how many packages have been gone through, out of how many
+	pub done: usize,
+	pub total: usize,
+	pub issues_filed: usize,
+	pub failed: usize,
+	/// when the next issue is going to be filed, while waiting for that
+	pub waiting_until: Option<Instant>
+}
+
+impl ControlHandle {
+	pub fn is_paused(&self) -> bool {
+		*self.pause_tx.borrow()
+	}
+
+	/// pauses the run after the package it's on if it isn't paused, otherwise
+	/// resumes it. returns whether it's paused now
+	pub fn toggle_pause(&self) -> bool {
+		let paused = !self.is_paused();
+		let _ = self.pause_tx.send(paused);
+		paused
+	}
+
+	/// stops the run cleanly after the package it's on
+	pub fn stop(&self) {
+		STOP.send_replace(true);
+	}
+
+	pub fn is_stopping(&self) -> bool {
+		*STOP.borrow()
+	}
+
+	/// leaves the packages the run is on for another time, before anything is filed for them
+	pub fn skip(&self) {
+		let _ = self.skip_tx.send(true);
+	}
+
+	pub fn progress(&self) -> Progress {
+		self.progress.lock().unwrap().clone()
+	}
+}
+
+impl RunControl {
+	pub fn new() -> Self {
+		let (pause_tx, paused) = watch::channel(false);
+		let stopping = STOP.subscribe();
+		let (skip_tx, skipping) = watch::channel(false);
+		let handle = ControlHandle {
+			pause_tx: Arc::new(pause_tx),
+			skip_tx: Arc::new(skip_tx),
+			progress: Arc::new(Mutex::new(Progress::default()))
+		};
+
+		#[cfg(unix)]
+		let signal_handle = handle.clone();
+		#[cfg(unix)]
+		tokio::spawn(async move {
+			use tokio::signal::unix::{ signal, SignalKind };
+
+			let mut signals = match signal(SignalKind::user_defined1()) {
+				Ok(signals) => { signals }
+				Err(e) => {
+					error!("error listening for SIGUSR1, pausing won't work: {e}");
+					return
+				}
+			};
+
+			while signals.recv().await.is_some() {
+				if signal_handle.toggle_pause() {
+					info!("received SIGUSR1, pausing after the current package");
+				} else {
+					info!("received SIGUSR1, resuming");
+				}
+			}
+		});
+
+		*RUNNING.lock().unwrap() = Some(handle.clone());
+		Self { paused, stopping, skipping, handle }
+	}
+
+	pub fn handle(&self) -> ControlHandle {
+		self.handle.clone()
+	}
+
+	/// updates what the run is up to
+	pub fn report(&self, f: impl FnOnce(&mut Progress)) {
+		let mut progress = self.handle.progress.lock().unwrap();
+		f(&mut progress);
+		crash::working_on(progress.current.clone());
+	}
+
+	/// whether the operator asked for the run to stop
+	pub fn stopping(&self) -> bool {
+		*self.stopping.borrow()
+	}
+
+	/// whether the operator asked to skip the packages the run is on, which
+	/// only counts once
+	pub fn take_skip(&mut self) -> bool {
+		let skipping = *self.skipping.borrow_and_update();
+		if skipping {
+			let _ = self.handle.skip_tx.send(false);
+			// so it doesn't look like another skip to `sleep_until`
+			self.skipping.borrow_and_update();
+		}
+		skipping
+	}
+
+	/// sleeps until `deadline`, or until the operator asks to stop (or skip)
+	pub async fn sleep_until(&mut self, deadline: Instant) {
+		if self.stopping() || *self.skipping.borrow() { return }
+
+		tokio::select! {
+			_ = tokio::time::sleep_until(deadline) => {}
+			_ = self.stopping.changed() => {}
+			_ = self.skipping.changed() => {}
+		}
+	}
+
+	/// waits until the run isn't paused. the db is saved before waiting, so
+	/// nothing is lost if the operator decides to stop it while it's paused
+	pub async fn wait_if_paused(&mut self, db: &DatabaseThing) {
+		if !*self.paused.borrow() { return }
+
+		db.flush();
+		info!("paused, send SIGUSR1 again to resume (pid {})", std::process::id());
+
+		while *self.paused.borrow() && !self.stopping() {
+			tokio::select! {
+				// sender is gone, nothing is ever going to resume it
+				res = self.paused.changed() => { if res.is_err() { return } }
+				_ = self.stopping.changed() => {}
+			}
+		}
+	}
+}
+
+impl Drop for RunControl {
+	fn drop(&mut self) {
+		*RUNNING.lock().unwrap() = None;
+	}
+}
+
+/// waits for ctrl-c, or SIGTERM on unix. returns false if signals can't be listened for
+async fn wait_for_shutdown_signal() -> bool {
+	#[cfg(unix)]
+	{
+		use tokio::signal::unix::{ signal, SignalKind };
+
+		let mut terminate = match signal(SignalKind::terminate()) {
+			Ok(terminate) => { terminate }
+			Err(e) => {
+				error!("error listening for SIGTERM, it won't stop runs cleanly: {e}");
+				return tokio::signal::ctrl_c().await.is_ok()
+			}
+		};
+
+		tokio::select! {
+			res = tokio::signal::ctrl_c() => { res.is_ok() }
+			_ = terminate.recv() => { true }
+		}
+	}
+
+	#[cfg(not(unix))]
+	{
+		tokio::signal::ctrl_c().await.is_ok()
+	}
+}
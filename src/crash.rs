@@ -0,0 +1,100 @@
+//! what's done when the bot panics. unwinding drops (and so saves) the database,
+//! but not if the panic aborts, or happens on a thread that doesn't own it, so
+//! a panic hook saves it straight away instead, and writes a crash file next to
+//! it saying what panicked, and which packages the bot was on. `doctor` shows
+//! the crash file, and the next `file`, `start`, or `daemon` warns about it
+
+use crate::db::DatabaseThing;
+use chrono::{ DateTime, Utc };
+use serde::{ Deserialize, Serialize };
+use std::any::Any;
+use std::fs;
+use std::panic;
+use std::sync::{ Mutex, PoisonError };
+
+lazy_static! {
+	/// the packages being contacted right now, see [`working_on`]
+	static ref WORKING_ON: Mutex<Option<String>> = Mutex::new(None);
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Crash {
+	pub at: DateTime<Utc>,
+	pub message: String,
+	/// where in the code it panicked
+	pub location: Option<String>,
+	/// the packages being contacted when it panicked, if any
+	pub package: Option<String>,
+	/// whether the database was saved before the bot stopped
+	pub saved: bool
+}
+
+impl Crash {
+	/// for logging
+	pub fn describe(&self) -> String {
+		let location = self.location.as_deref().map(|location| format!(" at {location}")).unwrap_or_default();
+		let package = self.package.as_deref().map(|package| format!(" while on {package}")).unwrap_or_default();
+		let saved = match self.saved {
+			true => { "the database was saved then" }
+			false => { "the database couldn't be saved then, changes since it was last saved are lost" }
+		};
+		format!("panicked{location} on {}{package}: {}, {saved}", self.at, self.message)
+	}
+}
+
+/// where the crash file for the database at `db_path` goes
+fn path(db_path: &str) -> String {
+	format!("{db_path}.crash")
+}
+
+/// records what the bot is on, from [`crate::control::RunControl::report`]
+pub fn working_on(package: Option<String>) {
+	*WORKING_ON.lock().unwrap_or_else(PoisonError::into_inner) = package;
+}
+
+/// saves `db` and writes the crash file when anything panics, before whatever
+/// handled panics before (like printing them, or sending them to sentry)
+pub fn install(db: &DatabaseThing, db_path: &str) {
+	let save = db.saver();
+	let path = path(db_path);
+	let previous = panic::take_hook();
+	panic::set_hook(Box::new(move |info| {
+		let crash = Crash {
+			at: Utc::now(),
+			message: message(info.payload()),
+			location: info.location().map(ToString::to_string),
+			// not waiting for it, in case it's what panicked
+			package: WORKING_ON.try_lock().ok().and_then(|package| package.clone()),
+			saved: save()
+		};
+		if let Ok(json) = serde_json::to_string_pretty(&crash) {
+			let _ = fs::write(&path, json);
+		}
+		previous(info);
+	}));
+}
+
+/// what was passed to `panic!`
+fn message(payload: &(dyn Any + Send)) -> String {
+	payload.downcast_ref::<&str>()
+		.map(|message| message.to_string())
+		.or_else(|| payload.downcast_ref::<String>().cloned())
+		.unwrap_or_else(|| "no message".into())
+}
+
+/// the crash file left by the last panic, if there is one
+pub fn last(db_path: &str) -> Option<Crash> {
+	let json = fs::read_to_string(path(db_path)).ok()?;
+	serde_json::from_str(&json).ok()
+}
+
+/// warns about the last panic, if there was one, and removes its crash file so
+/// it's only warned about once
+pub fn warn_about_last(db_path: &str) {
+	if let Some(crash) = last(db_path) {
+		warn!("the last run {}", crash.describe());
+		if let Err(e) = fs::remove_file(path(db_path)) {
+			warn!("error removing crash file {}: {e}", path(db_path));
+		}
+	}
+}
@@ -0,0 +1,326 @@
+//! a live view of a run in the terminal, for `file --dashboard`. everything the
+//! run prints goes to a log file instead while it's up, and the dashboard reads
+//! that back for the recent errors
+
+use crate::config::Config;
+use crate::control::{ ControlHandle, Progress, RunControl };
+use crate::db::DatabaseThing;
+use crate::forge::RateLimit;
+use crate::github::GithubClient;
+use crate::Result;
+use chrono::{ Duration, Utc };
+use crossterm::event::{ self, Event, KeyCode, KeyEventKind, KeyModifiers };
+use crossterm::terminal::{ self, EnterAlternateScreen, LeaveAlternateScreen };
+use crossterm::{ cursor, execute };
+use ratatui::backend::{ Backend, CrosstermBackend };
+use ratatui::layout::{ Constraint, Direction, Layout };
+use ratatui::style::{ Color, Modifier, Style };
+use ratatui::text::{ Span, Spans };
+use ratatui::widgets::{ Block, Borders, Gauge, Paragraph };
+use ratatui::{ Frame, Terminal };
+use std::collections::VecDeque;
+use std::fs::{ File, OpenOptions };
+use std::future::Future;
+use std::io::{ self, Write };
+use std::os::unix::io::AsRawFd;
+use std::sync::{ Arc, Mutex };
+use tokio::time::{ self, Instant };
+
+/// how many lines of the log are kept around to show
+const LOG_LINES: usize = 200;
+/// how many of the most recent errors are shown
+const RECENT_ERRORS: usize = 8;
+
+pub struct Dashboard {
+	db: DatabaseThing,
+	control: ControlHandle,
+	daily_limit: usize,
+	/// for checking the api rate limit on github.com, if there's a token for it
+	github: Option<GithubClient>,
+	log_path: String
+}
+
+/// what's been read from the log so far
+#[derive(Default)]
+struct Log {
+	lines: VecDeque<String>,
+	errors: VecDeque<String>,
+	/// the end of the log, if it's partway through a line
+	partial: String
+}
+
+/// the last rate limit that was checked, or why it couldn't be
+type RateLimitStatus = Option<std::result::Result<RateLimit, String>>;
+
+impl Dashboard {
+	pub fn new(db: &DatabaseThing, config: &Config, control: &RunControl, log_path: &str) -> Result<Self> {
+		let github = match (&config.github_token, config.simulate_forges) {
+			(Some(token), None) => { Some(GithubClient::new("github.com", token.expose())?) }
+			_ => { None }
+		};
+
+		Ok(Self {
+			db: db.clone(),
+			control: control.handle(),
+			daily_limit: config.daily_issue_limit,
+			github,
+			log_path: log_path.into()
+		})
+	}
+
+	/// shows the dashboard until `run` finishes, then prints the summary it
+	/// returns (or the end of the log, if it didn't get that far)
+	pub async fn run(self, run: impl Future<Output = Result<String>>) -> Result {
+		let log = OpenOptions::new().create(true).append(true).open(&self.log_path)
+			.map_err(|e| format!("error opening {}: {e}", self.log_path))?;
+		let mut log_reader = File::open(&self.log_path)
+			.map_err(|e| format!("error opening {}: {e}", self.log_path))?;
+		// only this run's part is interesting
+		io::copy(&mut log_reader, &mut io::sink())?;
+
+		let rate_limit = Arc::new(Mutex::new(None));
+		let checker = self.github.map(|github| tokio::spawn(check_rate_limit(github, rate_limit.clone())));
+
+		let mut read = Log::default();
+		let res = {
+			let screen = Screen::enter(&log)?;
+			let mut terminal = Terminal::new(CrosstermBackend::new(screen.tty.try_clone()?))?;
+
+			tokio::pin!(run);
+			let mut tick = time::interval(time::Duration::from_millis(250));
+			loop {
+				tokio::select! {
+					res = &mut run => { break res }
+					_ = tick.tick() => {}
+				}
+
+				handle_keys(&self.control)?;
+				read.read_from(&mut log_reader)?;
+				let rate_limit = rate_limit.lock().unwrap().clone();
+				terminal.draw(|frame| draw(frame, &self.db, &self.control, self.daily_limit, &rate_limit, &read))?;
+			}
+		};
+
+		if let Some(checker) = checker { checker.abort() }
+
+		match &res {
+			Ok(summary) => { println!("{summary}") }
+			// whatever happened instead is at the end of the log
+			Err(_) => {
+				read.read_from(&mut log_reader)?;
+				for line in read.lines.iter().skip(read.lines.len().saturating_sub(10)) {
+					println!("{line}");
+				}
+			}
+		}
+		println!("everything the run printed is in {}", self.log_path);
+
+		res.map(drop)
+	}
+}
+
+/// the dashboard taking over the terminal, and stdout going to the log. both
+/// are put back when it's dropped, even if something panics
+struct Screen {
+	tty: File,
+	/// where stdout pointed before
+	stdout: i32
+}
+
+impl Screen {
+	fn enter(log: &File) -> Result<Self> {
+		// the terminal itself, since stdout is about to go somewhere else
+		let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty")
+			.map_err(|e| format!("the dashboard needs a terminal: {e}"))?;
+
+		io::stdout().flush()?;
+		// safety: only file descriptors this owns (or stdout) are passed, and they're all open
+		let stdout = unsafe { libc::dup(libc::STDOUT_FILENO) };
+		if stdout < 0 || unsafe { libc::dup2(log.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+			return Err(format!("error sending output to the log: {}", io::Error::last_os_error()).into())
+		}
+		let screen = Self { tty: tty.try_clone()?, stdout };
+
+		terminal::enable_raw_mode()?;
+		execute!(tty, EnterAlternateScreen, cursor::Hide)?;
+		Ok(screen)
+	}
+}
+
+impl Drop for Screen {
+	fn drop(&mut self) {
+		let _ = terminal::disable_raw_mode();
+		let _ = execute!(self.tty, LeaveAlternateScreen, cursor::Show);
+
+		let _ = io::stdout().flush();
+		// safety: `self.stdout` was duplicated from stdout in `enter`, and isn't used again
+		unsafe {
+			libc::dup2(self.stdout, libc::STDOUT_FILENO);
+			libc::close(self.stdout);
+		}
+	}
+}
+
+impl Log {
+	/// reads whatever's been added to the log since last time
+	fn read_from(&mut self, reader: &mut File) -> Result {
+		let mut new = String::new();
+		io::Read::read_to_string(reader, &mut new)?;
+		if new.is_empty() { return Ok(()) }
+
+		let text = std::mem::take(&mut self.partial) + &new;
+		let mut lines = text.split('\n').collect::<Vec<_>>();
+		self.partial = lines.pop().unwrap_or_default().into();
+
+		for line in lines {
+			if line.to_lowercase().contains("error") {
+				self.errors.push_back(line.into());
+				if self.errors.len() > RECENT_ERRORS { self.errors.pop_front(); }
+			}
+			self.lines.push_back(line.into());
+			if self.lines.len() > LOG_LINES { self.lines.pop_front(); }
+		}
+		Ok(())
+	}
+}
+
+/// p pauses (and resumes), s skips the package the run is on, and q (or ctrl-c,
+/// since raw mode means it isn't a signal) stops it after that package
+fn handle_keys(control: &ControlHandle) -> Result {
+	while event::poll(std::time::Duration::ZERO)? {
+		let key = match event::read()? {
+			Event::Key(key) if key.kind == KeyEventKind::Press => { key }
+			_ => { continue }
+		};
+
+		match key.code {
+			KeyCode::Char('p') | KeyCode::Char(' ') => {
+				if control.toggle_pause() {
+					info!("pausing after the current package (from the dashboard)");
+				} else {
+					info!("resuming (from the dashboard)");
+				}
+			}
+			KeyCode::Char('s') => {
+				info!("skipping the current package (from the dashboard)");
+				control.skip();
+			}
+			KeyCode::Char('q') | KeyCode::Esc => { stop(control) }
+			KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => { stop(control) }
+			_ => {}
+		}
+	}
+	Ok(())
+}
+
+fn stop(control: &ControlHandle) {
+	if control.is_stopping() { return }
+	info!("stopping after the current package (from the dashboard)");
+	control.stop();
+}
+
+/// checks the rate limit every minute, since checking doesn't use any of it up
+async fn check_rate_limit(github: GithubClient, status: Arc<Mutex<RateLimitStatus>>) {
+	let mut interval = time::interval(time::Duration::from_secs(60));
+	loop {
+		interval.tick().await;
+		let rate_limit = github.rate_limit().await.map_err(|e| e.to_string());
+		*status.lock().unwrap() = Some(rate_limit);
+	}
+}
+
+fn draw<B: Backend>(
+	frame: &mut Frame<B>,
+	db: &DatabaseThing,
+	control: &ControlHandle,
+	daily_limit: usize,
+	rate_limit: &RateLimitStatus,
+	log: &Log
+) {
+	let progress = control.progress();
+	let areas = Layout::default()
+		.direction(Direction::Vertical)
+		.constraints([
+			Constraint::Length(5),
+			Constraint::Length(1),
+			Constraint::Length(5),
+			Constraint::Length(RECENT_ERRORS as u16 + 2),
+			Constraint::Min(3),
+			Constraint::Length(1)
+		])
+		.split(frame.size());
+	let (run_area, gauge_area, limits_area, errors_area, log_area, keys_area) = (areas[0], areas[1], areas[2], areas[3], areas[4], areas[5]);
+
+	frame.render_widget(Paragraph::new(run_lines(control, &progress)).block(bordered(" run ")), run_area);
+
+	let ratio = if progress.total == 0 { 0.0 } else { progress.done as f64 / progress.total as f64 };
+	frame.render_widget(
+		Gauge::default()
+			.gauge_style(Style::default().fg(Color::Green))
+			.ratio(ratio.min(1.0))
+			.label(format!("{} of {} packages", progress.done, progress.total)),
+		gauge_area
+	);
+
+	let last_hour = db.issues_filed_since(Utc::now() - Duration::hours(1));
+	let last_day = db.issues_filed_last_day();
+	let daily = if daily_limit > 0 {
+		format!("last 24 hours: {last_day}, {} of {daily_limit} left today", daily_limit.saturating_sub(last_day))
+	} else {
+		format!("last 24 hours: {last_day}, no daily limit")
+	};
+	let github = match rate_limit {
+		Some(Ok(rate_limit)) => { format!("github.com api: {} of {} requests left, resets at {}", rate_limit.remaining, rate_limit.limit, rate_limit.resets_at.format("%H:%M:%S")) }
+		Some(Err(e)) => { format!("github.com api: error checking the rate limit: {e}") }
+		None => { "github.com api: not checked".into() }
+	};
+	let limits = vec![
+		Spans::from(format!("issues filed in the last hour: {last_hour}")),
+		Spans::from(daily),
+		Spans::from(github)
+	];
+	frame.render_widget(Paragraph::new(limits).block(bordered(" limits ")), limits_area);
+
+	let errors = log.errors.iter().map(|line| Spans::from(Span::styled(line.as_str(), Style::default().fg(Color::Red)))).collect::<Vec<_>>();
+	frame.render_widget(Paragraph::new(errors).block(bordered(" recent errors ")), errors_area);
+
+	// as much of the end of the log as fits
+	let height = log_area.height.saturating_sub(2) as usize;
+	let lines = log.lines.iter()
+		.skip(log.lines.len().saturating_sub(height))
+		.map(|line| Spans::from(line.as_str()))
+		.collect::<Vec<_>>();
+	frame.render_widget(Paragraph::new(lines).block(bordered(" log ")), log_area);
+
+	let keys = Paragraph::new(" p pause/resume   s skip the current package   q stop")
+		.style(Style::default().add_modifier(Modifier::DIM));
+	frame.render_widget(keys, keys_area);
+}
+
+fn bordered(title: &'static str) -> Block<'static> {
+	Block::default().borders(Borders::ALL).title(title)
+}
+
+fn run_lines(control: &ControlHandle, progress: &Progress) -> Vec<Spans<'static>> {
+	let (state, color) = if control.is_stopping() {
+		("stopping after the current package", Color::Yellow)
+	} else if control.is_paused() {
+		("paused", Color::Yellow)
+	} else {
+		("running", Color::Green)
+	};
+	let current = match &progress.current {
+		Some(current) => { current.clone() }
+		None => { "-".into() }
+	};
+	let waiting = match progress.waiting_until {
+		Some(until) => { format!(", filing the next one in {}s", until.saturating_duration_since(Instant::now()).as_secs()) }
+		None => { String::new() }
+	};
+
+	vec![
+		Spans::from(vec![Span::raw("state: "), Span::styled(state, Style::default().fg(color))]),
+		Spans::from(format!("current: {current}")),
+		Spans::from(format!("issues filed: {}, failed: {}{waiting}", progress.issues_filed, progress.failed))
+	]
+}
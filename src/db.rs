@@ -1,12 +1,71 @@
 //! cheapo database-ish sort of file to store state
 
-use serde::{ Deserialize, Serialize };
+use crate::error::{ Class, Error, Failure };
+use crate::forge::{ IssueComment, RepoLocation };
+use crate::health;
+use crate::metrics;
+use chrono::{ DateTime, Utc };
+use serde::{ de, Deserialize, Deserializer, Serialize, Serializer };
+use std::collections::{ BTreeMap, BTreeSet };
+use std::fmt;
 use std::fs as sync_fs;
+use std::io::Write as _;
 use std::path;
-use std::sync::{ Arc, Mutex, MutexGuard };
+use std::str::FromStr;
+use std::sync::{ Arc, Mutex, MutexGuard, TryLockError };
 use std::thread::panicking;
-use std::time::SystemTime;
+use std::time::{ Duration, Instant, SystemTime };
 use tokio::fs as async_fs;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// bumped when the file changes in a way older versions can't read. files from
+/// before there was a version are 0, and everything since is backwards
+/// compatible with them
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// how many backups of the file are kept, and how often one is made, see [`back_up`]
+const BACKUPS_KEPT: usize = 5;
+const BACKUP_EVERY: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// for the names of backups and corrupt files, so they sort oldest first
+const FILE_TIME_FORMAT: &str = "%Y-%m-%dT%H-%M-%S";
+
+/// how many of the last runs are kept, see [`DatabaseThing::recent_runs`]
+const RUNS_KEPT: usize = 20;
+
+/// how many of the comments on a package's issue are kept, and how much of each
+const COMMENTS_KEPT: usize = 5;
+const COMMENT_MAX_LENGTH: usize = 300;
+
+/// what's left where an email address (or the file an email was written to)
+/// was, for owners who asked to be forgotten, see [`DatabaseThing::forget_owner`]
+pub const FORGOTTEN: &str = "(forgotten)";
+
+/// when saving the database waits for it to really be on the disk (fsync), rather
+/// than only handed to the os, which loses it if the machine (not only the bot)
+/// goes down before the os gets round to writing it
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Durability {
+	/// every save, the safest, but slow on slow disks, since it's saved after every package
+	Always,
+	/// when a batch of packages starts and finishes, and the last save before the bot stops
+	Batch,
+	/// never, leaving it to the os
+	Never
+}
+
+impl FromStr for Durability {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match &*s.trim().to_lowercase() {
+			"always" => { Ok(Self::Always) }
+			"batch" => { Ok(Self::Batch) }
+			"never" => { Ok(Self::Never) }
+			_ => { Err(format!("unknown durability {s} (expected always, batch, or never)")) }
+		}
+	}
+}
 
 #[derive(Clone)]
 pub struct DatabaseThing {
@@ -20,122 +79,1433 @@ struct DatabaseThingInner {
 
 struct DatabaseThingMeta {
 	pub filename: String,
-	pub last_write_call_time: SystemTime
+	pub last_write_call_time: SystemTime,
+	/// when the file was last backed up, see [`back_up`]. it's backed up the
+	/// first time it's saved, and every [`BACKUP_EVERY`] after
+	pub last_backup_at: Option<Instant>,
+	/// if set, changes are only kept in memory and never written to the file
+	pub read_only: bool,
+	pub durability: Durability,
+	/// whether the next save is a batch boundary, for [`Durability::Batch`]
+	pub sync_next_save: bool,
+	/// where to send packages' state changes, see [`crate::webhook`] and [`crate::notify`]
+	pub state_changes: Vec<UnboundedSender<StateChange>>
+}
+
+/// a package going from one state to another
+#[derive(Clone, Serialize)]
+pub struct StateChange {
+	pub package: String,
+	pub from: &'static str,
+	pub to: &'static str,
+	pub at: DateTime<Utc>,
+	/// the new state, with everything in it
+	pub state: PackageState
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 struct DatabaseThingData {
+	/// see [`SCHEMA_VERSION`]. it's whatever the file had until it's saved again
+	#[serde(default)]
+	pub schema_version: u32,
 	pub saved_on_panic: bool,
-	pub packages: Vec<PackageState>
+	pub packages: Vec<Package>,
+	#[serde(default)]
+	pub registry_sync: RegistrySyncState,
+	/// the run that's going on (or that crashed, if there is one at startup)
+	#[serde(default)]
+	pub batch: Option<Batch>,
+	/// repositories (by [`RepoLocation::key`]) that failed recently, and when they can be tried again
+	#[serde(default)]
+	pub repo_cooldowns: BTreeMap<String, DateTime<Utc>>,
+	/// forge hosts that rate limited the bot, and when they can be used again
+	#[serde(default)]
+	pub host_cooldowns: BTreeMap<String, DateTime<Utc>>,
+	/// forge hosts nothing's filed on for now, see [`crate::breaker`]
+	#[serde(default)]
+	pub breakers: BTreeMap<String, TrippedBreaker>,
+	/// when issues were filed, for the last week, so the daily limit holds across runs
+	#[serde(default)]
+	pub recent_filings: Vec<DateTime<Utc>>,
+	/// when the last run report was made, see [`crate::report`]
+	#[serde(default)]
+	pub last_report_at: Option<DateTime<Utc>>,
+	/// when the last digest was sent, see [`crate::digest`]
+	#[serde(default)]
+	pub last_digest_at: Option<DateTime<Utc>>,
+	/// the last [`RUNS_KEPT`] runs, oldest first
+	#[serde(default)]
+	pub recent_runs: Vec<RunRecord>,
+	/// times the file was found corrupt at startup, oldest first
+	#[serde(default)]
+	pub recoveries: Vec<Recovery>,
+	/// lowercase logins of owners who asked for what's stored about them to be
+	/// deleted, so it isn't collected again when their packages are synced
+	#[serde(default)]
+	pub forgotten_owners: BTreeSet<String>
+}
+
+/// the database file being corrupt at startup, and what was loaded instead
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Recovery {
+	pub at: DateTime<Utc>,
+	/// what was wrong with it
+	pub problem: String,
+	/// where the corrupt file was moved to
+	pub moved_to: String,
+	/// the backup that was loaded instead, if there was one that wasn't corrupt too
+	pub restored_from: Option<String>
+}
+
+/// everything tried on a forge host failing, so nothing's filed on it for a bit, see [`crate::breaker`]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TrippedBreaker {
+	pub tripped_at: DateTime<Utc>,
+	pub until: DateTime<Utc>,
+	/// how many failed in a row
+	pub failures: u32,
+	pub last_error: String
+}
+
+/// how a run went, for seeing if runs are getting slower or failing more
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RunRecord {
+	pub started_at: DateTime<Utc>,
+	pub finished_at: DateTime<Utc>,
+	/// packages gone through, contacted or not
+	pub processed: usize,
+	pub issues_filed: usize,
+	pub emailed: usize,
+	pub failed: usize
 }
 
-use package_state::*;
-pub use package_state::{ PackageNew, Repository };
+impl RunRecord {
+	/// packages gone through per hour
+	pub fn throughput(&self) -> f64 {
+		let hours = (self.finished_at - self.started_at).num_milliseconds() as f64 / 3_600_000.0;
+		match hours > 0.0 {
+			true => { self.processed as f64 / hours }
+			false => { 0.0 }
+		}
+	}
+
+	/// all of `runs` as one, for rates across them. `None` if there aren't any
+	pub fn combined(runs: &[RunRecord]) -> Option<RunRecord> {
+		Some(RunRecord {
+			started_at: runs.first()?.started_at,
+			finished_at: runs.last()?.finished_at,
+			processed: runs.iter().map(|run| run.processed).sum(),
+			issues_filed: runs.iter().map(|run| run.issues_filed).sum(),
+			emailed: runs.iter().map(|run| run.emailed).sum(),
+			failed: runs.iter().map(|run| run.failed).sum()
+		})
+	}
+
+	/// fraction (0 to 1) of the packages it tried contacting that failed
+	pub fn error_rate(&self) -> f64 {
+		let tried = self.issues_filed + self.emailed + self.failed;
+		match tried {
+			0 => { 0.0 }
+			tried => { self.failed as f64 / tried as f64 }
+		}
+	}
+}
+
+/// packages a run is going through, so it can be carried on after a crash, or
+/// after it stopped for a host's rate limit
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Batch {
+	pub started_at: DateTime<Utc>,
+	/// every package the run is going to go through, in order
+	pub packages: Vec<String>,
+	/// how many of `packages` have been gone through
+	pub progress: usize,
+	/// if the run stopped because a host ran out of api requests, when they
+	/// reset, which is when the daemon carries on with it
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub resume_at: Option<DateTime<Utc>>
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct RegistrySyncState {
+	/// when the last sync that went through to the end was started
+	pub last_sync: Option<DateTime<Utc>>,
+	/// when the last full sync, which refreshes the download and star counts of every package, was started
+	#[serde(default)]
+	pub counts_refreshed_at: Option<DateTime<Utc>>,
+	/// where a sync that hasn't finished got to, so it can carry on from there
+	#[serde(default)]
+	pub cursor: Option<RegistrySyncCursor>,
+	/// packages in the registry without a repository, and their downloads. they
+	/// aren't stored with the others, since there's no asking them, but they
+	/// count towards how much of the registry's downloads are covered
+	#[serde(default)]
+	pub without_repository: BTreeMap<String, u32>
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RegistrySyncCursor {
+	pub endpoint: String,
+	/// next page to fetch, or `None` if this endpoint is done
+	pub next: Option<String>,
+	/// pages of this endpoint done so far
+	pub page: usize,
+	pub incremental: bool,
+	pub started_at: DateTime<Utc>
+}
+
+pub use package_state::*;
 mod package_state {
 	use super::*;
-	#[derive(Clone, Deserialize, Serialize)]
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct Package {
+		pub info: PackageNew,
+		pub state: PackageState,
+		/// every state it's gone to, oldest first
+		#[serde(default, skip_serializing_if = "Vec::is_empty")]
+		pub history: Vec<Transition>,
+		/// the most recent comments on its issue, from the last time it was checked
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub comments: Option<SeenComments>,
+		/// when the first comment from someone else was seen on its issue, which
+		/// is kept even once the comment isn't one of the last few any more
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub first_reply_at: Option<DateTime<Utc>>,
+		/// what it was called on the registry before it was renamed, oldest first
+		#[serde(default, skip_serializing_if = "Vec::is_empty")]
+		pub previous_names: Vec<String>,
+		/// its repository's license, from when it was last checked before filing
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub license: Option<RepoLicense>,
+		/// how lately its repository's been worked on, from when it was last
+		/// checked before filing
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub activity: Option<RepoActivity>
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct Transition {
+		pub at: DateTime<Utc>,
+		pub from: String,
+		/// the state it went to, as it was then
+		pub state: PackageState,
+		/// why, for states set by hand with `mark`
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub note: Option<String>
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct RepoLicense {
+		/// its spdx id (what gitlab calls it, on gitlab), like `MIT`, or
+		/// `NOASSERTION` for one the forge didn't recognise. `None` if there isn't one
+		pub license: Option<String>,
+		pub checked_at: DateTime<Utc>
+	}
+
+	/// for telling the repositories anyone's still looking at from the abandoned ones
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct RepoActivity {
+		/// the last push to any branch on github, or the last commit on the default
+		/// branch on gitlab. `None` for empty repositories
+		pub pushed_at: Option<DateTime<Utc>>,
+		/// `None` if it's never had one
+		pub latest_release: Option<RepoRelease>,
+		pub checked_at: DateTime<Utc>
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct RepoRelease {
+		pub tag: String,
+		/// `None` if the forge doesn't say
+		pub released_at: Option<DateTime<Utc>>
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct SeenComments {
+		pub checked_at: DateTime<Utc>,
+		pub comments: Vec<SeenComment>
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct SeenComment {
+		pub url: String,
+		/// cut short if it's long
+		pub body: String,
+		pub created_at: DateTime<Utc>
+	}
+
+	impl Package {
+		/// removes its owner's personal details, and the comments on its issue,
+		/// keeping its state and history. returns the files emails to them were
+		/// written to, for deleting
+		pub fn forget_personal_details(&mut self) -> Vec<String> {
+			self.info.forget_personal_details();
+			self.comments = None;
+			let mut email_files = std::iter::once(&mut self.state)
+				.chain(self.history.iter_mut().map(|transition| &mut transition.state))
+				.filter_map(PackageState::forget_email)
+				.collect::<Vec<_>>();
+			email_files.sort();
+			email_files.dedup();
+			email_files
+		}
+	}
+
+	impl SeenComment {
+		pub fn new(comment: &IssueComment) -> Self {
+			let mut body = comment.body.trim().chars().take(COMMENT_MAX_LENGTH).collect::<String>();
+			if body.len() < comment.body.trim().len() { body.push('…') }
+			Self { url: comment.url.clone(), body, created_at: comment.created_at }
+		}
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
 	pub enum PackageState {
-		New(PackageNew),
-		IssueFiled(PackageIssueFiled)
+		New,
+		IssueFiled(PackageIssueFiled),
+		ContactedByEmail(PackageContactedByEmail),
+		/// not eligible to be contacted for now, reconsidered every run
+		Deferred(PackageDeferred),
+		/// deprecated or unpublished by its author, so not contacted at all
+		SkippedDeprecated(PackageSkipped),
+		/// something looked off, needs a human to look at it before anything is filed
+		NeedsReview(PackageNeedsReview),
+		/// already published on the pulsar backend, so there's nothing to ask for
+		AlreadyMigrated(PackageAlreadyMigrated),
+		/// on the blocklist, or contacting it failed in a way that's never going to
+		/// work (see [`crate::error::Class::Terminal`]), so never contacted
+		Blocked(PackageSkipped),
+		/// the owner asked to never be contacted, see [`crate::config::Config::opt_out_list`]
+		OptedOut(PackageSkipped),
+		/// its owner declined for another of their packages, so it's not asked
+		/// about, see [`crate::config::Config::skip_declined_owners`]
+		DeclinedByOwnerPolicy(PackageSkipped),
+		/// its repository hasn't been pushed to in years, so it's not asked about
+		/// (or only once everything else has been), see
+		/// [`crate::config::Config::dormant_after_years`]
+		Dormant(PackageSkipped),
+		/// contacting failed, tried again at the start of a run once `retry_at` has passed
+		RetryPending(PackageRetryPending),
+		/// saved right before filing an issue, and replaced once it's filed. if a run
+		/// starts with a package still in this state, the last one crashed partway
+		/// through filing, and the issue may or may not exist
+		IssueFilingInProgress(PackageFilingInProgress),
+		/// the maintainer said yes
+		MaintainerApproved(PackageApproved),
+		/// registered with the pulsar backend after being approved
+		Migrated(PackageMigrated),
+		/// the maintainer took back their permission after giving it
+		ConsentRevoked(PackageConsentRevoked)
+	}
+
+	impl PackageState {
+		/// the name of the variant, for picking states on the command line
+		pub fn kind(&self) -> &'static str {
+			match self {
+				Self::New => { "New" }
+				Self::IssueFiled(_) => { "IssueFiled" }
+				Self::ContactedByEmail(_) => { "ContactedByEmail" }
+				Self::Deferred(_) => { "Deferred" }
+				Self::SkippedDeprecated(_) => { "SkippedDeprecated" }
+				Self::NeedsReview(_) => { "NeedsReview" }
+				Self::AlreadyMigrated(_) => { "AlreadyMigrated" }
+				Self::Blocked(_) => { "Blocked" }
+				Self::OptedOut(_) => { "OptedOut" }
+				Self::DeclinedByOwnerPolicy(_) => { "DeclinedByOwnerPolicy" }
+				Self::Dormant(_) => { "Dormant" }
+				Self::RetryPending(_) => { "RetryPending" }
+				Self::IssueFilingInProgress(_) => { "IssueFilingInProgress" }
+				Self::MaintainerApproved(_) => { "MaintainerApproved" }
+				Self::Migrated(_) => { "Migrated" }
+				Self::ConsentRevoked(_) => { "ConsentRevoked" }
+			}
+		}
+
+		/// for printing
+		pub fn name(&self) -> &'static str {
+			match self {
+				Self::New => { "new" }
+				Self::IssueFiled(_) => { "issue filed" }
+				Self::ContactedByEmail(_) => { "contacted by email" }
+				Self::Deferred(_) => { "deferred" }
+				Self::SkippedDeprecated(_) => { "skipped (deprecated or unpublished)" }
+				Self::NeedsReview(_) => { "needs review" }
+				Self::AlreadyMigrated(_) => { "already migrated" }
+				Self::Blocked(_) => { "blocked" }
+				Self::OptedOut(_) => { "opted out" }
+				Self::DeclinedByOwnerPolicy(_) => { "skipped, its owner declined another package" }
+				Self::Dormant(_) => { "dormant, its repository hasn't been pushed to in years" }
+				Self::RetryPending(_) => { "waiting to be retried" }
+				Self::IssueFilingInProgress(_) => { "issue filing in progress" }
+				Self::MaintainerApproved(_) => { "approved by maintainer" }
+				Self::Migrated(_) => { "migrated" }
+				Self::ConsentRevoked(_) => { "consent revoked" }
+			}
+		}
+
+		/// replaces the address it was contacted at with [`FORGOTTEN`], returning
+		/// the file the email was written to, if there was one
+		fn forget_email(&mut self) -> Option<String> {
+			let approval = match self {
+				Self::ContactedByEmail(contacted) => {
+					contacted.email = FORGOTTEN.into();
+					let file = std::mem::replace(&mut contacted.email_file, FORGOTTEN.into());
+					return Some(file).filter(|file| file != FORGOTTEN && !file.is_empty())
+				}
+				Self::MaintainerApproved(approval) => { approval }
+				Self::Migrated(migrated) => { &mut migrated.approval }
+				Self::ConsentRevoked(revoked) => { &mut revoked.approval }
+				_ => { return None }
+			};
+			if let Some(email) = &mut approval.email {
+				*email = FORGOTTEN.into();
+			}
+			None
+		}
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct PackageApproved {
+		/// the issue permission was asked for on, if it was asked for with an issue
+		pub issue_url: Option<String>,
+		/// the address permission was asked for at, if it was asked for by email
+		pub email: Option<String>,
+		pub contacted_at: DateTime<Utc>,
+		/// link to the comment permission was given in (or some other reference to it, for emails)
+		pub approval_url: String,
+		pub approved_at: DateTime<Utc>,
+		/// what went wrong the last time registering it with the pulsar backend was tried
+		#[serde(default)]
+		pub registration_error: Option<String>
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct PackageMigrated {
+		pub approval: PackageApproved,
+		pub migrated_at: DateTime<Utc>,
+		/// what the pulsar backend said when it was registered
+		pub backend_response: String
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct PackageConsentRevoked {
+		pub approval: PackageApproved,
+		/// if it had been registered with the pulsar backend already, when, so it can be delisted
+		pub migrated_at: Option<DateTime<Utc>>,
+		/// link to the comment permission was taken back in (or some other reference to it, for emails)
+		pub revocation_url: String,
+		pub revoked_at: DateTime<Utc>
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct PackageFilingInProgress {
+		pub repository: RepoLocation,
+		pub started_at: DateTime<Utc>,
+		/// how many times filing had failed before this attempt
+		pub attempts: u32,
+		/// see [`PackageIssueFiled::template_variant`]
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub template_variant: Option<String>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub locale: Option<String>
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct PackageRetryPending {
+		pub attempts: u32,
+		pub last_error: String,
+		pub retry_at: DateTime<Utc>
 	}
 
-	#[derive(Clone, Deserialize, Serialize)]
+	#[derive(Clone, Debug, Deserialize, Serialize)]
 	pub struct PackageNew {
 		pub name: String,
 		pub repository: Repository,
 		pub downloads: u32,
-		pub stargazers_count: u32
+		pub stargazers_count: u32,
+		/// contents of the package's package.json, or at least the parts we care about
+		#[serde(default)]
+		pub metadata: PackageMetadata,
+		/// whether this came from the registry's themes endpoint
+		#[serde(default)]
+		pub theme: bool,
+		#[serde(default)]
+		pub releases: Releases,
+		/// every published version. the registry has these as a map of version
+		/// to that version's package.json, but we only keep the version numbers
+		#[serde(default, deserialize_with = "deserialize_versions")]
+		pub versions: Vec<String>,
+		/// who publishes the package, see [`PackageOwner::for_package`]
+		#[serde(default)]
+		pub owner: Option<PackageOwner>,
+		/// what its readme's written in, if it's not english, see [`crate::locale::detect`]
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub readme_locale: Option<String>
+	}
+
+	/// who to get in touch with about a package
+	#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+	pub struct PackageOwner {
+		/// forge account the package was published from
+		pub login: Option<String>,
+		pub name: Option<String>,
+		pub email: Option<String>
+	}
+
+	impl PackageOwner {
+		/// atom.io packages are published from the account that owns the
+		/// repository, unless the registry says otherwise. name and email
+		/// come from the package.json author, if there is one
+		pub fn for_package(package: &PackageNew, login: Option<String>) -> Self {
+			let login = login.or_else(|| {
+				RepoLocation::parse(&package.repository.url).map(|repo| repo.owner)
+			});
+			let author = package.metadata.author.clone().unwrap_or_default();
+
+			Self { login, name: author.name, email: author.email }
+		}
 	}
 
-	#[derive(Clone, Deserialize, Serialize)]
+	#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+	pub struct Releases {
+		#[serde(default)]
+		pub latest: Option<String>
+	}
+
+	#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+	pub struct PackageMetadata {
+		#[serde(default)]
+		pub version: Option<String>,
+		#[serde(default)]
+		pub author: Option<Author>,
+		/// `"ui"` or `"syntax"` for themes
+		#[serde(default)]
+		pub theme: Option<String>,
+		#[serde(default)]
+		pub description: Option<String>,
+		/// some package.json files have this as `true`, some as a message
+		#[serde(default, deserialize_with = "deserialize_deprecated")]
+		pub deprecated: Option<String>
+	}
+
+	/// package.json allows the author to be either `"name <email> (url)"`
+	/// or an object with those fields, this is stored as the former
+	#[derive(Clone, Debug, Default)]
+	pub struct Author {
+		pub name: Option<String>,
+		pub email: Option<String>,
+		pub url: Option<String>
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
 	pub struct Repository {
 		pub r#type: String,
 		pub url: String
 	}
 
-	#[derive(Clone, Deserialize, Serialize)]
+	#[derive(Clone, Debug, Deserialize, Serialize)]
 	pub struct PackageIssueFiled {
-		pub name: String,
-		pub repository: (String, String),
-		pub downloads: u32,
-		pub stargazers_count: u32
+		pub repository: RepoLocation,
+		pub issue_url: String,
+		pub filed_at: DateTime<Utc>,
+		/// other packages in the same repository that this issue was filed for too
+		#[serde(default)]
+		pub grouped_with: Vec<String>,
+		/// comments to post on the issue if nobody replies to it
+		#[serde(default)]
+		pub reminders: Vec<Reminder>,
+		/// when someone replied (or the issue was closed), which cancels any reminders left
+		#[serde(default)]
+		pub replied_at: Option<DateTime<Utc>>,
+		/// which of `template_variants` it was filed with, if there were any
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub template_variant: Option<String>,
+		/// which of `locales.templates` it was filed with, if it was
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub locale: Option<String>
+	}
+
+	#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+	pub enum ReminderKind {
+		FollowUp,
+		/// the last one, after this the package is left alone
+		FinalReminder
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct Reminder {
+		pub kind: ReminderKind,
+		pub due_at: DateTime<Utc>,
+		#[serde(default)]
+		pub sent_at: Option<DateTime<Utc>>
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct PackageContactedByEmail {
+		pub email: String,
+		/// where the generated email was written to
+		pub email_file: String,
+		/// if false, the email was only generated, and needs to be sent by hand
+		pub sent: bool,
+		pub contacted_at: DateTime<Utc>
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct PackageSkipped {
+		pub reason: String,
+		pub skipped_at: DateTime<Utc>,
+		/// when it's blocked because contacting it failed, see [`crate::retry`]
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub contact_failure: Option<ContactFailure>
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct PackageNeedsReview {
+		pub reason: String,
+		pub flagged_at: DateTime<Utc>,
+		/// when it needs review because contacting it failed, see [`crate::retry`]
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub contact_failure: Option<ContactFailure>
+	}
+
+	/// what went wrong contacting a package, so `retry` knows it can be tried again
+	#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+	pub struct ContactFailure {
+		pub failure: Failure,
+		pub class: Class
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct PackageAlreadyMigrated {
+		pub found_at: DateTime<Utc>
+	}
+
+	impl PackageNew {
+		/// fills in the owner from the rest of the package info if it's missing
+		pub fn with_owner(mut self) -> Self {
+			if self.owner.is_none() {
+				self.owner = Some(PackageOwner::for_package(&self, None));
+			}
+			self
+		}
+
+		/// email to contact the package's owner at, if there is one
+		pub fn contact_email(&self) -> Option<&str> {
+			self.owner.as_ref()
+				.and_then(|owner| owner.email.as_deref())
+				.or_else(|| self.metadata.author.as_ref().and_then(|author| author.email.as_deref()))
+		}
+
+		/// whether `login` publishes it, or owns the repository it's in
+		pub fn is_owned_by(&self, login: &str) -> bool {
+			let publisher = self.owner.as_ref().and_then(|owner| owner.login.as_deref());
+			let repo_owner = RepoLocation::parse(&self.repository.url).map(|repo| repo.owner);
+			publisher.into_iter().chain(repo_owner.as_deref()).any(|owner| owner.eq_ignore_ascii_case(login))
+		}
+
+		/// removes its owner's name and email, and the package.json author. the
+		/// login stays, since it's in the repository's url anyway
+		pub fn forget_personal_details(&mut self) {
+			self.metadata.author = None;
+			if let Some(owner) = &mut self.owner {
+				owner.name = None;
+				owner.email = None;
+			}
+		}
+
+		pub fn is_theme(&self) -> bool {
+			self.theme || self.metadata.theme.is_some()
+		}
+
+		/// why the package is deprecated, if it looks like it is
+		pub fn deprecation(&self) -> Option<String> {
+			if let Some(message) = &self.metadata.deprecated {
+				return Some(format!("package is deprecated: {message}"))
+			}
+
+			let description = self.metadata.description.as_deref().unwrap_or_default().trim();
+			let first_word = description.trim_start_matches(|c: char| !c.is_alphanumeric());
+			if first_word.to_lowercase().starts_with("deprecated") {
+				return Some(format!("description says package is deprecated: {description}"))
+			}
+
+			// the latest release was unpublished if it isn't one of the published versions
+			// (no versions at all means we just don't know, eg. data imported from elsewhere)
+			if let Some(latest) = &self.releases.latest {
+				if !self.versions.is_empty() && !self.versions.contains(latest) {
+					return Some(format!("latest version {latest} was unpublished"))
+				}
+			}
+
+			None
+		}
+	}
+
+	fn deserialize_deprecated<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+		#[derive(Deserialize)]
+		#[serde(untagged)]
+		enum DeprecatedRepr {
+			Flag(bool),
+			Message(String)
+		}
+
+		Ok(match Option::<DeprecatedRepr>::deserialize(deserializer)? {
+			Some(DeprecatedRepr::Flag(true)) => { Some("deprecated".into()) }
+			Some(DeprecatedRepr::Flag(false)) | None => { None }
+			Some(DeprecatedRepr::Message(message)) => { Some(message) }
+		})
+	}
+
+	/// accepts a map (from the registry, keys are kept) or a list (from our own state file)
+	pub fn deserialize_versions<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+		struct VersionsVisitor;
+
+		impl<'de> de::Visitor<'de> for VersionsVisitor {
+			type Value = Vec<String>;
+
+			fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+				f.write_str("a list of versions, or a map with versions as keys")
+			}
+
+			fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+				let mut versions = Vec::new();
+				while let Some(version) = seq.next_element()? {
+					versions.push(version);
+				}
+				Ok(versions)
+			}
+
+			fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+				let mut versions = Vec::new();
+				while let Some((version, _)) = map.next_entry::<String, de::IgnoredAny>()? {
+					versions.push(version);
+				}
+				Ok(versions)
+			}
+		}
+
+		deserializer.deserialize_any(VersionsVisitor)
+	}
+
+	#[derive(Clone, Debug, Deserialize, Serialize)]
+	pub struct PackageDeferred {
+		pub reason: String,
+		pub deferred_at: DateTime<Utc>
+	}
+
+	impl Author {
+		pub fn parse(author: &str) -> Self {
+			fn take_between(s: &str, open: char, close: char) -> (Option<String>, String) {
+				let start = match s.find(open) {
+					Some(start) => { start }
+					None => { return (None, s.into()) }
+				};
+				let end = match s[start..].find(close) {
+					Some(end) => { start + end }
+					None => { return (None, s.into()) }
+				};
+
+				let inner = s[start + 1..end].trim();
+				let rest = format!("{}{}", &s[..start], &s[end + 1..]);
+				let inner = if inner.is_empty() { None } else { Some(inner.into()) };
+				(inner, rest)
+			}
+
+			let (email, rest) = take_between(author, '<', '>');
+			let (url, rest) = take_between(&rest, '(', ')');
+			let name = rest.trim();
+
+			Self {
+				name: if name.is_empty() { None } else { Some(name.into()) },
+				email,
+				url
+			}
+		}
+	}
+
+	impl fmt::Display for Author {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			let mut parts = Vec::new();
+			if let Some(name) = &self.name { parts.push(name.clone()) }
+			if let Some(email) = &self.email { parts.push(format!("<{email}>")) }
+			if let Some(url) = &self.url { parts.push(format!("({url})")) }
+			f.write_str(&parts.join(" "))
+		}
+	}
+
+	impl Serialize for Author {
+		fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+			serializer.serialize_str(&self.to_string())
+		}
+	}
+
+	impl<'de> Deserialize<'de> for Author {
+		fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+			#[derive(Deserialize)]
+			#[serde(untagged)]
+			enum AuthorRepr {
+				Plain(String),
+				Object {
+					name: Option<String>,
+					email: Option<String>,
+					url: Option<String>
+				}
+			}
+
+			Ok(match AuthorRepr::deserialize(deserializer)? {
+				AuthorRepr::Plain(author) => { Self::parse(&author) }
+				AuthorRepr::Object { name, email, url } => { Self { name, email, url } }
+			})
+		}
 	}
 }
 
 impl DatabaseThing {
+	/// loads the database from `filename`, or creates it if there's no such file
 	pub async fn new(filename: &str) -> crate::Result<Self> {
-		let data = if path::Path::new(filename).exists() {
-			let data = async_fs::read(filename).await
-				.map_err(|e| format!("error reading file {filename}: {e}"))?;
+		Self::open(filename, false).await
+	}
 
-			let data = String::from_utf8(data)
-				.map_err(|e| format!("error parsing text in file {filename}: {e}"))?;
+	/// like [`Self::new`], but if the file is corrupt, it's moved aside (to
+	/// `<filename>.corrupt-<time>`), and the newest backup that isn't is loaded
+	/// instead, or an empty database if there isn't one. see [`Recovery`]
+	pub async fn new_or_recover(filename: &str) -> crate::Result<Self> {
+		Self::open(filename, true).await
+	}
+
+	/// for commands that only read it: like [`Self::new`], but if there's no such
+	/// file, it's an empty database that's never saved, so the file isn't made
+	pub async fn open_existing(filename: &str) -> crate::Result<Self> {
+		if path::Path::new(filename).exists() { return Self::new(filename).await }
+
+		let db = Self::with_data(filename, DatabaseThingData { schema_version: SCHEMA_VERSION, ..Default::default() });
+		db.set_read_only();
+		Ok(db)
+	}
 
-			ron::from_str(&data)
-				.map_err(|e| format!("error parsing ron in file {filename}: {e}"))?
+	async fn open(filename: &str, recover: bool) -> crate::Result<Self> {
+		let data = if path::Path::new(filename).exists() {
+			match read_file(filename).await? {
+				Ok(data) => { data }
+				Err(problem) if recover => { quarantine(filename, problem).await? }
+				Err(problem) => { return Err(Error::Database(problem)) }
+			}
 		} else {
-			let data = DatabaseThingData {
-				saved_on_panic: false,
-				packages: Vec::new()
-			};
-			// let ser_data = ron::to_string(&data)?;
-			let ser_data = ron::ser::to_string_pretty(&data, Self::pretty_config())?;
-			async_fs::write(filename, ser_data).await?;
+			let data = DatabaseThingData { schema_version: SCHEMA_VERSION, ..Default::default() };
+			let ser_data = serialize(&data)?;
+			async_fs::write(filename, ser_data).await
+				.map_err(|e| Error::Database(format!("error writing file {filename}: {e}")))?;
 			data
 		};
 
-		let new = Self {
+		Ok(Self::with_data(filename, data))
+	}
+
+	fn with_data(filename: &str, data: DatabaseThingData) -> Self {
+		Self {
 			inner: Arc::new(Mutex::new(DatabaseThingInner {
 				meta: DatabaseThingMeta {
 					filename: filename.into(),
-					last_write_call_time: SystemTime::now()
+					last_write_call_time: SystemTime::now(),
+					last_backup_at: None,
+					read_only: false,
+					durability: Durability::Batch,
+					sync_next_save: false,
+					state_changes: Vec::new()
 				},
 				data
 			}))
-		};
-
-		Ok(new)
+		}
 	}
 
-	pub fn add_package(&self, package: &PackageNew) -> Result<(), String> {
+	pub fn add_package(&self, package: &PackageNew) -> crate::Result {
 		let mut inner = self.lock_inner();
-		inner.data.packages.push(PackageState::New(package.clone()));
+		let info = inner.data.without_forgotten_details(package);
+		inner.data.packages.push(Package {
+			info,
+			state: PackageState::New,
+			history: Vec::new(),
+			comments: None,
+			first_reply_at: None,
+			previous_names: Vec::new(),
+			license: None,
+			activity: None
+		});
 
 		Ok(())
 	}
 
 	pub fn contains_package(&self, package_name: &str) -> bool {
 		let inner = self.lock_inner();
+		inner.data.packages.iter().any(|package| package.info.name == package_name)
+	}
+
+	/// packages that haven't been contacted yet, including deferred ones and ones waiting to be retried
+	pub fn pending_packages(&self) -> Vec<Package> {
+		let inner = self.lock_inner();
+		inner.data.packages.iter()
+			.filter(|package| matches!(package.state, PackageState::New | PackageState::Deferred(_) | PackageState::RetryPending(_)))
+			.cloned()
+			.collect()
+	}
+
+	/// packages from `repo`'s owner (in any of their repositories on its host)
+	/// that they said no to, or opted out of, like `declined_other_packages` in templates
+	pub fn declined_packages_of(&self, repo: &RepoLocation) -> Vec<String> {
+		let inner = self.lock_inner();
+		inner.data.packages.iter()
+			.filter(|package| matches!(package.state, PackageState::OptedOut(_) | PackageState::ConsentRevoked(_)))
+			.filter(|package| RepoLocation::parse(&package.info.repository.url).map_or(false, |other| {
+				other.host == repo.host && other.owner.eq_ignore_ascii_case(&repo.owner)
+			}))
+			.map(|package| package.info.name.clone())
+			.collect()
+	}
+
+	/// whether there's a file for it yet, see [`Self::open_existing`]
+	pub fn exists(&self) -> bool {
+		path::Path::new(&self.lock_inner().meta.filename).exists()
+	}
+
+	/// the schema version the file was written with, see [`SCHEMA_VERSION`]
+	pub fn schema_version(&self) -> u32 {
+		self.lock_inner().data.schema_version
+	}
+
+	/// every package, whatever state it's in
+	pub fn package_count(&self) -> usize {
+		self.lock_inner().data.packages.len()
+	}
+
+	pub fn packages(&self) -> Vec<Package> {
+		self.lock_inner().data.packages.clone()
+	}
+
+	pub fn package(&self, package_name: &str) -> Option<Package> {
+		let inner = self.lock_inner();
+		inner.data.packages.iter()
+			.find(|package| package.info.name == package_name)
+			.cloned()
+	}
+
+	pub fn package_state(&self, package_name: &str) -> Option<PackageState> {
+		let inner = self.lock_inner();
+		inner.data.packages.iter()
+			.find(|package| package.info.name == package_name)
+			.map(|package| package.state.clone())
+	}
+
+	/// what kind of state (like `IssueFiled`) every package is in, by name
+	pub fn state_kinds(&self) -> BTreeMap<String, &'static str> {
+		let inner = self.lock_inner();
+		inner.data.packages.iter()
+			.map(|package| (package.info.name.clone(), package.state.kind()))
+			.collect()
+	}
+
+	pub fn package_info(&self, package_name: &str) -> Option<PackageNew> {
+		let inner = self.lock_inner();
+		inner.data.packages.iter()
+			.find(|package| package.info.name == package_name)
+			.map(|package| package.info.clone())
+	}
+
+	/// replaces the info of a package (eg. with newer data from the registry), keeping its state
+	pub fn update_package_info(&self, info: &PackageNew) -> crate::Result {
+		let mut inner = self.lock_inner();
+		let info = inner.data.without_forgotten_details(info);
+		let package = inner.data.packages.iter_mut()
+			.find(|package| package.info.name == info.name)
+			.ok_or_else(|| Error::Database(format!("package {} not in database", info.name)))?;
+		package.info = info;
+
+		Ok(())
+	}
+
+	/// removes everything personal about `login`'s packages (see
+	/// [`Package::forget_personal_details`]), and keeps it from being stored
+	/// again. returns the packages, and the email files to delete
+	pub fn forget_owner(&self, login: &str) -> (Vec<String>, Vec<String>) {
+		let mut inner = self.lock_inner();
+		inner.data.forgotten_owners.insert(login.to_lowercase());
+		let (mut packages, mut email_files) = (Vec::new(), Vec::new());
+		for package in inner.data.packages.iter_mut().filter(|package| package.info.is_owned_by(login)) {
+			email_files.extend(package.forget_personal_details());
+			packages.push(package.info.name.clone());
+		}
+		inner.meta.sync_next_save = true;
+		(packages, email_files)
+	}
+
+	/// the other packages stored with the same repository as `package`
+	pub fn sharing_repository(&self, package: &PackageNew) -> Vec<String> {
+		let key = match RepoLocation::parse(&package.repository.url) {
+			Some(repo) => { repo.key() }
+			None => { return Vec::new() }
+		};
+		let inner = self.lock_inner();
+		inner.data.packages.iter()
+			.filter(|other| other.info.name != package.name)
+			.filter(|other| RepoLocation::parse(&other.info.repository.url).map_or(false, |repo| repo.key() == key))
+			.map(|other| other.info.name.clone())
+			.collect()
+	}
+
+	/// links `old` to `new`, which it was renamed to on the registry: `old` keeps
+	/// its state and history under the new name (and with `new`'s info), rather
+	/// than `new` being a package of its own that gets contacted all over again
+	pub fn rename_package(&self, old: &str, new: &str) -> crate::Result {
+		let mut inner = self.lock_inner();
+		let packages = &mut inner.data.packages;
+		let new_index = packages.iter().position(|package| package.info.name == new)
+			.ok_or_else(|| Error::Database(format!("package {new} not in database")))?;
+		let info = packages.remove(new_index).info;
+		let package = packages.iter_mut()
+			.find(|package| package.info.name == old)
+			.ok_or_else(|| Error::Database(format!("package {old} not in database")))?;
+		package.previous_names.push(old.into());
+		package.info = PackageNew { theme: package.info.theme || info.theme, ..info };
+		package.history.push(Transition {
+			at: Utc::now(),
+			from: package.state.name().into(),
+			state: package.state.clone(),
+			note: Some(format!("renamed from {old} on the registry"))
+		});
+
+		// what's filed for it together with other packages
+		for package in packages.iter_mut() {
+			if let PackageState::IssueFiled(filed) = &mut package.state {
+				for name in filed.grouped_with.iter_mut().filter(|name| *name == old) {
+					*name = new.into();
+				}
+			}
+		}
+		if let Some(batch) = &mut inner.data.batch {
+			for name in batch.packages.iter_mut().filter(|name| *name == old) {
+				*name = new.into();
+			}
+		}
+		inner.meta.sync_next_save = true;
+
+		Ok(())
+	}
+
+	/// repositories that have more than one package in them, with the names of those packages
+	pub fn shared_repositories(&self) -> BTreeMap<String, Vec<String>> {
+		let inner = self.lock_inner();
+		let mut repositories = BTreeMap::<String, Vec<String>>::new();
 
 		for package in inner.data.packages.iter() {
-			let res = match package {
-				PackageState::New(PackageNew { name, .. }) => { name == package_name }
-				PackageState::IssueFiled(PackageIssueFiled { name, .. }) => { name == package_name }
+			if let Some(repo) = RepoLocation::parse(&package.info.repository.url) {
+				repositories.entry(repo.key()).or_default().push(package.info.name.clone());
+			}
+		}
+
+		repositories.retain(|_, packages| packages.len() > 1);
+		repositories
+	}
+
+	pub fn batch(&self) -> Option<Batch> {
+		self.lock_inner().data.batch.clone()
+	}
+
+	pub fn set_batch(&self, batch: Option<Batch>) {
+		let mut inner = self.lock_inner();
+		inner.data.batch = batch;
+		inner.meta.sync_next_save = true;
+	}
+
+	pub fn last_report_at(&self) -> Option<DateTime<Utc>> {
+		self.lock_inner().data.last_report_at
+	}
+
+	pub fn set_last_report_at(&self, at: DateTime<Utc>) {
+		self.lock_inner().data.last_report_at = Some(at);
+	}
+
+	pub fn recoveries(&self) -> Vec<Recovery> {
+		self.lock_inner().data.recoveries.clone()
+	}
+
+	pub fn recent_runs(&self) -> Vec<RunRecord> {
+		self.lock_inner().data.recent_runs.clone()
+	}
+
+	pub fn record_run(&self, run: RunRecord) {
+		let runs = &mut self.lock_inner().data.recent_runs;
+		runs.push(run);
+		let extra = runs.len().saturating_sub(RUNS_KEPT);
+		runs.drain(..extra);
+	}
+
+	/// how big the database is saved, and how long turning it into that takes
+	pub fn measure_save(&self) -> crate::Result<(usize, Duration)> {
+		let inner = self.lock_inner();
+		let started = Instant::now();
+		let data = serialize(&inner.data)?;
+		Ok((data.len(), started.elapsed()))
+	}
+
+	pub fn last_digest_at(&self) -> Option<DateTime<Utc>> {
+		self.lock_inner().data.last_digest_at
+	}
+
+	pub fn set_last_digest_at(&self, at: DateTime<Utc>) {
+		self.lock_inner().data.last_digest_at = Some(at);
+	}
+
+	/// updates the current batch, if there is one
+	pub fn update_batch(&self, f: impl FnOnce(&mut Batch)) {
+		if let Some(batch) = &mut self.lock_inner().data.batch {
+			f(batch);
+		}
+	}
+
+	/// when a repository that failed can be tried again, if it's still cooling down
+	pub fn repo_cooldown(&self, repo: &RepoLocation) -> Option<DateTime<Utc>> {
+		let inner = self.lock_inner();
+		inner.data.repo_cooldowns.get(&repo.key())
+			.filter(|until| **until > Utc::now())
+			.copied()
+	}
+
+	/// `None` clears it
+	pub fn set_repo_cooldown(&self, repo: &RepoLocation, until: Option<DateTime<Utc>>) {
+		let mut inner = self.lock_inner();
+		match until {
+			Some(until) => { inner.data.repo_cooldowns.insert(repo.key(), until); }
+			None => { inner.data.repo_cooldowns.remove(&repo.key()); }
+		}
+	}
+
+	/// repositories (by [`RepoLocation::key`]) still cooling down, and until when
+	pub fn repo_cooldowns(&self) -> BTreeMap<String, DateTime<Utc>> {
+		let now = Utc::now();
+		self.lock_inner().data.repo_cooldowns.iter()
+			.filter(|(_, until)| **until > now)
+			.map(|(repo, until)| (repo.clone(), *until))
+			.collect()
+	}
+
+	/// when a forge host that rate limited the bot can be used again, if it's still cooling down
+	pub fn host_cooldown(&self, host: &str) -> Option<DateTime<Utc>> {
+		self.lock_inner().data.host_cooldowns.get(host)
+			.filter(|until| **until > Utc::now())
+			.copied()
+	}
+
+	/// hosts still cooling down, and until when
+	pub fn host_cooldowns(&self) -> BTreeMap<String, DateTime<Utc>> {
+		let now = Utc::now();
+		self.lock_inner().data.host_cooldowns.iter()
+			.filter(|(_, until)| **until > now)
+			.map(|(host, until)| (host.clone(), *until))
+			.collect()
+	}
+
+	/// forgets the ones that are over
+	pub fn set_host_cooldown(&self, host: &str, until: DateTime<Utc>) {
+		let now = Utc::now();
+		let mut inner = self.lock_inner();
+		inner.data.host_cooldowns.retain(|_, until| *until > now);
+		inner.data.host_cooldowns.insert(host.into(), until);
+	}
+
+	/// why nothing's filed on `host`, if its breaker is tripped
+	pub fn breaker(&self, host: &str) -> Option<TrippedBreaker> {
+		self.lock_inner().data.breakers.get(host)
+			.filter(|breaker| breaker.until > Utc::now())
+			.cloned()
+	}
+
+	/// hosts whose breakers are tripped
+	pub fn breakers(&self) -> BTreeMap<String, TrippedBreaker> {
+		let now = Utc::now();
+		self.lock_inner().data.breakers.iter()
+			.filter(|(_, breaker)| breaker.until > now)
+			.map(|(host, breaker)| (host.clone(), breaker.clone()))
+			.collect()
+	}
+
+	/// `None` resets it. forgets the ones that are over
+	pub fn set_breaker(&self, host: &str, breaker: Option<TrippedBreaker>) {
+		let now = Utc::now();
+		let mut inner = self.lock_inner();
+		inner.data.breakers.retain(|_, breaker| breaker.until > now);
+		match breaker {
+			Some(breaker) => { inner.data.breakers.insert(host.into(), breaker); }
+			None => { inner.data.breakers.remove(host); }
+		}
+	}
+
+	/// when the last issue was filed, if it was in the last week
+	pub fn last_filing(&self) -> Option<DateTime<Utc>> {
+		self.lock_inner().data.recent_filings.iter().max().copied()
+	}
+
+	/// how many issues were filed in the last 24 hours
+	pub fn issues_filed_last_day(&self) -> usize {
+		self.issues_filed_since(Utc::now() - chrono::Duration::days(1))
+	}
+
+	/// how many issues were filed since `since`, going back a week at most
+	pub fn issues_filed_since(&self, since: DateTime<Utc>) -> usize {
+		self.lock_inner().data.recent_filings.iter()
+			.filter(|filed_at| **filed_at > since)
+			.count()
+	}
+
+	/// adds an issue to the count for [`issues_filed_since`](Self::issues_filed_since),
+	/// forgetting ones older than a week
+	pub fn record_filing(&self, filed_at: DateTime<Utc>) {
+		let since = Utc::now() - chrono::Duration::days(7);
+		let mut inner = self.lock_inner();
+		inner.data.recent_filings.retain(|filed_at| *filed_at > since);
+		inner.data.recent_filings.push(filed_at);
+	}
+
+	pub fn registry_sync_state(&self) -> RegistrySyncState {
+		self.lock_inner().data.registry_sync.clone()
+	}
+
+	pub fn set_registry_sync_state(&self, state: RegistrySyncState) {
+		self.lock_inner().data.registry_sync = state;
+	}
+
+	/// keeps track of a package in the registry without a repository, see
+	/// [`RegistrySyncState::without_repository`]. `None` once it has one
+	pub fn set_without_repository(&self, name: &str, downloads: Option<u32>) {
+		let without_repository = &mut self.lock_inner().data.registry_sync.without_repository;
+		match downloads {
+			Some(downloads) => { without_repository.insert(name.into(), downloads); }
+			None => { without_repository.remove(name); }
+		}
+	}
+
+	/// packages that have an issue filed for them
+	pub fn filed_issues(&self) -> Vec<(String, PackageIssueFiled)> {
+		let inner = self.lock_inner();
+		inner.data.packages.iter()
+			.filter_map(|package| match &package.state {
+				PackageState::IssueFiled(filed) => { Some((package.info.name.clone(), filed.clone())) }
+				_ => { None }
+			})
+			.collect()
+	}
+
+	/// packages that a run stopped partway through filing an issue for
+	pub fn filings_in_progress(&self) -> Vec<(String, PackageFilingInProgress)> {
+		let inner = self.lock_inner();
+		inner.data.packages.iter()
+			.filter_map(|package| match &package.state {
+				PackageState::IssueFilingInProgress(filing) => { Some((package.info.name.clone(), filing.clone())) }
+				_ => { None }
+			})
+			.collect()
+	}
+
+	pub fn set_state(&self, package_name: &str, state: PackageState) -> crate::Result {
+		self.set_state_noted(package_name, state, None)
+	}
+
+	/// like [`Self::set_state`], for a state set by hand. it's always added to the
+	/// history, with `note`, even if the state didn't change
+	pub fn set_state_by_hand(&self, package_name: &str, state: PackageState, note: String) -> crate::Result {
+		self.set_state_noted(package_name, state, Some(note))
+	}
+
+	/// like [`Self::set_state`], but with a `note` it's always added to the history,
+	/// even if the state didn't change, for changes worth keeping (like a reminder
+	/// being posted) that a later state wouldn't have
+	pub fn set_state_noted(&self, package_name: &str, state: PackageState, note: Option<String>) -> crate::Result {
+		let mut inner = self.lock_inner();
+		let package = inner.data.packages.iter_mut()
+			.find(|package| package.info.name == package_name)
+			.ok_or_else(|| Error::Database(format!("package {package_name} not in database")))?;
+		let from = package.state.name();
+		package.state = state;
+		debug!(package = package_name, from, state = package.state.name(), "state changed");
+
+		let change = (from != package.state.name()).then(|| StateChange {
+			package: package_name.into(),
+			from,
+			to: package.state.name(),
+			at: Utc::now(),
+			state: package.state.clone()
+		});
+		if change.is_some() || note.is_some() {
+			package.history.push(Transition {
+				at: Utc::now(),
+				from: from.into(),
+				state: package.state.clone(),
+				note
+			});
+		}
+		if let Some(change) = &change {
+			metrics::state_changed(&change.state);
+		}
+		if let (Some(change), false) = (change, inner.meta.read_only) {
+			for tx in &inner.meta.state_changes {
+				// nothing listening any more is fine
+				let _ = tx.send(change.clone());
+			}
+		}
+
+		Ok(())
+	}
+
+	/// records what license a package's repository has, or that it doesn't have one
+	pub fn set_license(&self, package_name: &str, license: Option<String>) {
+		let mut inner = self.lock_inner();
+		if let Some(package) = inner.data.packages.iter_mut().find(|package| package.info.name == package_name) {
+			package.license = Some(RepoLicense { license, checked_at: Utc::now() });
+		}
+	}
+
+	/// records how lately a package's repository was pushed to and released
+	pub fn set_activity(&self, package_name: &str, activity: RepoActivity) {
+		let mut inner = self.lock_inner();
+		if let Some(package) = inner.data.packages.iter_mut().find(|package| package.info.name == package_name) {
+			package.activity = Some(activity);
+		}
+	}
+
+	/// keeps the last few comments seen on a package's issue, for `show`, and
+	/// when the first one was made
+	pub fn set_seen_comments(&self, package_name: &str, comments: &[IssueComment]) {
+		let mut inner = self.lock_inner();
+		let data = &mut inner.data;
+		let package = match data.packages.iter_mut().find(|package| package.info.name == package_name) {
+			Some(package) => { package }
+			None => { return }
+		};
+		if package.first_reply_at.is_none() {
+			package.first_reply_at = comments.first().map(|comment| comment.created_at);
+		}
+		if forgotten(&data.forgotten_owners, &package.info) { return }
+		package.comments = Some(SeenComments {
+			checked_at: Utc::now(),
+			comments: comments.iter()
+				.skip(comments.len().saturating_sub(COMMENTS_KEPT))
+				.map(SeenComment::new)
+				.collect()
+		});
+	}
+
+	/// like [`Self::set_seen_comments`], for one new comment (from a webhook)
+	pub fn add_seen_comment(&self, package_name: &str, comment: &IssueComment) {
+		let mut inner = self.lock_inner();
+		let data = &mut inner.data;
+		let package = match data.packages.iter_mut().find(|package| package.info.name == package_name) {
+			Some(package) => { package }
+			None => { return }
+		};
+		if package.first_reply_at.is_none() {
+			package.first_reply_at = Some(comment.created_at);
+		}
+		if forgotten(&data.forgotten_owners, &package.info) { return }
+		let seen = package.comments.get_or_insert_with(|| SeenComments { checked_at: Utc::now(), comments: Vec::new() });
+		seen.checked_at = Utc::now();
+		if seen.comments.iter().any(|seen| seen.url == comment.url) { return }
+		seen.comments.push(SeenComment::new(comment));
+		let extra = seen.comments.len().saturating_sub(COMMENTS_KEPT);
+		seen.comments.drain(..extra);
+	}
+
+	/// sends every state change from here on to `tx` too
+	pub fn add_state_changes(&self, tx: UnboundedSender<StateChange>) {
+		self.lock_inner().meta.state_changes.push(tx);
+	}
+
+	/// stops sending state changes to everything [`Self::add_state_changes`] was called with
+	pub fn stop_state_changes(&self) {
+		self.lock_inner().meta.state_changes.clear();
+	}
+
+	/// stops any changes from here on from being written to disk, for dry runs
+	pub fn set_read_only(&self) {
+		self.lock_inner().meta.read_only = true;
+	}
+
+	pub fn set_durability(&self, durability: Durability) {
+		self.lock_inner().meta.durability = durability;
+	}
+
+	pub fn is_read_only(&self) -> bool {
+		self.lock_inner().meta.read_only
+	}
+
+	/// something that saves the database without waiting, for [`crate::crash`]'s panic
+	/// hook. it doesn't keep the database around, so dropping it still saves it last,
+	/// and gives up if the database is locked (like by what's panicking), rather than
+	/// hanging. returns whether it saved
+	pub fn saver(&self) -> impl Fn() -> bool + Send + Sync + 'static {
+		let inner = Arc::downgrade(&self.inner);
+		move || {
+			let inner = match inner.upgrade() {
+				Some(inner) => { inner }
+				None => { return false }
+			};
+			let mut inner = match inner.try_lock() {
+				Ok(inner) => { inner }
+				Err(TryLockError::Poisoned(e)) => { e.into_inner() }
+				Err(TryLockError::WouldBlock) => { return false }
 			};
-			if res { return true }
+			if inner.meta.read_only { return false }
+
+			inner.data.saved_on_panic = true;
+			inner.data.schema_version = SCHEMA_VERSION;
+			let sync = inner.meta.durability != Durability::Never;
+			let saved = serialize(&inner.data)
+				.ok()
+				.map_or(false, |data| write(&inner.meta.filename, &data, sync).is_ok());
+			inner.data.saved_on_panic = false;
+			saved
 		}
+	}
 
-		false
+	/// writes the database to disk now, rather than waiting for it to be dropped
+	pub fn flush(&self) {
+		self.write_to_file_immediately();
 	}
 
 	fn write_to_file_immediately(&self) {
 		fn write_to_file_immediately_inner(db: &DatabaseThing) -> crate::Result {
 			let mut inner = db.lock_inner();
+			if inner.meta.read_only { return Ok(()) }
 
 			let now = SystemTime::now();
 			inner.meta.last_write_call_time = now;
+			inner.data.schema_version = SCHEMA_VERSION;
+			let sync = match inner.meta.durability {
+				Durability::Always => { true }
+				Durability::Batch => { std::mem::take(&mut inner.meta.sync_next_save) }
+				Durability::Never => { false }
+			};
 
-			let data = ron::ser::to_string_pretty(&inner.data, DatabaseThing::pretty_config())?;
+			let started = Instant::now();
+			let data = serialize(&inner.data)?;
 			let filename = inner.meta.filename.clone();
 			drop(inner);
+			metrics::database_saved(data.len(), started.elapsed());
 
-			sync_fs::write(&filename, &data)?;
+			if db.lock_inner().meta.last_backup_at.map_or(true, |at| at.elapsed() >= BACKUP_EVERY) {
+				back_up(&filename);
+				db.lock_inner().meta.last_backup_at = Some(Instant::now());
+			}
+			write(&filename, &data, sync)
+				.map_err(|e| Error::Database(format!("error writing file {filename}: {e}")))?;
+			health::database_saved(None);
 			Ok(())
 		}
 
 		let res = write_to_file_immediately_inner(self);
 		if let Err(e) = res {
-			println!("error when writing database file: {e}");
+			health::database_saved(Some(e.to_string()));
+			// this runs on drop too, so it can't be returned. everything since the last save is only in memory
+			let filename = self.lock_inner().meta.filename.clone();
+			error!(file = %filename, "error writing the database, changes since it was last saved aren't saved: {e}");
 		}
 	}
 
@@ -146,30 +1516,557 @@ impl DatabaseThing {
 		}
 	}
 
-	fn pretty_config() -> ron::ser::PrettyConfig {
-		ron::ser::PrettyConfig::new()
-			.new_line("\n".into())
-			.indentor("\t".into())
-			.struct_names(true)
+}
+
+/// writes `data` to `filename`. if `sync`, it's written to `<filename>.saving`,
+/// waited on until it's on the disk, then moved over `filename`, so the file is
+/// either all the old database or all the new one, whatever happens
+fn write(filename: &str, data: &str, sync: bool) -> std::io::Result<()> {
+	if !sync { return sync_fs::write(filename, data) }
+
+	let saving = format!("{filename}.saving");
+	let mut file = sync_fs::File::create(&saving)?;
+	file.write_all(data.as_bytes())?;
+	file.sync_all()?;
+	drop(file);
+	sync_fs::rename(&saving, filename)?;
+	// the move is only on the disk once the directory it's in is
+	#[cfg(unix)]
+	{
+		let dir = match path::Path::new(filename).parent() {
+			Some(dir) if !dir.as_os_str().is_empty() => { dir }
+			_ => { path::Path::new(".") }
+		};
+		sync_fs::File::open(dir)?.sync_all()?;
+	}
+	Ok(())
+}
+
+/// the database as it's saved
+impl DatabaseThingData {
+	/// `info`, without its owner's details if they asked to be forgotten
+	fn without_forgotten_details(&self, info: &PackageNew) -> PackageNew {
+		let mut info = info.clone();
+		if forgotten(&self.forgotten_owners, &info) {
+			info.forget_personal_details();
+		}
+		info
 	}
 }
 
+/// whether a package's owner is one of `owners`, who asked to be forgotten
+fn forgotten(owners: &BTreeSet<String>, info: &PackageNew) -> bool {
+	owners.iter().any(|owner| info.is_owned_by(owner))
+}
+
+fn serialize(data: &DatabaseThingData) -> crate::Result<String> {
+	let config = ron::ser::PrettyConfig::new()
+		.new_line("\n".into())
+		.indentor("\t".into())
+		.struct_names(true);
+	ron::ser::to_string_pretty(data, config).map_err(|e| Error::Database(format!("error serializing the database: {e}")))
+}
+
 impl Drop for DatabaseThing {
 	fn drop(&mut self) {
+		// only the last handle to the db should save it
+		if Arc::strong_count(&self.inner) > 1 { return }
+
 		let mut inner = self.lock_inner();
 
 		let panicking = panicking();
 		inner.data.saved_on_panic = panicking;
-		if panicking { println!("db dropped because of panick!") }
+		if panicking { error!("db dropped because of panick!") }
 
-		println!(
-			"db stats:\n   total packages: {}",
-			inner.data.packages.len()
-		);
+		// read only commands print nothing else, so they can be piped (like `list --format json`)
+		if !inner.meta.read_only {
+			info!(
+				"db stats:\n   total packages: {}",
+				inner.data.packages.len()
+			);
+		}
 
+		// the last save before stopping counts as the end of a batch
+		inner.meta.sync_next_save = true;
 		// without this we deadlock on the next call to `self.write_to_file_immediately();`
 		drop(inner);
 
 		self.write_to_file_immediately();
 	}
 }
+
+/// reads the database from `filename`. the outer error is for it not being readable,
+/// or being from a newer version of the bot, and the inner one for it being corrupt
+async fn read_file(filename: &str) -> crate::Result<std::result::Result<DatabaseThingData, String>> {
+	let data = async_fs::read(filename).await
+		.map_err(|e| Error::Database(format!("error reading file {filename}: {e}")))?;
+
+	let data = match String::from_utf8(data) {
+		Ok(data) => { data }
+		Err(e) => { return Ok(Err(format!("error parsing text in file {filename}: {e}"))) }
+	};
+	let data = match ron::from_str::<DatabaseThingData>(&data) {
+		Ok(data) => { data }
+		Err(e) => {
+			match ron::from_str::<legacy::DatabaseThingData>(&data) {
+				Ok(legacy) => {
+					info!("{filename} is from before packages were kept with everything about them, converting it");
+					legacy.convert()
+				}
+				Err(_) => { return Ok(Err(format!("error parsing ron in file {filename}: {e}"))) }
+			}
+		}
+	};
+	if data.schema_version > SCHEMA_VERSION {
+		return Err(Error::Database(format!(
+			"{filename} is from a newer version of the bot (schema version {}, this one only knows up to {SCHEMA_VERSION})",
+			data.schema_version
+		)))
+	}
+	Ok(Ok(data))
+}
+
+/// the file as it was before packages were kept with everything about them,
+/// when each one was only its state. these are schema version 0, like
+/// everything from before there was a version, and are converted as they're loaded
+mod legacy {
+	use super::*;
+
+	#[derive(Deserialize)]
+	#[serde(deny_unknown_fields)]
+	pub struct DatabaseThingData {
+		pub saved_on_panic: bool,
+		pub packages: Vec<PackageState>
+	}
+
+	#[derive(Deserialize)]
+	pub enum PackageState {
+		New(Box<PackageNew>),
+		IssueFiled(PackageIssueFiled)
+	}
+
+	#[derive(Deserialize)]
+	pub struct PackageIssueFiled {
+		pub name: String,
+		/// owner and name, on github
+		pub repository: (String, String),
+		pub downloads: u32,
+		pub stargazers_count: u32
+	}
+
+	impl DatabaseThingData {
+		pub fn convert(self) -> super::DatabaseThingData {
+			let packages = self.packages.into_iter()
+				.map(|package| {
+					let (info, state) = match package {
+						PackageState::New(info) => { (*info, super::PackageState::New) }
+						// which issue it was isn't known, so it's not filed again, but someone has to go and look
+						PackageState::IssueFiled(filed) => {
+							let (owner, repo) = filed.repository;
+							let info = PackageNew {
+								name: filed.name,
+								repository: Repository { r#type: "git".into(), url: format!("https://github.com/{owner}/{repo}") },
+								downloads: filed.downloads,
+								stargazers_count: filed.stargazers_count,
+								metadata: PackageMetadata::default(),
+								theme: false,
+								releases: Releases::default(),
+								versions: Vec::new(),
+								owner: None,
+								readme_locale: None
+							};
+							let state = super::PackageState::NeedsReview(PackageNeedsReview {
+								reason: format!("an issue was filed on {owner}/{repo} before the bot kept which one, look it up and mark it"),
+								flagged_at: Utc::now(),
+								contact_failure: None
+							});
+							(info, state)
+						}
+					};
+					Package {
+						info,
+						state,
+						history: Vec::new(),
+						comments: None,
+						first_reply_at: None,
+						previous_names: Vec::new(),
+						license: None,
+						activity: None
+					}
+				})
+				.collect();
+
+			super::DatabaseThingData {
+				schema_version: 0,
+				saved_on_panic: self.saved_on_panic,
+				packages,
+				..Default::default()
+			}
+		}
+	}
+}
+
+/// moves the corrupt file at `filename` aside, and gives the newest backup that
+/// loads instead, or an empty database, which is saved straight away
+async fn quarantine(filename: &str, problem: String) -> crate::Result<DatabaseThingData> {
+	let now = Utc::now();
+	let moved_to = format!("{filename}.corrupt-{}", now.format(FILE_TIME_FORMAT));
+	async_fs::rename(filename, &moved_to).await
+		.map_err(|e| Error::Database(format!("{problem}, and it couldn't be moved aside to {moved_to}: {e}")))?;
+	error!("the database is corrupt ({problem}), it's been moved to {moved_to}");
+
+	let mut restored = None;
+	for backup in backups(filename).into_iter().rev() {
+		match read_file(&backup).await {
+			Ok(Ok(data)) => {
+				restored = Some((backup, data));
+				break
+			}
+			Ok(Err(problem)) => { error!("backup {backup} is corrupt too: {problem}") }
+			Err(e) => { error!("backup {backup} can't be used: {e}") }
+		}
+	}
+
+	let (restored_from, mut data) = match restored {
+		Some((backup, data)) => {
+			error!("carrying on from backup {backup}, anything that happened after it was made is lost");
+			(Some(backup), data)
+		}
+		None => {
+			error!("there's no backup to carry on from, starting with an empty database");
+			(None, DatabaseThingData { schema_version: SCHEMA_VERSION, ..Default::default() })
+		}
+	};
+	data.recoveries.push(Recovery { at: now, problem, moved_to, restored_from });
+
+	// so it isn't lost if the bot stops before saving
+	async_fs::write(filename, serialize(&data)?).await
+		.map_err(|e| Error::Database(format!("error writing file {filename}: {e}")))?;
+	Ok(data)
+}
+
+/// backups of `filename`, oldest first
+fn backups(filename: &str) -> Vec<String> {
+	let path = path::Path::new(filename);
+	let prefix = format!("{}.backup-", path.file_name().unwrap_or_default().to_string_lossy());
+	let dir = match path.parent() {
+		Some(dir) if !dir.as_os_str().is_empty() => { dir }
+		_ => { path::Path::new(".") }
+	};
+	let mut backups = sync_fs::read_dir(dir)
+		.map(|entries| {
+			entries
+				.filter_map(|entry| entry.ok())
+				.filter_map(|entry| entry.file_name().to_str()?.strip_prefix(&prefix).map(|time| format!("{filename}.backup-{time}")))
+				.collect::<Vec<_>>()
+		})
+		.unwrap_or_default();
+	backups.sort();
+	backups
+}
+
+/// copies `filename` to `<filename>.backup-<time>` before it's saved over, keeping
+/// the newest [`BACKUPS_KEPT`]. it's carried on without one if it can't be made
+fn back_up(filename: &str) {
+	if !path::Path::new(filename).exists() { return }
+
+	let backup = format!("{filename}.backup-{}", Utc::now().format(FILE_TIME_FORMAT));
+	if let Err(e) = sync_fs::copy(filename, &backup) {
+		warn!("error backing up the database to {backup}: {e}");
+		return
+	}
+
+	let backups = backups(filename);
+	for old in backups.iter().take(backups.len().saturating_sub(BACKUPS_KEPT)) {
+		if let Err(e) = sync_fs::remove_file(old) {
+			warn!("error removing old database backup {old}: {e}");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::TimeZone;
+	use proptest::collection::{ btree_map, btree_set, vec };
+	use proptest::option::of;
+	use proptest::prelude::*;
+	use proptest::strategy::ValueTree;
+	use proptest::test_runner::TestRunner;
+
+	/// strings like the odd ones that turn up in package names, urls, and error
+	/// messages, and ones full of what means something in ron
+	fn weird_string() -> impl Strategy<Value = String> {
+		prop_oneof![
+			any::<String>(),
+			"[a-z0-9@/._#%?+-]{0,20}",
+			r#"[()\[\]{}"'\\,:;#/*rR ]{0,12}"#,
+			Just(String::new()),
+			Just("Some(\"x\")".into()),
+			Just("\u{0}\u{1b}\u{7f}\r\n\t".into()),
+			Just("r#\"raw\"#".into()),
+			Just("日本語 ümlaut 🦀".into())
+		]
+	}
+
+	/// the author is saved as `name <email> (url)`, so each part can't have those in it
+	fn author_part() -> impl Strategy<Value = Option<String>> {
+		of("[^<>()\\s][^<>()]{0,10}[^<>()\\s]")
+	}
+
+	fn time() -> impl Strategy<Value = DateTime<Utc>> {
+		(0i64..4_102_444_800, 0u32..1_000_000_000).prop_map(|(secs, nanos)| Utc.timestamp(secs, nanos))
+	}
+
+	fn repo_location() -> impl Strategy<Value = RepoLocation> {
+		(weird_string(), weird_string(), weird_string()).prop_map(|(host, owner, name)| RepoLocation { host, owner, name })
+	}
+
+	fn package_new() -> impl Strategy<Value = PackageNew> {
+		let metadata = (of(weird_string()), of((author_part(), author_part(), author_part())), of(weird_string()), of(weird_string()), of(weird_string()))
+			.prop_map(|(version, author, theme, description, deprecated)| PackageMetadata {
+				version,
+				author: author.map(|(name, email, url)| Author { name, email, url }),
+				theme,
+				description,
+				deprecated
+			});
+		let owner = of((of(weird_string()), of(weird_string()), of(weird_string())))
+			.prop_map(|owner| owner.map(|(login, name, email)| PackageOwner { login, name, email }));
+		(weird_string(), weird_string(), weird_string(), any::<u32>(), any::<u32>(), metadata, any::<bool>(), of(weird_string()), vec(weird_string(), 0..4), owner, of(weird_string()))
+			.prop_map(|(name, r#type, url, downloads, stargazers_count, metadata, theme, latest, versions, owner, readme_locale)| PackageNew {
+				name,
+				repository: Repository { r#type, url },
+				downloads,
+				stargazers_count,
+				metadata,
+				theme,
+				releases: Releases { latest },
+				versions,
+				owner,
+				readme_locale
+			})
+	}
+
+	fn approval() -> impl Strategy<Value = PackageApproved> {
+		(of(weird_string()), of(weird_string()), time(), weird_string(), time(), of(weird_string()))
+			.prop_map(|(issue_url, email, contacted_at, approval_url, approved_at, registration_error)| PackageApproved {
+				issue_url, email, contacted_at, approval_url, approved_at, registration_error
+			})
+	}
+
+	fn skipped() -> impl Strategy<Value = PackageSkipped> {
+		(weird_string(), time(), of(contact_failure())).prop_map(|(reason, skipped_at, contact_failure)| PackageSkipped { reason, skipped_at, contact_failure })
+	}
+
+	fn contact_failure() -> impl Strategy<Value = ContactFailure> {
+		let failure = prop_oneof![
+			Just(Failure::RateLimited),
+			Just(Failure::NotFound),
+			Just(Failure::Permanent),
+			Just(Failure::Network),
+			Just(Failure::Server),
+			Just(Failure::Other)
+		];
+		let class = prop_oneof![Just(Class::Retryable), Just(Class::Terminal), Just(Class::NeedsHuman)];
+		(failure, class).prop_map(|(failure, class)| ContactFailure { failure, class })
+	}
+
+	/// every variant, see [`every_variant_is_generated`]
+	fn package_state() -> impl Strategy<Value = PackageState> {
+		let reminder = (prop_oneof![Just(ReminderKind::FollowUp), Just(ReminderKind::FinalReminder)], time(), of(time()))
+			.prop_map(|(kind, due_at, sent_at)| Reminder { kind, due_at, sent_at });
+		prop_oneof![
+			Just(PackageState::New),
+			(repo_location(), weird_string(), time(), vec(weird_string(), 0..3), vec(reminder, 0..3), of(time()), of(weird_string()), of(weird_string()))
+				.prop_map(|(repository, issue_url, filed_at, grouped_with, reminders, replied_at, template_variant, locale)| PackageState::IssueFiled(PackageIssueFiled {
+					repository, issue_url, filed_at, grouped_with, reminders, replied_at, template_variant, locale
+				})),
+			(weird_string(), weird_string(), any::<bool>(), time())
+				.prop_map(|(email, email_file, sent, contacted_at)| PackageState::ContactedByEmail(PackageContactedByEmail {
+					email, email_file, sent, contacted_at
+				})),
+			(weird_string(), time()).prop_map(|(reason, deferred_at)| PackageState::Deferred(PackageDeferred { reason, deferred_at })),
+			skipped().prop_map(PackageState::SkippedDeprecated),
+			(weird_string(), time(), of(contact_failure()))
+				.prop_map(|(reason, flagged_at, contact_failure)| PackageState::NeedsReview(PackageNeedsReview { reason, flagged_at, contact_failure })),
+			time().prop_map(|found_at| PackageState::AlreadyMigrated(PackageAlreadyMigrated { found_at })),
+			skipped().prop_map(PackageState::Blocked),
+			skipped().prop_map(PackageState::OptedOut),
+			skipped().prop_map(PackageState::DeclinedByOwnerPolicy),
+			skipped().prop_map(PackageState::Dormant),
+			(any::<u32>(), weird_string(), time())
+				.prop_map(|(attempts, last_error, retry_at)| PackageState::RetryPending(PackageRetryPending { attempts, last_error, retry_at })),
+			(repo_location(), time(), any::<u32>(), of(weird_string()), of(weird_string()))
+				.prop_map(|(repository, started_at, attempts, template_variant, locale)| PackageState::IssueFilingInProgress(PackageFilingInProgress {
+					repository, started_at, attempts, template_variant, locale
+				})),
+			approval().prop_map(PackageState::MaintainerApproved),
+			(approval(), time(), weird_string())
+				.prop_map(|(approval, migrated_at, backend_response)| PackageState::Migrated(PackageMigrated { approval, migrated_at, backend_response })),
+			(approval(), of(time()), weird_string(), time())
+				.prop_map(|(approval, migrated_at, revocation_url, revoked_at)| PackageState::ConsentRevoked(PackageConsentRevoked {
+					approval, migrated_at, revocation_url, revoked_at
+				}))
+		]
+	}
+
+	fn package() -> impl Strategy<Value = Package> {
+		let transition = (time(), weird_string(), package_state(), of(weird_string()))
+			.prop_map(|(at, from, state, note)| Transition { at, from, state, note });
+		let comment = (weird_string(), weird_string(), time()).prop_map(|(url, body, created_at)| SeenComment { url, body, created_at });
+		let comments = (time(), vec(comment, 0..3)).prop_map(|(checked_at, comments)| SeenComments { checked_at, comments });
+		let license = (of(weird_string()), time()).prop_map(|(license, checked_at)| RepoLicense { license, checked_at });
+		let release = (weird_string(), of(time())).prop_map(|(tag, released_at)| RepoRelease { tag, released_at });
+		let activity = (of(time()), of(release), time()).prop_map(|(pushed_at, latest_release, checked_at)| RepoActivity { pushed_at, latest_release, checked_at });
+		(package_new(), package_state(), vec(transition, 0..3), of(comments), of(time()), vec(weird_string(), 0..2), of(license), of(activity))
+			.prop_map(|(info, state, history, comments, first_reply_at, previous_names, license, activity)| Package {
+				info, state, history, comments, first_reply_at, previous_names, license, activity
+			})
+	}
+
+	fn data() -> impl Strategy<Value = DatabaseThingData> {
+		let cursor = (weird_string(), of(weird_string()), any::<usize>(), any::<bool>(), time())
+			.prop_map(|(endpoint, next, page, incremental, started_at)| RegistrySyncCursor { endpoint, next, page, incremental, started_at });
+		let registry_sync = (of(time()), of(time()), of(cursor), btree_map(weird_string(), any::<u32>(), 0..4))
+			.prop_map(|(last_sync, counts_refreshed_at, cursor, without_repository)| RegistrySyncState {
+				last_sync, counts_refreshed_at, cursor, without_repository
+			});
+		let batch = of((time(), vec(weird_string(), 0..4), any::<usize>(), of(time())))
+			.prop_map(|batch| batch.map(|(started_at, packages, progress, resume_at)| Batch { started_at, packages, progress, resume_at }));
+		let breaker = (time(), time(), any::<u32>(), weird_string())
+			.prop_map(|(tripped_at, until, failures, last_error)| TrippedBreaker { tripped_at, until, failures, last_error });
+		let run = (time(), time(), any::<usize>(), any::<usize>(), any::<usize>(), any::<usize>())
+			.prop_map(|(started_at, finished_at, processed, issues_filed, emailed, failed)| RunRecord {
+				started_at, finished_at, processed, issues_filed, emailed, failed
+			});
+		let recovery = (time(), weird_string(), weird_string(), of(weird_string()))
+			.prop_map(|(at, problem, moved_to, restored_from)| Recovery { at, problem, moved_to, restored_from });
+		let cooldowns = || btree_map(weird_string(), time(), 0..3);
+
+		// boxed, since they're big enough that generating them overflows the stack otherwise
+		let state = (any::<u32>(), any::<bool>(), vec(package(), 0..4), registry_sync, batch, cooldowns(), cooldowns()).boxed();
+		let history = (btree_map(weird_string(), breaker, 0..3), vec(time(), 0..4), of(time()), of(time()), vec(run, 0..3), vec(recovery, 0..2), btree_set(weird_string(), 0..3)).boxed();
+		(state, history).prop_map(|(state, history)| {
+			let (schema_version, saved_on_panic, packages, registry_sync, batch, repo_cooldowns, host_cooldowns) = state;
+			let (breakers, recent_filings, last_report_at, last_digest_at, recent_runs, recoveries, forgotten_owners) = history;
+			DatabaseThingData {
+				schema_version,
+				saved_on_panic,
+				packages,
+				registry_sync,
+				batch,
+				repo_cooldowns,
+				host_cooldowns,
+				breakers,
+				recent_filings,
+				last_report_at,
+				last_digest_at,
+				recent_runs,
+				recoveries,
+				forgotten_owners
+			}
+		})
+	}
+
+	/// [`package_state`] comes up with every state in a few hundred goes. the
+	/// match doesn't compile once there's a new state, so it's added to both
+	#[test]
+	fn every_variant_is_generated() {
+		let variant = |state: &PackageState| match state {
+			PackageState::New => { 0 }
+			PackageState::IssueFiled(_) => { 1 }
+			PackageState::ContactedByEmail(_) => { 2 }
+			PackageState::Deferred(_) => { 3 }
+			PackageState::SkippedDeprecated(_) => { 4 }
+			PackageState::NeedsReview(_) => { 5 }
+			PackageState::AlreadyMigrated(_) => { 6 }
+			PackageState::Blocked(_) => { 7 }
+			PackageState::OptedOut(_) => { 8 }
+			PackageState::DeclinedByOwnerPolicy(_) => { 9 }
+			PackageState::Dormant(_) => { 10 }
+			PackageState::RetryPending(_) => { 11 }
+			PackageState::IssueFilingInProgress(_) => { 12 }
+			PackageState::MaintainerApproved(_) => { 13 }
+			PackageState::Migrated(_) => { 14 }
+			PackageState::ConsentRevoked(_) => { 15 }
+		};
+
+		let strategy = package_state();
+		let mut runner = TestRunner::deterministic();
+		let mut generated = [false; 16];
+		for _ in 0..500 {
+			generated[variant(&strategy.new_tree(&mut runner).unwrap().current())] = true;
+		}
+		let missing = generated.iter().enumerate().filter(|(_, generated)| !**generated).map(|(i, _)| i).collect::<Vec<_>>();
+		assert!(missing.is_empty(), "the states at {missing:?} in the match were never generated");
+	}
+
+	proptest! {
+		/// whatever's saved loads again, as what it was
+		#[test]
+		fn state_round_trips(data in data()) {
+			let saved = serialize(&data).unwrap();
+			let loaded = match ron::from_str::<DatabaseThingData>(&saved) {
+				Ok(loaded) => { loaded }
+				Err(e) => { panic!("saved database doesn't load: {e}\n{saved}") }
+			};
+			prop_assert_eq!(serialize(&loaded).unwrap(), saved);
+		}
+
+		/// one package on its own, which gives smaller cases when one doesn't load
+		#[test]
+		fn package_round_trips(package in package()) {
+			let saved = ron::to_string(&package).unwrap();
+			let loaded = ron::from_str::<Package>(&saved).unwrap();
+			prop_assert_eq!(ron::to_string(&loaded).unwrap(), saved);
+		}
+	}
+
+	/// files from before packages were kept with everything about them still load
+	#[tokio::test]
+	async fn baseline_format_is_converted() {
+		let saved = r#"DatabaseThingData(
+	saved_on_panic: false,
+	packages: [
+		New(PackageNew(
+			name: "one-light-ui",
+			repository: Repository(
+				type: "git",
+				url: "https://github.com/atom/one-light-ui",
+			),
+			downloads: 1234,
+			stargazers_count: 56,
+		)),
+		IssueFiled(PackageIssueFiled(
+			name: "minimap",
+			repository: ("atom-minimap", "minimap"),
+			downloads: 99,
+			stargazers_count: 7,
+		)),
+	],
+)
+"#;
+		let filename = std::env::temp_dir().join(format!("pulsar-bot-baseline-{}.ron", std::process::id()));
+		std::fs::write(&filename, saved).unwrap();
+		let loaded = read_file(filename.to_str().unwrap()).await.unwrap();
+		std::fs::remove_file(&filename).unwrap();
+		let loaded = loaded.unwrap();
+
+		assert_eq!(loaded.schema_version, 0);
+		assert_eq!(loaded.packages.len(), 2);
+		assert_eq!(loaded.packages[0].info.name, "one-light-ui");
+		assert_eq!(loaded.packages[0].info.downloads, 1234);
+		assert!(matches!(loaded.packages[0].state, PackageState::New));
+		assert_eq!(loaded.packages[1].info.repository.url, "https://github.com/atom-minimap/minimap");
+		assert!(matches!(loaded.packages[1].state, PackageState::NeedsReview(_)));
+	}
+
+	/// fields from a newer version of the bot that's still the same schema version are skipped
+	#[test]
+	fn unknown_fields_are_skipped() {
+		let saved = "(schema_version: 1, saved_on_panic: false, packages: [], something_new: Some([1, 2]))";
+		let loaded = ron::from_str::<DatabaseThingData>(saved).unwrap();
+		assert!(loaded.packages.is_empty());
+	}
+}
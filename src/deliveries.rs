@@ -0,0 +1,99 @@
+//! github's webhook deliveries, saved (in `<db_path>.webhooks`, a file each)
+//! as they're received and before they're handled, and removed once they have
+//! been. so ones that weren't (because handling them failed, or the bot was
+//! stopped part way through) are still there for [`crate::events::replay`],
+//! which `replay-webhooks` and starting to receive them again do
+
+use crate::random;
+use crate::Result;
+use chrono::{ DateTime, Utc };
+use rand::Rng;
+use serde::{ Deserialize, Serialize };
+use std::path::{ Path, PathBuf };
+use tokio::fs;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Delivery {
+	/// its `X-GitHub-Delivery`, which is the same when it's redelivered
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub id: Option<String>,
+	/// `issues` or `issue_comment`
+	pub kind: String,
+	pub received_at: DateTime<Utc>,
+	/// how many times handling it failed
+	#[serde(default)]
+	pub attempts: u32,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub last_error: Option<String>,
+	/// the event, as github sent it
+	pub body: String
+}
+
+/// the deliveries kept for one database
+pub struct Deliveries {
+	dir: PathBuf
+}
+
+impl Deliveries {
+	pub fn new(db_path: &str) -> Self {
+		Self { dir: PathBuf::from(format!("{db_path}.webhooks")) }
+	}
+
+	/// keeps `delivery` until it's [`Self::handled`], returning where it's kept.
+	/// a redelivery replaces the one it's of
+	pub async fn save(&self, delivery: &Delivery) -> Result<PathBuf> {
+		// ids are guids, but they come from outside
+		let name = match delivery.id.as_deref().map(|id| id.replace(|c: char| !c.is_ascii_alphanumeric() && c != '-', "")) {
+			Some(id) if !id.is_empty() => { id }
+			_ => { format!("{}-{:08x}", delivery.received_at.format("%Y%m%dT%H%M%S%.6f"), random::with(|rng| rng.gen::<u32>())) }
+		};
+		let path = self.dir.join(format!("{name}.json"));
+		fs::create_dir_all(&self.dir).await
+			.map_err(|e| format!("error creating {}: {e}", self.dir.display()))?;
+		self.write(&path, delivery).await?;
+		Ok(path)
+	}
+
+	/// it's been handled, so it isn't replayed
+	pub async fn handled(&self, path: &Path) -> Result {
+		fs::remove_file(path).await
+			.map_err(|e| format!("error removing handled webhook delivery {}: {e}", path.display()))?;
+		Ok(())
+	}
+
+	/// handling it failed, so it's kept for replaying, with why
+	pub async fn failed(&self, path: &Path, delivery: &mut Delivery, error: String) -> Result {
+		delivery.attempts += 1;
+		delivery.last_error = Some(error);
+		self.write(path, delivery).await
+	}
+
+	/// every delivery that hasn't been handled, oldest first. ones that can't be
+	/// read are left where they are for someone to look at
+	pub async fn pending(&self) -> Result<Vec<(PathBuf, Delivery)>> {
+		if !self.dir.exists() { return Ok(Vec::new()) }
+
+		let mut entries = fs::read_dir(&self.dir).await
+			.map_err(|e| format!("error reading {}: {e}", self.dir.display()))?;
+		let mut pending = Vec::new();
+		while let Some(entry) = entries.next_entry().await.map_err(|e| format!("error reading {}: {e}", self.dir.display()))? {
+			let path = entry.path();
+			if path.extension().map_or(true, |extension| extension != "json") { continue }
+			let read = fs::read_to_string(&path).await
+				.map_err(|e| e.to_string())
+				.and_then(|json| serde_json::from_str::<Delivery>(&json).map_err(|e| e.to_string()));
+			match read {
+				Ok(delivery) => { pending.push((path, delivery)) }
+				Err(e) => { warn!("error reading webhook delivery {}, leaving it: {e}", path.display()) }
+			}
+		}
+		pending.sort_by(|(a_path, a), (b_path, b)| a.received_at.cmp(&b.received_at).then_with(|| a_path.cmp(b_path)));
+		Ok(pending)
+	}
+
+	async fn write(&self, path: &Path, delivery: &Delivery) -> Result {
+		fs::write(path, serde_json::to_string_pretty(delivery)?).await
+			.map_err(|e| format!("error saving webhook delivery {}: {e}", path.display()))?;
+		Ok(())
+	}
+}
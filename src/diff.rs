@@ -0,0 +1,151 @@
+//! what a run changed, or for dry runs what it would have: the packages whose
+//! state changed, and the issues filed and emails sent. written to a file with
+//! `file --diff`, so a dry run can be reviewed, and then the real run checked
+//! against it with `file --check-diff`, to see it did what was reviewed
+
+use crate::db::DatabaseThing;
+use crate::forge::RepoLocation;
+use crate::Result;
+use chrono::{ DateTime, Utc };
+use serde::{ Deserialize, Serialize };
+use std::collections::{ BTreeMap, HashSet };
+use tokio::fs;
+
+#[derive(Deserialize, Serialize)]
+pub struct RunDiff {
+	pub created_at: DateTime<Utc>,
+	pub dry_run: bool,
+	/// by package
+	pub state_changes: BTreeMap<String, StateDiff>,
+	pub issues: Vec<IssueDiff>,
+	/// packages that were emailed
+	pub emails: Vec<String>
+}
+
+#[derive(Clone, Deserialize, PartialEq, Serialize)]
+pub struct StateDiff {
+	pub from: String,
+	pub to: String
+}
+
+/// an issue that was filed, or would have been
+#[derive(Deserialize, Serialize)]
+pub struct IssueDiff {
+	pub repository: RepoLocation,
+	pub packages: Vec<String>,
+	pub title: String
+}
+
+/// keeps track of what a run does, for [`RunDiff`]
+pub struct DiffRecorder {
+	started_at: DateTime<Utc>,
+	before: BTreeMap<String, &'static str>,
+	issues: Vec<IssueDiff>,
+	emails: Vec<String>
+}
+
+impl DiffRecorder {
+	/// before the run changes anything
+	pub fn start(db: &DatabaseThing) -> Self {
+		Self { started_at: Utc::now(), before: db.state_kinds(), issues: Vec::new(), emails: Vec::new() }
+	}
+
+	pub fn filed(&mut self, repo: &RepoLocation, packages: &[String], title: &str) {
+		self.issues.push(IssueDiff { repository: repo.clone(), packages: packages.to_vec(), title: title.into() });
+	}
+
+	pub fn emailed(&mut self, package: &str) {
+		self.emails.push(package.into());
+	}
+
+	/// what changed since [`Self::start`]. dry runs don't really file or email
+	/// anything, so the states those would have changed packages to are put in
+	pub fn finish(self, db: &DatabaseThing, dry_run: bool) -> RunDiff {
+		let mut state_changes = BTreeMap::new();
+		for (package, to) in db.state_kinds() {
+			let from = self.before.get(&package).copied().unwrap_or("New");
+			if from != to {
+				state_changes.insert(package, StateDiff { from: from.into(), to: to.into() });
+			}
+		}
+		if dry_run {
+			let contacted = self.issues.iter()
+				.flat_map(|issue| issue.packages.iter().map(|package| (package, "IssueFiled")))
+				.chain(self.emails.iter().map(|package| (package, "ContactedByEmail")));
+			for (package, to) in contacted {
+				let from = self.before.get(package).copied().unwrap_or("New");
+				state_changes.insert(package.clone(), StateDiff { from: from.into(), to: to.into() });
+			}
+		}
+
+		RunDiff { created_at: self.started_at, dry_run, state_changes, issues: self.issues, emails: self.emails }
+	}
+}
+
+impl RunDiff {
+	pub async fn write(&self, path: &str) -> Result {
+		let json = serde_json::to_string_pretty(self)?;
+		fs::write(path, json).await
+			.map_err(|e| format!("error writing diff {path}: {e}"))?;
+		info!(
+			"wrote what the run {} to {path}: {} state changes, {} issues, {} emails",
+			if self.dry_run { "would have done" } else { "did" },
+			self.state_changes.len(),
+			self.issues.len(),
+			self.emails.len()
+		);
+		Ok(())
+	}
+
+	pub async fn read(path: &str) -> Result<Self> {
+		let diff = fs::read_to_string(path).await
+			.map_err(|e| format!("error reading diff {path}: {e}"))?;
+		Ok(serde_json::from_str(&diff).map_err(|e| format!("error parsing diff {path}: {e}"))?)
+	}
+
+	/// logs everything this run did differently from `expected` (usually a dry
+	/// run), which was read from `path`
+	pub fn check_against(&self, expected: &RunDiff, path: &str) {
+		let mut differences = Vec::new();
+
+		let issue_key = |issue: &IssueDiff| (issue.repository.to_string(), issue.packages.clone());
+		let expected_issues = expected.issues.iter().map(issue_key).collect::<HashSet<_>>();
+		let issues = self.issues.iter().map(issue_key).collect::<HashSet<_>>();
+		for issue in self.issues.iter().filter(|issue| !expected_issues.contains(&issue_key(issue))) {
+			differences.push(format!("filed an issue on {} for {}, which wasn't in {path}", issue.repository, issue.packages.join(", ")));
+		}
+		for issue in expected.issues.iter().filter(|issue| !issues.contains(&issue_key(issue))) {
+			differences.push(format!("didn't file the issue on {} for {} from {path}", issue.repository, issue.packages.join(", ")));
+		}
+
+		for package in self.emails.iter().filter(|package| !expected.emails.contains(package)) {
+			differences.push(format!("emailed {package}, which wasn't in {path}"));
+		}
+		for package in expected.emails.iter().filter(|package| !self.emails.contains(package)) {
+			differences.push(format!("didn't email {package} like {path} said"));
+		}
+
+		for (package, change) in &self.state_changes {
+			match expected.state_changes.get(package) {
+				Some(expected) if expected == change => {}
+				Some(expected) => {
+					differences.push(format!("{package} went from {} to {}, {path} had it going to {}", change.from, change.to, expected.to));
+				}
+				None => { differences.push(format!("{package} went from {} to {}, which wasn't in {path}", change.from, change.to)) }
+			}
+		}
+		for (package, expected) in expected.state_changes.iter().filter(|(package, _)| !self.state_changes.contains_key(*package)) {
+			differences.push(format!("{package} didn't go from {} to {} like {path} said", expected.from, expected.to));
+		}
+
+		match differences.is_empty() {
+			true => { info!("the run did what {path} (from {}) said it would", expected.created_at) }
+			false => {
+				warn!("the run did {} things differently from {path} (from {}):", differences.len(), expected.created_at);
+				for difference in &differences {
+					warn!("   {difference}");
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,152 @@
+//! an email every day or week for the team, with what happened since the last
+//! one (issues filed, approvals and declines) and the issues still waiting on
+//! a reply, for anyone who doesn't keep up with the chat
+
+use crate::config::Config;
+use crate::db::{ DatabaseThing, PackageState };
+use crate::email::Mailer;
+use crate::Result;
+use chrono::{ DateTime, Duration, Utc };
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+/// how many of the issues waiting on a reply are listed, the oldest first
+const WAITING_LISTED: usize = 20;
+
+/// who gets the digest, and how often
+#[derive(Clone, PartialEq)]
+pub struct DigestConfig {
+	pub to: Vec<String>,
+	pub every: DigestEvery
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DigestEvery {
+	Daily,
+	Weekly
+}
+
+impl DigestEvery {
+	fn duration(self) -> Duration {
+		match self {
+			Self::Daily => { Duration::days(1) }
+			Self::Weekly => { Duration::weeks(1) }
+		}
+	}
+}
+
+impl FromStr for DigestEvery {
+	type Err = String;
+
+	fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+		match s.to_lowercase().as_str() {
+			"daily" => { Ok(Self::Daily) }
+			"weekly" => { Ok(Self::Weekly) }
+			_ => { Err(format!("unknown digest interval {s} (expected daily or weekly)")) }
+		}
+	}
+}
+
+/// whether it's been long enough since the last digest for another one
+pub fn is_due(db: &DatabaseThing, digest: &DigestConfig) -> bool {
+	db.last_digest_at().map_or(true, |last| Utc::now() - last >= digest.every.duration())
+}
+
+/// sends the digest if it's been long enough since the last one (or `force`).
+/// for dry runs, it's printed instead
+pub async fn send_if_due(db: &DatabaseThing, config: &Config, mailer: &Mailer, force: bool, dry_run: bool) -> Result {
+	let digest = match &config.digest {
+		Some(digest) => { digest }
+		None => { return Err("digest.to isn't set, so there's nobody to send a digest to".into()) }
+	};
+
+	let now = Utc::now();
+	let last = db.last_digest_at();
+	if let Some(last) = last.filter(|_| !force && !is_due(db, digest)) {
+		info!("the digest isn't due until {}", (last + digest.every.duration()).format("%Y-%m-%d %H:%M UTC"));
+		return Ok(())
+	}
+
+	let since = last.unwrap_or_else(|| now - digest.every.duration());
+	let (subject, body) = render(db, since, now)?;
+	if dry_run {
+		println!("digest to {}:\n{subject}\n\n{body}", digest.to.join(", "));
+		return Ok(())
+	}
+
+	let generated = mailer.send_digest(&digest.to, &subject, &body).await?;
+	db.set_last_digest_at(now);
+	db.flush();
+	match generated.sent {
+		true => { info!("sent the digest to {}", digest.to.join(", ")) }
+		false => { info!("wrote the digest to {} (email.smtp isn't set, so it wasn't sent)", generated.file) }
+	}
+	Ok(())
+}
+
+/// the subject and (plain text) body of the digest for `since` to `now`
+fn render(db: &DatabaseThing, since: DateTime<Utc>, now: DateTime<Utc>) -> std::result::Result<(String, String), std::fmt::Error> {
+	let (mut filed, mut approved, mut declined) = (Vec::new(), Vec::new(), Vec::new());
+	let mut waiting = Vec::new();
+	let mut waiting_issues = HashSet::new();
+
+	for package in db.packages() {
+		let name = package.info.name;
+		for transition in package.history.iter().filter(|transition| transition.at >= since) {
+			match &transition.state {
+				PackageState::IssueFiled(issue) => { filed.push(format!("{name}: {}", issue.issue_url)) }
+				PackageState::MaintainerApproved(approval) => { approved.push(format!("{name}: {}", approval.approval_url)) }
+				PackageState::ConsentRevoked(revoked) => { declined.push(format!("{name}: took back their permission, {}", revoked.revocation_url)) }
+				PackageState::OptedOut(skipped) => { declined.push(format!("{name}: opted out, {}", skipped.reason)) }
+				_ => {}
+			}
+		}
+
+		// packages that share an issue are waiting on the same reply
+		if let PackageState::IssueFiled(issue) = package.state {
+			if issue.replied_at.is_none() && waiting_issues.insert(issue.issue_url.clone()) {
+				let next_reminder = issue.reminders.iter()
+					.filter(|reminder| reminder.sent_at.is_none())
+					.map(|reminder| reminder.due_at)
+					.min();
+				waiting.push((issue.filed_at, name, issue.issue_url, next_reminder));
+			}
+		}
+	}
+	waiting.sort();
+
+	let subject = format!(
+		"pulsar migration digest: {} filed, {} approved, {} declined",
+		filed.len(), approved.len(), declined.len()
+	);
+
+	let mut body = String::new();
+	writeln!(body, "From {} to {}.", since.format("%Y-%m-%d %H:%M UTC"), now.format("%Y-%m-%d %H:%M UTC"))?;
+	for (title, lines) in [("Issues filed", &filed), ("Approved", &approved), ("Declined", &declined)] {
+		writeln!(body)?;
+		writeln!(body, "{title} ({}):", lines.len())?;
+		if lines.is_empty() {
+			writeln!(body, "  none")?;
+		}
+		for line in lines {
+			writeln!(body, "  - {line}")?;
+		}
+	}
+
+	writeln!(body)?;
+	writeln!(body, "Waiting on a reply ({} issues, the oldest first):", waiting.len())?;
+	for (filed_at, name, issue_url, next_reminder) in waiting.iter().take(WAITING_LISTED) {
+		let days = (now - *filed_at).num_days();
+		match next_reminder {
+			Some(due_at) if *due_at <= now => { writeln!(body, "  - {name}: {issue_url}, filed {days} days ago, a reminder is due")? }
+			Some(due_at) => { writeln!(body, "  - {name}: {issue_url}, filed {days} days ago, next reminder on {}", due_at.format("%Y-%m-%d"))? }
+			None => { writeln!(body, "  - {name}: {issue_url}, filed {days} days ago, no reminders left")? }
+		}
+	}
+	if waiting.len() > WAITING_LISTED {
+		writeln!(body, "  and {} more", waiting.len() - WAITING_LISTED)?;
+	}
+
+	Ok((subject, body))
+}
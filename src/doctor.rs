@@ -0,0 +1,249 @@
+//! checks that everything a run needs is set up right, for before a big one
+
+use crate::config::{ self, Config };
+use crate::crash;
+use crate::db::{ DatabaseThing, SCHEMA_VERSION };
+use crate::forge::{ self, ForgeKind };
+use crate::github::GithubClient;
+use crate::gitlab::GitlabClient;
+use crate::registry::RegistryClient;
+use crate::templates;
+use crate::Result;
+use std::path::Path;
+
+/// scopes that are enough to file issues on public repositories
+pub const ISSUE_SCOPES: &[&str] = &["public_repo", "repo"];
+
+#[derive(Default)]
+struct Checklist {
+	failed: usize,
+	warned: usize,
+	passed: usize
+}
+
+impl Checklist {
+	fn pass(&mut self, what: &str, message: impl AsRef<str>) {
+		self.passed += 1;
+		println!("[ok]   {what}: {}", message.as_ref());
+	}
+
+	/// works, but probably not how it was meant to
+	fn warn(&mut self, what: &str, message: impl AsRef<str>) {
+		self.warned += 1;
+		println!("[warn] {what}: {}", message.as_ref());
+	}
+
+	fn fail(&mut self, what: &str, message: impl AsRef<str>) {
+		self.failed += 1;
+		println!("[fail] {what}: {}", message.as_ref());
+	}
+
+	fn skip(&self, what: &str, message: impl AsRef<str>) {
+		println!("[skip] {what}: {}", message.as_ref());
+	}
+
+	fn finish(self) -> Result {
+		println!("{} passed, {} warnings, {} failed", self.passed, self.warned, self.failed);
+		match self.failed {
+			0 => { Ok(()) }
+			failed => { Err(format!("{failed} checks failed").into()) }
+		}
+	}
+}
+
+/// prints a checklist of the config, database, forge tokens, and registry,
+/// and fails if anything on it did. nothing is changed
+pub async fn run(config_path: Option<&str>, profile: Option<&str>) -> Result {
+	let mut checklist = Checklist::default();
+
+	let config = match Config::load(config_path, profile) {
+		Ok(config) => {
+			let profile = match &config.profile {
+				Some(profile) => { format!(", with profile {profile}") }
+				None => { String::new() }
+			};
+			match config_path {
+				Some(path) => { checklist.pass("config", format!("loaded {path}{profile}")) }
+				None if Path::new(config::DEFAULT_PATH).exists() => { checklist.pass("config", format!("loaded {}{profile}", config::DEFAULT_PATH)) }
+				None => { checklist.warn("config", format!("there's no {}, so everything is the default", config::DEFAULT_PATH)) }
+			}
+			config
+		}
+		Err(e) => {
+			checklist.fail("config", e.to_string());
+			checklist.skip("everything else", "needs the config");
+			return checklist.finish()
+		}
+	};
+
+	check_templates(&mut checklist, &config);
+	check_db(&mut checklist, &config).await;
+	check_github(&mut checklist, &config).await;
+	check_gitlab(&mut checklist, &config).await;
+	check_registry(&mut checklist, &config).await;
+
+	checklist.finish()
+}
+
+/// loading the config already checked they fill in, which is said here, along
+/// with templates_dir having something in it that isn't a template
+fn check_templates(checklist: &mut Checklist, config: &Config) {
+	let found = config.template_files().filter(|file| file.exists()).count();
+	let others = match (config.template_variants.len(), config.locales.templates.len()) {
+		(0, 0) => { String::new() }
+		(variants, locales) => { format!(", with {variants} variants and {locales} translations") }
+	};
+	checklist.pass("templates", format!(
+		"{found} template files in use (the rest are built in){others}, they all fill in, say which packages they're for, and fit in an issue"
+	));
+
+	let dir = match config.templates.files().first().and_then(|file| file.parent()) {
+		Some(dir) => { dir }
+		None => { return }
+	};
+	if !dir.is_dir() {
+		checklist.warn("templates", format!("templates_dir {} isn't a directory, so they're all built in", dir.display()));
+		return
+	}
+	let names = templates::FILES.iter().map(|(file, _, _)| *file).collect::<Vec<_>>();
+	let stray = std::fs::read_dir(dir).into_iter().flatten().flatten()
+		.map(|entry| entry.file_name().to_string_lossy().into_owned())
+		.filter(|name| name.ends_with(".md") && !names.contains(&name.as_str()))
+		.collect::<Vec<_>>();
+	if !stray.is_empty() {
+		checklist.warn("templates", format!(
+			"templates_dir has {}, which isn't used (the templates are {})",
+			stray.join(", "),
+			names.join(", ")
+		));
+	}
+}
+
+async fn check_db(checklist: &mut Checklist, config: &Config) {
+	let path = &config.db_path;
+	if !Path::new(path).exists() {
+		// loading it would create it, which isn't this one's job
+		checklist.pass("database", format!("{path} doesn't exist yet, it's created on the first run"));
+		return
+	}
+
+	let db = match DatabaseThing::new(path).await {
+		Ok(db) => { db }
+		Err(e) => {
+			checklist.fail("database", e.to_string());
+			return
+		}
+	};
+	db.set_read_only();
+
+	let packages = db.packages().len();
+	match db.schema_version() {
+		SCHEMA_VERSION => { checklist.pass("database", format!("{path} has {packages} packages, schema version {SCHEMA_VERSION}")) }
+		version => {
+			checklist.pass("database", format!(
+				"{path} has {packages} packages, schema version {version} (it's upgraded to {SCHEMA_VERSION} the next time it's saved)"
+			))
+		}
+	}
+	if let Some(recovery) = db.recoveries().last() {
+		let restored = match &recovery.restored_from {
+			Some(backup) => { format!("backup {backup} was loaded instead") }
+			None => { "there was no backup, so it started again from nothing".into() }
+		};
+		checklist.warn("database", format!("was corrupt on {} ({}), it was moved to {}, and {restored}", recovery.at, recovery.problem, recovery.moved_to));
+	}
+	if let Some(crash) = crash::last(path) {
+		checklist.warn("last crash", format!("the bot {}", crash.describe()));
+	}
+}
+
+/// the token for github.com, and any github enterprise hosts there's one for
+async fn check_github(checklist: &mut Checklist, config: &Config) {
+	if config.simulate_forges.is_some() {
+		checklist.skip("github", "forges.simulate is set, so no tokens are used");
+		return
+	}
+
+	let mut hosts = Vec::new();
+	match &config.github_token {
+		Some(token) => { hosts.push(("github.com".to_string(), token.clone())) }
+		None => { checklist.warn("github.com token", "not set, so packages on github.com can't be contacted") }
+	}
+	hosts.extend(config.forge_tokens.iter()
+		.filter(|(host, _)| forge::forge_for_host(host, config) == Some(ForgeKind::GitHub))
+		.map(|(host, token)| (host.clone(), token.clone())));
+
+	for (host, token) in hosts {
+		let what = format!("{host} token");
+		let info = match GithubClient::new(&host, token.expose()) {
+			Ok(client) => { client.token_info().await }
+			Err(e) => { Err(e) }
+		};
+
+		match info {
+			Ok((login, Some(scopes))) => {
+				if scopes.iter().any(|scope| ISSUE_SCOPES.contains(&scope.as_str())) {
+					checklist.pass(&what, format!("valid, for {login}, with scopes {}", scopes.join(", ")));
+				} else {
+					checklist.fail(&what, format!(
+						"valid, for {login}, but it needs the public_repo or repo scope to file issues (it has {})",
+						if scopes.is_empty() { "none".to_string() } else { scopes.join(", ") }
+					));
+				}
+			}
+			Ok((login, None)) => {
+				checklist.warn(&what, format!(
+					"valid, for {login}, but it's a fine-grained token so its permissions can't be checked. it needs read and write access to issues"
+				));
+			}
+			Err(e) => {
+				// octocrab puts a backtrace after the message for network errors
+				let e = e.to_string();
+				let message = e.lines().next().unwrap_or_default();
+				checklist.fail(&what, token.redact(&format!("error checking it: {message}")))
+			}
+		}
+	}
+}
+
+/// the tokens for gitlab.com and self hosted instances, which can only be
+/// checked to work, since gitlab doesn't say what they can do
+async fn check_gitlab(checklist: &mut Checklist, config: &Config) {
+	if config.simulate_forges.is_some() { return }
+
+	for (host, _) in config.token_hosts().into_iter().filter(|(_, kind)| *kind == ForgeKind::GitLab) {
+		let what = format!("{host} token");
+		let token = match config.forge_tokens.get(&host).or_else(|| config.gitlab_token.as_ref().filter(|_| host == "gitlab.com")) {
+			Some(token) => { token }
+			None => { continue }
+		};
+		let username = match GitlabClient::new(&host, token.expose()) {
+			Ok(client) => { client.with_auth(config.auth_for(&host)).username().await.map(String::from) }
+			Err(e) => { Err(e) }
+		};
+		match username {
+			Ok(username) => { checklist.pass(&what, format!("valid, for {username}. it needs the api scope to file issues")) }
+			Err(e) => { checklist.fail(&what, token.redact(&format!("error checking it: {e}"))) }
+		}
+	}
+}
+
+async fn check_registry(checklist: &mut Checklist, config: &Config) {
+	let registry = match RegistryClient::new(config) {
+		Ok(registry) => { registry }
+		Err(e) => {
+			checklist.fail("registry", e.to_string());
+			return
+		}
+	};
+
+	for (i, (url, res)) in registry.check().await.into_iter().enumerate() {
+		let what = if i == 0 { "registry".to_string() } else { format!("registry mirror {url}") };
+		match res {
+			Ok(packages) => { checklist.pass(&what, format!("{url} responded, with {packages} packages on the first page")) }
+			// mirrors are only fallbacks
+			Err(e) if i > 0 => { checklist.warn(&what, e.to_string()) }
+			Err(e) => { checklist.fail(&what, e.to_string()) }
+		}
+	}
+}
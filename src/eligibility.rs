@@ -0,0 +1,166 @@
+//! deciding whether a package should be contacted (yet)
+
+use crate::config::Config;
+use crate::db::PackageNew;
+use crate::forge::RepoLocation;
+use std::str::FromStr;
+
+pub enum Eligibility {
+	Eligible,
+	/// not contacted for now, but kept in case it becomes eligible later
+	/// (eg. thresholds are lowered)
+	Deferred(String),
+	/// deprecated or unpublished on purpose, so never contacted
+	SkipDeprecated(String),
+	/// something looks off, a human should have a look first
+	NeedsReview(String),
+	/// on the blocklist, so never contacted
+	Blocked
+}
+
+/// what's done with packages whose repository is [`Config::dormant_after_years`] old
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DormantPolicy {
+	/// never contacted
+	Skip,
+	/// contacted once there's nothing else left to
+	Last
+}
+
+impl FromStr for DormantPolicy {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match &*s.trim().to_lowercase() {
+			"skip" => { Ok(Self::Skip) }
+			"last" => { Ok(Self::Last) }
+			_ => { Err(format!("unknown policy {s} (expected skip or last)")) }
+		}
+	}
+}
+
+pub fn check(package: &PackageNew, config: &Config) -> Eligibility {
+	if config.blocklist.matches(package) {
+		return Eligibility::Blocked
+	}
+
+	if let Some(allowlist) = &config.allowlist {
+		if !allowlist.matches(package) {
+			return Eligibility::Deferred("not on the allowlist".into())
+		}
+	}
+
+	if let Some(reason) = package.deprecation() {
+		return Eligibility::SkipDeprecated(reason)
+	}
+
+	if package.is_theme() && !config.include_themes {
+		return Eligibility::Deferred("themes are excluded".into())
+	}
+
+	if package.downloads < config.min_downloads {
+		return Eligibility::Deferred(format!(
+			"{} downloads is below the minimum of {}",
+			package.downloads,
+			config.min_downloads
+		))
+	}
+
+	if package.stargazers_count < config.min_stars {
+		return Eligibility::Deferred(format!(
+			"{} stars is below the minimum of {}",
+			package.stargazers_count,
+			config.min_stars
+		))
+	}
+
+	if config.ownership_check {
+		if let Some(reason) = check_ownership(package) {
+			return Eligibility::NeedsReview(reason)
+		}
+	}
+
+	Eligibility::Eligible
+}
+
+/// the repository owner should look at least a bit like the registry
+/// author, if it doesn't, the registry metadata is likely stale, or the
+/// repository has been taken over by someone else
+fn check_ownership(package: &PackageNew) -> Option<String> {
+	let repo = RepoLocation::parse(&package.repository.url)?;
+	// can't tell anything without an author
+	let author = package.metadata.author.as_ref()?;
+
+	let mut candidates = Vec::new();
+	if let Some(name) = &author.name { candidates.push(name.clone()) }
+	if let Some(email) = &author.email {
+		candidates.push(email.split('@').next().unwrap_or_default().into());
+	}
+	if let Some(url) = &author.url { candidates.push(url.clone()) }
+
+	// the last component of a gitlab group path is as good as the github owner
+	let owner = repo.owner.rsplit('/').next().unwrap_or(&repo.owner);
+	if candidates.iter().any(|candidate| resembles(owner, candidate)) {
+		return None
+	}
+
+	Some(format!(
+		"repository owner {} doesn't look like the package author {author}",
+		repo.owner
+	))
+}
+
+fn normalise(s: &str) -> String {
+	s.chars()
+		.filter(|c| c.is_alphanumeric())
+		.flat_map(char::to_lowercase)
+		.collect()
+}
+
+/// bits of urls and emails that don't say anything about who someone is
+const NOISE_WORDS: &[&str] = &["http", "https", "www", "com", "org", "net", "dev", "github", "gitlab", "mail", "gmail"];
+
+fn resembles(owner: &str, candidate: &str) -> bool {
+	let owner = normalise(owner);
+	let whole = normalise(candidate);
+	if owner.is_empty() || whole.is_empty() { return false }
+
+	// eg. owner `jdoe`, author `jdoe` or `jdoe.dev`, or the other way around
+	if owner.len() >= 3 && whole.contains(&owner) { return true }
+	if whole.len() >= 3 && owner.contains(&whole) { return true }
+
+	// eg. owner `janedoe-atom`, author `Jane Doe`
+	let shares_word = candidate
+		.split(|c: char| !c.is_alphanumeric())
+		.map(normalise)
+		.filter(|word| word.len() >= 3 && !NOISE_WORDS.contains(&&**word))
+		.any(|word| owner.contains(&word));
+	if shares_word { return true }
+
+	// typos and small variations, eg. owner `jonhdoe`, author `johndoe`
+	let distance = levenshtein(&owner, &whole);
+	let longest = owner.chars().count().max(whole.chars().count());
+	distance * 3 <= longest
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+	let b = b.chars().collect::<Vec<_>>();
+	let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+	for (i, ca) in a.chars().enumerate() {
+		let mut previous = row[0];
+		row[0] = i + 1;
+
+		for (j, cb) in b.iter().enumerate() {
+			let current = row[j + 1];
+			row[j + 1] = if ca == *cb {
+				previous
+			} else {
+				1 + previous.min(row[j]).min(row[j + 1])
+			};
+			previous = current;
+		}
+	}
+
+	row[b.len()]
+}
@@ -0,0 +1,110 @@
+//! emails, for asking about packages whose repository isn't on a forge we can file issues on
+
+use crate::config::{ Config, SmtpConfig };
+use crate::db::PackageNew;
+use crate::templates::{ Templates, Vars };
+use crate::Result;
+use chrono::Utc;
+use lettre::{ AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor };
+use lettre::transport::smtp::authentication::Credentials;
+use std::path::PathBuf;
+use tokio::fs;
+
+pub struct Mailer {
+	outbox: PathBuf,
+	templates: Templates,
+	smtp: Option<(AsyncSmtpTransport<Tokio1Executor>, String)>
+}
+
+pub struct EmailGenerated {
+	pub file: String,
+	pub sent: bool
+}
+
+impl Mailer {
+	pub fn new(config: &Config) -> Result<Self> {
+		let smtp = match &config.smtp {
+			Some(SmtpConfig { host, port, username, password, from }) => {
+				let mut transport = AsyncSmtpTransport::<Tokio1Executor>::relay(host)?
+					.credentials(Credentials::new(username.clone(), password.expose().into()));
+				if let Some(port) = port {
+					transport = transport.port(*port);
+				}
+
+				Some((transport.build(), from.clone()))
+			}
+			None => { None }
+		};
+
+		Ok(Self {
+			outbox: config.email_outbox.clone().into(),
+			templates: config.templates.clone(),
+			smtp
+		})
+	}
+
+	/// for a reloaded config, the rest of it is only read at startup
+	pub fn set_templates(&mut self, templates: Templates) {
+		self.templates = templates;
+	}
+
+	/// generates an email asking permission for a package, writes it to the outbox,
+	/// and sends it if smtp is configured
+	pub async fn send_permission_request(&self, package: &PackageNew, to: &str) -> Result<EmailGenerated> {
+		let (subject, body) = self.templates.email(&Vars::email(package))?;
+
+		fs::create_dir_all(&self.outbox).await?;
+		let file = self.outbox.join(format!("{}.eml", sanitise_filename(&package.name)));
+		let file_str = file.to_string_lossy().into_owned();
+
+		let mut sent = false;
+		if let Some((transport, from)) = &self.smtp {
+			let message = Message::builder()
+				.from(from.parse()?)
+				.to(to.parse()?)
+				.subject(&subject)
+				.body(body.clone())?;
+
+			fs::write(&file, message.formatted()).await?;
+			transport.send(message).await?;
+			sent = true;
+		} else {
+			fs::write(&file, format!("To: {to}\nSubject: {subject}\n\n{body}")).await?;
+		}
+
+		Ok(EmailGenerated { file: file_str, sent })
+	}
+
+	/// writes the [`crate::digest`] to the outbox, and sends it to everyone in `to`
+	/// if smtp is configured
+	pub async fn send_digest(&self, to: &[String], subject: &str, body: &str) -> Result<EmailGenerated> {
+		fs::create_dir_all(&self.outbox).await?;
+		let file = self.outbox.join(format!("digest-{}.eml", Utc::now().format("%Y-%m-%dT%H-%M-%S")));
+		let file_str = file.to_string_lossy().into_owned();
+
+		let mut sent = false;
+		if let Some((transport, from)) = &self.smtp {
+			let mut message = Message::builder()
+				.from(from.parse()?)
+				.subject(subject);
+			for to in to {
+				message = message.to(to.parse()?);
+			}
+			let message = message.body(body.to_string())?;
+
+			fs::write(&file, message.formatted()).await?;
+			transport.send(message).await?;
+			sent = true;
+		} else {
+			fs::write(&file, format!("To: {}\nSubject: {subject}\n\n{body}", to.join(", "))).await?;
+		}
+
+		Ok(EmailGenerated { file: file_str, sent })
+	}
+}
+
+fn sanitise_filename(name: &str) -> String {
+	name.chars()
+		.map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+		.collect()
+}
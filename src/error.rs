@@ -0,0 +1,318 @@
+//! what can go wrong. errors say what failed (the config, the database, the
+//! registry, a forge), and for the registry and forges roughly why (see
+//! [`Failure`]), which is worked out once, where the error comes from. so what
+//! to do about one (retrying, cooling a host down, giving up on a package) is
+//! decided by what it is, rather than by what its message happens to say.
+//! [`Context`] says what was being done, keeping the error it happened with.
+//! every failure is also one of a few [`Class`]es, for what's done about it
+
+use serde::{ Deserialize, Serialize };
+use std::error::Error as StdError;
+use std::fmt::Display;
+
+pub type BoxError = Box<dyn StdError + Send + Sync>;
+
+/// github api error messages that retrying isn't going to fix
+const PERMANENT_GITHUB_ERRORS: &[&str] = &[
+	"Not Found",
+	"Issues are disabled for this repo",
+	"Repository was archived so is read-only.",
+	"Repository access blocked",
+	"Bad credentials"
+];
+
+#[derive(thiserror::Error)]
+pub enum Error {
+	/// something in the config (or the environment) doesn't work
+	#[error("{0}")]
+	Config(String),
+	/// what was about to be posted isn't right, like a template filled in with
+	/// something that would mention someone, see [`crate::templates`]
+	#[error("{0}")]
+	Content(String),
+	/// the database couldn't be loaded, saved, or changed like that
+	#[error("{0}")]
+	Database(String),
+	/// a request to the atom.io package registry
+	#[error("{source}")]
+	Registry { failure: Failure, #[source] source: BoxError },
+	/// a request to a forge (github, gitlab, or a pretend one)
+	#[error("{source}")]
+	Forge { failure: Failure, #[source] source: BoxError },
+	/// another error, with what was being done when it happened
+	#[error("{context}: {source}")]
+	Context { context: String, #[source] source: Box<Error> },
+	#[error(transparent)]
+	Io(#[from] std::io::Error),
+	/// anything else that went wrong
+	#[error("{0}")]
+	Other(String),
+	/// an error from a library, from anything that isn't the registry or a forge
+	#[error(transparent)]
+	Library(BoxError)
+}
+
+/// roughly why a request to the registry or a forge failed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Failure {
+	RateLimited,
+	NotFound,
+	/// retrying isn't going to fix it, like the repository being archived
+	Permanent,
+	/// timing out or not connecting, which probably works next time
+	Network,
+	/// a 5xx, the forge or registry having a bad day
+	Server,
+	/// none of the above, which `--strict` stops for
+	Other
+}
+
+/// what's done about something failing, the same for retrying, cooling down, and reports
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Class {
+	/// likely to work later (timeouts, rate limits, outages), so it's tried again
+	Retryable,
+	/// never going to work, like the repository not existing, so it's given up on
+	Terminal,
+	/// not going to work until someone sorts something out, like issues being turned off
+	NeedsHuman
+}
+
+impl Class {
+	/// what it's called in metrics
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Retryable => { "retryable" }
+			Self::Terminal => { "terminal" }
+			Self::NeedsHuman => { "needs_human" }
+		}
+	}
+}
+
+impl Failure {
+	/// what it's called in metrics
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::RateLimited => { "rate_limited" }
+			Self::NotFound => { "not_found" }
+			Self::Permanent => { "permanent" }
+			Self::Network => { "network" }
+			Self::Server => { "server" }
+			Self::Other => { "other" }
+		}
+	}
+
+	pub fn class(self) -> Class {
+		match self {
+			Self::RateLimited | Self::Network | Self::Server | Self::Other => { Class::Retryable }
+			Self::NotFound => { Class::Terminal }
+			Self::Permanent => { Class::NeedsHuman }
+		}
+	}
+
+	/// from the errors of the libraries that make requests. when unsure it's
+	/// [`Failure::Other`], which is retried, since retries are capped anyway
+	fn of(e: &(dyn StdError + 'static)) -> Self {
+		let mut source = Some(e);
+		while let Some(e) = source {
+			// the error itself, or what it says, for errors that are transparent over
+			// theirs (like [`Error::Library`]), which skip straight to that
+			let github_error = match e.downcast_ref::<octocrab::Error>() {
+				Some(octocrab::Error::GitHub { source, .. }) => { Some(source) }
+				_ => { e.downcast_ref::<octocrab::GitHubError>() }
+			};
+			if let Some(github_error) = github_error {
+				let lowercase = github_error.message.to_lowercase();
+				return match github_error.message.as_str() {
+					"Not Found" => { Self::NotFound }
+					// the secondary limit used to be called abuse detection
+					_ if lowercase.contains("rate limit") || lowercase.contains("abuse detection") => { Self::RateLimited }
+					message if PERMANENT_GITHUB_ERRORS.contains(&message) => { Self::Permanent }
+					_ => { Self::Other }
+				}
+			}
+			if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+				return match e.status() {
+					Some(status) => { Self::of_status(status) }
+					None if e.is_timeout() || e.is_connect() || e.is_request() => { Self::Network }
+					None => { Self::Other }
+				}
+			}
+			// a 5xx from the mail server, like the address not existing
+			if let Some(e) = e.downcast_ref::<lettre::transport::smtp::Error>() {
+				return match e.is_permanent() {
+					true => { Self::Permanent }
+					false => { Self::Network }
+				}
+			}
+			if let Some(e) = e.downcast_ref::<std::io::Error>() {
+				use std::io::ErrorKind::*;
+				if matches!(e.kind(), TimedOut | ConnectionRefused | ConnectionReset | ConnectionAborted | NotConnected | BrokenPipe | UnexpectedEof) {
+					return Self::Network
+				}
+			}
+			source = e.source();
+		}
+
+		Self::Other
+	}
+
+	pub fn of_status(status: reqwest::StatusCode) -> Self {
+		match status.as_u16() {
+			429 => { Self::RateLimited }
+			404 => { Self::NotFound }
+			408 => { Self::Network }
+			400..=499 => { Self::Permanent }
+			500..=599 => { Self::Server }
+			_ => { Self::Other }
+		}
+	}
+}
+
+impl Error {
+	/// `e`, from a request to a forge. it's worked out why it failed, unless it
+	/// already was
+	pub fn forge(e: impl Into<Error>) -> Self {
+		match e.into() {
+			e @ (Self::Forge { .. } | Self::Registry { .. } | Self::Context { .. }) => { e }
+			e => { Self::Forge { failure: Failure::of(&e), source: e.into_boxed() } }
+		}
+	}
+
+	/// `e`, from a request to the registry, like [`Self::forge`]
+	pub fn registry(e: impl Into<Error>) -> Self {
+		match e.into() {
+			e @ (Self::Forge { .. } | Self::Registry { .. } | Self::Context { .. }) => { e }
+			e => { Self::Registry { failure: Failure::of(&e), source: e.into_boxed() } }
+		}
+	}
+
+	/// why a request failed, or [`Failure::Other`] if it wasn't a request (or a
+	/// library's error that doesn't say)
+	pub fn failure(&self) -> Failure {
+		match self {
+			Self::Forge { failure, .. } | Self::Registry { failure, .. } => { *failure }
+			Self::Context { source, .. } => { source.failure() }
+			Self::Io(e) => { Failure::of(e) }
+			Self::Library(e) => { Failure::of(e.as_ref()) }
+			_ => { Failure::Other }
+		}
+	}
+
+	/// what's to be done about it. anything that isn't known to be terminal or to
+	/// need a human is retried, since retries are capped anyway
+	pub fn class(&self) -> Class {
+		match self {
+			Self::Config(_) | Self::Content(_) => { Class::NeedsHuman }
+			Self::Context { source, .. } => { source.class() }
+			_ => { self.failure().class() }
+		}
+	}
+
+	/// whether it's not known why it failed, so it's only retried because that's
+	/// the least bad guess, see `--strict`
+	pub fn is_unexpected(&self) -> bool {
+		self.class() == Class::Retryable && self.failure() == Failure::Other
+	}
+
+	/// it and every error it came from, one per line, for looking into it
+	pub fn full_context(&self) -> String {
+		let mut lines = vec![self.to_string()];
+		let mut source = StdError::source(self);
+		while let Some(e) = source {
+			let line = e.to_string();
+			// contexts say what their source says already
+			if !lines.last().map_or(false, |last| last.ends_with(&line)) {
+				lines.push(line);
+			}
+			source = e.source();
+		}
+		lines.join("\n   caused by: ")
+	}
+
+	/// whether it was the thing asked for not existing
+	pub fn is_not_found(&self) -> bool {
+		self.failure() == Failure::NotFound
+	}
+
+	/// the error a library's error wraps up for [`Self::forge`] and [`Self::registry`],
+	/// so they can still find it
+	fn into_boxed(self) -> BoxError {
+		match self {
+			Self::Library(e) => { e }
+			Self::Io(e) => { Box::new(e) }
+			e => { Box::new(e) }
+		}
+	}
+}
+
+// what `main` returning an error prints, so it's the message rather than the variants
+impl std::fmt::Debug for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		std::fmt::Display::fmt(self, f)
+	}
+}
+
+impl From<String> for Error {
+	fn from(message: String) -> Self {
+		Self::Other(message)
+	}
+}
+
+impl From<&str> for Error {
+	fn from(message: &str) -> Self {
+		Self::Other(message.into())
+	}
+}
+
+impl From<BoxError> for Error {
+	fn from(e: BoxError) -> Self {
+		match e.downcast::<Error>() {
+			Ok(e) => { *e }
+			Err(e) => { Self::Library(e) }
+		}
+	}
+}
+
+impl From<octocrab::Error> for Error {
+	fn from(e: octocrab::Error) -> Self {
+		Self::forge(Self::Library(Box::new(e)))
+	}
+}
+
+/// errors from libraries that aren't worth telling apart from each other
+macro_rules! library_errors {
+	($($error:ty),*) => {
+		$(
+			impl From<$error> for Error {
+				fn from(e: $error) -> Self {
+					Self::Library(Box::new(e))
+				}
+			}
+		)*
+	};
+}
+
+library_errors!(
+	reqwest::Error,
+	serde_json::Error,
+	url::ParseError,
+	std::fmt::Error,
+	tokio::task::JoinError,
+	lettre::address::AddressError,
+	lettre::error::Error,
+	lettre::transport::smtp::Error
+);
+
+/// adding what was being done to an error, see [`Error::Context`]
+pub trait Context<T> {
+	fn context(self, context: impl Display) -> Result<T, Error>;
+}
+
+impl<T, E: Into<Error>> Context<T> for Result<T, E> {
+	fn context(self, context: impl Display) -> Result<T, Error> {
+		self.map_err(|e| Error::Context { context: context.to_string(), source: Box::new(e.into()) })
+	}
+}
@@ -0,0 +1,393 @@
+//! receiving github's `issues` and `issue_comment` webhook events (from an org
+//! webhook, or a github app) on `github_webhooks.listen`, so replies, issues
+//! being closed, and maintainers taking back their permission are noticed as
+//! they happen, instead of by polling every issue. replies saying yes or no,
+//! and issues closed without a yes, move their packages on straight away, the
+//! same as when they're polled for (see [`consent::answer_from_replies`]).
+//! while they're being received, issues on github aren't polled for
+//! permission being taken back. deliveries are saved before they're handled
+//! (see [`crate::deliveries`]), so the ones that weren't can be [`replay`]ed
+
+use crate::config::Config;
+use crate::consent;
+use crate::db::{ DatabaseThing, PackageState };
+use crate::deliveries::{ Deliveries, Delivery };
+use crate::forge::IssueComment;
+use crate::github::GithubClient;
+use crate::http::{ self, Request };
+use crate::metrics;
+use crate::reminders;
+use crate::secrets::Secret;
+use crate::Result;
+use chrono::{ DateTime, Utc };
+use ring::hmac;
+use serde::Deserialize;
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::sync::Arc;
+use tokio::net::{ TcpListener, TcpStream };
+
+/// github sends up to 25 MB, but issue events are nowhere near that
+const MAX_EVENT: usize = 1024 * 1024;
+
+/// whether [`serve`] is running, see [`receiving`]
+static RECEIVING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, PartialEq)]
+pub struct GithubWebhooksConfig {
+	/// address to listen on, like `0.0.0.0:9186`
+	pub listen: String,
+	/// what the webhook was set up with, for checking events' signatures.
+	/// nothing is received without it
+	pub secret: Option<Secret>
+}
+
+#[derive(Deserialize)]
+struct Event {
+	action: String,
+	issue: Issue,
+	/// only for `issue_comment`
+	comment: Option<Comment>,
+	/// who did it
+	sender: User
+}
+
+#[derive(Deserialize)]
+struct Issue {
+	html_url: String
+}
+
+#[derive(Deserialize)]
+struct Comment {
+	html_url: String,
+	#[serde(default)]
+	body: Option<String>,
+	created_at: DateTime<Utc>,
+	user: User
+}
+
+#[derive(Deserialize)]
+struct User {
+	login: String,
+	#[serde(rename = "type")]
+	kind: String
+}
+
+/// what's done with events, whether they were just received or are being replayed
+struct Handler {
+	db: DatabaseThing,
+	/// for approving packages, and registering them with the pulsar backend
+	config: Config,
+	/// for telling which comments are our own
+	github: Option<GithubClient>
+}
+
+struct Receiver {
+	handler: Handler,
+	key: hmac::Key,
+	deliveries: Deliveries
+}
+
+/// starts receiving events, in the background for as long as the bot runs
+pub async fn serve(db: &DatabaseThing, config: &Config, github: Option<GithubClient>) -> Result {
+	let webhooks = config.github_webhooks.as_ref().ok_or("github_webhooks.listen isn't set")?;
+	let listen = &webhooks.listen;
+	// otherwise anyone could say a maintainer said yes, or took back their permission
+	let secret = webhooks.secret.as_ref()
+		.ok_or("github_webhooks.secret isn't set, so there's no telling whether events really come from github")?;
+	let listener = TcpListener::bind(listen).await
+		.map_err(|e| format!("error listening for github's webhooks on {listen}: {e}"))?;
+	info!("receiving github's webhooks on http://{listen}/");
+	RECEIVING.store(true, Ordering::Relaxed);
+
+	let receiver = Arc::new(Receiver {
+		handler: Handler { db: db.clone(), config: config.clone(), github },
+		key: hmac::Key::new(hmac::HMAC_SHA256, secret.expose().as_bytes()),
+		deliveries: Deliveries::new(&config.db_path)
+	});
+	tokio::spawn(async move {
+		// the ones that weren't handled last time, before anything new
+		match receiver.handler.replay(&receiver.deliveries).await {
+			Ok((0, 0)) => {}
+			Ok((replayed, failed)) => { info!("replayed {replayed} webhook deliveries that weren't handled before, {failed} of them failed again") }
+			Err(e) => { warn!("error replaying webhook deliveries that weren't handled before: {e}") }
+		}
+		loop {
+			let stream = match listener.accept().await {
+				Ok((stream, _)) => { stream }
+				Err(e) => {
+					warn!("error accepting a webhook connection: {e}");
+					continue
+				}
+			};
+			let receiver = receiver.clone();
+			tokio::spawn(async move {
+				if let Err(e) = receiver.respond(stream).await {
+					debug!("error answering a webhook: {e}");
+				}
+			});
+		}
+	});
+
+	Ok(())
+}
+
+/// whether events are being received, so issues on github don't need polling
+pub fn receiving() -> bool {
+	RECEIVING.load(Ordering::Relaxed)
+}
+
+/// handles the deliveries that were saved but never handled, oldest first, for
+/// `replay-webhooks`. or (for `dry_run`) prints them
+pub async fn replay(db: &DatabaseThing, config: &Config, github: Option<GithubClient>, dry_run: bool) -> Result {
+	let deliveries = Deliveries::new(&config.db_path);
+	if dry_run {
+		let pending = deliveries.pending().await?;
+		if pending.is_empty() {
+			println!("there aren't any webhook deliveries waiting to be handled");
+		}
+		for (path, delivery) in pending {
+			let failed = match &delivery.last_error {
+				Some(error) => { format!(", failed {} times, last with: {error}", delivery.attempts) }
+				None => { String::new() }
+			};
+			println!("{} {} from {}{failed}", path.display(), delivery.kind, delivery.received_at);
+		}
+		return Ok(())
+	}
+
+	let handler = Handler { db: db.clone(), config: config.clone(), github };
+	let (replayed, failed) = handler.replay(&deliveries).await?;
+	info!("replayed {replayed} webhook deliveries, {failed} of them failed");
+	if failed > 0 {
+		return Err(format!("{failed} webhook deliveries failed again, they're kept for trying again").into())
+	}
+	Ok(())
+}
+
+impl Receiver {
+	async fn respond(&self, mut stream: TcpStream) -> Result {
+		let request = Request::read(&mut stream, MAX_EVENT).await?;
+		if request.method != "POST" {
+			return http::respond(&mut stream, "405 Method Not Allowed", "text/plain", &[], "only github's webhooks are here\n").await
+		}
+		if let Err(rejected) = self.verify(&request) {
+			let from = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_else(|_| "somewhere".into());
+			warn!("turned away a webhook from {from}: {}", rejected.message());
+			metrics::webhook_rejected(rejected.kind());
+			return http::respond(&mut stream, "401 Unauthorized", "text/plain", &[], &format!("{}\n", rejected.message())).await
+		}
+
+		let (status, body) = match request.header("x-github-event") {
+			Some("ping") => { ("200 OK", "pong".to_string()) }
+			Some(kind @ ("issues" | "issue_comment")) => {
+				match serde_json::from_slice::<Event>(&request.body) {
+					Ok(event) => {
+						let delivery = Delivery {
+							id: request.header("x-github-delivery").map(String::from),
+							kind: kind.into(),
+							received_at: Utc::now(),
+							attempts: 0,
+							last_error: None,
+							body: String::from_utf8_lossy(&request.body).into_owned()
+						};
+						self.receive(delivery, event).await
+					}
+					Err(e) => { ("400 Bad Request", format!("can't read the event: {e}")) }
+				}
+			}
+			_ => { ("200 OK", "ignored, only issues and issue_comment events are used".to_string()) }
+		};
+		http::respond(&mut stream, status, "text/plain", &[], &format!("{body}\n")).await
+	}
+
+	/// checks `x-hub-signature-256` is the body signed with the secret, so
+	/// nobody else can say a maintainer replied or took back their permission.
+	/// the older sha-1 `x-hub-signature` isn't enough
+	fn verify(&self, request: &Request) -> std::result::Result<(), Rejected> {
+		let signature = request.header("x-hub-signature-256").ok_or(Rejected::Unsigned)?;
+		let signature = signature.strip_prefix("sha256=")
+			.and_then(http::decode_hex)
+			.ok_or(Rejected::Malformed)?;
+		// in constant time, so the signature can't be guessed a byte at a time
+		hmac::verify(&self.key, &request.body, &signature).map_err(|_| Rejected::Mismatch)
+	}
+
+	/// saves the delivery, then handles it, keeping it if that fails
+	async fn receive(&self, mut delivery: Delivery, event: Event) -> (&'static str, String) {
+		// it's still handled, there just isn't anything to replay if that fails
+		let saved = match self.deliveries.save(&delivery).await {
+			Ok(path) => { Some(path) }
+			Err(e) => {
+				warn!("{e}, handling it without keeping it");
+				None
+			}
+		};
+
+		let kind = delivery.kind.clone();
+		match self.handler.handle(&kind, event).await {
+			Ok(done) => {
+				if let Some(path) = &saved {
+					// replaying it again doesn't do anything it didn't already
+					if let Err(e) = self.deliveries.handled(path).await { warn!("{e}") }
+				}
+				("200 OK", done)
+			}
+			Err(e) => {
+				error!("error handling a github {kind} event, it's kept for replay-webhooks: {e}");
+				if let Some(path) = &saved {
+					if let Err(e) = self.deliveries.failed(path, &mut delivery, e.to_string()).await { warn!("{e}") }
+				}
+				("500 Internal Server Error", e.to_string())
+			}
+		}
+	}
+}
+
+impl Handler {
+	/// handles every delivery in `deliveries` that wasn't, returning how many
+	/// were, and how many failed again
+	async fn replay(&self, deliveries: &Deliveries) -> Result<(usize, usize)> {
+		let (mut replayed, mut failed) = (0, 0);
+		for (path, mut delivery) in deliveries.pending().await? {
+			let handled = match serde_json::from_str::<Event>(&delivery.body) {
+				Ok(event) => { self.handle(&delivery.kind, event).await }
+				Err(e) => { Err(format!("can't read the event: {e}").into()) }
+			};
+			match handled {
+				Ok(done) => {
+					info!("replayed the {} event received {}: {}", delivery.kind, delivery.received_at, done.replace('\n', ", "));
+					deliveries.handled(&path).await?;
+					replayed += 1;
+				}
+				Err(e) => {
+					warn!("error replaying the {} event received {}, keeping it: {e}", delivery.kind, delivery.received_at);
+					deliveries.failed(&path, &mut delivery, e.to_string()).await?;
+					failed += 1;
+				}
+			}
+		}
+		Ok((replayed, failed))
+	}
+
+	/// updates the packages the event's issue was filed for, and says what was done
+	async fn handle(&self, kind: &str, event: Event) -> Result<String> {
+		let issue_url = event.issue.html_url;
+		let comment = match (kind, event.action.as_str(), event.comment) {
+			("issue_comment", "created", Some(comment)) => {
+				if self.is_ours(&comment.user).await {
+					return Ok("ignored, it's our own comment".into())
+				}
+				Some(IssueComment { url: comment.html_url, body: comment.body.unwrap_or_default(), created_at: comment.created_at })
+			}
+			("issues", "closed", _) => {
+				// like when rolling back
+				if self.is_ours(&event.sender).await {
+					return Ok("ignored, we closed it".into())
+				}
+				None
+			}
+			_ => { return Ok(format!("ignored, nothing to do for {kind} {}", event.action)) }
+		};
+
+		let db = &self.db;
+		let mut done = Vec::new();
+		// packages sharing an issue are answered for together
+		let mut answered = false;
+		for package in db.packages() {
+			let name = package.info.name;
+			let approval = match package.state {
+				PackageState::IssueFiled(filed) if filed.issue_url == issue_url => {
+					if answered { continue }
+					let replies = match &comment {
+						Some(comment) => {
+							db.add_seen_comment(&name, comment);
+							vec![IssueComment { url: comment.url.clone(), body: comment.body.clone(), created_at: comment.created_at }]
+						}
+						// a yes might have come before it was closed
+						None => {
+							package.comments.iter()
+								.flat_map(|seen| seen.comments.iter())
+								.map(|seen| IssueComment { url: seen.url.clone(), body: seen.body.clone(), created_at: seen.created_at })
+								.collect()
+						}
+					};
+					if let Some(answer) = consent::answer_from_replies(db, &self.config, &name, &replies, comment.is_none()).await? {
+						done.push(format!("{name}: {answer}"));
+						answered = true;
+						continue
+					}
+					if filed.replied_at.is_none() {
+						info!("{issue_url} was {}, cancelling its reminders", if comment.is_some() { "replied to" } else { "closed" });
+						reminders::update_filed(db, &name, None, |filed| filed.replied_at = Some(Utc::now()))?;
+						done.push(format!("{name}: cancelled reminders"));
+					}
+					continue
+				}
+				PackageState::MaintainerApproved(approved) => { approved }
+				PackageState::Migrated(migrated) => { migrated.approval }
+				_ => { continue }
+			};
+			let comment = match &comment {
+				Some(comment) if approval.issue_url.as_deref() == Some(issue_url.as_str()) => { comment }
+				_ => { continue }
+			};
+			db.add_seen_comment(&name, comment);
+			if comment.created_at > approval.approved_at && consent::is_revocation(&comment.body) {
+				info!("package {name}: the maintainer took back their permission in {}", comment.url);
+				consent::revoke(db, &name, comment.url.clone(), comment.created_at)?;
+				done.push(format!("{name}: permission taken back"));
+			}
+		}
+		db.flush();
+
+		Ok(match done.is_empty() {
+			true => { format!("nothing to do for {issue_url}") }
+			false => { done.join("\n") }
+		})
+	}
+
+	/// comments from the bot (or any other bot) aren't replies
+	async fn is_ours(&self, user: &User) -> bool {
+		if user.kind == "Bot" { return true }
+		let github = match &self.github {
+			Some(github) => { github }
+			None => { return false }
+		};
+		match github.login().await {
+			Ok(login) => { login == user.login }
+			Err(e) => {
+				warn!("error checking who the github token is for, taking the comment to be a reply: {e}");
+				false
+			}
+		}
+	}
+}
+
+/// why a delivery was turned away
+#[derive(Clone, Copy)]
+enum Rejected {
+	Unsigned,
+	/// not `sha256=` and hex
+	Malformed,
+	/// signed with some other secret, or changed since it was signed
+	Mismatch
+}
+
+impl Rejected {
+	/// for [`metrics::webhook_rejected`]
+	fn kind(self) -> &'static str {
+		match self {
+			Self::Unsigned => { "unsigned" }
+			Self::Malformed => { "malformed" }
+			Self::Mismatch => { "mismatch" }
+		}
+	}
+
+	fn message(self) -> &'static str {
+		match self {
+			Self::Unsigned => { "no X-Hub-Signature-256, is the webhook's secret set?" }
+			Self::Malformed => { "X-Hub-Signature-256 isn't sha256=<hex>" }
+			Self::Mismatch => { "X-Hub-Signature-256 doesn't match, is the webhook's secret the same as github_webhooks.secret?" }
+		}
+	}
+}
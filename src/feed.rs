@@ -0,0 +1,97 @@
+//! an atom feed of the issues filed and approvals recorded lately, on the api's
+//! `/feed.atom`, for people following the migration to subscribe to rather than
+//! asking how it's going. packages sharing an issue (or an approval) are one
+//! entry between them
+
+use crate::db::{ DatabaseThing, PackageApproved, PackageState };
+use crate::Result;
+use chrono::{ DateTime, Utc };
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// newest first, the rest are left out
+const ENTRIES: usize = 50;
+
+struct Entry {
+	id: String,
+	title: String,
+	link: Option<String>,
+	summary: String,
+	updated: DateTime<Utc>
+}
+
+/// the feed, saying it's at `http://{host}/feed.atom` if there's a `host`
+pub fn atom(db: &DatabaseThing, host: Option<&str>) -> Result<String> {
+	// by issue and by approval, for the packages they're shared between
+	let mut filings = BTreeMap::<String, (DateTime<Utc>, Vec<String>)>::new();
+	let mut approvals = BTreeMap::<String, (PackageApproved, Vec<String>)>::new();
+	for package in db.packages() {
+		let name = package.info.name;
+		for state in package.history.into_iter().map(|transition| transition.state).chain([package.state]) {
+			let approval = match state {
+				PackageState::IssueFiled(filed) => {
+					let names = &mut filings.entry(filed.issue_url).or_insert_with(|| (filed.filed_at, Vec::new())).1;
+					if !names.contains(&name) { names.push(name.clone()) }
+					continue
+				}
+				PackageState::MaintainerApproved(approved) => { approved }
+				PackageState::Migrated(migrated) => { migrated.approval }
+				PackageState::ConsentRevoked(revoked) => { revoked.approval }
+				_ => { continue }
+			};
+			let names = &mut approvals.entry(approval.approval_url.clone()).or_insert_with(|| (approval, Vec::new())).1;
+			if !names.contains(&name) { names.push(name.clone()) }
+		}
+	}
+
+	let mut entries = filings.into_iter()
+		.map(|(issue_url, (filed_at, names))| Entry {
+			id: issue_url.clone(),
+			title: format!("asked for permission to migrate {}", names.join(", ")),
+			summary: format!("an issue was filed asking the maintainer of {} if it can be migrated to pulsar", names.join(", ")),
+			link: Some(issue_url),
+			updated: filed_at
+		})
+		.chain(approvals.into_iter().map(|(approval_url, (approval, names))| {
+			// for emails, it's whatever was given with `approve`
+			let link = (approval_url.starts_with("https://") || approval_url.starts_with("http://")).then(|| approval_url.clone());
+			Entry {
+				id: link.clone().unwrap_or_else(|| format!("urn:pulsar-migrator-issue-bot:approval:{}", names[0])),
+				title: format!("{} can be migrated", names.join(", ")),
+				summary: format!("the maintainer of {} gave permission for it to be migrated to pulsar", names.join(", ")),
+				link: link.or(approval.issue_url),
+				updated: approval.approved_at
+			}
+		}))
+		.collect::<Vec<_>>();
+	entries.sort_by_key(|entry| std::cmp::Reverse(entry.updated));
+	entries.truncate(ENTRIES);
+
+	let self_url = host.map(|host| format!("http://{host}/feed.atom"));
+	let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+	writeln!(out, "\t<id>{}</id>", escape(self_url.as_deref().unwrap_or("urn:pulsar-migrator-issue-bot:feed")))?;
+	out.push_str("\t<title>pulsar package migration</title>\n");
+	out.push_str("\t<subtitle>issues filed asking maintainers if their packages can be migrated to pulsar, and their approvals</subtitle>\n");
+	writeln!(out, "\t<updated>{}</updated>", entries.first().map_or_else(Utc::now, |entry| entry.updated).to_rfc3339())?;
+	out.push_str("\t<author><name>pulsar-migrator-issue-bot</name></author>\n");
+	if let Some(self_url) = &self_url {
+		writeln!(out, "\t<link rel=\"self\" href=\"{}\"/>", escape(self_url))?;
+	}
+	for entry in entries {
+		out.push_str("\t<entry>\n");
+		writeln!(out, "\t\t<id>{}</id>", escape(&entry.id))?;
+		writeln!(out, "\t\t<title>{}</title>", escape(&entry.title))?;
+		writeln!(out, "\t\t<updated>{}</updated>", entry.updated.to_rfc3339())?;
+		if let Some(link) = &entry.link {
+			writeln!(out, "\t\t<link href=\"{}\"/>", escape(link))?;
+		}
+		writeln!(out, "\t\t<summary>{}</summary>", escape(&entry.summary))?;
+		out.push_str("\t</entry>\n");
+	}
+	out.push_str("</feed>\n");
+	Ok(out)
+}
+
+fn escape(s: &str) -> String {
+	s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
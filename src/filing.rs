@@ -0,0 +1,111 @@
+//! filing an issue and recording it, shared by normal runs and publishing drafts
+
+use crate::activity;
+use crate::config::Config;
+use crate::db::{ self, DatabaseThing, PackageState };
+use crate::forge::{ ForgeClient, RepoLocation };
+use crate::reminders;
+use crate::sandbox;
+use crate::Result;
+use chrono::Utc;
+use crate::random;
+use rand::Rng;
+use std::time::Duration;
+use tracing::instrument;
+
+/// how long to wait before filing the next issue, so they don't all go out
+/// in a burst (which looks like spam, to people and to forges)
+pub fn next_delay(config: &Config) -> Duration {
+	let (min, max) = config.filing_delay;
+	Duration::from_secs(random::with(|rng| rng.gen_range(min..=max)))
+}
+
+/// what's filed, from [`Templates::issue`](crate::templates::Templates::issue)
+pub struct Issue<'a> {
+	pub title: &'a str,
+	pub body: &'a str,
+	/// which of `template_variants` it's from, if it is
+	pub template_variant: Option<&'a str>,
+	/// which of `locales.templates` it's from, if it is
+	pub locale: Option<&'a str>
+}
+
+pub struct Filed {
+	pub issue_url: String,
+	/// there was an issue already, so it was recorded rather than filing another
+	pub already_filed: bool
+}
+
+/// files an issue on `repo` for `packages` (which all live in it), unless
+/// there's one already, and records it. on errors, the packages are left for
+/// the caller to record the failure for, with [`crate::retry::record_failure`]
+#[instrument(skip_all, fields(package = %packages.join(", "), owner = %repo.owner))]
+pub async fn file_issue(
+	db: &DatabaseThing,
+	config: &Config,
+	client: &ForgeClient,
+	repo: &RepoLocation,
+	packages: &[String],
+	issue: Issue<'_>
+) -> Result<Filed> {
+	let Issue { title, body, template_variant, locale } = issue;
+	let names_str = packages.join(", ");
+	// drafts can be edited by hand after they were filled in
+	config.templates_for(template_variant, locale).check_issue(repo, title, body)?;
+	sandbox::prepare(config, client, repo).await?;
+
+	// the db can be wrong (eg. an old copy of it), so check there isn't an issue already
+	let existing = client.find_permission_request_issue(repo, &config.issue_titles).await?;
+	let already_filed = existing.is_some();
+	let issue_url = match existing {
+		Some(issue_url) => {
+			info!("{repo} already has an issue for package {names_str}, not filing another: {issue_url}");
+			issue_url
+		}
+		None => {
+			// if this crashes before the state is saved, the next run checks if the issue got filed
+			for name in packages {
+				let attempts = match db.package_state(name) {
+					Some(PackageState::RetryPending(retry)) => { retry.attempts }
+					_ => { 0 }
+				};
+				db.set_state(name, PackageState::IssueFilingInProgress(db::PackageFilingInProgress {
+					repository: repo.clone(),
+					started_at: Utc::now(),
+					attempts,
+					template_variant: template_variant.map(String::from),
+					locale: locale.map(String::from)
+				}))?;
+			}
+			db.flush();
+
+			let issue_url = client.create_permission_request_issue(repo, title, body).await?;
+			info!("filed issue for package {names_str}: {issue_url}");
+			activity::record(db, &format!("filed issue {issue_url} for {}", activity::packages(packages)));
+			issue_url
+		}
+	};
+
+	let filed_at = Utc::now();
+	for name in packages {
+		db.set_state(name, PackageState::IssueFiled(db::PackageIssueFiled {
+			repository: repo.clone(),
+			issue_url: issue_url.clone(),
+			filed_at,
+			grouped_with: packages.iter().filter(|other| *other != name).cloned().collect(),
+			reminders: reminders::schedule(filed_at, config),
+			replied_at: None,
+			// an issue that was there already could've been from any of them
+			template_variant: template_variant.filter(|_| !already_filed).map(String::from),
+			locale: locale.filter(|_| !already_filed).map(String::from)
+		}))?;
+	}
+	// an old cooldown that's run out
+	db.set_repo_cooldown(repo, None);
+	if !already_filed {
+		db.record_filing(filed_at);
+	}
+	db.flush();
+
+	Ok(Filed { issue_url, already_filed })
+}
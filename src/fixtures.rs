@@ -0,0 +1,275 @@
+//! recording what the registry, the forges, and the pulsar backend answer on a
+//! run (`--record <dir>`), and answering with that instead on later runs
+//! (`--replay <dir>`), so something that went wrong can be gone through again
+//! offline, as many times as it takes. requests go through a proxy on
+//! localhost that's started for the run, which everything's pointed at (see
+//! [`Config::use_fixtures`](crate::config::Config::use_fixtures)). every
+//! response is its own file, numbered in the order they came, so they can be
+//! edited to see what the bot makes of something else
+
+use crate::http::{ self, Request };
+use crate::Result;
+use serde::{ Deserialize, Serialize };
+use std::collections::{ BTreeMap, VecDeque };
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, Mutex };
+use tokio::fs;
+use tokio::net::{ TcpListener, TcpStream };
+
+/// bytes, for a request's headers and body together
+const MAX_REQUEST: usize = 16 * 1024 * 1024;
+
+/// headers that are about the connection rather than what's in it, so they're
+/// neither passed on nor recorded
+const CONNECTION_HEADERS: &[&str] = &["host", "connection", "keep-alive", "content-length", "transfer-encoding", "accept-encoding"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+	Record,
+	Replay
+}
+
+/// a request, and what it was answered with
+#[derive(Clone, Deserialize, Serialize)]
+struct Exchange {
+	method: String,
+	url: String,
+	/// only for reading, it's not matched on
+	#[serde(default, skip_serializing_if = "String::is_empty")]
+	request_body: String,
+	status: u16,
+	/// ones there were more than one of are joined with commas
+	headers: BTreeMap<String, String>,
+	/// in hex, if it wasn't text
+	body: String,
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	body_is_hex: bool
+}
+
+enum Recordings {
+	Record { dir: PathBuf, client: reqwest::Client, recorded: usize },
+	/// by method and url, in the order they were recorded. the last one for a
+	/// request keeps being answered with once the others are used up
+	Replay { dir: PathBuf, exchanges: BTreeMap<(String, String), VecDeque<Exchange>> }
+}
+
+/// starts the proxy, in the background for as long as the bot runs, returning
+/// the url it's at
+pub async fn start(mode: Mode, dir: &str) -> Result<String> {
+	let recordings = match mode {
+		Mode::Record => { start_recording(dir).await? }
+		Mode::Replay => { load(dir).await? }
+	};
+	let recordings = Arc::new(Mutex::new(recordings));
+
+	let listener = TcpListener::bind("127.0.0.1:0").await
+		.map_err(|e| format!("error starting the proxy for --record or --replay: {e}"))?;
+	let base = format!("http://{}", listener.local_addr()?);
+	match mode {
+		Mode::Record => { info!("recording the responses to every request to {dir}") }
+		Mode::Replay => { info!("answering every request with the responses recorded in {dir}") }
+	}
+
+	let proxy_base = base.clone();
+	tokio::spawn(async move {
+		loop {
+			let stream = match listener.accept().await {
+				Ok((stream, _)) => { stream }
+				Err(e) => {
+					warn!("error accepting a connection to the proxy: {e}");
+					continue
+				}
+			};
+			let recordings = recordings.clone();
+			let base = proxy_base.clone();
+			tokio::spawn(async move {
+				// not answering, so it fails like the request would have
+				if let Err(e) = respond(stream, &recordings, &base).await {
+					warn!("{e}");
+				}
+			});
+		}
+	});
+	Ok(base)
+}
+
+/// `url`, through the proxy at `base`
+pub fn proxied(base: &str, url: &str) -> String {
+	match url.split_once("://") {
+		Some((scheme, rest)) => { format!("{base}/{scheme}/{rest}") }
+		None => { url.to_string() }
+	}
+}
+
+async fn start_recording(dir: &str) -> Result<Recordings> {
+	fs::create_dir_all(dir).await
+		.map_err(|e| format!("error creating {dir} for recording responses in: {e}"))?;
+	if !fixture_files(dir).await?.is_empty() {
+		return Err(format!("{dir} has recorded responses in it already, record to an empty directory").into())
+	}
+
+	let client = reqwest::Client::builder()
+		// so they're recorded as they came, and the bot follows them through the proxy
+		.redirect(reqwest::redirect::Policy::none())
+		.build()?;
+	Ok(Recordings::Record { dir: dir.into(), client, recorded: 0 })
+}
+
+async fn load(dir: &str) -> Result<Recordings> {
+	let files = fixture_files(dir).await?;
+	if files.is_empty() {
+		return Err(format!("there aren't any recorded responses in {dir}, record some with --record first").into())
+	}
+
+	let mut exchanges = BTreeMap::<_, VecDeque<_>>::new();
+	for file in files {
+		let json = fs::read_to_string(&file).await
+			.map_err(|e| format!("error reading recorded response {}: {e}", file.display()))?;
+		let exchange = serde_json::from_str::<Exchange>(&json)
+			.map_err(|e| format!("error parsing recorded response {}: {e}", file.display()))?;
+		exchanges.entry((exchange.method.clone(), exchange.url.clone()))
+			.or_default()
+			.push_back(exchange);
+	}
+	Ok(Recordings::Replay { dir: dir.into(), exchanges })
+}
+
+/// the recorded responses in `dir`, in the order they were recorded
+async fn fixture_files(dir: &str) -> Result<Vec<PathBuf>> {
+	if !Path::new(dir).exists() { return Ok(Vec::new()) }
+
+	let mut files = Vec::new();
+	let mut entries = fs::read_dir(dir).await
+		.map_err(|e| format!("error reading {dir}: {e}"))?;
+	while let Some(entry) = entries.next_entry().await? {
+		let path = entry.path();
+		if path.extension().map_or(false, |extension| extension == "json") {
+			files.push(path);
+		}
+	}
+	files.sort();
+	Ok(files)
+}
+
+async fn respond(mut stream: TcpStream, recordings: &Mutex<Recordings>, base: &str) -> Result {
+	let request = Request::read(&mut stream, MAX_REQUEST).await?;
+	// `/https/api.github.com/user` is for `https://api.github.com/user`
+	let url = match request.path.trim_start_matches('/').split_once('/') {
+		Some((scheme, rest)) => {
+			let query = request.query.as_ref().map(|query| format!("?{query}")).unwrap_or_default();
+			format!("{scheme}://{rest}{query}")
+		}
+		None => { return http::respond(&mut stream, "404 Not Found", "text/plain", &[], "not a proxied url").await }
+	};
+
+	let replayed = {
+		let mut recordings = recordings.lock().unwrap();
+		match &mut *recordings {
+			Recordings::Record { .. } => { None }
+			Recordings::Replay { dir, exchanges } => {
+				let key = (request.method.clone(), url.clone());
+				let exchange = match exchanges.get_mut(&key) {
+					Some(recorded) if recorded.len() > 1 => { recorded.pop_front() }
+					Some(recorded) => { recorded.front().cloned() }
+					None => { None }
+				};
+				Some(exchange.ok_or_else(|| format!("there's no recorded response to {} {url} in {}", request.method, dir.display())))
+			}
+		}
+	};
+	let exchange = match replayed {
+		Some(Ok(exchange)) => { exchange }
+		Some(Err(message)) => {
+			warn!("{message}");
+			let body = serde_json::json!({ "message": message }).to_string();
+			return http::respond(&mut stream, "502 Bad Gateway", "application/json", &[], &body).await
+		}
+		None => { record(recordings, request, url).await? }
+	};
+
+	let body = match exchange.body_is_hex {
+		true => { http::decode_hex(&exchange.body).ok_or("a recorded response's body isn't hex")? }
+		false => { exchange.body.clone().into_bytes() }
+	};
+	// so where they point is through the proxy too
+	let headers = exchange.headers.iter()
+		.map(|(name, value)| match name.as_str() {
+			"link" => { (name.as_str(), proxied_links(base, value)) }
+			"location" => { (name.as_str(), proxied(base, value)) }
+			_ => { (name.as_str(), value.clone()) }
+		})
+		.collect::<Vec<_>>();
+	let headers = headers.iter().map(|(name, value)| (*name, value.as_str())).collect::<Vec<_>>();
+	let status = reqwest::StatusCode::from_u16(exchange.status)
+		.map_err(|e| format!("a recorded response's status isn't one: {e}"))?;
+	let status = format!("{} {}", status.as_u16(), status.canonical_reason().unwrap_or_default());
+	http::respond_bytes(&mut stream, &status, &headers, &body).await
+}
+
+/// makes the request to where it was going, saving what it was answered with
+async fn record(recordings: &Mutex<Recordings>, request: Request, url: String) -> Result<Exchange> {
+	let client = match &*recordings.lock().unwrap() {
+		Recordings::Record { client, .. } => { client.clone() }
+		Recordings::Replay { .. } => { unreachable!("only recordings are recorded") }
+	};
+	let method = reqwest::Method::from_bytes(request.method.as_bytes())
+		.map_err(|e| format!("error proxying {url}: {e}"))?;
+	let mut req = client.request(method, &url).body(request.body.clone());
+	for (name, value) in request.headers.iter().filter(|(name, _)| !CONNECTION_HEADERS.contains(&name.as_str())) {
+		req = req.header(name, value);
+	}
+	let res = req.send().await
+		.map_err(|e| format!("error proxying {} {url}: {e}", request.method))?;
+
+	let status = res.status().as_u16();
+	let mut headers = BTreeMap::<String, String>::new();
+	for (name, value) in res.headers().iter().filter(|(name, _)| !CONNECTION_HEADERS.contains(&name.as_str())) {
+		let value = match value.to_str() {
+			Ok(value) => { value }
+			Err(_) => { continue }
+		};
+		headers.entry(name.to_string())
+			.and_modify(|values| *values = format!("{values}, {value}"))
+			.or_insert_with(|| value.to_string());
+	}
+	let bytes = res.bytes().await
+		.map_err(|e| format!("error proxying {} {url}: {e}", request.method))?;
+	let (body, body_is_hex) = match String::from_utf8(bytes.to_vec()) {
+		Ok(body) => { (body, false) }
+		Err(_) => { (http::encode_hex(&bytes), true) }
+	};
+	let exchange = Exchange {
+		method: request.method,
+		url,
+		request_body: String::from_utf8_lossy(&request.body).into_owned(),
+		status,
+		headers,
+		body,
+		body_is_hex
+	};
+
+	let path = match &mut *recordings.lock().unwrap() {
+		Recordings::Record { dir, recorded, .. } => {
+			*recorded += 1;
+			let host = exchange.url.split('/').nth(2).unwrap_or_default().replace(':', "_");
+			dir.join(format!("{:05}-{}-{host}.json", *recorded, exchange.method.to_lowercase()))
+		}
+		Recordings::Replay { .. } => { unreachable!("only recordings are recorded") }
+	};
+	fs::write(&path, serde_json::to_string_pretty(&exchange)?).await
+		.map_err(|e| format!("error saving recorded response {}: {e}", path.display()))?;
+	Ok(exchange)
+}
+
+/// a `Link` header (like `<https://...?page=2>; rel="next"`), with its urls through the proxy
+fn proxied_links(base: &str, links: &str) -> String {
+	links.split(',')
+		.map(|link| match (link.find('<'), link.find('>')) {
+			(Some(start), Some(end)) if start < end => {
+				format!("{}<{}>{}", &link[..start], proxied(base, &link[start + 1..end]), &link[end + 1..])
+			}
+			_ => { link.to_string() }
+		})
+		.collect::<Vec<_>>()
+		.join(",")
+}
@@ -0,0 +1,446 @@
+//! forges (github, gitlab, etc) that package repositories live on, and
+//! figuring out which one a repository url points to
+
+use crate::config::Config;
+use crate::db::{ PackageNew, RepoActivity };
+use crate::github::GithubClient;
+use crate::gitlab::GitlabClient;
+use crate::health;
+use crate::metrics;
+use crate::repo_url;
+use crate::simulated::SimulatedClient;
+use crate::templates::IssueTitles;
+use crate::usage;
+use crate::error::Failure;
+use crate::{ Error, Result };
+use chrono::{ DateTime, Utc };
+use serde::{ Deserialize, Serialize };
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::time::Instant;
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum ForgeKind {
+	GitHub,
+	GitLab
+}
+
+impl ForgeKind {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match &*name.to_lowercase() {
+			"github" => { Some(Self::GitHub) }
+			"gitlab" => { Some(Self::GitLab) }
+			_ => { None }
+		}
+	}
+}
+
+/// how a host's token is sent, see `forges.auth`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ForgeAuth {
+	/// like the forge takes personal (or project, or group) access tokens
+	Token,
+	/// as an oauth access token, `Authorization: Bearer`. github takes those like
+	/// any other token, so it's only different for gitlab
+	OAuth
+}
+
+impl ForgeAuth {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match &*name.to_lowercase() {
+			"token" => { Some(Self::Token) }
+			"oauth" => { Some(Self::OAuth) }
+			_ => { None }
+		}
+	}
+}
+
+impl fmt::Display for ForgeKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::GitHub => { f.write_str("github") }
+			Self::GitLab => { f.write_str("gitlab") }
+		}
+	}
+}
+
+/// where a repository lives
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RepoLocation {
+	pub host: String,
+	/// user, org, or for gitlab, group (including subgroups, separated with `/`)
+	pub owner: String,
+	pub name: String
+}
+
+impl RepoLocation {
+	/// where a repository url from a package.json file points, see [`repo_url::parse`]
+	pub fn parse(url: &str) -> Option<Self> {
+		let (host, owner, name) = repo_url::parse(url)?;
+		Some(Self { host, owner, name })
+	}
+
+	/// which forge this repository is (probably) on
+	pub fn forge(&self, config: &Config) -> Option<ForgeKind> {
+		forge_for_host(&self.host, config)
+	}
+
+	/// for telling if two urls point to the same repository. forges treat
+	/// owner and repository names case insensitively
+	pub fn key(&self) -> String {
+		self.to_string().to_lowercase()
+	}
+}
+
+/// which forge runs on `host` (probably)
+pub fn forge_for_host(host: &str, config: &Config) -> Option<ForgeKind> {
+	if let Some(kind) = config.forge_hosts.get(host) {
+		return Some(*kind)
+	}
+
+	match host {
+		"github.com" | "www.github.com" => { return Some(ForgeKind::GitHub) }
+		"gitlab.com" | "www.gitlab.com" => { return Some(ForgeKind::GitLab) }
+		_ => {}
+	}
+
+	if repo_url::is_gitlab(host) {
+		return Some(ForgeKind::GitLab)
+	}
+
+	None
+}
+
+impl fmt::Display for RepoLocation {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}/{}/{}", self.host, self.owner, self.name)
+	}
+}
+
+/// how a package is going to be contacted
+pub enum ContactRoute {
+	/// file an issue on the forge the repository is on
+	Forge(RepoLocation, ForgeKind),
+	/// the repository is on a forge we support, but it's been turned off
+	ForgeDisabled(RepoLocation, ForgeKind),
+	/// the repository isn't on a supported forge, email the author instead
+	Email(String),
+	/// can't do anything for this package (unsupported host, and no email or email fallback is off)
+	Unsupported
+}
+
+impl ContactRoute {
+	pub fn for_package(package: &PackageNew, config: &Config) -> Self {
+		let repo = RepoLocation::parse(&package.repository.url);
+		let forge = repo.as_ref().and_then(|repo| repo.forge(config));
+
+		if let (Some(repo), Some(forge)) = (repo, forge) {
+			if config.disabled_forges.contains(&forge) {
+				return Self::ForgeDisabled(repo, forge)
+			}
+			return Self::Forge(repo, forge)
+		}
+
+		match package.contact_email() {
+			Some(email) if config.email_fallback => { Self::Email(email.into()) }
+			_ => { Self::Unsupported }
+		}
+	}
+}
+
+/// what's happened on an issue since it was filed
+pub struct IssueActivity {
+	/// when the issue was opened
+	pub opened_at: DateTime<Utc>,
+	pub closed: bool,
+	/// someone other than us commented
+	pub replied: bool,
+	/// comments from everyone but us, oldest first
+	pub comments: Vec<IssueComment>
+}
+
+#[derive(Clone)]
+pub struct RateLimit {
+	pub limit: u32,
+	pub remaining: u32,
+	pub resets_at: DateTime<Utc>
+}
+
+pub struct IssueComment {
+	pub url: String,
+	pub body: String,
+	pub created_at: DateTime<Utc>
+}
+
+/// the issue number at the end of an issue's url, for github
+/// (`.../issues/12`) and gitlab (`.../-/issues/12`) alike
+pub fn issue_number(issue_url: &str) -> Option<u64> {
+	issue_url.trim_end_matches('/').rsplit('/').next()?.parse().ok()
+}
+
+pub enum ForgeClient {
+	GitHub(GithubClient),
+	GitLab(GitlabClient),
+	/// see [`Config::simulate_forges`]
+	Simulated(SimulatedClient)
+}
+
+impl ForgeClient {
+	/// what it's called in metrics
+	fn api_name(&self) -> &'static str {
+		match self {
+			Self::GitHub(_) => { "github" }
+			Self::GitLab(_) => { "gitlab" }
+			Self::Simulated(_) => { "simulated" }
+		}
+	}
+
+	/// waits for `request`, recording how long it took for [`metrics`], and
+	/// counting it for [`usage`]
+	async fn timed<T>(&self, endpoint: &'static str, request: impl Future<Output = Result<T>>) -> Result<T> {
+		usage::count(self.api_name(), endpoint);
+		let started = Instant::now();
+		// so it's known why it failed, however it did
+		let res = request.await.map_err(Error::forge);
+		metrics::observe_latency(self.api_name(), endpoint, started.elapsed());
+		// not found is still the forge answering
+		health::request_finished(res.as_ref().map_or_else(|e| e.is_not_found(), |_| true));
+		if let Err(e) = &res {
+			// whatever it was for says what went wrong, this is for what request it was
+			debug!(api = self.api_name(), endpoint, "request failed: {e}");
+		}
+		res
+	}
+
+	/// files the issue, usually from [`Templates::issue`](crate::templates::Templates::issue)
+	pub async fn create_permission_request_issue(&self, repo: &RepoLocation, title: &str, body: &str) -> Result<String> {
+		self.timed("create_issue", async {
+			match self {
+				Self::GitHub(client) => { client.create_permission_request_issue(&repo.owner, &repo.name, title, body).await }
+				Self::GitLab(client) => { client.create_permission_request_issue(&repo.owner, &repo.name, title, body).await }
+				Self::Simulated(client) => { client.create_permission_request_issue(&repo.owner, &repo.name, title, body).await }
+			}
+		}).await
+	}
+
+	/// files the issue `repo` would get on `canary` instead, to check that
+	/// filing works (and looks right) before doing it for real
+	pub async fn create_canary_issue(&self, canary: &RepoLocation, repo: &RepoLocation, title: &str, body: &str) -> Result<String> {
+		let title = format!("[canary for {repo}] {title}");
+		self.timed("create_canary_issue", async {
+			match self {
+				Self::GitHub(client) => { client.create_permission_request_issue(&canary.owner, &canary.name, &title, body).await }
+				Self::GitLab(client) => { client.create_permission_request_issue(&canary.owner, &canary.name, &title, body).await }
+				Self::Simulated(client) => { client.create_permission_request_issue(&canary.owner, &canary.name, &title, body).await }
+			}
+		}).await
+	}
+
+	/// makes `repo` in its organisation, to file in with `--sandbox`, if it isn't
+	/// there already. returns whether it was made
+	pub async fn create_sandbox_repo(&self, repo: &RepoLocation) -> Result<bool> {
+		self.timed("create_sandbox_repo", async {
+			match self {
+				Self::GitHub(client) => { client.create_repo_if_missing(&repo.owner, &repo.name).await }
+				Self::GitLab(_) => { Err(format!("sandbox repositories can only be made on github, not {}", repo.host).into()) }
+				Self::Simulated(client) => { client.maybe_fail().await.map(|_| false) }
+			}
+		}).await
+	}
+
+	pub async fn comment_on_issue(&self, repo: &RepoLocation, number: u64, body: &str) -> Result {
+		self.timed("comment", async {
+			match self {
+				Self::GitHub(client) => { client.comment_on_issue(&repo.owner, &repo.name, number, body).await }
+				Self::GitLab(client) => { client.comment_on_issue(&repo.owner, &repo.name, number, body).await }
+				Self::Simulated(client) => { client.comment_on_issue(&repo.owner, &repo.name, number, body).await }
+			}
+		}).await
+	}
+
+	pub async fn close_issue(&self, repo: &RepoLocation, number: u64) -> Result {
+		self.timed("close_issue", async {
+			match self {
+				Self::GitHub(client) => { client.close_issue(&repo.owner, &repo.name, number).await }
+				Self::GitLab(client) => { client.close_issue(&repo.owner, &repo.name, number).await }
+				Self::Simulated(client) => { client.close_issue(&repo.owner, &repo.name, number).await }
+			}
+		}).await
+	}
+
+	pub async fn issue_activity(&self, repo: &RepoLocation, number: u64) -> Result<IssueActivity> {
+		self.timed("issue_activity", async {
+			match self {
+				Self::GitHub(client) => { client.issue_activity(&repo.owner, &repo.name, number).await }
+				Self::GitLab(client) => { client.issue_activity(&repo.owner, &repo.name, number).await }
+				Self::Simulated(client) => { client.issue_activity(&repo.owner, &repo.name, number).await }
+			}
+		}).await
+	}
+
+	/// url of a permission request issue (one with one of `titles`) already filed on `repo`, if there is one
+	pub async fn find_permission_request_issue(&self, repo: &RepoLocation, titles: &IssueTitles) -> Result<Option<String>> {
+		self.timed("find_issue", async {
+			match self {
+				Self::GitHub(client) => { client.find_issue(&repo.owner, &repo.name, titles).await }
+				Self::GitLab(client) => { client.find_issue(&repo.owner, &repo.name, titles).await }
+				Self::Simulated(client) => { client.find_issue(&repo.owner, &repo.name, titles).await }
+			}
+		}).await
+	}
+
+	/// the account the token is for, which checks that it works
+	pub async fn whoami(&self) -> Result<String> {
+		self.timed("user", async {
+			match self {
+				Self::GitHub(client) => { client.login().await.map(String::from) }
+				Self::GitLab(client) => { client.username().await.map(String::from) }
+				Self::Simulated(client) => { client.maybe_fail().await.map(|_| "simulated".into()) }
+			}
+		}).await
+	}
+
+	/// how many api requests are left, if the forge says
+	pub async fn rate_limit(&self) -> Result<Option<RateLimit>> {
+		match self {
+			Self::GitHub(client) => { client.rate_limit().await.map(Some) }
+			// gitlab only says in the headers of each response
+			Self::GitLab(_) => { Ok(None) }
+			Self::Simulated(_) => { Ok(None) }
+		}
+	}
+
+	/// the repository's license, see [`crate::db::RepoLicense`]
+	pub async fn license(&self, repo: &RepoLocation) -> Result<Option<String>> {
+		self.timed("license", async {
+			match self {
+				Self::GitHub(client) => { client.license(&repo.owner, &repo.name).await }
+				Self::GitLab(client) => { client.license(&repo.owner, &repo.name).await }
+				// every simulated repository has the most common one
+				Self::Simulated(client) => { client.maybe_fail().await.map(|_| Some("MIT".into())) }
+			}
+		}).await
+	}
+
+	/// how lately the repository's been pushed to and released, see [`RepoActivity`]
+	pub async fn activity(&self, repo: &RepoLocation) -> Result<RepoActivity> {
+		self.timed("activity", async {
+			match self {
+				Self::GitHub(client) => { client.activity(&repo.owner, &repo.name).await }
+				Self::GitLab(client) => { client.activity(&repo.owner, &repo.name).await }
+				// simulated repositories are all still being worked on, without releasing
+				Self::Simulated(client) => {
+					client.maybe_fail().await?;
+					Ok(RepoActivity { pushed_at: Some(Utc::now()), latest_release: None, checked_at: Utc::now() })
+				}
+			}
+		}).await
+	}
+
+	/// fetches a file from the repository's default branch, or `None` if it doesn't exist
+	pub async fn get_file(&self, repo: &RepoLocation, path: &str) -> Result<Option<String>> {
+		self.timed("get_file", async {
+			match self {
+				Self::GitHub(client) => { client.get_file(&repo.owner, &repo.name, path).await }
+				Self::GitLab(client) => { client.get_file(&repo.owner, &repo.name, path).await }
+				// simulated repositories don't have any files
+				Self::Simulated(client) => { client.maybe_fail().await.map(|_| None) }
+			}
+		}).await
+	}
+}
+
+/// clients for every host we've needed so far, created lazily with the
+/// right credentials for that host
+pub struct Forges {
+	config: Config,
+	/// or why one couldn't be created for that host
+	clients: HashMap<String, std::result::Result<ForgeClient, String>>
+}
+
+impl Forges {
+	pub fn new(config: Config) -> Self {
+		Self { config, clients: HashMap::new() }
+	}
+
+	pub fn config(&self) -> &Config {
+		&self.config
+	}
+
+	/// swaps the config for a reloaded one. clients are set up again when they're
+	/// next needed, in case what host is which forge changed
+	pub fn set_config(&mut self, config: Config) {
+		self.config = config;
+		self.clients.clear();
+	}
+
+	pub fn client_for(&mut self, repo: &RepoLocation) -> Result<&ForgeClient> {
+		self.client_for_host(&repo.host)
+	}
+
+	pub fn client_for_host(&mut self, host: &str) -> Result<&ForgeClient> {
+		if !self.clients.contains_key(host) {
+			let client = self.create_client(host).map_err(|e| e.to_string());
+			self.clients.insert(host.into(), client);
+		}
+
+		match &self.clients[host] {
+			Ok(client) => { Ok(client) }
+			Err(e) => { Err(e.clone().into()) }
+		}
+	}
+
+	/// the client for a repository's host, if it's been created already with [`client_for`](Self::client_for)
+	pub fn client(&self, repo: &RepoLocation) -> Result<&ForgeClient> {
+		self.created_client_for_host(&repo.host)
+	}
+
+	/// like [`client`](Self::client), for a host
+	pub fn created_client_for_host(&self, host: &str) -> Result<&ForgeClient> {
+		match self.clients.get(host) {
+			Some(Ok(client)) => { Ok(client) }
+			Some(Err(e)) => { Err(e.clone().into()) }
+			None => { Err(format!("no client set up for host {host}").into()) }
+		}
+	}
+
+	/// checks the token for every forge that has one (see [`Config::token_hosts`])
+	/// works, before anything's contacted. one being turned down is an error,
+	/// since everything on that host would fail, but not being able to check
+	/// (like being offline) is left for the run to deal with
+	pub async fn check_tokens(&mut self) -> Result {
+		if !self.config.check_tokens || self.config.simulate_forges.is_some() { return Ok(()) }
+
+		for (host, kind) in self.config.token_hosts() {
+			let client = self.client_for_host(&host)?;
+			match client.whoami().await {
+				Ok(login) => { debug!("the {host} token ({kind}) is for {login}") }
+				Err(e) if e.failure() == Failure::Permanent => {
+					return Err(Error::Config(format!("the token for {host} ({kind}) was turned down, fix it or leave it out of the config: {e}")))
+				}
+				Err(e) => { warn!("couldn't check the token for {host} ({kind}), carrying on anyway: {e}") }
+			}
+		}
+		Ok(())
+	}
+
+	fn create_client(&self, host: &str) -> Result<ForgeClient> {
+		let kind = forge_for_host(host, &self.config)
+			.ok_or_else(|| format!("host {host} is not a supported forge"))?;
+		if let Some(failure_rate) = self.config.simulate_forges {
+			return Ok(ForgeClient::Simulated(SimulatedClient::new(host, failure_rate, &self.config.simulation)))
+		}
+		let token = self.config.token_for(host, kind)
+			.ok_or_else(|| format!("no token configured for host {host} ({kind})"))?;
+
+		let api_url = self.config.forge_api_url(host, kind);
+		Ok(match kind {
+			ForgeKind::GitHub => { ForgeClient::GitHub(GithubClient::with_api_url(host, token, api_url.as_deref())?) }
+			ForgeKind::GitLab => {
+				let client = GitlabClient::with_api_url(host, token, api_url.as_deref())?;
+				ForgeClient::GitLab(client.with_auth(self.config.auth_for(host)))
+			}
+		})
+	}
+}
@@ -0,0 +1,83 @@
+//! deleting what's stored about a maintainer, for ones who ask us to. their
+//! packages keep their states (so nobody's asked again as if nothing had
+//! happened), but their names and email addresses, the package.json authors,
+//! the comments seen on their issues, and the emails written to them are gone,
+//! and aren't stored again when their packages are synced
+
+use crate::db::{ DatabaseThing, Package, PackageState, FORGOTTEN };
+use crate::Result;
+use tokio::fs;
+
+pub async fn run(db: &DatabaseThing, owner: &str, dry_run: bool) -> Result {
+	let owner = owner.trim().trim_start_matches('@');
+	if owner.is_empty() {
+		return Err("forget needs the login of the owner to forget".into())
+	}
+
+	let packages = db.packages().into_iter()
+		.filter(|package| package.info.is_owned_by(owner))
+		.collect::<Vec<_>>();
+	for package in packages.iter() {
+		let stored = stored_details(package);
+		match stored.is_empty() {
+			true => { println!("{}: nothing personal stored", package.info.name) }
+			false => { println!("{}: {}", package.info.name, stored.join(", ")) }
+		}
+	}
+
+	if dry_run {
+		println!("dry run, nothing deleted");
+		return Ok(())
+	}
+
+	let (_, email_files) = db.forget_owner(owner);
+	db.flush();
+	for file in email_files {
+		match fs::remove_file(&file).await {
+			Ok(()) => { println!("deleted {file}") }
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+			Err(e) => { warn!("error deleting {file}, it'll need deleting by hand: {e}") }
+		}
+	}
+
+	match packages.len() {
+		0 => { println!("no packages from {owner} in the database, their details won't be stored if any are synced") }
+		count => { println!("forgot {owner}'s details on {count} packages") }
+	}
+	println!("backups of the database still have them, until they're replaced by newer ones");
+
+	Ok(())
+}
+
+/// what personal details are stored with a package, for printing
+fn stored_details(package: &Package) -> Vec<String> {
+	let mut stored = Vec::new();
+	if package.info.metadata.author.is_some() {
+		stored.push("package.json author".to_string());
+	}
+	if let Some(owner) = &package.info.owner {
+		if owner.name.is_some() { stored.push("owner's name".into()) }
+		if owner.email.is_some() { stored.push("owner's email".into()) }
+	}
+	let comments = package.comments.as_ref().map_or(0, |seen| seen.comments.len());
+	if comments > 0 {
+		stored.push(format!("{comments} comments"));
+	}
+
+	let emailed = std::iter::once(&package.state)
+		.chain(package.history.iter().map(|transition| &transition.state))
+		.any(|state| {
+			let email = match state {
+				PackageState::ContactedByEmail(contacted) => { Some(contacted.email.as_str()) }
+				PackageState::MaintainerApproved(approval) => { approval.email.as_deref() }
+				PackageState::Migrated(migrated) => { migrated.approval.email.as_deref() }
+				PackageState::ConsentRevoked(revoked) => { revoked.approval.email.as_deref() }
+				_ => { None }
+			};
+			email.map_or(false, |email| email != FORGOTTEN)
+		});
+	if emailed {
+		stored.push("email address contacted at".into());
+	}
+	stored
+}
@@ -1,42 +1,258 @@
 //! github client used to access github's api
 
-use crate::db::DatabaseThing;
+use crate::db::{ RepoActivity, RepoRelease };
+use crate::forge::{ IssueActivity, IssueComment, RateLimit };
+use crate::templates::IssueTitles;
+use chrono::{ DateTime, TimeZone, Utc };
+use octocrab::models::IssueState;
+use octocrab::models::repos::Content;
 use octocrab::Octocrab;
-use std::sync::Mutex;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tokio::sync::OnceCell;
 
-lazy_static! {
-	static ref ISSUE_CONTENT: (String, String) = {
-		let plain = include_str!("../resources/issue_template.md");
-		let (title, body) = plain.split_once("\n\n").unwrap();
-		(title.into(), body.into())
-	};
+#[derive(Deserialize)]
+struct RateLimitResponse {
+	resources: RateLimitResources
+}
+
+#[derive(Deserialize)]
+struct RateLimitResources {
+	core: RateLimitCore
+}
+
+#[derive(Deserialize)]
+struct RateLimitCore {
+	limit: u32,
+	remaining: u32,
+	/// unix timestamp
+	reset: i64
+}
+
+#[derive(Deserialize)]
+struct User {
+	login: String
+}
+
+#[derive(Deserialize)]
+struct RepoLicense {
+	license: Option<LicenseInfo>
+}
+
+#[derive(Deserialize)]
+struct LicenseInfo {
+	spdx_id: Option<String>
+}
+
+#[derive(Deserialize)]
+struct Repo {
+	pushed_at: Option<DateTime<Utc>>
+}
+
+#[derive(Deserialize)]
+struct Release {
+	tag_name: String,
+	published_at: Option<DateTime<Utc>>
 }
 
 pub struct GithubClient {
-	octocrab: Octocrab
+	octocrab: Octocrab,
+	/// the account the token is for, fetched the first time it's needed
+	login: OnceCell<String>
 }
 
 impl GithubClient {
-	pub fn new(token: &str, db: DatabaseThing) -> crate::Result<Self> {
-		let octocrab = Octocrab::builder()
+	pub fn new(host: &str, token: &str) -> crate::Result<Self> {
+		Self::with_api_url(host, token, None)
+	}
+
+	/// with the api somewhere else than usual, see `forges.api_urls`
+	pub fn with_api_url(host: &str, token: &str, api_url: Option<&str>) -> crate::Result<Self> {
+		let api_url = api_url.map_or_else(|| Self::default_api_url(host), String::from);
+		let builder = Octocrab::builder()
 			.personal_token(token.into())
-			.build()?;
+			.base_url(api_url)?;
+
+		let octocrab = builder.build()?;
+		Ok(Self { octocrab, login: OnceCell::new() })
+	}
 
-		Ok(Self { octocrab })
+	/// where `host`'s api is
+	pub fn default_api_url(host: &str) -> String {
+		match host {
+			"github.com" | "www.github.com" => { "https://api.github.com/".into() }
+			// github enterprise instances have their api under /api/v3
+			host => { format!("https://{host}/api/v3/") }
+		}
 	}
 
 	pub async fn create_permission_request_issue(
 		&self,
 		owner: &str,
-		repo: &str
+		repo: &str,
+		title: &str,
+		body: &str
 	) -> crate::Result<String> {
-		let (title, body) = &*ISSUE_CONTENT;
-
 		let req = self.octocrab.issues(owner, repo)
 			.create(title)
 			.body(body)
 			.send().await?;
 
-		Ok(req.url.to_string())
+		Ok(req.html_url.to_string())
+	}
+
+	/// makes a private repository `repo` in the organisation `org`, unless there's
+	/// one already. returns whether it was made
+	pub async fn create_repo_if_missing(&self, org: &str, repo: &str) -> crate::Result<bool> {
+		let url = self.octocrab.absolute_url(format!("repos/{org}/{repo}"))?;
+		let res = self.octocrab._get(url, None::<&()>).await?;
+		if res.status() != StatusCode::NOT_FOUND {
+			octocrab::map_github_error(res).await?;
+			return Ok(false)
+		}
+
+		let body = serde_json::json!({
+			"name": repo,
+			"description": "for trying out the pulsar migrator issue bot, see its --sandbox",
+			"private": true,
+			"has_issues": true
+		});
+		self.octocrab.post::<_, serde_json::Value>(format!("orgs/{org}/repos"), Some(&body)).await?;
+		Ok(true)
+	}
+
+	pub async fn comment_on_issue(&self, owner: &str, repo: &str, number: u64, body: &str) -> crate::Result {
+		self.octocrab.issues(owner, repo)
+			.create_comment(number, body).await?;
+		Ok(())
+	}
+
+	pub async fn close_issue(&self, owner: &str, repo: &str, number: u64) -> crate::Result {
+		self.octocrab.issues(owner, repo)
+			.update(number)
+			.state(IssueState::Closed)
+			.send().await?;
+		Ok(())
+	}
+
+	/// how much of the api rate limit is left (checking doesn't use any up)
+	pub async fn rate_limit(&self) -> crate::Result<RateLimit> {
+		let res = self.octocrab.get::<RateLimitResponse, _, ()>("rate_limit", None).await?;
+		let core = res.resources.core;
+		Ok(RateLimit {
+			limit: core.limit,
+			remaining: core.remaining,
+			resets_at: Utc.timestamp(core.reset, 0)
+		})
+	}
+
+	/// the account the token is for, and its scopes. fine-grained tokens
+	/// don't have scopes, so those are `None`
+	pub async fn token_info(&self) -> crate::Result<(String, Option<Vec<String>>)> {
+		let url = self.octocrab.absolute_url("user")?;
+		let res = self.octocrab._get(url, None::<&()>).await?;
+		let res = octocrab::map_github_error(res).await?;
+
+		let scopes = res.headers()
+			.get("x-oauth-scopes")
+			.and_then(|value| value.to_str().ok())
+			.map(|value| value.split(',')
+				.map(|scope| scope.trim().to_string())
+				.filter(|scope| !scope.is_empty())
+				.collect());
+		let user = res.json::<User>().await?;
+		Ok((user.login, scopes))
+	}
+
+	/// whether anyone other than us commented on an issue, and if it's closed
+	/// the account the token is for
+	pub async fn login(&self) -> crate::Result<&str> {
+		let login = self.login.get_or_try_init(|| async {
+			self.octocrab.current().user().await.map(|user| user.login)
+		}).await?;
+		Ok(login)
+	}
+
+	pub async fn issue_activity(&self, owner: &str, repo: &str, number: u64) -> crate::Result<IssueActivity> {
+		let login = self.login().await?;
+
+		let issue = self.octocrab.issues(owner, repo).get(number).await?;
+		let comments = self.octocrab.issues(owner, repo)
+			.list_comments(number)
+			.per_page(100)
+			.send().await?;
+
+		let comments = comments.items.into_iter()
+			.filter(|comment| comment.user.login != login)
+			.map(|comment| IssueComment {
+				url: comment.html_url.to_string(),
+				body: comment.body.unwrap_or_default(),
+				created_at: comment.created_at
+			})
+			.collect::<Vec<_>>();
+
+		Ok(IssueActivity {
+			opened_at: issue.created_at,
+			closed: issue.state == "closed",
+			replied: !comments.is_empty(),
+			comments
+		})
+	}
+
+	/// url of the most recent issue in the repository with this title, if there is one
+	pub async fn find_issue(&self, owner: &str, repo: &str, titles: &IssueTitles) -> crate::Result<Option<String>> {
+		// newest first, so one page is plenty for finding an issue that was just filed
+		let page = self.octocrab.issues(owner, repo)
+			.list()
+			.state(octocrab::params::State::All)
+			.per_page(100)
+			.send().await?;
+
+		Ok(page.items.into_iter()
+			.find(|issue| titles.matches(&issue.title))
+			.map(|issue| issue.html_url.to_string()))
+	}
+
+	/// the spdx id of the repository's license, or `None` if it doesn't have one
+	pub async fn license(&self, owner: &str, repo: &str) -> crate::Result<Option<String>> {
+		let url = self.octocrab.absolute_url(format!("repos/{owner}/{repo}/license"))?;
+		let res = self.octocrab._get(url, None::<&()>).await?;
+
+		if res.status() == StatusCode::NOT_FOUND { return Ok(None) }
+		let res = octocrab::map_github_error(res).await?;
+		// it's NOASSERTION for ones github didn't recognise
+		Ok(res.json::<RepoLicense>().await?.license.and_then(|license| license.spdx_id))
+	}
+
+	/// when the repository was last pushed to, and its latest release
+	pub async fn activity(&self, owner: &str, repo: &str) -> crate::Result<RepoActivity> {
+		let url = self.octocrab.absolute_url(format!("repos/{owner}/{repo}"))?;
+		let res = octocrab::map_github_error(self.octocrab._get(url, None::<&()>).await?).await?;
+		let pushed_at = res.json::<Repo>().await?.pushed_at;
+
+		// drafts and prereleases aren't the latest, so it's not found if that's all there are
+		let url = self.octocrab.absolute_url(format!("repos/{owner}/{repo}/releases/latest"))?;
+		let res = self.octocrab._get(url, None::<&()>).await?;
+		let latest_release = match res.status() {
+			StatusCode::NOT_FOUND => { None }
+			_ => {
+				let release = octocrab::map_github_error(res).await?.json::<Release>().await?;
+				Some(RepoRelease { tag: release.tag_name, released_at: release.published_at })
+			}
+		};
+
+		Ok(RepoActivity { pushed_at, latest_release, checked_at: Utc::now() })
+	}
+
+	/// fetches a file from the default branch of a repository, or `None` if it doesn't exist
+	pub async fn get_file(&self, owner: &str, repo: &str, path: &str) -> crate::Result<Option<String>> {
+		let url = self.octocrab.absolute_url(format!("repos/{owner}/{repo}/contents/{path}"))?;
+		let res = self.octocrab._get(url, None::<&()>).await?;
+
+		if res.status() == StatusCode::NOT_FOUND { return Ok(None) }
+		let res = octocrab::map_github_error(res).await?;
+
+		let content = res.json::<Content>().await?;
+		Ok(content.decoded_content())
 	}
 }
@@ -0,0 +1,299 @@
+//! gitlab client used to access gitlab's api, for gitlab.com as well as self hosted instances
+
+use crate::db::{ RepoActivity, RepoRelease };
+use crate::forge::{ ForgeAuth, IssueActivity, IssueComment };
+use crate::templates::IssueTitles;
+use chrono::{ DateTime, Utc };
+use reqwest::{ Client, StatusCode };
+use serde::Deserialize;
+use tokio::sync::OnceCell;
+
+pub struct GitlabClient {
+	client: Client,
+	api_base: String,
+	/// `PRIVATE-TOKEN`, or `Authorization` for oauth tokens, see [`ForgeAuth`]
+	auth_header: &'static str,
+	/// what's sent in it
+	token: String,
+	/// the account the token is for, fetched the first time it's needed
+	username: OnceCell<String>
+}
+
+#[derive(Deserialize)]
+struct CreatedIssue {
+	web_url: String
+}
+
+#[derive(Deserialize)]
+struct User {
+	username: String
+}
+
+#[derive(Deserialize)]
+struct Note {
+	id: u64,
+	body: String,
+	created_at: DateTime<Utc>,
+	author: User,
+	/// notes gitlab adds itself, like "changed the description"
+	system: bool
+}
+
+#[derive(Deserialize)]
+struct IssueState {
+	state: String,
+	web_url: String,
+	created_at: DateTime<Utc>
+}
+
+#[derive(Deserialize)]
+struct ListedIssue {
+	title: String,
+	web_url: String
+}
+
+#[derive(Deserialize)]
+struct Project {
+	default_branch: Option<String>,
+	/// only there when asked for
+	#[serde(default)]
+	license: Option<License>
+}
+
+#[derive(Deserialize)]
+struct License {
+	key: String
+}
+
+#[derive(Deserialize)]
+struct Commit {
+	committed_date: DateTime<Utc>
+}
+
+#[derive(Deserialize)]
+struct Release {
+	tag_name: String,
+	released_at: Option<DateTime<Utc>>
+}
+
+impl GitlabClient {
+	pub fn new(host: &str, token: &str) -> crate::Result<Self> {
+		Self::with_api_url(host, token, None)
+	}
+
+	/// with the api somewhere else than usual, see `forges.api_urls`
+	pub fn with_api_url(host: &str, token: &str, api_url: Option<&str>) -> crate::Result<Self> {
+		let client = Client::builder()
+			.user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+			.build()?;
+
+		Ok(Self {
+			client,
+			api_base: api_url.map_or_else(|| Self::default_api_url(host), String::from).trim_end_matches('/').to_string(),
+			auth_header: "PRIVATE-TOKEN",
+			token: token.into(),
+			username: OnceCell::new()
+		})
+	}
+
+	/// sending the token like `auth` says instead of as a personal access token
+	pub fn with_auth(mut self, auth: ForgeAuth) -> Self {
+		if auth == ForgeAuth::OAuth {
+			self.auth_header = "Authorization";
+			self.token = format!("Bearer {}", self.token);
+		}
+		self
+	}
+
+	/// where `host`'s api is
+	pub fn default_api_url(host: &str) -> String {
+		format!("https://{host}/api/v4")
+	}
+
+	pub async fn create_permission_request_issue(
+		&self,
+		owner: &str,
+		repo: &str,
+		title: &str,
+		body: &str
+	) -> crate::Result<String> {
+		let url = format!("{}/issues", self.project_url(owner, repo));
+
+		let issue = self.client.post(url)
+			.header(self.auth_header, &self.token)
+			.form(&[("title", title), ("description", body)])
+			.send().await?
+			.error_for_status()?
+			.json::<CreatedIssue>().await?;
+
+		Ok(issue.web_url)
+	}
+
+	pub async fn comment_on_issue(&self, owner: &str, repo: &str, number: u64, body: &str) -> crate::Result {
+		let url = format!("{}/issues/{number}/notes", self.project_url(owner, repo));
+
+		self.client.post(url)
+			.header(self.auth_header, &self.token)
+			.form(&[("body", body)])
+			.send().await?
+			.error_for_status()?;
+		Ok(())
+	}
+
+	pub async fn close_issue(&self, owner: &str, repo: &str, number: u64) -> crate::Result {
+		let url = format!("{}/issues/{number}", self.project_url(owner, repo));
+
+		self.client.put(url)
+			.header(self.auth_header, &self.token)
+			.form(&[("state_event", "close")])
+			.send().await?
+			.error_for_status()?;
+		Ok(())
+	}
+
+	/// the account the token is for
+	pub async fn username(&self) -> crate::Result<&str> {
+		let username = self.username.get_or_try_init(|| async {
+			let user = self.client.get(format!("{}/user", self.api_base))
+				.header(self.auth_header, &self.token)
+				.send().await?
+				.error_for_status()?
+				.json::<User>().await?;
+			Ok::<_, reqwest::Error>(user.username)
+		}).await?;
+		Ok(username)
+	}
+
+	/// whether anyone other than us commented on an issue, and if it's closed
+	pub async fn issue_activity(&self, owner: &str, repo: &str, number: u64) -> crate::Result<IssueActivity> {
+		let username = self.username().await?;
+
+		let issue_url = format!("{}/issues/{number}", self.project_url(owner, repo));
+		let issue = self.client.get(&issue_url)
+			.header(self.auth_header, &self.token)
+			.send().await?
+			.error_for_status()?
+			.json::<IssueState>().await?;
+		let notes = self.client.get(format!("{issue_url}/notes"))
+			.query(&[("per_page", "100"), ("sort", "asc")])
+			.header(self.auth_header, &self.token)
+			.send().await?
+			.error_for_status()?
+			.json::<Vec<Note>>().await?;
+
+		let comments = notes.into_iter()
+			.filter(|note| !note.system && note.author.username != username)
+			.map(|note| IssueComment {
+				url: format!("{}#note_{}", issue.web_url, note.id),
+				body: note.body,
+				created_at: note.created_at
+			})
+			.collect::<Vec<_>>();
+
+		Ok(IssueActivity {
+			opened_at: issue.created_at,
+			closed: issue.state == "closed",
+			replied: !comments.is_empty(),
+			comments
+		})
+	}
+
+	/// url of the most recent issue in the repository with this title, if there is one
+	pub async fn find_issue(&self, owner: &str, repo: &str, titles: &IssueTitles) -> crate::Result<Option<String>> {
+		let url = format!("{}/issues", self.project_url(owner, repo));
+
+		for term in titles.search_terms() {
+			let mut query = vec![("scope", "all"), ("per_page", "100")];
+			// titles that are only filled in can't be searched for, newest first has to do
+			if !term.is_empty() {
+				query.extend([("search", term), ("in", "title")]);
+			}
+			let issues = self.client.get(&url)
+				.query(&query)
+				.header(self.auth_header, &self.token)
+				.send().await?
+				.error_for_status()?
+				.json::<Vec<ListedIssue>>().await?;
+
+			// search is fuzzy, so check it's actually one of the titles
+			if let Some(issue) = issues.into_iter().find(|issue| titles.matches(&issue.title)) {
+				return Ok(Some(issue.web_url))
+			}
+		}
+		Ok(None)
+	}
+
+	/// what gitlab calls the repository's license (like `mit`), or `None` if it doesn't have one
+	pub async fn license(&self, owner: &str, repo: &str) -> crate::Result<Option<String>> {
+		let project = self.client.get(self.project_url(owner, repo))
+			.query(&[("license", "true")])
+			.header(self.auth_header, &self.token)
+			.send().await?
+			.error_for_status()?
+			.json::<Project>().await?;
+		Ok(project.license.map(|license| license.key))
+	}
+
+	/// when the last commit on the repository's default branch was, and its latest release
+	pub async fn activity(&self, owner: &str, repo: &str) -> crate::Result<RepoActivity> {
+		let project_url = self.project_url(owner, repo);
+
+		// newest first. empty repositories don't have any
+		let res = self.client.get(format!("{project_url}/repository/commits"))
+			.query(&[("per_page", "1")])
+			.header(self.auth_header, &self.token)
+			.send().await?;
+		let pushed_at = match res.status() {
+			StatusCode::NOT_FOUND => { None }
+			_ => { res.error_for_status()?.json::<Vec<Commit>>().await?.into_iter().next().map(|commit| commit.committed_date) }
+		};
+
+		// newest first too
+		let releases = self.client.get(format!("{project_url}/releases"))
+			.query(&[("per_page", "1")])
+			.header(self.auth_header, &self.token)
+			.send().await?
+			.error_for_status()?
+			.json::<Vec<Release>>().await?;
+		let latest_release = releases.into_iter().next()
+			.map(|release| RepoRelease { tag: release.tag_name, released_at: release.released_at });
+
+		Ok(RepoActivity { pushed_at, latest_release, checked_at: Utc::now() })
+	}
+
+	/// fetches a file from the default branch of a repository, or `None` if it doesn't exist
+	pub async fn get_file(&self, owner: &str, repo: &str, path: &str) -> crate::Result<Option<String>> {
+		let project_url = self.project_url(owner, repo);
+
+		let res = self.client.get(&project_url)
+			.header(self.auth_header, &self.token)
+			.send().await?;
+		if res.status() == StatusCode::NOT_FOUND { return Ok(None) }
+
+		let project = res.error_for_status()?
+			.json::<Project>().await?;
+		let branch = match project.default_branch {
+			Some(branch) => { branch }
+			// empty repository
+			None => { return Ok(None) }
+		};
+
+		let path = encode(path);
+		let res = self.client.get(format!("{project_url}/repository/files/{path}/raw"))
+			.query(&[("ref", &branch)])
+			.header(self.auth_header, &self.token)
+			.send().await?;
+
+		if res.status() == StatusCode::NOT_FOUND { return Ok(None) }
+		Ok(Some(res.error_for_status()?.text().await?))
+	}
+
+	fn project_url(&self, owner: &str, repo: &str) -> String {
+		// projects can be referred to by their url encoded full path
+		format!("{}/projects/{}", self.api_base, encode(&format!("{owner}/{repo}")))
+	}
+}
+
+fn encode(s: &str) -> String {
+	url::form_urlencoded::byte_serialize(s.as_bytes()).collect()
+}
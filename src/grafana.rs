@@ -0,0 +1,149 @@
+//! daily numbers (issues filed, approvals, declines, and approvals so far) for
+//! grafana's json datasource plugin, served under `/grafana` next to the
+//! [`crate::metrics`], so charting how the migration is going only needs the
+//! datasource pointed at `http://<metrics.listen>/grafana`
+
+use crate::db::{ DatabaseThing, Package, PackageState };
+use chrono::{ DateTime, Duration, NaiveDate, Utc };
+use serde::{ Deserialize, Serialize };
+use std::collections::{ BTreeMap, HashSet };
+
+/// what can be charted, see [`Series`]
+const TARGETS: &[&str] = &["issues_filed", "approvals", "declines", "approvals_total"];
+
+#[derive(Deserialize)]
+struct QueryRequest {
+	range: QueryRange,
+	#[serde(default)]
+	targets: Vec<QueryTarget>
+}
+
+#[derive(Deserialize)]
+struct QueryRange {
+	from: DateTime<Utc>,
+	to: DateTime<Utc>
+}
+
+#[derive(Deserialize)]
+struct QueryTarget {
+	/// older versions of the plugin call it `target`, newer ones `metric`
+	#[serde(alias = "metric")]
+	target: Option<String>
+}
+
+#[derive(Serialize)]
+struct TimeSeries {
+	target: String,
+	/// [value, unix timestamp in milliseconds], for the start of each day
+	datapoints: Vec<(usize, i64)>
+}
+
+#[derive(Serialize)]
+struct MetricOption {
+	label: &'static str,
+	value: &'static str
+}
+
+/// the answer to a request for `path` (with `/grafana` taken off), as the status
+/// line and json body, or `None` if it's not something the plugin asks for
+pub fn respond(db: &DatabaseThing, method: &str, path: &str, body: &str) -> Option<(&'static str, String)> {
+	let json = match (method, path) {
+		// the plugin checks this works when the datasource is saved
+		("GET", "" | "/") => { return Some(("200 OK", "{}".into())) }
+		("POST", "/search") => { serde_json::to_string(TARGETS) }
+		("POST", "/metrics") => {
+			serde_json::to_string(&TARGETS.iter().map(|target| MetricOption { label: target, value: target }).collect::<Vec<_>>())
+		}
+		("POST", "/query") => {
+			let request = match serde_json::from_str::<QueryRequest>(body) {
+				Ok(request) => { request }
+				Err(e) => { return Some(("400 Bad Request", serde_json::json!({ "error": e.to_string() }).to_string())) }
+			};
+			serde_json::to_string(&query(db, &request))
+		}
+		("POST", "/annotations" | "/tag-keys" | "/tag-values" | "/variable") => { Ok("[]".into()) }
+		_ => { return None }
+	};
+	Some(("200 OK", json.unwrap_or_else(|e| serde_json::json!({ "error": e.to_string() }).to_string())))
+}
+
+fn query(db: &DatabaseThing, request: &QueryRequest) -> Vec<TimeSeries> {
+	let series = Series::gather(&db.packages());
+	let from = request.range.from.naive_utc().date();
+	let to = request.range.to.naive_utc().date();
+
+	request.targets.iter()
+		.filter_map(|target| target.target.as_deref())
+		.filter(|target| TARGETS.contains(target))
+		.map(|target| {
+			let mut total = series.approvals.range(..from).map(|(_, count)| count).sum::<usize>();
+			let datapoints = days(from, to)
+				.map(|day| {
+					let count = |by_day: &BTreeMap<NaiveDate, usize>| by_day.get(&day).copied().unwrap_or(0);
+					let value = match target {
+						"issues_filed" => { count(&series.issues_filed) }
+						"approvals" => { count(&series.approvals) }
+						"declines" => { count(&series.declines) }
+						_ => {
+							total += count(&series.approvals);
+							total
+						}
+					};
+					(value, day.and_hms(0, 0, 0).timestamp_millis())
+				})
+				.collect();
+			TimeSeries { target: target.into(), datapoints }
+		})
+		.collect()
+}
+
+fn days(from: NaiveDate, to: NaiveDate) -> impl Iterator<Item = NaiveDate> {
+	let count = (to - from).num_days().max(-1) + 1;
+	(0..count).map(move |i| from + Duration::days(i))
+}
+
+/// how many of each thing happened, by day (utc)
+#[derive(Default)]
+struct Series {
+	/// issues, not packages, since packages in the same repository share one
+	issues_filed: BTreeMap<NaiveDate, usize>,
+	approvals: BTreeMap<NaiveDate, usize>,
+	/// opting out, or taking back permission
+	declines: BTreeMap<NaiveDate, usize>
+}
+
+impl Series {
+	fn gather(packages: &[Package]) -> Self {
+		let mut series = Self::default();
+		let mut issues = HashSet::new();
+		for package in packages {
+			// a state can be in the history more than once (like an issue
+			// having a reminder posted on it), so each is only counted once
+			let mut approved = HashSet::new();
+			let mut declined = HashSet::new();
+			// the current state is in the history too, unless it's from before
+			// there was a history
+			let states = package.history.iter().map(|transition| (Some(transition.at), &transition.state)).chain([(None, &package.state)]);
+			for (at, state) in states {
+				match state {
+					PackageState::IssueFiled(filed) if issues.insert(filed.issue_url.clone()) => {
+						*series.issues_filed.entry(filed.filed_at.naive_utc().date()).or_default() += 1;
+					}
+					PackageState::MaintainerApproved(approval) => { approved.insert(approval.approved_at); }
+					PackageState::Migrated(migrated) => { approved.insert(migrated.approval.approved_at); }
+					PackageState::ConsentRevoked(revoked) => { declined.insert(revoked.revoked_at); }
+					// only the history has when someone opted out
+					PackageState::OptedOut(_) => { declined.extend(at); }
+					_ => {}
+				}
+			}
+			for at in approved {
+				*series.approvals.entry(at.naive_utc().date()).or_default() += 1;
+			}
+			for at in declined {
+				*series.declines.entry(at.naive_utc().date()).or_default() += 1;
+			}
+		}
+		series
+	}
+}
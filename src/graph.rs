@@ -0,0 +1,227 @@
+//! the owners, repositories, and packages in the database as a graph, for
+//! `graph`: every owner's repositories, every repository's packages, and which
+//! of them are asked together in one issue, for seeing the monorepos and the
+//! owners with a lot of packages when planning who's asked next. as graphviz's
+//! dot (`graph | dot -Tsvg > graph.svg`), or json for anything else
+
+use crate::db::{ DatabaseThing, Package };
+use crate::forge::RepoLocation;
+use crate::status::{ self, ListFilter, OutputFormat };
+use crate::Result;
+use serde::Serialize;
+use std::collections::{ BTreeMap, BTreeSet };
+use std::fmt::Write;
+use std::str::FromStr;
+use tokio::fs;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GraphFormat {
+	/// for graphviz
+	Dot,
+	Json
+}
+
+impl FromStr for GraphFormat {
+	type Err = String;
+
+	fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+		match &*s.trim().to_lowercase() {
+			"dot" => { Ok(Self::Dot) }
+			"json" => { Ok(Self::Json) }
+			_ => { Err(format!("unknown format {s} (expected dot or json)")) }
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct Graph {
+	/// most packages first
+	owners: Vec<Owner>,
+	/// packages whose repository isn't one, so they aren't anyone's
+	without_repository: Vec<String>
+}
+
+#[derive(Serialize)]
+struct Owner {
+	host: String,
+	owner: String,
+	packages: usize,
+	downloads: u64,
+	repositories: Vec<Repository>
+}
+
+#[derive(Serialize)]
+struct Repository {
+	/// like `github.com/owner/name`
+	repository: String,
+	name: String,
+	/// whether it has more than one package, so they're asked in one issue between them
+	grouped: bool,
+	/// the issues its packages were asked in, usually just the one
+	issues: Vec<String>,
+	packages: Vec<GraphPackage>
+}
+
+#[derive(Serialize)]
+struct GraphPackage {
+	name: String,
+	/// a [`crate::db::PackageState::kind`]
+	state: &'static str,
+	downloads: u32,
+	issue_url: Option<String>
+}
+
+/// writes the graph of the packages (in `state`, if it's given) of `owner` (or
+/// everyone), leaving out owners with fewer than `min_packages`
+pub async fn run(
+	db: &DatabaseThing,
+	format: GraphFormat,
+	output: Option<String>,
+	state: Option<String>,
+	owner: Option<String>,
+	min_packages: usize
+) -> Result {
+	let packages = status::matching(db, &ListFilter {
+		state,
+		min_downloads: 0,
+		min_stars: 0,
+		search: None,
+		owner,
+		sort: None,
+		limit: None,
+		format: OutputFormat::Text
+	})?;
+	let graph = build(packages, min_packages);
+
+	let out = match format {
+		GraphFormat::Dot => { dot(&graph)? }
+		GraphFormat::Json => { serde_json::to_string_pretty(&graph)? }
+	};
+	match output {
+		Some(path) => {
+			fs::write(&path, out).await
+				.map_err(|e| format!("error writing {path}: {e}"))?;
+			let repositories = graph.owners.iter().map(|owner| owner.repositories.len()).sum::<usize>();
+			info!("wrote a graph of {} owners and {repositories} repositories to {path}", graph.owners.len());
+		}
+		None => { println!("{out}") }
+	}
+
+	Ok(())
+}
+
+fn build(packages: Vec<Package>, min_packages: usize) -> Graph {
+	// owners and repositories are by their lowercase names, since forges don't mind the case
+	let mut owners = BTreeMap::<(String, String), BTreeMap<String, (RepoLocation, Vec<Package>)>>::new();
+	let mut without_repository = Vec::new();
+	for package in packages {
+		match RepoLocation::parse(&package.info.repository.url) {
+			Some(repo) => {
+				owners.entry((repo.host.clone(), repo.owner.to_lowercase())).or_default()
+					.entry(repo.key())
+					.or_insert_with(|| (repo, Vec::new()))
+					.1.push(package);
+			}
+			None => { without_repository.push(package.info.name) }
+		}
+	}
+
+	let mut owners = owners.into_values()
+		.map(|repositories| {
+			// as it's written in the first one, rather than lowercased
+			let (host, owner) = repositories.values().next().map(|(repo, _)| (repo.host.clone(), repo.owner.clone())).unwrap_or_default();
+			let repositories = repositories.into_values()
+				.map(|(repo, mut packages)| {
+					packages.sort_by(|a, b| b.info.downloads.cmp(&a.info.downloads).then_with(|| a.info.name.cmp(&b.info.name)));
+					let packages = packages.iter()
+						.map(|package| GraphPackage {
+							name: package.info.name.clone(),
+							state: package.state.kind(),
+							downloads: package.info.downloads,
+							issue_url: status::issue_url(package)
+						})
+						.collect::<Vec<_>>();
+					Repository {
+						repository: repo.to_string(),
+						name: repo.name.clone(),
+						grouped: packages.len() > 1,
+						issues: packages.iter().filter_map(|package| package.issue_url.clone()).collect::<BTreeSet<_>>().into_iter().collect(),
+						packages
+					}
+				})
+				.collect::<Vec<_>>();
+			Owner {
+				host,
+				owner,
+				packages: repositories.iter().map(|repo| repo.packages.len()).sum(),
+				downloads: repositories.iter().flat_map(|repo| repo.packages.iter()).map(|package| package.downloads as u64).sum(),
+				repositories
+			}
+		})
+		.filter(|owner| owner.packages >= min_packages)
+		.collect::<Vec<_>>();
+	owners.sort_by(|a, b| b.packages.cmp(&a.packages).then_with(|| b.downloads.cmp(&a.downloads)));
+
+	Graph { owners, without_repository }
+}
+
+/// owners are boxes, repositories folders, and packages ellipses. the packages
+/// of a repository that's asked in one issue are in a box of their own
+fn dot(graph: &Graph) -> Result<String> {
+	let mut out = String::from("digraph packages {\n\trankdir=LR;\n\tnode [fontname=\"sans-serif\"];\n");
+	for owner in graph.owners.iter() {
+		let owner_id = format!("owner:{}/{}", owner.host, owner.owner);
+		writeln!(
+			out,
+			"\t{} [shape=box, label={}];",
+			quote(&owner_id),
+			label(&[&owner.owner, &format!("{} packages, {} downloads", owner.packages, owner.downloads)])
+		)?;
+		for repo in owner.repositories.iter() {
+			let repo_id = format!("repo:{}", repo.repository);
+			let issues = match repo.issues.len() {
+				0 => { String::new() }
+				1 => { "1 issue".into() }
+				n => { format!("{n} issues") }
+			};
+			writeln!(out, "\t{} [shape=folder, label={}];", quote(&repo_id), label(&[&repo.name, &issues]))?;
+			writeln!(out, "\t{} -> {};", quote(&owner_id), quote(&repo_id))?;
+
+			if repo.grouped {
+				writeln!(out, "\tsubgraph {} {{", quote(&format!("cluster:{}", repo.repository)))?;
+				writeln!(out, "\t\tlabel={};\n\t\tstyle=dashed;", label(&[&format!("one issue for {} packages", repo.packages.len())]))?;
+			}
+			let indent = if repo.grouped { "\t\t" } else { "\t" };
+			for package in repo.packages.iter() {
+				writeln!(
+					out,
+					"{indent}{} [label={}];",
+					quote(&format!("package:{}", package.name)),
+					label(&[&package.name, &format!("{}, {} downloads", package.state, package.downloads)])
+				)?;
+			}
+			if repo.grouped {
+				out.push_str("\t}\n");
+			}
+			for package in repo.packages.iter() {
+				writeln!(out, "\t{} -> {};", quote(&repo_id), quote(&format!("package:{}", package.name)))?;
+			}
+		}
+	}
+	out.push('}');
+	Ok(out)
+}
+
+/// a dot string, quoted
+fn quote(s: &str) -> String {
+	format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// a dot label of these lines, leaving out empty ones
+fn label(lines: &[&str]) -> String {
+	let lines = lines.iter()
+		.filter(|line| !line.is_empty())
+		.map(|line| line.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', " "))
+		.collect::<Vec<_>>();
+	format!("\"{}\"", lines.join("\\n"))
+}
@@ -0,0 +1,128 @@
+//! `/healthz` and `/readyz` on `metrics.listen`, for kubernetes (or systemd, or
+//! anything else that polls) to tell whether the bot needs restarting, and
+//! whether it's able to get anything done:
+//!
+//! - `/healthz` is 200 as long as the bot answers and the database isn't stuck
+//! - `/readyz` is 200 if the database last saved fine, github.com's token
+//!   worked the last time it was checked (every minute, if there is one), and
+//!   forge and registry requests haven't all been failing for a while, nor so
+//!   much on one host that its [`crate::breaker`] tripped. it's 503
+//!   otherwise, with what's wrong
+
+use crate::db::DatabaseThing;
+use chrono::{ DateTime, Duration, Utc };
+use serde::Serialize;
+use std::sync::Mutex;
+
+/// minutes of every request failing before the bot isn't ready
+const FAILING_FOR_MINUTES: i64 = 15;
+
+lazy_static! {
+	static ref HEALTH: Mutex<Health> = Mutex::new(Health::default());
+}
+
+#[derive(Default)]
+struct Health {
+	last_saved_at: Option<DateTime<Utc>>,
+	/// why the last save didn't work, if it didn't
+	save_error: Option<String>,
+	/// whether checking github.com's rate limit (which needs a working token) worked
+	github_checked: Option<(DateTime<Utc>, Option<String>)>,
+	last_request_answered_at: Option<DateTime<Utc>>,
+	/// since when every request has failed
+	failing_since: Option<DateTime<Utc>>
+}
+
+#[derive(Serialize)]
+struct Readiness {
+	ready: bool,
+	checks: Vec<Check>
+}
+
+#[derive(Serialize)]
+struct Check {
+	name: &'static str,
+	ok: bool,
+	detail: String
+}
+
+/// records the database being saved, or not, from [`DatabaseThing::flush`]
+pub fn database_saved(error: Option<String>) {
+	let mut health = HEALTH.lock().unwrap();
+	if error.is_none() {
+		health.last_saved_at = Some(Utc::now());
+	}
+	health.save_error = error;
+}
+
+/// records the result of checking github.com's token
+pub fn github_checked(error: Option<String>) {
+	HEALTH.lock().unwrap().github_checked = Some((Utc::now(), error));
+}
+
+/// records a forge or registry request getting an answer (even a not found), or failing
+pub fn request_finished(answered: bool) {
+	let mut health = HEALTH.lock().unwrap();
+	match answered {
+		true => {
+			health.last_request_answered_at = Some(Utc::now());
+			health.failing_since = None;
+		}
+		false => { health.failing_since.get_or_insert_with(Utc::now); }
+	}
+}
+
+/// the answer to `path`, if it's `/healthz` or `/readyz`, as the status line and json body
+pub fn respond(db: &DatabaseThing, path: &str) -> Option<(&'static str, String)> {
+	let readiness = match path {
+		"/healthz" => {
+			// takes the database's lock, so it hangs (and fails the check) if something's holding on to it
+			let packages = db.package_count();
+			return Some(("200 OK", serde_json::json!({ "ok": true, "packages": packages }).to_string()))
+		}
+		"/readyz" => { readiness(db) }
+		_ => { return None }
+	};
+	let status = if readiness.ready { "200 OK" } else { "503 Service Unavailable" };
+	Some((status, serde_json::to_string(&readiness).unwrap_or_default()))
+}
+
+fn readiness(db: &DatabaseThing) -> Readiness {
+	let breakers = db.breakers();
+	let health = HEALTH.lock().unwrap();
+	let now = Utc::now();
+	let mut checks = Vec::new();
+
+	checks.push(match (&health.save_error, health.last_saved_at) {
+		(Some(e), _) => { Check { name: "database", ok: false, detail: format!("the last save failed: {e}") } }
+		(None, Some(saved_at)) => { Check { name: "database", ok: true, detail: format!("last saved at {saved_at}") } }
+		(None, None) => { Check { name: "database", ok: true, detail: "loaded, not saved yet".into() } }
+	});
+
+	if let Some((checked_at, error)) = &health.github_checked {
+		checks.push(match error {
+			Some(e) => { Check { name: "github_token", ok: false, detail: format!("checking it at {checked_at} failed: {e}") } }
+			None => { Check { name: "github_token", ok: true, detail: format!("worked at {checked_at}") } }
+		});
+	}
+
+	let last_answered = match health.last_request_answered_at {
+		Some(answered_at) => { format!("the last answer was at {answered_at}") }
+		None => { "nothing has answered yet".into() }
+	};
+	checks.push(match health.failing_since {
+		Some(since) if now - since >= Duration::minutes(FAILING_FOR_MINUTES) => {
+			Check { name: "requests", ok: false, detail: format!("every request since {since} has failed, {last_answered}") }
+		}
+		_ => { Check { name: "requests", ok: true, detail: last_answered } }
+	});
+
+	checks.push(match breakers.iter().next() {
+		Some((host, breaker)) => {
+			Check { name: "breakers", ok: false, detail: format!("nothing is filed on {host} until {}, the last error: {}", breaker.until, breaker.last_error) }
+		}
+		None => { Check { name: "breakers", ok: true, detail: "filing everywhere".into() } }
+	});
+
+	Readiness { ready: checks.iter().all(|check| check.ok), checks }
+}
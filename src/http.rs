@@ -0,0 +1,154 @@
+//! just enough http/1.1 for answering the few requests the bot takes (metrics,
+//! grafana, github's webhooks, and the api), one per connection
+
+use crate::Result;
+use std::time::Duration;
+use tokio::io::{ AsyncRead, AsyncReadExt, AsyncWriteExt };
+use tokio::net::TcpStream;
+use tokio::time;
+
+pub struct Request {
+	pub method: String,
+	/// without the query
+	pub path: String,
+	/// still percent-encoded, see [`Self::query_pairs`]
+	pub query: Option<String>,
+	/// names are lowercase
+	pub headers: Vec<(String, String)>,
+	pub body: Vec<u8>
+}
+
+impl Request {
+	/// reads one request, if it's at most `max` bytes (headers and body together)
+	pub async fn read(stream: &mut TcpStream, max: usize) -> Result<Self> {
+		time::timeout(Duration::from_secs(10), Self::read_inner(stream, max)).await
+			.map_err(|_| "timed out reading the request")?
+	}
+
+	async fn read_inner(stream: &mut (impl AsyncRead + Unpin), max: usize) -> Result<Self> {
+		let mut request = Vec::new();
+		let mut buf = [0; 8192];
+		let head_end = loop {
+			if let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+				break end + 4
+			}
+			if request.len() > max {
+				return Err("request too big".into())
+			}
+			match stream.read(&mut buf).await? {
+				0 => { break request.len() }
+				read => { request.extend_from_slice(&buf[..read]) }
+			}
+		};
+
+		let head = String::from_utf8_lossy(&request[..head_end]).into_owned();
+		let mut lines = head.lines();
+		let mut request_line = lines.next().unwrap_or_default().split(' ');
+		let method = request_line.next().unwrap_or_default().to_string();
+		let target = request_line.next().unwrap_or_default();
+		let (path, query) = match target.split_once('?') {
+			Some((path, query)) => { (path.to_string(), Some(query.to_string())) }
+			None => { (target.to_string(), None) }
+		};
+		let headers = lines
+			.filter_map(|line| line.split_once(':'))
+			.map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+			.collect::<Vec<_>>();
+
+		let content_length = headers.iter()
+			.find(|(name, _)| name == "content-length")
+			.and_then(|(_, length)| length.parse::<usize>().ok())
+			.unwrap_or(0);
+		// it's whatever the request says, so it can be anything
+		let end = head_end.checked_add(content_length).filter(|end| *end <= max)
+			.ok_or("request too big")?;
+		while request.len() < end {
+			match stream.read(&mut buf).await? {
+				0 => { break }
+				read => { request.extend_from_slice(&buf[..read]) }
+			}
+		}
+		let body_end = request.len().min(end);
+		let body = request[head_end..body_end].to_vec();
+
+		Ok(Self { method, path, query, headers, body })
+	}
+
+	/// the query's `name=value`s, decoded
+	pub fn query_pairs(&self) -> Vec<(String, String)> {
+		url::form_urlencoded::parse(self.query.as_deref().unwrap_or_default().as_bytes())
+			.map(|(name, value)| (name.into_owned(), value.into_owned()))
+			.collect()
+	}
+
+	/// the first header called `name` (lowercase)
+	pub fn header(&self, name: &str) -> Option<&str> {
+		self.headers.iter().find(|(header, _)| header == name).map(|(_, value)| value.as_str())
+	}
+}
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+	if hex.len() % 2 != 0 { return None }
+	(0..hex.len())
+		.step_by(2)
+		.map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+		.collect()
+}
+
+/// writes the response (with any other `headers`), and closes the connection
+pub async fn respond(stream: &mut TcpStream, status: &str, content_type: &str, headers: &[(&str, &str)], body: &str) -> Result {
+	let headers = std::iter::once(("Content-Type", content_type))
+		.chain(headers.iter().copied())
+		.collect::<Vec<_>>();
+	respond_bytes(stream, status, &headers, body.as_bytes()).await
+}
+
+/// like [`respond`], for bodies that might not be text, with the content type
+/// in `headers` (if there is one)
+pub async fn respond_bytes(stream: &mut TcpStream, status: &str, headers: &[(&str, &str)], body: &[u8]) -> Result {
+	let headers = headers.iter().map(|(name, value)| format!("{name}: {value}\r\n")).collect::<String>();
+	let head = format!("HTTP/1.1 {status}\r\nContent-Length: {}\r\n{headers}Connection: close\r\n\r\n", body.len());
+	stream.write_all(head.as_bytes()).await?;
+	stream.write_all(body).await?;
+	stream.shutdown().await?;
+	Ok(())
+}
+
+/// writes just the head of a response that goes on for as long as the connection is
+/// open (like server-sent events), for the body to be written to `stream` as it comes
+pub async fn respond_streaming(stream: &mut TcpStream, content_type: &str, headers: &[(&str, &str)]) -> Result {
+	let headers = headers.iter().map(|(name, value)| format!("{name}: {value}\r\n")).collect::<String>();
+	let head = format!("HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nCache-Control: no-cache\r\n{headers}Connection: close\r\n\r\n");
+	stream.write_all(head.as_bytes()).await?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	async fn read(request: &str, max: usize) -> Result<Request> {
+		Request::read_inner(&mut request.as_bytes(), max).await
+	}
+
+	#[tokio::test]
+	async fn reads_the_body() {
+		let request = read("POST /hook?a=1 HTTP/1.1\r\nContent-Length: 4\r\n\r\nbodyextra", 1024).await.unwrap();
+		assert_eq!(request.method, "POST");
+		assert_eq!(request.path, "/hook");
+		assert_eq!(request.body, b"body");
+	}
+
+	#[tokio::test]
+	async fn turns_away_content_lengths_that_are_too_big() {
+		for length in ["2000", &usize::MAX.to_string()] {
+			let request = format!("POST / HTTP/1.1\r\nContent-Length: {length}\r\n\r\nbody");
+			let e = read(&request, 1024).await.err().unwrap();
+			assert_eq!(e.to_string(), "request too big");
+		}
+	}
+}
@@ -0,0 +1,192 @@
+//! recording issues that were filed by hand (like the ones from before the bot
+//! existed) as `IssueFiled`, so they're polled, followed up, and answered like
+//! the ones the bot filed. they're given by url, or found by their title on the
+//! repositories of an owner's packages
+
+use crate::consent::{ self, Answer };
+use crate::db::{ self, DatabaseThing, PackageState };
+use crate::forge::{ self, Forges, RepoLocation };
+use crate::reminders;
+use crate::Result;
+use chrono::Utc;
+use std::collections::BTreeMap;
+
+/// records the issues at `urls`, and the ones a repository of `owner`'s
+/// packages has with one of the usual titles or `titles`
+pub async fn run(db: &DatabaseThing, forges: &mut Forges, urls: Vec<String>, owner: Option<String>, titles: Vec<String>, dry_run: bool) -> Result {
+	if urls.is_empty() && owner.is_none() {
+		return Err("import needs the urls of the issues, or --owner to look for them on their repositories".into())
+	}
+	if dry_run { db.set_read_only() }
+
+	// every package, by repository
+	let mut repos = BTreeMap::<String, (RepoLocation, Vec<String>)>::new();
+	for package in db.packages() {
+		if let Some(repo) = RepoLocation::parse(&package.info.repository.url) {
+			repos.entry(repo.key())
+				.or_insert_with(|| (repo, Vec::new()))
+				.1.push(package.info.name);
+		}
+	}
+
+	let mut issues = BTreeMap::<String, (RepoLocation, Vec<String>)>::new();
+	for url in urls {
+		// without anything after the issue number, like a link to a comment
+		let url = url.trim().split(['#', '?']).next().unwrap_or_default().trim_end_matches('/').to_string();
+		let repo = match issue_repository(&url) {
+			Some(repo) => { repo }
+			None => {
+				println!("{url}: isn't the url of an issue");
+				continue
+			}
+		};
+		match repos.get(&repo.key()) {
+			Some(found) => { issues.insert(url, found.clone()); }
+			None => { println!("{url}: no package in the database has {repo} as its repository") }
+		}
+	}
+
+	if let Some(owner) = owner {
+		let titles = forges.config().issue_titles.clone().with(titles.iter().map(String::as_str));
+		for (repo, names) in repos.values().filter(|(repo, _)| repo.owner.eq_ignore_ascii_case(&owner)) {
+			// nothing to look for on repositories that were dealt with already
+			if !names.iter().any(|name| db.package_state(name).map_or(false, |state| importable(&state))) { continue }
+			let found = match forges.client_for(repo) {
+				Ok(client) => { client.find_permission_request_issue(repo, &titles).await }
+				Err(e) => { Err(e) }
+			};
+			match found {
+				Ok(Some(url)) => { issues.insert(url, (repo.clone(), names.clone())); }
+				Ok(None) => {}
+				Err(e) => { println!("{repo}: error looking for an issue: {e}") }
+			}
+		}
+	}
+
+	let mut imported = 0;
+	for (url, (repo, names)) in issues {
+		if import(db, forges, repo, url, names, dry_run).await? {
+			imported += 1;
+		}
+		db.flush();
+	}
+	println!("{imported} issues recorded{}", if dry_run { " (dry run, nothing was saved)" } else { "" });
+
+	Ok(())
+}
+
+/// records `issue_url` for the packages in `names` that haven't been contacted
+/// yet, and answers them if the maintainer already has. returns whether it did
+async fn import(db: &DatabaseThing, forges: &mut Forges, repo: RepoLocation, issue_url: String, names: Vec<String>, dry_run: bool) -> Result<bool> {
+	let mut importing = Vec::new();
+	for name in names {
+		match db.package_state(&name) {
+			Some(PackageState::IssueFiled(filed)) if filed.issue_url == issue_url => {
+				println!("{name}: {issue_url} is recorded already");
+			}
+			Some(PackageState::IssueFiled(filed)) => {
+				println!("{name}: left alone, it has {} recorded already", filed.issue_url);
+			}
+			Some(state) if importable(&state) => { importing.push(name) }
+			Some(state) => { println!("{name}: left alone, it's {} already", state.kind()) }
+			None => {}
+		}
+	}
+	if importing.is_empty() { return Ok(false) }
+	let names_str = importing.join(", ");
+
+	let number = match forge::issue_number(&issue_url) {
+		Some(number) => { number }
+		None => {
+			println!("{issue_url} (for {names_str}): can't tell what issue number it is");
+			return Ok(false)
+		}
+	};
+	let config = forges.config().clone();
+	let activity = match forges.client_for(&repo) {
+		Ok(client) => { client.issue_activity(&repo, number).await }
+		Err(e) => { Err(e) }
+	};
+	let activity = match activity {
+		Ok(activity) => { activity }
+		Err(e) => {
+			println!("{issue_url} (for {names_str}): error fetching it: {e}");
+			return Ok(false)
+		}
+	};
+
+	// as if the bot had filed it when it was opened, so its reminders are due
+	// when they would've been, and only replies since count as answers
+	let filed_at = activity.opened_at;
+	for name in importing.iter() {
+		db.set_state(name, PackageState::IssueFiled(db::PackageIssueFiled {
+			repository: repo.clone(),
+			issue_url: issue_url.clone(),
+			filed_at,
+			grouped_with: importing.iter().filter(|other| *other != name).cloned().collect(),
+			reminders: reminders::schedule(filed_at, &config),
+			replied_at: None,
+			template_variant: None,
+			locale: None
+		}))?;
+		db.set_seen_comments(name, &activity.comments);
+	}
+
+	let answered = match dry_run {
+		// answering registers approvals with the pulsar backend, even in a dry run
+		true => {
+			activity.comments.iter().find_map(|comment| match consent::classify(&comment.body)? {
+				Answer::Yes => { Some(format!("would be approved in {}", comment.url)) }
+				Answer::No => { Some(format!("would be opted out, declined in {}", comment.url)) }
+			})
+				.or_else(|| activity.closed.then(|| "would be opted out, it was closed without an answer".to_string()))
+		}
+		false => { consent::answer_from_replies(db, &config, &importing[0], &activity.comments, activity.closed).await? }
+	};
+	let outcome = match answered {
+		Some(answered) => { answered }
+		None if activity.replied || activity.closed => {
+			let replied_at = activity.comments.first().map_or_else(Utc::now, |comment| comment.created_at);
+			for name in importing.iter() {
+				if let Some(PackageState::IssueFiled(mut filed)) = db.package_state(name) {
+					filed.replied_at = Some(replied_at);
+					db.set_state(name, PackageState::IssueFiled(filed))?;
+				}
+			}
+			format!("{}, so it won't get reminders", if activity.replied { "replied to" } else { "closed" })
+		}
+		None => {
+			match reminders::schedule(filed_at, &config).iter().any(|reminder| reminder.due_at <= Utc::now()) {
+				true => { "nobody's replied, the next poll follows it up".into() }
+				false => { "nobody's replied yet".into() }
+			}
+		}
+	};
+	println!("{names_str}: recorded {issue_url} (opened {}), {outcome}", filed_at.format("%Y-%m-%d"));
+
+	Ok(true)
+}
+
+/// whether a package can have an issue recorded for it: it hasn't been
+/// contacted, or answered, or dealt with some other way
+fn importable(state: &PackageState) -> bool {
+	matches!(
+		state,
+		PackageState::New
+			| PackageState::Deferred(_)
+			| PackageState::SkippedDeprecated(_)
+			| PackageState::NeedsReview(_)
+			| PackageState::Blocked(_)
+			| PackageState::DeclinedByOwnerPolicy(_)
+			| PackageState::Dormant(_)
+			| PackageState::RetryPending(_)
+	)
+}
+
+/// the repository an issue's url is on, for github (`.../issues/12`) and
+/// gitlab (`.../-/issues/12`) alike
+fn issue_repository(url: &str) -> Option<RepoLocation> {
+	let (repo, _) = url.rsplit_once("/issues/")?;
+	forge::issue_number(url)?;
+	RepoLocation::parse(repo.strip_suffix("/-").unwrap_or(repo))
+}
@@ -0,0 +1,200 @@
+//! `init`, which asks a few questions and writes a config to start from, for
+//! anyone setting the bot up for the first time
+
+use crate::config;
+use crate::doctor;
+use crate::forge::RepoLocation;
+use crate::github::GithubClient;
+use crate::secrets::SecretSource;
+use crate::Result;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::str::FromStr;
+use tokio::fs;
+
+/// where the github.com token comes from, see [`crate::secrets`]
+enum TokenSource {
+	Env(String),
+	File(String),
+	Keyring(String),
+	None
+}
+
+/// asks for everything a first run needs, checks what it can as it goes, writes
+/// the config to `path` (or [`config::DEFAULT_PATH`]), then runs `doctor` on it
+pub async fn run(path: Option<&str>) -> Result {
+	let path = path.unwrap_or(config::DEFAULT_PATH);
+	println!("this writes a config to {path} to start from. press enter to use what's in [brackets],");
+	println!("and see config.example.toml for everything else that can go in it");
+	if Path::new(path).exists() && !ask_yes_no(&format!("{path} already exists, replace it?"), false).await? {
+		println!("leaving {path} alone");
+		return Ok(())
+	}
+
+	let token = ask_token().await?;
+	let db_path = ask("file to keep the database in", "state.ron").await?;
+	let min_downloads = ask_number::<u32>("packages with less downloads than this are left for later", 0).await?;
+	let min_stars = ask_number::<u32>("and less stars than this", 0).await?;
+	let daily_limit = ask_number::<usize>("most issues to file in any 24 hours (0 for no limit)", 20).await?;
+	let delay_min = ask_number::<u64>("seconds to wait between filing issues", 30).await?;
+	let canary_repo = loop {
+		let repo = ask("a test repository the first issue of every run is filed on too, to check it looks right (like github.com/you/sandbox, or none)", "none").await?;
+		if repo == "none" { break None }
+		match RepoLocation::parse(&repo).or_else(|| RepoLocation::parse(&format!("https://{repo}"))) {
+			Some(_) => { break Some(repo) }
+			None => { println!("{repo} doesn't look like a repository, it should be like github.com/owner/name") }
+		}
+	};
+
+	let mut file = String::new();
+	writeln!(file, "# made with `pulsar-migrator-issue-bot init`. everything else that can go here is in")?;
+	writeln!(file, "# config.example.toml")?;
+	writeln!(file)?;
+	writeln!(file, "db_path = {}", quote(&db_path))?;
+	writeln!(file)?;
+	writeln!(file, "[forges]")?;
+	match &token {
+		TokenSource::Env(name) => { writeln!(file, "github_token_env = {}", quote(name))? }
+		TokenSource::File(file_path) => { writeln!(file, "github_token_file = {}", quote(file_path))? }
+		TokenSource::Keyring(name) => { writeln!(file, "github_token_keyring = {}", quote(name))? }
+		// not even the default GITHUB_TOKEN
+		TokenSource::None => { writeln!(file, "github_token_env = \"\" # none")? }
+	}
+	writeln!(file)?;
+	writeln!(file, "[eligibility]")?;
+	writeln!(file, "min_downloads = {min_downloads}")?;
+	writeln!(file, "min_stars = {min_stars}")?;
+	writeln!(file)?;
+	writeln!(file, "[filing]")?;
+	writeln!(file, "daily_limit = {daily_limit}")?;
+	writeln!(file, "delay_min = {delay_min}")?;
+	writeln!(file, "delay_max = {}", delay_min * 2)?;
+	if let Some(canary_repo) = &canary_repo {
+		writeln!(file, "canary_repo = {}", quote(canary_repo))?;
+	}
+
+	fs::write(path, file).await
+		.map_err(|e| format!("error writing {path}: {e}"))?;
+	println!("wrote {path}, checking it:");
+	doctor::run(Some(path), None).await
+}
+
+/// asks where the github.com token comes from until it's somewhere that works,
+/// or the answer is to use it anyway
+async fn ask_token() -> Result<TokenSource> {
+	loop {
+		let source = ask("where should the github.com token be read from: env, file, keyring, or none", "env").await?;
+		let (source, env, file, keyring) = match &*source.to_lowercase() {
+			"env" => {
+				let name = ask("environment variable (or .env line) it's in", "GITHUB_TOKEN").await?;
+				(TokenSource::Env(name.clone()), Some(name), None, None)
+			}
+			"file" => {
+				let file_path = ask("file it's in", "/run/secrets/github_token").await?;
+				(TokenSource::File(file_path.clone()), None, Some(file_path), None)
+			}
+			"keyring" => {
+				let name = ask("service name it's saved under in the keyring", "pulsar-github").await?;
+				(TokenSource::Keyring(name.clone()), None, None, Some(name))
+			}
+			"none" => {
+				println!("without a token, packages on github.com can't be contacted");
+				return Ok(TokenSource::None)
+			}
+			other => {
+				println!("{other} isn't one of env, file, keyring, or none");
+				continue
+			}
+		};
+
+		let secret = SecretSource {
+			key: "forges.github_token",
+			env: env.as_deref(),
+			file: file.as_deref(),
+			keyring: keyring.as_deref()
+		}.load();
+		let problem = match secret {
+			Ok(Some(token)) => {
+				let info = match GithubClient::new("github.com", token.expose()) {
+					Ok(client) => { client.token_info().await }
+					Err(e) => { Err(e) }
+				};
+				match info {
+					Ok((login, Some(scopes))) if scopes.iter().any(|scope| doctor::ISSUE_SCOPES.contains(&scope.as_str())) => {
+						println!("the token works, it's for {login}");
+						None
+					}
+					Ok((login, Some(_))) => { Some(format!("the token is for {login}, but it needs the public_repo scope to file issues")) }
+					Ok((login, None)) => {
+						println!("the token is for {login}. it's a fine-grained token, so make sure it can read and write issues");
+						None
+					}
+					Err(e) => {
+						// octocrab puts a backtrace after the message for network errors
+						let e = token.redact(&e.to_string());
+						Some(format!("error checking the token: {}", e.lines().next().unwrap_or_default()))
+					}
+				}
+			}
+			Ok(None) => { Some("there's no token there right now".into()) }
+			Err(e) => { Some(e) }
+		};
+
+		match problem {
+			None => { return Ok(source) }
+			Some(problem) => {
+				println!("{problem}");
+				if ask_yes_no("use it anyway (and fix it before running)?", false).await? {
+					return Ok(source)
+				}
+			}
+		}
+	}
+}
+
+/// a toml string
+fn quote(s: &str) -> String {
+	toml::Value::String(s.into()).to_string()
+}
+
+/// the answer, or `default` if there isn't one
+async fn ask(question: &str, default: &str) -> Result<String> {
+	println!("{question} [{default}]");
+	let answer = read_answer().await?;
+	Ok(if answer.is_empty() { default.into() } else { answer })
+}
+
+async fn ask_yes_no(question: &str, default: bool) -> Result<bool> {
+	loop {
+		println!("{question} [{}]", if default { "Y/n" } else { "y/N" });
+		match &*read_answer().await?.to_lowercase() {
+			"" => { return Ok(default) }
+			"y" | "yes" => { return Ok(true) }
+			"n" | "no" => { return Ok(false) }
+			_ => {}
+		}
+	}
+}
+
+async fn read_answer() -> Result<String> {
+	let (read, answer) = tokio::task::spawn_blocking(|| {
+		let mut line = String::new();
+		std::io::stdin().read_line(&mut line).map(|read| (read, line))
+	}).await??;
+
+	// eof, nothing more is coming
+	if read == 0 {
+		return Err("stopped before answering everything, nothing was written".into())
+	}
+	Ok(answer.trim().into())
+}
+
+async fn ask_number<T: FromStr + ToString>(question: &str, default: T) -> Result<T> {
+	loop {
+		let answer = ask(question, &default.to_string()).await?;
+		match answer.parse() {
+			Ok(number) => { return Ok(number) }
+			Err(_) => { println!("{answer} isn't a number") }
+		}
+	}
+}
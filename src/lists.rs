@@ -0,0 +1,78 @@
+//! operator provided lists of packages and owners, for the blocklist and allowlist
+
+use crate::db::PackageNew;
+use crate::forge::RepoLocation;
+use crate::Result;
+use std::collections::HashSet;
+use std::fs;
+
+/// a list of package names and repository owners, loaded from a plain text
+/// file with one entry per line. blank lines and lines starting with `#` are
+/// ignored. entries match both package names and repository owners, case
+/// insensitively. `package:name` or `owner:name` only matches one or the other.
+/// owners can be forge accounts or email addresses
+#[derive(Clone, Default)]
+pub struct PackageList {
+	packages: HashSet<String>,
+	owners: HashSet<String>
+}
+
+impl PackageList {
+	pub fn load(path: &str) -> Result<Self> {
+		let contents = fs::read_to_string(path)
+			.map_err(|e| format!("error reading list {path}: {e}"))?;
+		Ok(Self::parse(&contents))
+	}
+
+	/// like [`load`](Self::load), but `location` can be a url too, for lists
+	/// shared between bots
+	pub async fn fetch(location: &str) -> Result<Self> {
+		if !location.starts_with("https://") && !location.starts_with("http://") {
+			return Self::load(location)
+		}
+
+		let contents = reqwest::get(location).await
+			.and_then(|res| res.error_for_status())
+			.map_err(|e| format!("error fetching list {location}: {e}"))?
+			.text().await
+			.map_err(|e| format!("error fetching list {location}: {e}"))?;
+		Ok(Self::parse(&contents))
+	}
+
+	pub fn parse(contents: &str) -> Self {
+		let mut list = Self::default();
+
+		for line in contents.lines() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') { continue }
+
+			if let Some(package) = line.strip_prefix("package:") {
+				list.packages.insert(package.trim().to_lowercase());
+			} else if let Some(owner) = line.strip_prefix("owner:") {
+				list.owners.insert(owner.trim().to_lowercase());
+			} else {
+				list.packages.insert(line.to_lowercase());
+				list.owners.insert(line.to_lowercase());
+			}
+		}
+
+		list
+	}
+
+	/// the entries that could be package names
+	pub fn package_names(&self) -> impl Iterator<Item = &str> {
+		self.packages.iter().map(String::as_str)
+	}
+
+	pub fn matches(&self, package: &PackageNew) -> bool {
+		if self.packages.contains(&package.name.to_lowercase()) { return true }
+
+		let repo_owner = RepoLocation::parse(&package.repository.url).map(|repo| repo.owner);
+		let login = package.owner.as_ref().and_then(|owner| owner.login.clone());
+		let email = package.contact_email().map(String::from);
+
+		[repo_owner, login, email].into_iter()
+			.flatten()
+			.any(|owner| self.owners.contains(&owner.to_lowercase()))
+	}
+}
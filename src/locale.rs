@@ -0,0 +1,86 @@
+//! filing issues in the language the maintainer writes in, for the prolific
+//! authors who mostly write japanese or chinese. a package's language is from
+//! `locales.packages`, or its owner's from `locales.owners`, or (with
+//! `locales.detect`) guessed from its readme. it's only used if there are
+//! templates for it in `locales.templates`, otherwise it's the usual ones
+
+use crate::db::PackageNew;
+use crate::forge::RepoLocation;
+use crate::templates::Templates;
+use std::collections::{ BTreeMap, HashMap };
+
+/// less of a readme than this in another script and it's counted as english,
+/// since plenty of english ones have a bit of something else in them
+const MIN_SHARE: f64 = 0.1;
+
+#[derive(Clone, Default)]
+pub struct LocaleConfig {
+	/// by locale, like `ja`
+	pub templates: BTreeMap<String, Templates>,
+	/// package name to locale
+	pub packages: HashMap<String, String>,
+	/// lowercase repository owner to locale
+	pub owners: HashMap<String, String>,
+	/// whether to guess from readmes for packages that aren't in either
+	pub detect: bool
+}
+
+impl LocaleConfig {
+	/// what an issue on `repo` for `packages` should be written in, if there are
+	/// templates for it. the first package that says decides, for grouped issues
+	pub fn for_issue(&self, repo: &RepoLocation, packages: &[PackageNew]) -> Option<&str> {
+		let configured = packages.iter().find_map(|package| self.packages.get(&package.name))
+			.or_else(|| self.owners.get(&repo.owner.to_lowercase()))
+			.map(String::as_str);
+		let detected = || match self.detect {
+			true => { packages.iter().find_map(|package| package.readme_locale.as_deref()) }
+			false => { None }
+		};
+		let locale = configured.or_else(detected)?;
+		self.templates.get_key_value(locale).map(|(locale, _)| locale.as_str())
+	}
+}
+
+/// the language `text` (a readme) seems to be written in, going by its script:
+/// `ja`, `zh`, or `ko`, or none for anything written in latin letters
+pub fn detect(text: &str) -> Option<&'static str> {
+	let (mut letters, mut kana, mut han, mut hangul) = (0, 0, 0, 0);
+	for c in text.chars().filter(|c| c.is_alphabetic()) {
+		letters += 1;
+		match c {
+			'\u{3040}'..='\u{30ff}' | '\u{31f0}'..='\u{31ff}' | '\u{ff66}'..='\u{ff9f}' => { kana += 1 }
+			'\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{f900}'..='\u{faff}' => { han += 1 }
+			'\u{1100}'..='\u{11ff}' | '\u{3130}'..='\u{318f}' | '\u{ac00}'..='\u{d7af}' => { hangul += 1 }
+			_ => {}
+		}
+	}
+	let cjk = kana + han + hangul;
+	if cjk == 0 || (cjk as f64) < letters as f64 * MIN_SHARE { return None }
+
+	// japanese is written with kanji (han) too, but there's always kana between them
+	if kana * 10 >= cjk { return Some("ja") }
+	match hangul > han {
+		true => { Some("ko") }
+		false => { Some("zh") }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn detects_the_readmes_language() {
+		for (readme, locale) in [
+			("# linter-eslint\n\nLints JavaScript on the fly, using ESLint.", None),
+			("# 日本語-syntax\n\nAtomで日本語のシンタックスハイライトを使えるようにするパッケージです。\n\n```js\nconst x = 1\n```", Some("ja")),
+			("# 中文-syntax\n\n为 Atom 提供中文语法高亮的插件。安装后重启编辑器即可。", Some("zh")),
+			("# 한국어\n\nAtom에서 한국어 맞춤법을 검사하는 패키지입니다.", Some("ko")),
+			// an english readme that happens to have an example in japanese in it
+			("# translate\n\nTranslates the selection into whatever language you would like it to be in, like so:\n\n> こんにちは\n\nand a lot more words after that to make it clearly english", None),
+			("", None)
+		] {
+			assert_eq!(detect(readme), locale, "{readme}");
+		}
+	}
+}
@@ -0,0 +1,304 @@
+//! what happens along the way, logged with [`tracing`] (its `error!`, `warn!`,
+//! `info!`, and `debug!` are used everywhere). what commands are asked for (like
+//! `status` or `list`) is always printed with `println!` instead. how much gets
+//! logged is picked with `-v`, `-vv`, and `--quiet`, or `RUST_LOG` for more
+//! control, like `RUST_LOG=pulsar_migrator_issue_bot::registry=debug`. it can
+//! go to a file too (`log.file` in the config), which is rotated so it doesn't
+//! grow forever, see [`LogFile`]
+
+use chrono::{ DateTime, Local, NaiveDate };
+use std::fs::{ self, File, OpenOptions };
+use std::io::{ self, Write };
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{ EnvFilter, Layer, Registry };
+
+lazy_static! {
+	/// where log lines go besides stdout, once the config's been loaded
+	static ref FILE: Mutex<Option<RotatingFile>> = Mutex::new(None);
+}
+
+/// how log lines look
+#[derive(Clone, Copy)]
+pub enum LogFormat {
+	/// a line of text, with the package (and so on) it's about after it
+	Text,
+	/// one json object per line, with every field, for log collectors
+	Json
+}
+
+impl FromStr for LogFormat {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_lowercase().as_str() {
+			"text" => { Ok(Self::Text) }
+			"json" => { Ok(Self::Json) }
+			_ => { Err(format!("unknown log format {s} (expected text or json)")) }
+		}
+	}
+}
+
+/// a file logs are written to, and how it's kept from getting too big. the old
+/// ones are renamed to `<path>.1` (the newest), `<path>.2`, and so on
+#[derive(Clone, PartialEq)]
+pub struct LogFile {
+	pub path: String,
+	pub rotate: LogRotation,
+	/// how many old ones are kept, the oldest is deleted when there'd be more
+	pub keep: usize
+}
+
+/// when a log file is started again
+#[derive(Clone, Copy, PartialEq)]
+pub enum LogRotation {
+	/// at midnight (local time)
+	Daily,
+	/// when it would get bigger than this many bytes
+	Size(u64)
+}
+
+impl FromStr for LogRotation {
+	type Err = String;
+
+	/// `daily`, or a size like `10MB`, `500KB`, or `1GB` (or plain bytes)
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim().to_uppercase();
+		if s == "DAILY" {
+			return Ok(Self::Daily)
+		}
+
+		let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+		let multiplier = match unit.trim() {
+			"" | "B" => { 1 }
+			"KB" => { 1024 }
+			"MB" => { 1024 * 1024 }
+			"GB" => { 1024 * 1024 * 1024 }
+			_ => { return Err(format!("can't tell what {s} means (expected daily, or a size like 10MB)")) }
+		};
+		match number.parse::<u64>() {
+			Ok(number) if number > 0 => { Ok(Self::Size(number * multiplier)) }
+			_ => { Err(format!("can't tell what {s} means (expected daily, or a size like 10MB)")) }
+		}
+	}
+}
+
+/// starts logging, with the level from the command line flags: `--quiet`,
+/// nothing, `-v`, or `-vv` (or more, which logs every http request too).
+/// `RUST_LOG` is used instead if it's set. nothing goes to a file until
+/// [`log_to_file`] is called, and `--quiet` doesn't apply to it, so it still
+/// has what quiet runs did
+pub fn init(quiet: bool, verbose: u8, format: LogFormat) {
+	let filter = |quiet: bool| {
+		let directives = match (quiet, verbose) {
+			(true, _) => { "warn" }
+			(false, 0) => { "warn,pulsar_migrator_issue_bot=info" }
+			(false, 1) => { "warn,pulsar_migrator_issue_bot=debug" }
+			// reqwest (which octocrab uses too) logs every request it makes
+			(false, _) => { "warn,pulsar_migrator_issue_bot=trace,reqwest=trace" }
+		};
+		EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(directives))
+	};
+
+	let layers: Vec<Box<dyn Layer<Registry> + Send + Sync>> = match format {
+		LogFormat::Text => {
+			vec![
+				tracing_subscriber::fmt::layer()
+					.without_time()
+					.with_target(false)
+					.with_ansi(false)
+					.with_writer(AboveProgressBar)
+					.with_filter(filter(quiet))
+					.boxed(),
+				// with the time, since it's kept around
+				tracing_subscriber::fmt::layer()
+					.with_target(false)
+					.with_ansi(false)
+					.with_writer(ToFile)
+					.with_filter(filter(false))
+					.boxed(),
+				sentry_layer(),
+				crate::traces::layer()
+			]
+		}
+		LogFormat::Json => {
+			vec![
+				tracing_subscriber::fmt::layer()
+					.json()
+					.with_current_span(true)
+					.with_span_list(false)
+					.with_writer(AboveProgressBar)
+					.with_filter(filter(quiet))
+					.boxed(),
+				tracing_subscriber::fmt::layer()
+					.json()
+					.with_current_span(true)
+					.with_span_list(false)
+					.with_writer(ToFile)
+					.with_filter(filter(false))
+					.boxed(),
+				sentry_layer(),
+				crate::traces::layer()
+			]
+		}
+	};
+	// this passes on what's logged with the `log` crate too, which reqwest uses
+	let _ = tracing_subscriber::registry().with(layers).try_init();
+}
+
+/// something worth knowing about how the bot's set up, like `forges.simulate`.
+/// it's on stderr, so it doesn't end up in what commands print as json, unless
+/// logs are json too, when it's logged like everything else, all on stdout
+pub fn notice(format: LogFormat, message: &str) {
+	match format {
+		LogFormat::Text => { eprintln!("{message}") }
+		LogFormat::Json => { info!("{message}") }
+	}
+}
+
+/// errors, and what the bot did before them for context, for [`crate::reporting`]
+fn sentry_layer() -> Box<dyn Layer<Registry> + Send + Sync> {
+	crate::reporting::layer()
+		.with_filter(EnvFilter::new("warn,pulsar_migrator_issue_bot=debug"))
+		.boxed()
+}
+
+/// starts writing everything that's logged to `file` too
+pub fn log_to_file(file: &LogFile) -> crate::Result {
+	*FILE.lock().unwrap() = Some(RotatingFile::open(file.clone())?);
+	Ok(())
+}
+
+/// writes log lines with [`crate::progress::print_line`], so they don't get mixed
+/// up with the progress bar
+struct AboveProgressBar;
+
+impl<'a> MakeWriter<'a> for AboveProgressBar {
+	type Writer = LineWriter;
+
+	fn make_writer(&'a self) -> Self::Writer {
+		LineWriter(Vec::new())
+	}
+}
+
+/// one log line, printed when it's dropped
+struct LineWriter(Vec<u8>);
+
+impl Write for LineWriter {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.0.extend_from_slice(buf);
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+impl Drop for LineWriter {
+	fn drop(&mut self) {
+		let line = String::from_utf8_lossy(&self.0);
+		let line = line.trim_end_matches('\n');
+		if !line.is_empty() {
+			crate::progress::print_line(line.into());
+		}
+	}
+}
+
+/// writes log lines to the [`log_to_file`] file, if there is one
+struct ToFile;
+
+impl<'a> MakeWriter<'a> for ToFile {
+	type Writer = FileLineWriter;
+
+	fn make_writer(&'a self) -> Self::Writer {
+		FileLineWriter(Vec::new())
+	}
+}
+
+/// one log line, written to the file when it's dropped
+struct FileLineWriter(Vec<u8>);
+
+impl Write for FileLineWriter {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.0.extend_from_slice(buf);
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+impl Drop for FileLineWriter {
+	fn drop(&mut self) {
+		let mut file = FILE.lock().unwrap();
+		if let Some(file) = file.as_mut() {
+			if let Err(e) = file.write_line(&self.0) {
+				// not logged, that'd come straight back here
+				eprintln!("error writing log file {}: {e}", file.config.path);
+			}
+		}
+	}
+}
+
+struct RotatingFile {
+	config: LogFile,
+	file: File,
+	size: u64,
+	opened_on: NaiveDate
+}
+
+impl RotatingFile {
+	fn open(config: LogFile) -> crate::Result<Self> {
+		if let Some(dir) = Path::new(&config.path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+			fs::create_dir_all(dir).map_err(|e| format!("error creating log directory {}: {e}", dir.display()))?;
+		}
+		let file = OpenOptions::new().create(true).append(true).open(&config.path)
+			.map_err(|e| format!("error opening log file {}: {e}", config.path))?;
+		let size = file.metadata()?.len();
+		// a file left from before is rotated the first time something's written on another day
+		let opened_on = file.metadata()?.modified()
+			.map(|modified| DateTime::<Local>::from(modified).naive_local().date())
+			.unwrap_or_else(|_| Local::now().naive_local().date());
+
+		Ok(Self { config, file, size, opened_on })
+	}
+
+	fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+		let rotate = match self.config.rotate {
+			LogRotation::Daily => { Local::now().naive_local().date() != self.opened_on }
+			LogRotation::Size(max) => { self.size > 0 && self.size + line.len() as u64 > max }
+		};
+		if rotate {
+			self.rotate()?;
+		}
+
+		self.file.write_all(line)?;
+		self.size += line.len() as u64;
+		Ok(())
+	}
+
+	/// `<path>.1` becomes `<path>.2` and so on, the current one becomes `<path>.1`,
+	/// and a new one is started
+	fn rotate(&mut self) -> io::Result<()> {
+		let path = &self.config.path;
+		let old = |n: usize| format!("{path}.{n}");
+		let _ = fs::remove_file(old(self.config.keep));
+		for n in (1..self.config.keep).rev() {
+			let _ = fs::rename(old(n), old(n + 1));
+		}
+		match self.config.keep {
+			0 => { fs::remove_file(path)? }
+			_ => { fs::rename(path, old(1))? }
+		}
+
+		self.file = OpenOptions::new().create(true).append(true).open(path)?;
+		self.size = 0;
+		self.opened_on = Local::now().naive_local().date();
+		Ok(())
+	}
+}
@@ -3,43 +3,360 @@
 
 #[macro_use]
 extern crate lazy_static;
+// `error!`, `warn!`, `info!`, and `debug!`, see logging.rs
+#[macro_use]
+extern crate tracing;
+
+// first, so its `println!` is the one everything else uses
+#[macro_use]
+mod progress;
 
+use chrono::Utc;
 use clap::{ Parser, Subcommand };
 use dotenvy::dotenv;
-use std::env::var;
+use sentry::{ Hub, SentryFutureExt };
 use std::path::PathBuf;
 use tokio::fs;
 
+mod activity;
+mod analytics;
+mod api;
+mod archive;
+mod breaker;
+mod claims;
 mod cli;
+mod config;
+mod consent;
+mod control;
+mod crash;
+mod dashboard;
 mod db;
+mod deliveries;
+mod diff;
+mod digest;
+mod doctor;
+mod eligibility;
+mod email;
+mod error;
+mod events;
+mod feed;
+mod filing;
+mod fixtures;
+mod forget;
+mod forge;
 mod github;
+mod gitlab;
+mod grafana;
+mod graph;
+mod health;
+mod http;
+mod import;
+mod init;
+mod lists;
+mod locale;
+mod logging;
+mod mark;
+mod metrics;
+mod notify;
+mod offline;
+mod plan;
+mod preflight;
+mod pulsar;
+mod queue;
+mod random;
+mod reconcile;
+mod registry;
+mod reminders;
+mod repo_url;
+mod report;
+mod reporting;
+mod responses;
+mod retry;
+mod rollback;
+mod runs;
+mod sandbox;
+mod secrets;
+mod simulated;
+mod staging;
+mod status;
+mod systemd;
+mod templates;
+mod traces;
+mod usage;
+mod validate;
+mod webhook;
+mod window;
 
-use cli::Cli;
+use cli::{ Cli, ConfigWatch, RunOptions };
+use config::Config;
+use control::RunControl;
+use dashboard::Dashboard;
 use db::DatabaseThing;
+use email::Mailer;
+use forge::{ ForgeKind, Forges };
+use github::GithubClient;
+use status::OutputFormat;
+
+pub use error::Error;
 
-pub type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+pub type Result<T = ()> = std::result::Result<T, Error>;
 
 #[tokio::main]
 async fn main() -> Result {
 	let _ = dotenv();
 
-	let db_filename = "state.ron";
-	let token = var("GITHUB_TOKEN")
-		.map_err(|e| format!("error in fetching GITHUB_TOKEN: {}", e))?;
-
 	let cli = Cli::parse();
-	let db = DatabaseThing::new("state.ron").await?;
+	logging::init(cli.quiet, cli.verbose, cli.log_format);
+	random::init(cli.seed);
+	// these don't need the config, and doctor reports one that doesn't load instead of stopping at it
+	match cli.command {
+		cli::Subcommands::Init => { return init::run(cli.config.as_deref()).await }
+		cli::Subcommands::Doctor => { return doctor::run(cli.config.as_deref(), cli.profile.as_deref()).await }
+		cli::Subcommands::ValidateConfig => { return validate::run(cli.config.as_deref(), cli.profile.as_deref()) }
+		cli::Subcommands::Completions { shell } => {
+			cli::print_completions(shell);
+			return Ok(())
+		}
+		_ => {}
+	}
+	let mut config = Config::load(cli.config.as_deref(), cli.profile.as_deref())?;
+	if let Some(log_file) = &config.log_file {
+		logging::log_to_file(log_file)?;
+	}
+	// kept until the end, since dropping it sends what's left
+	let _sentry = reporting::init(&config);
+	let _traces = traces::init(&config)?;
+	if let Some(profile) = &config.profile {
+		logging::notice(cli.log_format, &format!("using profile {profile}"));
+	}
+
+	let fixtures = match (&cli.record, &cli.replay) {
+		(Some(dir), _) => { Some((fixtures::Mode::Record, dir)) }
+		(None, Some(dir)) => { Some((fixtures::Mode::Replay, dir)) }
+		(None, None) => { None }
+	};
+	if let Some((mode, dir)) = fixtures {
+		config.use_fixtures(fixtures::start(mode, dir).await?);
+	}
+	if let Some(org) = &cli.sandbox {
+		config.use_sandbox(org.clone());
+		logging::notice(cli.log_format, &format!("--sandbox is set, so every issue is filed in a repository in {}/{org} instead, and the database is {}", sandbox::HOST, config.db_path));
+	}
+
+	if let Some(failure_rate) = config.simulate_forges {
+		logging::notice(cli.log_format, &format!("forges.simulate is set, so forges are only pretend, and {}% of requests to them fail", failure_rate * 100.0));
+	}
+
+	activity::init(config.activity_log.clone());
+	// only the commands that save it start over from a backup, the rest stop at why it didn't load
+	let db = match cli.command.reads_only() {
+		true => { DatabaseThing::open_existing(&config.db_path).await? }
+		false => { DatabaseThing::new_or_recover(&config.db_path).await? }
+	};
+	db.set_durability(config.db_durability);
+	crash::install(&db, &config.db_path);
+	control::handle_shutdown(&db, config.stop_timeout);
 
+	let webhook = match config.webhook_url.clone() {
+		Some(url) => { Some(webhook::spawn(&db, url)?) }
+		None => { None }
+	};
+	let notifications = notify::spawn(&db, &config);
+
+	// only for the commands that run for a while, so they don't fight over the port
+	let long_running = matches!(cli.command, cli::Subcommands::Daemon { .. } | cli::Subcommands::File { .. } | cli::Subcommands::Start { .. });
+	if long_running {
+		crash::warn_about_last(&config.db_path);
+	}
+	let github = || github_client(&config);
+	if long_running {
+		control::snapshot_on_signal(&db, github()?, config.daily_issue_limit);
+	}
+	if let Some(listen) = config.metrics_listen.as_ref().filter(|_| long_running) {
+		if let Err(e) = metrics::serve(&db, listen, github()?).await {
+			warn!("{e}, carrying on without metrics");
+		}
+	}
+	if let Some(api) = config.api.as_ref().filter(|_| long_running) {
+		if let Err(e) = api::serve(&db, api).await {
+			warn!("{e}, carrying on without the api");
+		}
+	}
+	if config.github_webhooks.is_some() && long_running {
+		if let Err(e) = events::serve(&db, &config, github()?).await {
+			warn!("{e}, carrying on without github's webhooks");
+		}
+	}
+
+	// so everything (like breadcrumbs) is seen by the same hub, whatever thread it's polled on
+	let res = run(cli, &db, config).bind_hub(Hub::current()).await;
+	if let Err(e) = &res {
+		sentry::capture_error(e);
+	}
+
+	db.stop_state_changes();
+	if let Some(webhook) = webhook {
+		webhook::finish(webhook).await;
+	}
+	if let Some(notifications) = notifications {
+		notify::finish(notifications).await;
+	}
+
+	res
+}
+
+/// a client for github.com, if there's a token for it (and forges aren't simulated)
+fn github_client(config: &Config) -> Result<Option<GithubClient>> {
+	match (&config.github_token, config.simulate_forges) {
+		(Some(token), None) => {
+			let api_url = config.forge_api_url("github.com", ForgeKind::GitHub);
+			Ok(Some(GithubClient::with_api_url("github.com", token.expose(), api_url.as_deref())?))
+		}
+		_ => { Ok(None) }
+	}
+}
+
+async fn run(cli: Cli, db: &DatabaseThing, config: Config) -> Result {
 	use cli::Subcommands::*;
 	match cli.command {
 		ReadPackageData { files } => {
-			cli::read_package_data(db, files).await?;
+			cli::read_package_data(db.clone(), &config, files).await?;
+		}
+		Sync { full, from_dump, packages } => {
+			cli::sync_registry(db, &config, full, from_dump, packages).await?;
+		}
+		File { args } => {
+			let mailer = Mailer::new(&config)?;
+			let mut control = RunControl::new();
+			let dashboard = match &args.dashboard {
+				Some(log) => { Some(Dashboard::new(db, &config, &control, log)?) }
+				None => { None }
+			};
+			let mut forges = Forges::new(config);
+			forges.check_tokens().await?;
+			let options = args.into_options();
+			let run = cli::file(db, &mut forges, &mailer, &mut control, &options);
+			match dashboard {
+				Some(dashboard) => { dashboard.run(run).await? }
+				None => { run.await?; }
+			}
+		}
+		Poll { dry_run } => {
+			let mut forges = Forges::new(config);
+			forges.check_tokens().await?;
+			cli::poll(db, &mut forges, dry_run).await?;
+		}
+		PollResponses => {
+			responses::poll(db, &mut Forges::new(config)).await?;
+		}
+		ReplayWebhooks { dry_run } => {
+			if dry_run { db.set_read_only() }
+			events::replay(db, &config, github_client(&config)?, dry_run).await?;
+		}
+		Start { args } => {
+			let mailer = Mailer::new(&config)?;
+			let mut control = RunControl::new();
+			let dashboard = match &args.dashboard {
+				Some(log) => { Some(Dashboard::new(db, &config, &control, log)?) }
+				None => { None }
+			};
+			let mut forges = Forges::new(config);
+			forges.check_tokens().await?;
+			let options = args.into_options();
+			let run = cli::start(db, &mut forges, &mailer, &mut control, &options);
+			match dashboard {
+				Some(dashboard) => { dashboard.run(run).await? }
+				None => { run.await?; }
+			}
+		}
+		Init | Doctor | ValidateConfig | Completions { .. } => { unreachable!("these are run before the config is loaded") }
+		Status { format } => {
+			db.set_read_only();
+			if !db.exists() && format == OutputFormat::Text {
+				println!("no state yet, {} is made by the first sync", config.db_path);
+				return Ok(())
+			}
+			status::print(db, &mut Forges::new(config), format).await?;
+		}
+		Retry { state, dry_run, packages } => {
+			let mailer = Mailer::new(&config)?;
+			let mut control = RunControl::new();
+			let mut forges = Forges::new(config);
+			forges.check_tokens().await?;
+			cli::retry(db, &mut forges, &mailer, &mut control, &state, &packages, dry_run).await?;
+		}
+		List { state, min_downloads, min_stars, search, owner, sort, limit, format } => {
+			db.set_read_only();
+			status::list(db, &status::ListFilter { state, min_downloads, min_stars, search, owner, sort, limit, format })?;
+		}
+		Analytics { format } => {
+			db.set_read_only();
+			analytics::run(db, &runs::load(&config.db_path).await?, format)?;
+		}
+		LastRun { format } => {
+			db.set_read_only();
+			runs::print_last(&config.db_path, format).await?;
+		}
+		Show { package, format } => {
+			db.set_read_only();
+			status::show(db, &package, format)?;
+		}
+		Publish { draft } => {
+			staging::publish(db, &mut Forges::new(config), &draft).await?;
+		}
+		Preview { package } => {
+			plan::preview(db, &config, &package)?;
+		}
+		Plan { count } => {
+			plan::run(db, &config, count).await?;
+		}
+		Approve { package, approval_url, at } => {
+			consent::approve(db, &config, &package, approval_url, at.unwrap_or_else(Utc::now)).await?;
+		}
+		Revoke { package, revocation_url, at } => {
+			consent::revoke(db, &package, revocation_url, at.unwrap_or_else(Utc::now))?;
+		}
+		Mark { package, state, note } => {
+			mark::run(db, &package, &state, note)?;
+		}
+		Forget { owner, dry_run } => {
+			forget::run(db, &owner, dry_run).await?;
+		}
+		Export { output, licenses } => {
+			db.set_read_only();
+			consent::export(db, output, &licenses).await?;
+		}
+		Graph { format, output, state, owner, min_packages } => {
+			db.set_read_only();
+			graph::run(db, format, output, state, owner, min_packages).await?;
+		}
+		Archive { output } => {
+			// so what's archived is everything up to now
+			db.flush();
+			db.set_read_only();
+			archive::run(db, &config, output).await?;
+		}
+		Rollback { since, dry_run } => {
+			rollback::run(db, &mut Forges::new(config), since, dry_run).await?;
+		}
+		Import { issues, owner, titles, dry_run } => {
+			import::run(db, &mut Forges::new(config), issues, owner, titles, dry_run).await?;
+		}
+		Reconcile { sample, dry_run } => {
+			reconcile::run(db, &mut Forges::new(config), sample, dry_run).await?;
+		}
+		Digest { now, dry_run } => {
+			let mailer = Mailer::new(&config)?;
+			digest::send_if_due(db, &config, &mailer, now, dry_run).await?;
 		}
-		Start => {
-			// cli::start(db).await;
-			let gh = github::GithubClient::new(&token, db)?;
-			let url = gh.create_permission_request_issue("autumnblazey", "pulsar-migrator-issue-bot").await?;
-			println!("{url}");
+		Daemon { batch_every, poll_every, sync_every, max_issues } => {
+			let mut mailer = Mailer::new(&config)?;
+			let options = RunOptions { dry_run: false, interactive: false, max_issues, max_runtime: None, only: None, draft: None, diff: None, check_diff: None, strict: false };
+			let watch = ConfigWatch::new(cli.config.as_deref(), cli.profile.as_deref(), &config);
+			let mut forges = Forges::new(config);
+			forges.check_tokens().await?;
+			let schedule = cli::Schedule { sync_every, poll_every, batch_every };
+			cli::daemon(db, &mut forges, &mut mailer, watch, &schedule, &options).await?;
 		}
 	}
 
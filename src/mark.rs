@@ -0,0 +1,82 @@
+//! setting a package's state by hand, for things that happened outside the bot,
+//! like a maintainer saying yes by email, or a repository being dealt with by hand
+
+use crate::db::{ DatabaseThing, PackageAlreadyMigrated, PackageApproved, PackageDeferred, PackageMigrated, PackageNeedsReview, PackageSkipped, PackageState };
+use crate::Result;
+use chrono::Utc;
+
+/// the states that can be set by hand. the rest need things only the bot
+/// knows, like an issue it filed, or have their own subcommand
+pub const MARKABLE_STATES: &[&str] = &[
+	"New",
+	"Deferred",
+	"SkippedDeprecated",
+	"NeedsReview",
+	"AlreadyMigrated",
+	"Blocked",
+	"OptedOut",
+	"MaintainerApproved",
+	"Migrated"
+];
+
+/// sets a package to `state` (a [`PackageState::kind`], in any case), and adds
+/// it to its history with `note`
+pub fn run(db: &DatabaseThing, package: &str, state: &str, note: Option<String>) -> Result {
+	let current = db.package_state(package)
+		.ok_or_else(|| format!("package {package} is not in the database"))?;
+	let note = note.filter(|note| !note.trim().is_empty()).unwrap_or_else(|| "marked by hand".into());
+	let now = Utc::now();
+
+	let kind = MARKABLE_STATES.iter()
+		.find(|kind| kind.eq_ignore_ascii_case(state))
+		.ok_or_else(|| match state.to_ascii_lowercase().as_str() {
+			"consentrevoked" => { "use `revoke` to record permission being taken back".to_string() }
+			_ => { format!("can't mark a package as {state} (expected one of {})", MARKABLE_STATES.join(", ")) }
+		})?;
+
+	let state = match *kind {
+		"New" => { PackageState::New }
+		"Deferred" => { PackageState::Deferred(PackageDeferred { reason: note.clone(), deferred_at: now }) }
+		"SkippedDeprecated" => { PackageState::SkippedDeprecated(PackageSkipped { reason: note.clone(), skipped_at: now, contact_failure: None }) }
+		"NeedsReview" => { PackageState::NeedsReview(PackageNeedsReview { reason: note.clone(), flagged_at: now, contact_failure: None }) }
+		"AlreadyMigrated" => { PackageState::AlreadyMigrated(PackageAlreadyMigrated { found_at: now }) }
+		"Blocked" => { PackageState::Blocked(PackageSkipped { reason: note.clone(), skipped_at: now, contact_failure: None }) }
+		"OptedOut" => { PackageState::OptedOut(PackageSkipped { reason: note.clone(), skipped_at: now, contact_failure: None }) }
+		"MaintainerApproved" => { PackageState::MaintainerApproved(approval(&current, &note)) }
+		"Migrated" => {
+			let approval = match &current {
+				PackageState::MaintainerApproved(approved) => { approved.clone() }
+				// registered by hand without the bot ever asking, so the note is all there is
+				_ => { approval(&current, &note) }
+			};
+			PackageState::Migrated(PackageMigrated { approval, migrated_at: now, backend_response: note.clone() })
+		}
+		_ => { unreachable!("every markable state is handled") }
+	};
+
+	println!("package {package}: {} -> {} ({note})", current.name(), state.name());
+	db.set_state_by_hand(package, state, note)?;
+	db.flush();
+
+	Ok(())
+}
+
+/// permission given somewhere the bot can't see, like an email. it was asked
+/// for on the issue (or at the address) the bot contacted, if it did
+fn approval(current: &PackageState, note: &str) -> PackageApproved {
+	let now = Utc::now();
+	let (issue_url, email, contacted_at) = match current {
+		PackageState::IssueFiled(filed) => { (Some(filed.issue_url.clone()), None, filed.filed_at) }
+		PackageState::ContactedByEmail(contacted) => { (None, Some(contacted.email.clone()), contacted.contacted_at) }
+		_ => { (None, None, now) }
+	};
+
+	PackageApproved {
+		issue_url,
+		email,
+		contacted_at,
+		approval_url: note.into(),
+		approved_at: now,
+		registration_error: None
+	}
+}
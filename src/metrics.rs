@@ -0,0 +1,263 @@
+//! prometheus metrics, served on `/metrics` at `metrics.listen` while the bot
+//! runs, for putting it on a grafana dashboard. counters start from 0 every
+//! time the bot does, which prometheus deals with. and daily numbers for
+//! grafana's json datasource, on `/grafana` (see [`crate::grafana`]), and
+//! health checks on `/healthz` and `/readyz` (see [`crate::health`])
+
+use crate::db::{ DatabaseThing, PackageState, RunRecord };
+use crate::error::{ Class, Failure };
+use crate::github::GithubClient;
+use crate::grafana;
+use crate::health;
+use crate::http::{ self, Request };
+use crate::Result;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::net::{ TcpListener, TcpStream };
+use tokio::time;
+
+/// bytes, for the headers and body together
+const MAX_REQUEST: usize = 64 * 1024;
+
+/// upper bounds of the histogram buckets (api latency, and saving the database), in seconds
+const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+lazy_static! {
+	static ref METRICS: Mutex<Metrics> = Mutex::new(Metrics::default());
+}
+
+#[derive(Default)]
+struct Metrics {
+	issues_filed: u64,
+	approvals: u64,
+	/// by [`Failure::name`] and [`Class::name`]
+	failures: BTreeMap<(&'static str, &'static str), u64>,
+	/// github webhook deliveries turned away, by [`crate::events`]' reason
+	webhooks_rejected: BTreeMap<&'static str, u64>,
+	/// github.com's, checked every minute
+	rate_limit: Option<(u32, u32)>,
+	/// by api (`github`, `gitlab`, `registry`, ...) and what was asked for
+	latency: BTreeMap<(&'static str, &'static str), Histogram>,
+	/// bytes, the last time it was saved
+	db_size: Option<usize>,
+	/// how long turning the database into what's saved took
+	db_serialize: Histogram
+}
+
+#[derive(Default)]
+struct Histogram {
+	/// how many were at most each of [`LATENCY_BUCKETS`]
+	buckets: [u64; LATENCY_BUCKETS.len()],
+	sum: f64,
+	count: u64
+}
+
+/// counts what a package changing state means, from [`DatabaseThing::set_state`]
+pub fn state_changed(state: &PackageState) {
+	let mut metrics = METRICS.lock().unwrap();
+	match state {
+		PackageState::IssueFiled(_) => { metrics.issues_filed += 1 }
+		PackageState::MaintainerApproved(_) => { metrics.approvals += 1 }
+		_ => {}
+	}
+}
+
+/// counts something that went wrong contacting a package
+pub fn failed(failure: Failure, class: Class) {
+	*METRICS.lock().unwrap().failures.entry((failure.name(), class.name())).or_default() += 1;
+}
+
+/// counts a github webhook delivery that was turned away, like for not being signed
+pub fn webhook_rejected(reason: &'static str) {
+	*METRICS.lock().unwrap().webhooks_rejected.entry(reason).or_default() += 1;
+}
+
+/// records how long a request to `api` for `endpoint` took
+pub fn observe_latency(api: &'static str, endpoint: &'static str, took: Duration) {
+	METRICS.lock().unwrap().latency.entry((api, endpoint)).or_default().observe(took);
+}
+
+impl Histogram {
+	fn observe(&mut self, took: Duration) {
+		let seconds = took.as_secs_f64();
+		for (bucket, le) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+			if seconds <= *le {
+				*bucket += 1;
+			}
+		}
+		self.sum += seconds;
+		self.count += 1;
+	}
+
+	/// the `_bucket`, `_sum`, and `_count` lines, with `labels` (like `api="github"`)
+	fn write(&self, out: &mut String, name: &str, labels: &str) -> std::fmt::Result {
+		let (bucket_labels, labels) = match labels {
+			"" => { (String::new(), String::new()) }
+			labels => { (format!("{labels},"), format!("{{{labels}}}")) }
+		};
+		for (count, le) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+			writeln!(out, "{name}_bucket{{{bucket_labels}le=\"{le}\"}} {count}")?;
+		}
+		writeln!(out, "{name}_bucket{{{bucket_labels}le=\"+Inf\"}} {}", self.count)?;
+		writeln!(out, "{name}_sum{labels} {}", self.sum)?;
+		writeln!(out, "{name}_count{labels} {}", self.count)
+	}
+}
+
+/// records the database being saved, from [`DatabaseThing::flush`]
+pub fn database_saved(size: usize, serializing_took: Duration) {
+	let mut metrics = METRICS.lock().unwrap();
+	metrics.db_size = Some(size);
+	metrics.db_serialize.observe(serializing_took);
+}
+
+/// starts serving metrics on `listen` (like `127.0.0.1:9184`), in the background
+/// for as long as the bot runs
+pub async fn serve(db: &DatabaseThing, listen: &str, github: Option<GithubClient>) -> Result {
+	let listener = TcpListener::bind(listen).await
+		.map_err(|e| format!("error listening for metrics on {listen}: {e}"))?;
+	info!("serving metrics on http://{listen}/metrics");
+
+	if let Some(github) = github {
+		tokio::spawn(check_rate_limit(github));
+	}
+
+	let db = db.clone();
+	tokio::spawn(async move {
+		loop {
+			let stream = match listener.accept().await {
+				Ok((stream, _)) => { stream }
+				Err(e) => {
+					warn!("error accepting a metrics connection: {e}");
+					continue
+				}
+			};
+			let db = db.clone();
+			tokio::spawn(async move {
+				if let Err(e) = respond(stream, &db).await {
+					debug!("error answering a metrics request: {e}");
+				}
+			});
+		}
+	});
+
+	Ok(())
+}
+
+/// checks the rate limit every minute, since checking doesn't use any of it up
+async fn check_rate_limit(github: GithubClient) {
+	let mut interval = time::interval(Duration::from_secs(60));
+	loop {
+		interval.tick().await;
+		match github.rate_limit().await {
+			Ok(rate_limit) => {
+				METRICS.lock().unwrap().rate_limit = Some((rate_limit.remaining, rate_limit.limit));
+				health::github_checked(None);
+			}
+			Err(e) => {
+				debug!("error checking the rate limit for metrics: {e}");
+				// octocrab's errors go on with a backtrace
+				health::github_checked(Some(e.to_string().lines().next().unwrap_or_default().to_string()));
+			}
+		}
+	}
+}
+
+/// answers one http request, with the metrics if it's for `/metrics`
+async fn respond(mut stream: TcpStream, db: &DatabaseThing) -> Result {
+	let request = Request::read(&mut stream, MAX_REQUEST).await?;
+	let (method, path) = (request.method.as_str(), request.path.as_str());
+
+	const TEXT: &str = "text/plain; version=0.0.4";
+	// everything that isn't the metrics is json
+	let json = path.strip_prefix("/grafana")
+		.filter(|rest| rest.is_empty() || rest.starts_with('/'))
+		.and_then(|rest| grafana::respond(db, method, rest, &String::from_utf8_lossy(&request.body)))
+		.or_else(|| health::respond(db, path).filter(|_| method == "GET"));
+	let (status, content_type, body) = match (json, method, path) {
+		(Some((status, body)), _, _) => { (status, "application/json", body) }
+		(None, "GET", "/metrics") => { ("200 OK", TEXT, render(db)?) }
+		(None, "GET", _) => { ("404 Not Found", TEXT, "only /metrics, /grafana, /healthz, and /readyz are here\n".to_string()) }
+		(None, _, _) => { ("405 Method Not Allowed", TEXT, "only GET /metrics, /grafana, /healthz, and /readyz work\n".to_string()) }
+	};
+	http::respond(&mut stream, status, content_type, &[], &body).await
+}
+
+/// everything, in prometheus' text format
+fn render(db: &DatabaseThing) -> std::result::Result<String, std::fmt::Error> {
+	let mut states = BTreeMap::<&str, usize>::new();
+	for package in db.packages() {
+		*states.entry(package.state.kind()).or_default() += 1;
+	}
+
+	let runs = db.recent_runs();
+
+	let metrics = METRICS.lock().unwrap();
+	let mut out = String::new();
+
+	writeln!(out, "# HELP pulsar_bot_issues_filed_total issues filed since the bot started")?;
+	writeln!(out, "# TYPE pulsar_bot_issues_filed_total counter")?;
+	writeln!(out, "pulsar_bot_issues_filed_total {}", metrics.issues_filed)?;
+
+	writeln!(out, "# HELP pulsar_bot_approvals_total packages maintainers gave permission for since the bot started")?;
+	writeln!(out, "# TYPE pulsar_bot_approvals_total counter")?;
+	writeln!(out, "pulsar_bot_approvals_total {}", metrics.approvals)?;
+
+	writeln!(out, "# HELP pulsar_bot_failures_total times contacting a package failed since the bot started, by what went wrong and what was done about it")?;
+	writeln!(out, "# TYPE pulsar_bot_failures_total counter")?;
+	for ((kind, class), count) in &metrics.failures {
+		writeln!(out, "pulsar_bot_failures_total{{kind=\"{kind}\",class=\"{class}\"}} {count}")?;
+	}
+
+	writeln!(out, "# HELP pulsar_bot_webhooks_rejected_total github webhook deliveries turned away since the bot started, by why")?;
+	writeln!(out, "# TYPE pulsar_bot_webhooks_rejected_total counter")?;
+	for (reason, count) in &metrics.webhooks_rejected {
+		writeln!(out, "pulsar_bot_webhooks_rejected_total{{reason=\"{reason}\"}} {count}")?;
+	}
+
+	writeln!(out, "# HELP pulsar_bot_packages packages in the database, by state")?;
+	writeln!(out, "# TYPE pulsar_bot_packages gauge")?;
+	for (state, count) in &states {
+		writeln!(out, "pulsar_bot_packages{{state=\"{state}\"}} {count}")?;
+	}
+
+	if let Some((remaining, limit)) = metrics.rate_limit {
+		writeln!(out, "# HELP pulsar_bot_github_rate_limit_remaining github.com api requests left until the limit resets")?;
+		writeln!(out, "# TYPE pulsar_bot_github_rate_limit_remaining gauge")?;
+		writeln!(out, "pulsar_bot_github_rate_limit_remaining {remaining}")?;
+		writeln!(out, "# HELP pulsar_bot_github_rate_limit github.com api requests allowed per hour")?;
+		writeln!(out, "# TYPE pulsar_bot_github_rate_limit gauge")?;
+		writeln!(out, "pulsar_bot_github_rate_limit {limit}")?;
+	}
+
+	writeln!(out, "# HELP pulsar_bot_api_request_duration_seconds how long requests to forges and the registry took")?;
+	writeln!(out, "# TYPE pulsar_bot_api_request_duration_seconds histogram")?;
+	for ((api, endpoint), histogram) in &metrics.latency {
+		histogram.write(&mut out, "pulsar_bot_api_request_duration_seconds", &format!("api=\"{api}\",endpoint=\"{endpoint}\""))?;
+	}
+
+	if let Some(size) = metrics.db_size {
+		writeln!(out, "# HELP pulsar_bot_database_size_bytes how big the database file was the last time it was saved")?;
+		writeln!(out, "# TYPE pulsar_bot_database_size_bytes gauge")?;
+		writeln!(out, "pulsar_bot_database_size_bytes {size}")?;
+	}
+	writeln!(out, "# HELP pulsar_bot_database_serialize_duration_seconds how long turning the database into what's saved took")?;
+	writeln!(out, "# TYPE pulsar_bot_database_serialize_duration_seconds histogram")?;
+	metrics.db_serialize.write(&mut out, "pulsar_bot_database_serialize_duration_seconds", "")?;
+
+	if let (Some(last_run), Some(recent)) = (runs.last(), RunRecord::combined(&runs)) {
+		writeln!(out, "# HELP pulsar_bot_last_run_packages_per_hour packages the last run went through per hour")?;
+		writeln!(out, "# TYPE pulsar_bot_last_run_packages_per_hour gauge")?;
+		writeln!(out, "pulsar_bot_last_run_packages_per_hour {}", last_run.throughput())?;
+		writeln!(out, "# HELP pulsar_bot_last_run_error_rate fraction of the packages the last run tried contacting that failed")?;
+		writeln!(out, "# TYPE pulsar_bot_last_run_error_rate gauge")?;
+		writeln!(out, "pulsar_bot_last_run_error_rate {}", last_run.error_rate())?;
+		writeln!(out, "# HELP pulsar_bot_recent_runs_error_rate fraction of the packages the last few runs tried contacting that failed")?;
+		writeln!(out, "# TYPE pulsar_bot_recent_runs_error_rate gauge")?;
+		writeln!(out, "pulsar_bot_recent_runs_error_rate{{runs=\"{}\"}} {}", runs.len(), recent.error_rate())?;
+	}
+
+	Ok(out)
+}
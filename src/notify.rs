@@ -0,0 +1,134 @@
+//! telling the pulsar team about things worth knowing about in their chat, by
+//! posting to a slack or discord webhook (`notify.url`): runs finishing, a lot
+//! of them failing, and well known packages being approved or declined
+
+use crate::config::Config;
+use crate::db::{ DatabaseThing, PackageState, StateChange };
+use crate::Result;
+use reqwest::Client;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// how many packages a run has to have tried contacting before its failure
+/// rate means anything
+const FAILURE_RATE_MIN_TRIED: usize = 10;
+
+/// where notifications go, and what's worth one
+#[derive(Clone, PartialEq)]
+pub struct NotifyConfig {
+	/// a slack or discord incoming webhook
+	pub url: String,
+	pub chat: Chat,
+	/// fraction (0 to 1) of the packages tried in a run failing that's told about
+	pub failure_rate: f64,
+	/// packages with at least this many downloads are told about when they're approved or declined
+	pub min_downloads: u32
+}
+
+/// what the webhook is for, which changes what gets posted to it
+#[derive(Clone, Copy, PartialEq)]
+pub enum Chat {
+	Slack,
+	Discord
+}
+
+impl Chat {
+	/// going by the webhook's url: discord's are on discord.com, anything else is
+	/// taken to be slack's (which mattermost and others take too)
+	pub fn for_url(url: &str) -> Self {
+		match url::Url::parse(url).ok().as_ref().and_then(url::Url::host_str) {
+			Some("discord.com" | "discordapp.com" | "ptb.discord.com" | "canary.discord.com") => { Self::Discord }
+			_ => { Self::Slack }
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct SlackMessage<'a> {
+	text: &'a str
+}
+
+#[derive(Serialize)]
+struct DiscordMessage<'a> {
+	content: &'a str
+}
+
+/// posts `text` (markdown, with `**bold**`) to the chat if `notify.url` is set.
+/// not being able to isn't worth stopping for, so it's only logged
+pub async fn send(config: &Config, text: &str) {
+	if let Some(notify) = &config.notify {
+		if let Err(e) = post(notify, text).await {
+			error!("error posting notification: {e}");
+		}
+	}
+}
+
+async fn post(notify: &NotifyConfig, text: &str) -> Result {
+	let client = Client::builder()
+		.user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+		.build()?;
+	let request = match notify.chat {
+		// slack's bold is only one *
+		Chat::Slack => { client.post(&notify.url).json(&SlackMessage { text: &text.replace("**", "*") }) }
+		// discord only takes 2000 characters
+		Chat::Discord => { client.post(&notify.url).json(&DiscordMessage { content: &truncate(text, 2000) }) }
+	};
+	// the url is a secret, so it's left out of errors
+	request.send().await
+		.and_then(|res| res.error_for_status())
+		.map_err(|e| e.without_url())?;
+	Ok(())
+}
+
+fn truncate(text: &str, max: usize) -> String {
+	match text.char_indices().nth(max - 1) {
+		Some((end, _)) => { format!("{}…", &text[..end]) }
+		None => { text.into() }
+	}
+}
+
+/// whether `failed` out of `tried` packages failing is enough to tell someone about
+pub fn failure_rate_high(config: &Config, tried: usize, failed: usize) -> bool {
+	match &config.notify {
+		Some(notify) => { tried >= FAILURE_RATE_MIN_TRIED && failed as f64 / tried as f64 >= notify.failure_rate }
+		None => { false }
+	}
+}
+
+/// starts posting about packages with at least `notify.min_downloads` downloads
+/// being approved or declined (opting out, or taking back their permission), in
+/// the background. stopped along with the [`crate::webhook`] one
+pub fn spawn(db: &DatabaseThing, config: &Config) -> Option<JoinHandle<()>> {
+	let notify = config.notify.clone()?;
+	let (tx, mut rx) = mpsc::unbounded_channel::<StateChange>();
+	db.add_state_changes(tx);
+
+	let db = db.clone();
+	Some(tokio::spawn(async move {
+		while let Some(change) = rx.recv().await {
+			let what = match &change.state {
+				PackageState::MaintainerApproved(approved) => { format!("was approved by its maintainer: {}", approved.approval_url) }
+				PackageState::ConsentRevoked(revoked) => { format!("was declined, its maintainer took back their permission: {}", revoked.revocation_url) }
+				PackageState::OptedOut(skipped) => { format!("was declined, its owner opted out: {}", skipped.reason) }
+				_ => { continue }
+			};
+			let downloads = match db.package(&change.package) {
+				Some(package) => { package.info.downloads }
+				None => { continue }
+			};
+			if downloads < notify.min_downloads { continue }
+
+			let text = format!("**{}** ({downloads} downloads) {what}", change.package);
+			if let Err(e) = post(&notify, &text).await {
+				error!("error posting notification about package {}: {e}", change.package);
+			}
+		}
+	}))
+}
+
+/// waits for the notifications already started to be posted, once
+/// [`DatabaseThing::stop_state_changes`] has been called
+pub async fn finish(handle: JoinHandle<()>) {
+	let _ = handle.await;
+}
@@ -0,0 +1,82 @@
+//! noticing the network going away partway through a run. rather than every
+//! package left failing (and waiting half an hour to be retried), the run waits
+//! where it is for the network to come back, then carries on with the package
+//! it was on. the packages it hasn't got to stay as they were, so if it's stopped
+//! while waiting, the next run picks them up like any others
+
+use crate::config::Config;
+use crate::control::RunControl;
+use crate::error::Failure;
+use crate::Error;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time::{ timeout, Instant };
+
+/// how long checking whether a host can be reached waits for it
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// how often it's checked while it can't be, at first, and at most
+const CHECK_EVERY_FIRST: Duration = Duration::from_secs(5);
+const CHECK_EVERY_MAX: Duration = Duration::from_secs(60);
+
+pub enum Outage {
+	/// it wasn't the network being down, so it's a normal failure
+	NotOne,
+	/// it was, and now it's back, so whatever failed can be tried again
+	Over,
+	/// the run was stopped while waiting
+	Stopped
+}
+
+/// keeps track of outages for a run
+#[derive(Default)]
+pub struct Connectivity {
+	last_outage_over: Option<Instant>
+}
+
+impl Connectivity {
+	/// if `error` from a request to `host` (started at `started`) was because the
+	/// network is down, waits until it isn't, checking every so often
+	pub async fn wait_out(&mut self, control: &mut RunControl, config: &Config, host: &str, error: &Error, started: Instant) -> Outage {
+		// the pretend forges' timeouts are only pretend too
+		if error.failure() != Failure::Network || config.simulate_forges.is_some() { return Outage::NotOne }
+		// it was started before the network came back (like things looked up ahead of time)
+		if self.last_outage_over.map_or(false, |over| started < over) { return Outage::Over }
+
+		let problem = match reachable(host).await {
+			Ok(()) => { return Outage::NotOne }
+			Err(problem) => { problem }
+		};
+		warn!("can't reach {host} ({problem}), the network seems to be down. waiting for it to come back before carrying on");
+		control.report(|progress| progress.current = Some(format!("waiting for {host} to be reachable again")));
+
+		let waiting_since = Instant::now();
+		let mut every = CHECK_EVERY_FIRST;
+		loop {
+			control.sleep_until(Instant::now() + every).await;
+			if control.stopping() { return Outage::Stopped }
+			if control.take_skip() {
+				info!("skipped by the operator, leaving it for another run");
+				return Outage::NotOne
+			}
+			if reachable(host).await.is_ok() { break }
+			every = (every * 2).min(CHECK_EVERY_MAX);
+		}
+
+		info!("{host} can be reached again after {}s, carrying on", waiting_since.elapsed().as_secs());
+		self.last_outage_over = Some(Instant::now());
+		Outage::Over
+	}
+}
+
+/// whether a connection can be made to `host`'s https port (or the port it has)
+async fn reachable(host: &str) -> Result<(), String> {
+	let address = match host.contains(':') {
+		true => { host.to_string() }
+		false => { format!("{host}:443") }
+	};
+	match timeout(CHECK_TIMEOUT, TcpStream::connect(&address)).await {
+		Ok(Ok(_)) => { Ok(()) }
+		Ok(Err(e)) => { Err(e.to_string()) }
+		Err(_) => { Err("timed out connecting".into()) }
+	}
+}
@@ -0,0 +1,202 @@
+//! showing what the next run would do, for checking config changes before
+//! anyone actually gets contacted
+
+use crate::config::Config;
+use crate::db::{ DatabaseThing, PackageState };
+use crate::eligibility::{ self, Eligibility };
+use crate::forge::{ ContactRoute, RepoLocation };
+use crate::lists::PackageList;
+use crate::queue;
+use crate::templates::Vars;
+use crate::Result;
+use chrono::Utc;
+use std::collections::{ HashMap, HashSet };
+
+/// prints the next `count` issues and emails a run would send, in order, and
+/// why every package that comes before the last of them is skipped
+pub async fn run(db: &DatabaseThing, config: &Config, count: usize) -> Result {
+	db.set_read_only();
+
+	let opt_out = match &config.opt_out_list {
+		Some(location) => { Some(PackageList::fetch(location).await?) }
+		None => { None }
+	};
+
+	// the same order `file` goes in, with the eligible packages and the skipped ones mixed in
+	let mut packages = Vec::new();
+	let mut skipped = HashMap::new();
+	let mut retrying = HashSet::new();
+	for package in db.pending_packages() {
+		let name = package.info.name.clone();
+		if let PackageState::RetryPending(retry) = &package.state {
+			if retry.retry_at > Utc::now() {
+				skipped.insert(name, format!("waiting to be retried after {}", retry.retry_at));
+				packages.push(package.info);
+				continue
+			}
+			retrying.insert(name.clone());
+		}
+
+		let reason = match eligibility::check(&package.info, config) {
+			Eligibility::Eligible => { None }
+			Eligibility::Deferred(reason) => { Some(format!("deferred, {reason}")) }
+			Eligibility::NeedsReview(reason) => { Some(format!("needs review, {reason}")) }
+			Eligibility::Blocked => { Some("on the blocklist".into()) }
+			Eligibility::SkipDeprecated(reason) => { Some(reason) }
+		};
+		let reason = reason.or_else(|| {
+			opt_out.as_ref()
+				.filter(|opt_out| opt_out.matches(&package.info))
+				.map(|_| "owner opted out of being contacted".into())
+		});
+		let reason = reason.or_else(|| {
+			let repo = RepoLocation::parse(&package.info.repository.url).filter(|_| config.skip_declined_owners)?;
+			let declined = db.declined_packages_of(&repo);
+			(!declined.is_empty()).then(|| format!("its owner declined for {}", declined.join(", ")))
+		});
+		if let Some(reason) = reason {
+			skipped.insert(name, reason);
+		}
+		packages.push(package.info);
+	}
+	queue::sort(&mut packages, config.queue_order);
+	packages.sort_by_key(|package| !retrying.contains(&package.name));
+	if let Some(batch) = db.batch() {
+		queue::resume_first(&mut packages, &batch);
+	}
+
+	let eligible = packages.iter()
+		.filter(|package| !skipped.contains_key(&package.name))
+		.cloned()
+		.collect();
+	let mut groups = HashMap::new();
+	for group in queue::group_by_repository(eligible) {
+		groups.insert(group[0].name.clone(), group);
+	}
+
+	if !config.filing_window.contains(Utc::now()) {
+		println!("(outside the filing window right now, so a run would stop before contacting anyone)");
+	}
+	if config.daily_issue_limit > 0 {
+		let left = config.daily_issue_limit.saturating_sub(db.issues_filed_last_day());
+		println!("(only {left} more issues can be filed right now, because of filing.daily_limit)");
+	}
+	println!("next {count} issues and emails (queue order: {}):", format!("{:?}", config.queue_order).to_lowercase());
+
+	let mut planned = 0;
+	let mut canaries_left = config.canary_filings;
+	for package in packages.iter() {
+		if planned >= count { break }
+
+		if let Some(reason) = skipped.get(&package.name) {
+			println!("   skip {}: {reason}", package.name);
+			continue
+		}
+		// packages after the first in a group were shown with it
+		let group = match groups.get(&package.name) {
+			Some(group) => { group }
+			None => { continue }
+		};
+
+		let names = group.iter()
+			.map(|package| package.name.as_str())
+			.collect::<Vec<_>>()
+			.join(", ");
+		match ContactRoute::for_package(package, config) {
+			ContactRoute::Forge(repo, forge) => {
+				if config.token_for(&repo.host, forge).is_none() && config.simulate_forges.is_none() {
+					println!("   skip {names}: no token configured for {} ({forge})", repo.host);
+					continue
+				}
+				if let Some(until) = db.repo_cooldown(&repo) {
+					println!("   skip {names}: something failed on {repo} recently, it's left alone until {until}");
+					continue
+				}
+				planned += 1;
+				println!("{planned}. issue on {repo} for {names}{}", canary_note(config, &mut canaries_left));
+			}
+			// not filing issues, so every package is contacted on its own
+			_ => {
+				for package in group.iter() {
+					if planned >= count { break }
+					match ContactRoute::for_package(package, config) {
+						ContactRoute::Email(email) => {
+							planned += 1;
+							println!("{planned}. email to {email} for {}", package.name);
+						}
+						ContactRoute::ForgeDisabled(_, forge) => {
+							println!("   skip {}: {forge} is disabled", package.name);
+						}
+						_ => {
+							println!("   skip {}: repository isn't on a supported forge, and there's no email to use instead", package.name);
+						}
+					}
+				}
+			}
+		}
+	}
+
+	if planned < count {
+		println!("that's everything, {planned} in total");
+	}
+
+	Ok(())
+}
+
+/// prints the issue (or email) a run would send for one package, exactly as it
+/// would be sent, for checking template changes. nothing is contacted
+pub fn preview(db: &DatabaseThing, config: &Config, name: &str) -> Result {
+	db.set_read_only();
+
+	let package = db.package(name)
+		.ok_or_else(|| format!("package {name} is not in the database"))?;
+	if !matches!(package.state, PackageState::New | PackageState::Deferred(_) | PackageState::RetryPending(_)) {
+		println!("(package {name} is {}, so a run wouldn't contact it, but this is what it would send)", package.state.name());
+	}
+
+	let route = ContactRoute::for_package(&package.info, config);
+	if let ContactRoute::ForgeDisabled(_, forge) = &route {
+		println!("({forge} is disabled, so a run wouldn't file this)");
+	}
+	match route {
+		ContactRoute::Forge(repo, _) | ContactRoute::ForgeDisabled(repo, _) => {
+			// the other pending packages in the repository get the same issue, in queue order
+			let mut pending = db.pending_packages().into_iter()
+				.map(|package| package.info)
+				.filter(|other| other.name != name && RepoLocation::parse(&other.repository.url).map_or(false, |other| other.key() == repo.key()))
+				.collect::<Vec<_>>();
+			pending.push(package.info);
+			queue::sort(&mut pending, config.queue_order);
+			let names = pending.iter().map(|package| package.name.clone()).collect::<Vec<_>>();
+
+			let (templates, picked) = config.pick_templates(&repo, &pending);
+			let (title, body) = templates.issue(&Vars::issue(&repo, &pending).with_declined(db.declined_packages_of(&repo)))?;
+			if let Some(variant) = picked.variant {
+				println!("(from template variant {variant}, picked at random)");
+			}
+			if let Some(locale) = picked.locale {
+				println!("(in {locale})");
+			}
+			println!("issue on {repo} for {}:\n{title}\n\n{body}", names.join(", "));
+		}
+		ContactRoute::Email(email) => {
+			let (subject, body) = config.templates.email(&Vars::email(&package.info))?;
+			println!("email to {email}:\n{subject}\n\n{body}");
+		}
+		ContactRoute::Unsupported => {
+			println!("package {name} can't be contacted: its repository isn't on a supported forge, and there's no email to use instead");
+		}
+	}
+
+	Ok(())
+}
+
+fn canary_note(config: &Config, canaries_left: &mut usize) -> String {
+	match &config.canary_repo {
+		Some(canary) if *canaries_left > 0 => {
+			*canaries_left -= 1;
+			format!(" (after a canary on {canary})")
+		}
+		_ => { String::new() }
+	}
+}
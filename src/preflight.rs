@@ -0,0 +1,120 @@
+//! checks against a package's repository, done right before filing, that
+//! catch things the registry data alone can't tell us
+
+use crate::db::{ DatabaseThing, PackageNew, RepoActivity };
+use crate::error::Context;
+use crate::forge::{ ForgeClient, RepoLocation };
+use crate::usage;
+use crate::Result;
+use chrono::{ Duration, Utc };
+use serde::Deserialize;
+use tracing::instrument;
+
+pub enum Preflight {
+	Ok,
+	/// something looks off, a human should have a look before anything is filed
+	NeedsReview(String),
+	/// its repository hasn't been pushed to in
+	/// [`dormant_after_years`](crate::config::Config::dormant_after_years)
+	Dormant(String)
+}
+
+#[derive(Deserialize)]
+struct PackageJson {
+	name: Option<String>
+}
+
+/// checks a repository before filing an issue for `packages`, which all live in
+/// it. it's dormant if it hasn't been pushed to in `dormant_after_years`, unless that's 0
+#[instrument(name = "preflight", skip_all, fields(package = %packages.iter().map(|package| package.name.as_str()).collect::<Vec<_>>().join(", "), owner = %repo.owner))]
+pub async fn check(db: &DatabaseThing, packages: &[PackageNew], client: &ForgeClient, repo: &RepoLocation, dormant_after_years: u32) -> Result<Preflight> {
+	record_license(db, packages, client, repo).await;
+	let activity = record_activity(db, packages, client, repo).await;
+	if let Some(reason) = activity.as_ref().and_then(|activity| dormant(activity, dormant_after_years)) {
+		return Ok(Preflight::Dormant(reason))
+	}
+
+	// monorepos have their packages in subdirectories, and the root
+	// package.json (if there is one) isn't any of them
+	if packages.len() > 1 { return Ok(Preflight::Ok) }
+
+	check_package_json(&packages[0], client, repo).await
+}
+
+/// records the repository's license for `packages`, for `export --license`. it's
+/// only good to know, so not being able to tell doesn't stop anything being filed
+async fn record_license(db: &DatabaseThing, packages: &[PackageNew], client: &ForgeClient, repo: &RepoLocation) {
+	match client.license(repo).await {
+		Ok(license) => {
+			for package in packages {
+				db.set_license(&package.name, license.clone());
+			}
+		}
+		Err(e) => { warn!("error checking {repo}'s license, carrying on without it: {e}") }
+	}
+}
+
+/// records when the repository was last pushed to and released for `packages`,
+/// for `list` and `export`, since abandoned repositories rarely answer. like the
+/// license, it isn't worth not filing over
+async fn record_activity(db: &DatabaseThing, packages: &[PackageNew], client: &ForgeClient, repo: &RepoLocation) -> Option<RepoActivity> {
+	match client.activity(repo).await {
+		Ok(activity) => {
+			for package in packages {
+				db.set_activity(&package.name, activity.clone());
+			}
+			Some(activity)
+		}
+		Err(e) => {
+			warn!("error checking {repo}'s activity, carrying on without it: {e}");
+			None
+		}
+	}
+}
+
+/// why the repository's dormant, if it is. empty ones aren't, since there's
+/// nothing to go by
+fn dormant(activity: &RepoActivity, years: u32) -> Option<String> {
+	if years == 0 { return None }
+	let pushed_at = activity.pushed_at?;
+	// a year's close enough to 365 days for this
+	(pushed_at < Utc::now() - Duration::days(365 * years as i64))
+		.then(|| format!("its repository hasn't been pushed to since {}", pushed_at.format("%Y-%m-%d")))
+}
+
+/// the repository should still contain the package. if it doesn't, the repo
+/// was probably reused for something else, or taken over after the package
+/// was published
+async fn check_package_json(package: &PackageNew, client: &ForgeClient, repo: &RepoLocation) -> Result<Preflight> {
+	if let ForgeClient::Simulated(client) = client {
+		// there's no package.json to check, only the chance of a failure (and
+		// the call it would have been)
+		usage::count("simulated", "get_file");
+		client.maybe_fail().await
+			.context(format!("error fetching package.json from {repo}"))?;
+		return Ok(Preflight::Ok)
+	}
+
+	let file = client.get_file(repo, "package.json").await
+		.context(format!("error fetching package.json from {repo}"))?;
+	let file = match file {
+		Some(file) => { file }
+		None => { return Ok(Preflight::NeedsReview(format!("{repo} has no package.json"))) }
+	};
+
+	let package_json = match serde_json::from_str::<PackageJson>(&file) {
+		Ok(package_json) => { package_json }
+		Err(e) => { return Ok(Preflight::NeedsReview(format!("package.json in {repo} could not be parsed: {e}"))) }
+	};
+
+	match package_json.name {
+		Some(name) if name == package.name => { Ok(Preflight::Ok) }
+		Some(name) => {
+			Ok(Preflight::NeedsReview(format!(
+				"package.json in {repo} is for package {name}, not {}",
+				package.name
+			)))
+		}
+		None => { Ok(Preflight::NeedsReview(format!("package.json in {repo} has no name"))) }
+	}
+}
@@ -0,0 +1,91 @@
+//! progress bars for long runs, when someone's watching. when stdout isn't a
+//! terminal (like in cron, or with `--dashboard`), they're hidden, and what
+//! gets printed is the same as it's always been
+
+use indicatif::{ ProgressBar, ProgressDrawTarget, ProgressStyle };
+use std::sync::Mutex;
+
+lazy_static! {
+	/// the bar being shown, so lines can be printed above it
+	static ref SHOWN: Mutex<Option<ProgressBar>> = Mutex::new(None);
+}
+
+/// `println!`, but printed above the progress bar while there is one, so they
+/// don't get mixed up. it's declared before every other module, so it's the one
+/// they all use
+macro_rules! println {
+	() => { $crate::progress::print_line(String::new()) };
+	($($arg:tt)*) => { $crate::progress::print_line(format!($($arg)*)) };
+}
+
+pub fn print_line(line: String) {
+	match SHOWN.lock().unwrap().as_ref() {
+		Some(bar) => { bar.println(line) }
+		None => { std::println!("{line}") }
+	}
+}
+
+/// a progress bar for going through `len` things, or a spinner if how many
+/// isn't known (yet). it's cleared when it's dropped
+pub struct Bar {
+	bar: ProgressBar
+}
+
+impl Bar {
+	pub fn new(what: &str, len: Option<u64>) -> Self {
+		let bar = ProgressBar::with_draw_target(len, ProgressDrawTarget::stdout());
+		bar.set_style(style(len.is_some()));
+		bar.set_prefix(what.to_string());
+
+		if !bar.is_hidden() {
+			bar.enable_steady_tick(std::time::Duration::from_millis(200));
+			*SHOWN.lock().unwrap() = Some(bar.clone());
+		}
+		Self { bar }
+	}
+
+	/// one that's never shown, for when something else needs the terminal
+	pub fn hidden() -> Self {
+		Self { bar: ProgressBar::hidden() }
+	}
+
+	/// whether it's being shown, for leaving out lines that would only repeat it
+	pub fn is_shown(&self) -> bool {
+		!self.bar.is_hidden()
+	}
+
+	pub fn set_position(&self, position: usize) {
+		self.bar.set_position(position as u64);
+	}
+
+	pub fn set_length(&self, len: usize) {
+		if self.bar.length().is_none() {
+			self.bar.set_style(style(true));
+		}
+		self.bar.set_length(len as u64);
+	}
+
+	/// what's being counted, and any other counters, like `packages, 3 filed, 1 failed`
+	pub fn set_message(&self, message: String) {
+		self.bar.set_message(message);
+	}
+}
+
+impl Drop for Bar {
+	fn drop(&mut self) {
+		self.bar.finish_and_clear();
+		// there's only ever one at a time
+		*SHOWN.lock().unwrap() = None;
+	}
+}
+
+fn style(has_length: bool) -> ProgressStyle {
+	let template = if has_length {
+		"{prefix} [{bar:20}] {pos}/{len} {wide_msg} {eta} left"
+	} else {
+		"{prefix} {spinner} {pos} {wide_msg}"
+	};
+	ProgressStyle::with_template(template)
+		.expect("templates are valid")
+		.progress_chars("=> ")
+}
@@ -0,0 +1,76 @@
+//! client for the pulsar package backend
+
+use crate::config::Config;
+use crate::secrets::Secret;
+use crate::Result;
+use reqwest::{ Client, StatusCode, Url };
+use serde::Serialize;
+
+pub struct PulsarClient {
+	client: Client,
+	base_url: Url,
+	token: Option<Secret>
+}
+
+impl PulsarClient {
+	pub fn new(config: &Config) -> Result<Self> {
+		let client = Client::builder()
+			.user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+			.build()?;
+		let base_url = Url::parse(&config.pulsar_backend_url)
+			.map_err(|e| format!("error parsing pulsar backend url {}: {e}", config.pulsar_backend_url))?;
+
+		Ok(Self { client, base_url, token: config.pulsar_backend_token.clone() })
+	}
+
+	/// whether a package has been published to the pulsar backend already
+	pub async fn package_exists(&self, name: &str) -> Result<bool> {
+		let url = self.package_url(name)?;
+		let res = self.client.get(url.clone())
+			.send().await
+			.map_err(|e| format!("error fetching {url}: {e}"))?;
+
+		match res.status() {
+			StatusCode::NOT_FOUND => { Ok(false) }
+			status if status.is_success() => { Ok(true) }
+			status => { Err(format!("error fetching {url}: unexpected status {status}").into()) }
+		}
+	}
+
+	/// registers a package the maintainer approved, returning what the backend said
+	pub async fn register<T: Serialize>(&self, package: &T) -> Result<String> {
+		let token = self.token.as_ref()
+			.ok_or("no pulsar backend token configured (pulsar.token_env, pulsar.token_file, or pulsar.token_keyring)")?;
+		let url = self.packages_url()?;
+		let res = self.client.post(url.clone())
+			.header("Authorization", token.expose())
+			.json(package)
+			.send().await
+			.map_err(|e| format!("error posting to {url}: {e}"))?;
+
+		let status = res.status();
+		let body = res.text().await
+			.map_err(|e| format!("error reading response from {url}: {e}"))?;
+		if !status.is_success() {
+			return Err(format!("error posting to {url}: unexpected status {status}: {}", token.redact(&body)).into())
+		}
+		Ok(body)
+	}
+
+	fn packages_url(&self) -> Result<Url> {
+		let mut url = self.base_url.clone();
+		url.path_segments_mut()
+			.map_err(|_| format!("pulsar backend url {} can't have paths", self.base_url))?
+			.pop_if_empty()
+			.push("packages");
+		Ok(url)
+	}
+
+	fn package_url(&self, name: &str) -> Result<Url> {
+		let mut url = self.packages_url()?;
+		url.path_segments_mut()
+			.map_err(|_| format!("pulsar backend url {} can't have paths", self.base_url))?
+			.push(name);
+		Ok(url)
+	}
+}
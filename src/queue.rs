@@ -0,0 +1,90 @@
+//! the order pending packages get contacted in
+
+use crate::db::{ Batch, PackageNew };
+use crate::forge::RepoLocation;
+use crate::random;
+use rand::seq::SliceRandom;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QueueOrder {
+	/// most downloaded first
+	Downloads,
+	/// most starred first
+	Stars,
+	/// by name
+	Alphabetical,
+	Random
+}
+
+impl FromStr for QueueOrder {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match &*s.trim().to_lowercase() {
+			"downloads" => { Ok(Self::Downloads) }
+			"stars" => { Ok(Self::Stars) }
+			"alphabetical" => { Ok(Self::Alphabetical) }
+			"random" => { Ok(Self::Random) }
+			_ => { Err(format!("unknown queue order {s} (expected downloads, stars, alphabetical, or random)")) }
+		}
+	}
+}
+
+pub fn sort(packages: &mut [PackageNew], order: QueueOrder) {
+	match order {
+		QueueOrder::Downloads => {
+			packages.sort_by(|a, b| b.downloads.cmp(&a.downloads).then_with(|| a.name.cmp(&b.name)));
+		}
+		QueueOrder::Stars => {
+			packages.sort_by(|a, b| b.stargazers_count.cmp(&a.stargazers_count).then_with(|| a.name.cmp(&b.name)));
+		}
+		QueueOrder::Alphabetical => {
+			packages.sort_by(|a, b| a.name.cmp(&b.name));
+		}
+		QueueOrder::Random => {
+			random::with(|rng| packages.shuffle(rng));
+		}
+	}
+}
+
+/// puts the packages an unfinished run hadn't got to yet first, in the order it
+/// was going to go through them. everything else keeps its order after them
+pub fn resume_first(packages: &mut [PackageNew], batch: &Batch) {
+	let remaining = batch.packages.iter()
+		.skip(batch.progress)
+		.enumerate()
+		.map(|(i, name)| (name.as_str(), i))
+		.collect::<HashMap<_, _>>();
+	// stable sort
+	packages.sort_by_key(|package| remaining.get(package.name.as_str()).copied().unwrap_or(usize::MAX));
+}
+
+/// groups packages that live in the same repository (monorepos), so they get
+/// one issue between them. groups are in the order of their first package,
+/// so this should be done after sorting
+pub fn group_by_repository(packages: Vec<PackageNew>) -> Vec<Vec<PackageNew>> {
+	let mut groups = Vec::<Vec<PackageNew>>::new();
+	let mut indices = HashMap::<String, usize>::new();
+
+	for package in packages {
+		let key = match RepoLocation::parse(&package.repository.url) {
+			Some(repo) => { repo.key() }
+			None => {
+				groups.push(vec![package]);
+				continue
+			}
+		};
+
+		match indices.get(&key) {
+			Some(&i) => { groups[i].push(package) }
+			None => {
+				indices.insert(key, groups.len());
+				groups.push(vec![package]);
+			}
+		}
+	}
+
+	groups
+}
@@ -0,0 +1,25 @@
+//! everything picked at random (the delay between filings, `queue.order =
+//! "random"`, which issues `reconcile --sample` checks, simulated failures) comes
+//! from one rng, seeded with `--seed`, so a run that went some odd way can be
+//! made to go the same way again. without `--seed` it's seeded at random, and
+//! the seed is logged (with `-v`)
+
+use rand::rngs::StdRng;
+use rand::{ Rng, SeedableRng };
+use std::sync::Mutex;
+
+lazy_static! {
+	static ref RNG: Mutex<StdRng> = Mutex::new(StdRng::from_entropy());
+}
+
+/// seeds the rng with `seed`, or a random one, before anything's picked
+pub fn init(seed: Option<u64>) {
+	let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
+	debug!("random seed is {seed}, run with --seed {seed} to pick everything the same way again");
+	*RNG.lock().unwrap() = StdRng::seed_from_u64(seed);
+}
+
+/// runs `f` with the rng
+pub fn with<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+	f(&mut RNG.lock().unwrap())
+}
@@ -0,0 +1,272 @@
+//! checking that what the database says about issues matches what's actually
+//! on the forges, and fixing it where they've drifted apart
+
+use crate::db::{ self, DatabaseThing, PackageFilingInProgress, PackageIssueFiled, PackageState };
+use crate::forge::{ self, ContactRoute, Forges, RepoLocation };
+use crate::queue;
+use crate::random;
+use crate::reminders;
+use crate::Result;
+use chrono::Utc;
+use rand::seq::SliceRandom;
+use std::collections::BTreeMap;
+use tracing::instrument;
+
+/// something to compare against the forge
+enum Check {
+	/// an issue the database says was filed
+	Filed {
+		issue_url: String,
+		filed: PackageIssueFiled,
+		names: Vec<String>
+	},
+	/// a repository the database says hasn't had an issue filed on it yet
+	Pending {
+		repo: RepoLocation,
+		names: Vec<String>
+	}
+}
+
+/// checks every filed issue and pending repository (or `sample` of them picked
+/// at random), fixing what it can and printing what it can't
+pub async fn run(db: &DatabaseThing, forges: &mut Forges, sample: Option<usize>, dry_run: bool) -> Result {
+	if dry_run { db.set_read_only() }
+
+	settle_filings_in_progress(db, forges).await?;
+
+	let mut issues = BTreeMap::<String, (PackageIssueFiled, Vec<String>)>::new();
+	for (name, filed) in db.filed_issues() {
+		issues.entry(filed.issue_url.clone())
+			.or_insert_with(|| (filed, Vec::new()))
+			.1.push(name);
+	}
+	let mut checks = issues.into_iter()
+		.map(|(issue_url, (filed, names))| Check::Filed { issue_url, filed, names })
+		.collect::<Vec<_>>();
+
+	let pending = db.pending_packages().into_iter()
+		.map(|package| package.info)
+		.collect();
+	for group in queue::group_by_repository(pending) {
+		if let ContactRoute::Forge(repo, _) = ContactRoute::for_package(&group[0], forges.config()) {
+			let names = group.into_iter().map(|package| package.name).collect();
+			checks.push(Check::Pending { repo, names });
+		}
+	}
+
+	if let Some(sample) = sample {
+		random::with(|rng| checks.shuffle(rng));
+		checks.truncate(sample);
+	}
+	info!("checking {} issues and repositories", checks.len());
+
+	let mut fixed = Vec::new();
+	let mut unresolved = Vec::new();
+	for check in checks {
+		match check {
+			Check::Filed { issue_url, filed, names } => {
+				reconcile_filed(db, forges, issue_url, filed, names, &mut fixed, &mut unresolved).await?;
+			}
+			Check::Pending { repo, names } => {
+				reconcile_pending(db, forges, repo, names, &mut fixed, &mut unresolved).await?;
+			}
+		}
+		db.flush();
+	}
+
+	println!("{} fixed{}:", fixed.len(), if dry_run { " (dry run, nothing was saved)" } else { "" });
+	for line in fixed.iter() {
+		println!("- {line}");
+	}
+	println!("{} couldn't be fixed automatically:", unresolved.len());
+	for line in unresolved.iter() {
+		println!("- {line}");
+	}
+
+	Ok(())
+}
+
+#[instrument(skip_all, fields(package = %names.join(", "), issue = %issue_url))]
+async fn reconcile_filed(
+	db: &DatabaseThing,
+	forges: &mut Forges,
+	issue_url: String,
+	filed: PackageIssueFiled,
+	names: Vec<String>,
+	fixed: &mut Vec<String>,
+	unresolved: &mut Vec<String>
+) -> Result {
+	let names_str = names.join(", ");
+	let titles = forges.config().issue_titles.clone();
+	let number = match forge::issue_number(&issue_url) {
+		Some(number) => { number }
+		None => {
+			unresolved.push(format!("{issue_url} (for {names_str}): can't tell what issue number it is"));
+			return Ok(())
+		}
+	};
+	let client = match forges.client_for(&filed.repository) {
+		Ok(client) => { client }
+		Err(e) => {
+			unresolved.push(format!("{issue_url} (for {names_str}): {e}"));
+			return Ok(())
+		}
+	};
+
+	match client.issue_activity(&filed.repository, number).await {
+		Ok(activity) => {
+			for name in names.iter() {
+				db.set_seen_comments(name, &activity.comments);
+			}
+			if (activity.replied || activity.closed) && filed.replied_at.is_none() {
+				for name in names.iter() {
+					update_filed(db, name, |filed| filed.replied_at = Some(Utc::now()))?;
+				}
+				fixed.push(format!("{issue_url} (for {names_str}) was {}, stopped its reminders", if activity.replied { "replied to" } else { "closed" }));
+			}
+		}
+		Err(e) if e.is_not_found() => {
+			// the issue might have been moved (or deleted and filed again)
+			match client.find_permission_request_issue(&filed.repository, &titles).await {
+				Ok(Some(found)) if found != issue_url => {
+					for name in names.iter() {
+						update_filed(db, name, |filed| filed.issue_url = found.clone())?;
+					}
+					fixed.push(format!("{issue_url} (for {names_str}) doesn't exist, but {found} does, so that's recorded instead"));
+				}
+				Ok(_) => {
+					unresolved.push(format!("{issue_url} (for {names_str}) doesn't exist any more"));
+				}
+				Err(e) => {
+					unresolved.push(format!("{issue_url} (for {names_str}) doesn't exist any more, and looking for another one failed: {e}"));
+				}
+			}
+		}
+		Err(e) => {
+			unresolved.push(format!("{issue_url} (for {names_str}): error checking it: {e}"));
+		}
+	}
+
+	Ok(())
+}
+
+#[instrument(skip_all, fields(package = %names.join(", "), owner = %repo.owner))]
+async fn reconcile_pending(
+	db: &DatabaseThing,
+	forges: &mut Forges,
+	repo: RepoLocation,
+	names: Vec<String>,
+	fixed: &mut Vec<String>,
+	unresolved: &mut Vec<String>
+) -> Result {
+	let names_str = names.join(", ");
+	let titles = forges.config().issue_titles.clone();
+	let found = match forges.client_for(&repo) {
+		Ok(client) => { client.find_permission_request_issue(&repo, &titles).await }
+		Err(e) => { Err(e) }
+	};
+
+	match found {
+		Ok(Some(issue_url)) => {
+			let filed_at = Utc::now();
+			for name in names.iter() {
+				db.set_state(name, PackageState::IssueFiled(db::PackageIssueFiled {
+					repository: repo.clone(),
+					issue_url: issue_url.clone(),
+					filed_at,
+					grouped_with: names.iter().filter(|other| *other != name).cloned().collect(),
+					reminders: reminders::schedule(filed_at, forges.config()),
+					replied_at: None,
+					// it could've been filed with any of them
+					template_variant: None,
+					locale: None
+				}))?;
+			}
+			fixed.push(format!("{names_str} already has an issue filed ({issue_url}), so that's recorded"));
+		}
+		Ok(None) => {}
+		Err(e) => {
+			unresolved.push(format!("{repo} (for {names_str}): error checking for an issue: {e}"));
+		}
+	}
+
+	Ok(())
+}
+
+/// sorts out packages a run stopped partway through filing an issue for: if the
+/// issue got filed, it's recorded, and if it didn't, they go back to being pending
+pub async fn settle_filings_in_progress(db: &DatabaseThing, forges: &mut Forges) -> Result {
+	let titles = forges.config().issue_titles.clone();
+	let mut repos = BTreeMap::<String, (RepoLocation, Vec<(String, PackageFilingInProgress)>)>::new();
+	for (name, filing) in db.filings_in_progress() {
+		repos.entry(filing.repository.key())
+			.or_insert_with(|| (filing.repository.clone(), Vec::new()))
+			.1.push((name, filing));
+	}
+
+	for (_, (repo, filings)) in repos {
+		let names = filings.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+		let names_str = names.join(", ");
+		let found = match forges.client_for(&repo) {
+			Ok(client) => { client.find_permission_request_issue(&repo, &titles).await }
+			Err(e) => { Err(e) }
+		};
+
+		match found {
+			Ok(Some(issue_url)) => {
+				info!("the last run filed an issue for package {names_str} before it stopped: {issue_url}");
+				for (name, filing) in filings.iter() {
+					db.set_state(name, PackageState::IssueFiled(db::PackageIssueFiled {
+						repository: repo.clone(),
+						issue_url: issue_url.clone(),
+						filed_at: filing.started_at,
+						grouped_with: names.iter().filter(|other| *other != name).cloned().collect(),
+						reminders: reminders::schedule(filing.started_at, forges.config()),
+						replied_at: None,
+						template_variant: filing.template_variant.clone(),
+						locale: filing.locale.clone()
+					}))?;
+				}
+			}
+			Ok(None) => {
+				info!("the last run stopped before filing an issue for package {names_str}, it'll be filed again");
+				for (name, filing) in filings.iter() {
+					let state = match filing.attempts {
+						0 => { PackageState::New }
+						attempts => {
+							PackageState::RetryPending(db::PackageRetryPending {
+								attempts,
+								last_error: "the run stopped while filing an issue".into(),
+								retry_at: Utc::now()
+							})
+						}
+					};
+					db.set_state(name, state)?;
+				}
+			}
+			Err(e) => {
+				// safer to have someone check than to risk filing it twice
+				let reason = format!("the last run stopped while filing an issue for this package, and checking if it was filed failed: {e}");
+				warn!("package {names_str} needs review: {reason}");
+				for name in names.iter() {
+					db.set_state(name, PackageState::NeedsReview(db::PackageNeedsReview {
+						reason: reason.clone(),
+						flagged_at: Utc::now(),
+						contact_failure: None
+					}))?;
+				}
+			}
+		}
+		db.flush();
+	}
+
+	Ok(())
+}
+
+fn update_filed(db: &DatabaseThing, name: &str, f: impl FnOnce(&mut PackageIssueFiled)) -> Result {
+	if let Some(PackageState::IssueFiled(mut filed)) = db.package_state(name) {
+		f(&mut filed);
+		db.set_state(name, PackageState::IssueFiled(filed))?;
+	}
+	Ok(())
+}
@@ -0,0 +1,766 @@
+//! fetching the package list from the atom.io registry
+
+use crate::config::Config;
+use crate::db::{ self, DatabaseThing, PackageMetadata, PackageNew, RegistrySyncCursor, RegistrySyncState, Releases, Repository };
+use crate::error::{ Context, Failure };
+use crate::lists::PackageList;
+use crate::locale;
+use crate::health;
+use crate::metrics;
+use crate::progress;
+use crate::simulated;
+use crate::{ Error, Result };
+use chrono::{ DateTime, Duration, Utc };
+use reqwest::header::{ ETAG, IF_NONE_MATCH, LINK, RETRY_AFTER };
+use reqwest::{ Client, Response, StatusCode, Url };
+use serde::Deserialize;
+use tracing::instrument;
+use std::collections::{ HashMap, HashSet };
+use std::path::Path;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use std::time::Duration as StdDuration;
+use tokio::time::sleep;
+
+mod cache;
+mod dump;
+use cache::{ CachedPage, RegistryCache };
+
+/// a package, as returned by the registry api. mostly the same as
+/// [`PackageNew`], except the registry has some packages with no repository
+#[derive(Deserialize)]
+pub struct RegistryPackage {
+	pub name: String,
+	pub repository: Option<Repository>,
+	#[serde(default)]
+	pub downloads: u32,
+	#[serde(default)]
+	pub stargazers_count: u32,
+	#[serde(default)]
+	pub metadata: PackageMetadata,
+	#[serde(default)]
+	pub releases: Releases,
+	#[serde(default, deserialize_with = "db::deserialize_versions")]
+	pub versions: Vec<String>,
+	/// atom.io doesn't have this, but the pulsar backend and some mirrors do
+	#[serde(default)]
+	pub owner: Option<String>,
+	/// only what it's written in is kept, see [`locale::detect`]
+	#[serde(default)]
+	pub readme: Option<String>
+}
+
+impl RegistryPackage {
+	pub fn into_package(self, theme: bool) -> Option<PackageNew> {
+		let mut package = PackageNew {
+			name: self.name,
+			repository: self.repository?,
+			downloads: self.downloads,
+			stargazers_count: self.stargazers_count,
+			metadata: self.metadata,
+			theme,
+			releases: self.releases,
+			versions: self.versions,
+			owner: None,
+			readme_locale: self.readme.as_deref().and_then(locale::detect).map(String::from)
+		};
+		package.owner = Some(db::PackageOwner::for_package(&package, self.owner));
+		Some(package)
+	}
+}
+
+/// which packages from the registry should be added to the db
+#[derive(Clone)]
+pub struct SyncFilter {
+	include_themes: bool,
+	blocklist: PackageList,
+	allowlist: Option<PackageList>
+}
+
+impl SyncFilter {
+	pub fn new(config: &Config) -> Self {
+		Self {
+			include_themes: config.include_themes,
+			blocklist: config.blocklist.clone(),
+			allowlist: config.allowlist.clone()
+		}
+	}
+}
+
+pub struct RegistryClient {
+	client: Client,
+	/// the main registry url, followed by any mirrors
+	base_urls: Vec<String>,
+	/// index into `base_urls` of the registry currently being used
+	active: AtomicUsize,
+	filter: SyncFilter,
+	cache: Option<RegistryCache>,
+	max_retries: u32,
+	counts_refresh_interval: Option<Duration>
+}
+
+#[derive(Default)]
+pub struct SyncStats {
+	pub pages: usize,
+	pub seen: usize,
+	pub added: usize,
+	pub updated: usize,
+	pub no_repository: usize,
+	pub themes_excluded: usize,
+	/// blocklisted, or not on the allowlist
+	pub not_allowed: usize,
+	pub incremental: bool,
+	/// carried on from a sync that didn't finish
+	pub resumed: bool,
+	/// known packages whose download or star counts changed
+	pub counts_refreshed: usize,
+	pub from_dump: bool,
+	/// packages linked to the ones they were renamed from
+	pub renamed: usize,
+	/// every package seen so far, which are all still on the registry
+	seen_names: HashSet<String>,
+	/// packages just added that share a repository with packages that were
+	/// there already, and might be one of them renamed, see [`link_renames`]
+	possibly_renamed: Vec<(String, Vec<String>)>
+}
+
+impl RegistryClient {
+	pub fn new(config: &Config) -> Result<Self> {
+		let client = Client::builder()
+			.user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+			.timeout(StdDuration::from_secs(config.registry_timeout))
+			.build()?;
+
+		Ok(Self {
+			client,
+			max_retries: config.registry_max_retries,
+			counts_refresh_interval: match config.counts_refresh_interval {
+				0 => { None }
+				secs => { Some(Duration::seconds(secs as i64)) }
+			},
+			base_urls: std::iter::once(&config.registry_url)
+				.chain(config.registry_mirrors.iter())
+				.map(|url| url.trim_end_matches('/').to_string())
+				.collect(),
+			active: AtomicUsize::new(0),
+			filter: SyncFilter::new(config),
+			cache: config.registry_cache_dir.as_ref().map(|dir| {
+				RegistryCache::new(dir, Duration::seconds(config.registry_cache_max_age as i64))
+			})
+		})
+	}
+
+	/// fetches one page of packages, returning the packages, the url of the next
+	/// page if there is one, and how many pages there are if the registry said
+	#[instrument(skip_all, fields(url = %url))]
+	pub async fn fetch_page(&self, url: &str) -> Result<(Vec<RegistryPackage>, Option<String>, Option<usize>)> {
+		let page = self.fetch_page_cached(url).await?;
+
+		let packages = serde_json::from_str::<Vec<RegistryPackage>>(&page.body)
+			.map_err(Error::registry).context(format!("error parsing response from {url}"))?;
+
+		Ok((packages, page.next, page.last_page))
+	}
+
+	/// gets a page from the cache if it's recent enough or the registry says it hasn't
+	/// changed since, otherwise fetches it (and caches it, if caching is on)
+	async fn fetch_page_cached(&self, url: &str) -> Result<CachedPage> {
+		let cached = match &self.cache {
+			Some(cache) => { cache.get(url).await }
+			None => { None }
+		};
+
+		if let (Some(cache), Some(cached)) = (&self.cache, &cached) {
+			if cached.is_fresh(cache.max_age()) {
+				return Ok(cached.clone())
+			}
+		}
+
+		let etag = cached.as_ref().and_then(|cached| cached.etag.as_deref());
+		let res = self.get_with_failover(url, etag).await?;
+
+		if let (StatusCode::NOT_MODIFIED, Some(cached)) = (res.status(), &cached) {
+			let page = CachedPage { fetched_at: Utc::now(), ..cached.clone() };
+			self.put_cache(&page).await;
+			return Ok(page)
+		}
+
+		let res = res.error_for_status()
+			.map_err(Error::registry).context(format!("error fetching {url}"))?;
+
+		let links = res.headers()
+			.get_all(LINK)
+			.iter()
+			.filter_map(|value| value.to_str().ok())
+			.collect::<Vec<_>>();
+		let next = links.iter().find_map(|header| find_link(header, "next"));
+		let last_page = links.iter()
+			.find_map(|header| find_link(header, "last"))
+			.and_then(|url| page_number(&url));
+		let etag = res.headers()
+			.get(ETAG)
+			.and_then(|value| value.to_str().ok())
+			.map(String::from);
+
+		let body = res.text().await
+			.map_err(Error::registry).context(format!("error fetching {url}"))?;
+
+		let page = CachedPage {
+			url: url.into(),
+			etag,
+			next,
+			last_page,
+			fetched_at: Utc::now(),
+			body
+		};
+		self.put_cache(&page).await;
+
+		Ok(page)
+	}
+
+	/// the registry currently being used
+	fn base_url(&self) -> &str {
+		&self.base_urls[self.active.load(Ordering::Relaxed)]
+	}
+
+	/// gets a url from the registry currently being used, with retries. if it
+	/// still fails, moves on to the next mirror, and sticks with that one for
+	/// the rest of the run
+	async fn get_with_failover(&self, url: &str, etag: Option<&str>) -> Result<Response> {
+		// url without the registry it's for, so it can be requested from a mirror
+		let path = self.base_urls.iter()
+			.filter(|base_url| url.starts_with(base_url.as_str()))
+			.max_by_key(|base_url| base_url.len())
+			.map(|base_url| &url[base_url.len()..]);
+		let path = match path {
+			Some(path) => { path }
+			// not a registry url we know of (eg. a next link pointing somewhere else)
+			None => { return self.get_with_retry(url, etag).await }
+		};
+
+		loop {
+			let active = self.active.load(Ordering::Relaxed);
+			let url = format!("{}{path}", self.base_urls[active]);
+
+			match self.get_with_retry(&url, etag).await {
+				Ok(res) => { return Ok(res) }
+				Err(e) if active + 1 < self.base_urls.len() => {
+					warn!("{e}, switching to registry mirror {}", self.base_urls[active + 1]);
+					self.active.store(active + 1, Ordering::Relaxed);
+				}
+				Err(e) => { return Err(e) }
+			}
+		}
+	}
+
+	/// sends a get request, retrying with backoff while the registry is rate
+	/// limiting us, having an outage, or timing out
+	async fn get_with_retry(&self, url: &str, etag: Option<&str>) -> Result<Response> {
+		let mut attempt = 0;
+
+		loop {
+			let mut req = self.client.get(url);
+			if let Some(etag) = etag {
+				req = req.header(IF_NONE_MATCH, etag);
+			}
+
+			let started = std::time::Instant::now();
+			let res = req.send().await;
+			metrics::observe_latency("registry", "get", started.elapsed());
+			health::request_finished(matches!(&res, Ok(res) if res.status() != StatusCode::TOO_MANY_REQUESTS && !res.status().is_server_error()));
+			let (retry_after, failure, problem) = match res {
+				Ok(res) if res.status() == StatusCode::TOO_MANY_REQUESTS || res.status().is_server_error() => {
+					(retry_after(&res), Failure::of_status(res.status()), format!("status {}", res.status()))
+				}
+				Ok(res) => { return Ok(res) }
+				Err(e) if e.is_timeout() || e.is_connect() || e.is_request() => { (None, Failure::Network, e.to_string()) }
+				Err(e) => { return Err(Error::registry(e)).context(format!("error fetching {url}")) }
+			};
+
+			attempt += 1;
+			if attempt > self.max_retries {
+				let problem = format!("{problem}, gave up after {} retries", self.max_retries);
+				return Err(Error::Registry { failure, source: problem.into() }).context(format!("error fetching {url}"))
+			}
+
+			let delay = retry_after.unwrap_or_else(|| backoff(attempt));
+			warn!(
+				"error fetching {url}: {problem}, retrying in {}s ({attempt}/{})",
+				delay.as_secs(),
+				self.max_retries
+			);
+			sleep(delay).await;
+		}
+	}
+
+	async fn put_cache(&self, page: &CachedPage) {
+		if let Some(cache) = &self.cache {
+			if let Err(e) = cache.put(page).await {
+				error!("error caching registry page {}: {e}", page.url);
+			}
+		}
+	}
+
+	/// walks the registry, adding packages that aren't in the db yet. if
+	/// `incremental` and there's been a sync before, packages are fetched
+	/// most recently updated first, and the walk stops at the first page
+	/// with nothing new or changed, rather than going through all of it
+	///
+	/// if a previous sync of the same kind didn't finish, this carries on
+	/// from the page it got to
+	pub async fn sync(&self, db: &DatabaseThing, incremental: bool) -> Result<SyncStats> {
+		let sync_state = db.registry_sync_state();
+		let mut incremental = incremental && sync_state.last_sync.is_some();
+
+		// an incremental sync only sees packages that were updated, so the
+		// download and star counts of everything else go stale. every so
+		// often, walk the whole registry to refresh them
+		if let Some(interval) = self.counts_refresh_interval {
+			let stale = sync_state.counts_refreshed_at.map_or(true, |refreshed_at| Utc::now() - refreshed_at >= interval);
+			if incremental && stale {
+				info!("download and star counts haven't been refreshed in a while, doing a full sync");
+				incremental = false;
+			}
+		}
+
+		// themes live under their own endpoint
+		let mut endpoints = vec![("packages", false)];
+		if self.filter.include_themes {
+			endpoints.push(("themes", true));
+		}
+
+		let cursor = sync_state.cursor.filter(|cursor| {
+			cursor.incremental == incremental
+				&& cursor.next.as_ref().map_or(true, |next| self.base_urls.iter().any(|base_url| next.starts_with(base_url.as_str())))
+				&& endpoints.iter().any(|(endpoint, _)| *endpoint == cursor.endpoint)
+		});
+		let started_at = cursor.as_ref().map_or_else(Utc::now, |cursor| cursor.started_at);
+
+		let mut stats = SyncStats { incremental, resumed: cursor.is_some(), ..Default::default() };
+		if let Some(cursor) = &cursor {
+			info!("resuming unfinished registry sync from {} page {}", cursor.endpoint, cursor.page + 1);
+		}
+
+		let mut skipping = cursor.is_some();
+		for (endpoint, themes) in endpoints {
+			let resume_from = match &cursor {
+				Some(cursor) if skipping => {
+					// endpoints before the one the cursor is on are done already
+					if cursor.endpoint != endpoint { continue }
+					skipping = false;
+					match &cursor.next {
+						Some(next) => { Some((next.clone(), cursor.page)) }
+						None => { continue }
+					}
+				}
+				_ => { None }
+			};
+
+			self.sync_endpoint(db, endpoint, themes, resume_from, started_at, &mut stats).await?;
+		}
+
+		db.set_registry_sync_state(RegistrySyncState {
+			last_sync: Some(started_at),
+			// a full walk saw every package
+			counts_refreshed_at: if incremental { sync_state.counts_refreshed_at } else { Some(started_at) },
+			cursor: None,
+			..db.registry_sync_state()
+		});
+		db.flush();
+
+		Ok(stats)
+	}
+
+	/// fetches the first page of packages from the registry and each mirror,
+	/// once and without the cache, for `doctor`. gives how many packages each
+	/// page had, or why it couldn't be fetched
+	pub async fn check(&self) -> Vec<(String, Result<usize>)> {
+		let mut results = Vec::new();
+		for base_url in self.base_urls.iter() {
+			let url = format!("{base_url}/packages?page=1");
+			let res = async {
+				let body = self.client.get(&url).send().await
+					.and_then(|res| res.error_for_status())
+					.map_err(Error::registry).context(format!("error fetching {url}"))?
+					.text().await
+					.map_err(Error::registry).context(format!("error fetching {url}"))?;
+				let packages = serde_json::from_str::<Vec<RegistryPackage>>(&body)
+					.map_err(Error::registry).context(format!("error parsing response from {url}"))?;
+				Ok(packages.len())
+			}.await;
+			results.push((base_url.clone(), res));
+		}
+		results
+	}
+
+	/// fetches a single package from the registry's detail endpoint, or `None` if there's no such package
+	#[instrument(skip_all, fields(package = %name))]
+	pub async fn fetch_package(&self, name: &str) -> Result<Option<RegistryPackage>> {
+		let url = package_url(self.base_url(), name)?;
+		let res = self.get_with_failover(url.as_str(), None).await?;
+		if res.status() == StatusCode::NOT_FOUND { return Ok(None) }
+
+		let package = res.error_for_status()
+			.map_err(Error::registry).context(format!("error fetching {url}"))?
+			.json::<RegistryPackage>().await
+			.map_err(Error::registry).context(format!("error parsing response from {url}"))?;
+		Ok(Some(package))
+	}
+
+	/// looks up the packages sharing a repository with `stats.possibly_renamed`
+	/// that haven't been seen in this sync, for [`link_renames`]
+	async fn gone(&self, stats: &SyncStats) -> Gone {
+		let mut gone = Gone::new();
+		for other in stats.possibly_renamed.iter().flat_map(|(_, sharing)| sharing) {
+			if stats.seen_names.contains(other) || gone.contains_key(other) { continue }
+			let res = match self.fetch_package(other).await {
+				Ok(None) => { Ok(true) }
+				// the registry might still answer to the old name, with the new one
+				Ok(Some(package)) => { Ok(package.name != *other) }
+				Err(e) => { Err(e.to_string()) }
+			};
+			gone.insert(other.clone(), res);
+		}
+		gone
+	}
+
+	/// syncs just the given packages, using the registry's detail endpoint
+	pub async fn sync_packages(&self, db: &DatabaseThing, names: &[String]) -> Result<SyncStats> {
+		let mut stats = SyncStats::default();
+
+		let bar = progress::Bar::new("syncing packages", Some(names.len() as u64));
+		for (i, name) in names.iter().enumerate() {
+			bar.set_position(i);
+			match self.fetch_package(name).await? {
+				Some(package) => {
+					stats.seen += 1;
+					apply_packages(db, vec![package], false, &self.filter, &mut stats)?;
+					let gone = self.gone(&stats).await;
+					link_renames(db, &mut stats, &gone)?;
+				}
+				None => { info!("package {name} is not on the registry") }
+			}
+		}
+		db.flush();
+
+		Ok(stats)
+	}
+
+	async fn sync_endpoint(
+		&self,
+		db: &DatabaseThing,
+		endpoint: &str,
+		themes: bool,
+		resume_from: Option<(String, usize)>,
+		started_at: DateTime<Utc>,
+		stats: &mut SyncStats
+	) -> Result {
+		let (mut next, mut page) = match resume_from {
+			Some((next, page)) => { (Some(next), page) }
+			None if stats.incremental => {
+				(Some(format!("{}/{endpoint}?page=1&sort=updated_at&direction=desc", self.base_url())), 0)
+			}
+			None => { (Some(format!("{}/{endpoint}?page=1", self.base_url())), 0) }
+		};
+
+		let bar = progress::Bar::new(&format!("syncing {endpoint}"), None);
+		while let Some(url) = next {
+			let (packages, next_page, last_page) = self.fetch_page(&url).await?;
+			page += 1;
+			stats.pages += 1;
+			stats.seen += packages.len();
+
+			let page_changed = apply_packages(db, packages, themes, &self.filter, stats)?;
+			let gone = self.gone(stats).await;
+			link_renames(db, stats, &gone)?;
+			let done = next_page.is_none() || (stats.incremental && !page_changed);
+
+			// so a sync that fails halfway through doesn't lose what it got so far,
+			// and can carry on from the next page
+			db.set_registry_sync_state(RegistrySyncState {
+				cursor: Some(RegistrySyncCursor {
+					endpoint: endpoint.into(),
+					next: if done { None } else { next_page.clone() },
+					page,
+					incremental: stats.incremental,
+					started_at
+				}),
+				..db.registry_sync_state()
+			});
+			db.flush();
+
+			// an incremental sync stops well before the last page, so how many there are isn't much use
+			if let Some(last_page) = last_page.filter(|_| !stats.incremental) {
+				bar.set_length(last_page);
+			}
+			bar.set_position(page);
+			bar.set_message(format!("pages, {} packages seen, {} added, {} updated", stats.seen, stats.added, stats.updated));
+			if !bar.is_shown() {
+				debug!(
+					"synced {endpoint} page {page} ({} packages seen, {} added, {} updated)",
+					stats.seen,
+					stats.added,
+					stats.updated
+				);
+			}
+
+			if stats.incremental && !page_changed {
+				info!("{endpoint} page {page} had no new or updated packages, stopping incremental sync");
+				break
+			}
+			next = next_page;
+		}
+
+		Ok(())
+	}
+}
+
+/// loads packages from an offline dump of the registry instead of the live api,
+/// see [`dump::load`] for the formats that are understood
+pub fn sync_from_dump(db: &DatabaseThing, config: &Config, path: &str) -> Result<SyncStats> {
+	let packages = dump::load(Path::new(path))?;
+	info!("loaded {} packages from dump {path}", packages.len());
+
+	let mut stats = SyncStats { pages: 1, seen: packages.len(), from_dump: true, ..Default::default() };
+	apply_packages(db, packages, false, &SyncFilter::new(config), &mut stats)?;
+	let gone = gone_unless_seen(&stats);
+	link_renames(db, &mut stats, &gone)?;
+	db.flush();
+
+	Ok(stats)
+}
+
+/// like [`sync_from_dump`], with the made up packages of `simulation.packages`
+pub fn sync_simulated(db: &DatabaseThing, config: &Config) -> Result<SyncStats> {
+	let packages = simulated::registry(&config.simulation);
+	info!("made up {} packages (simulation.packages)", packages.len());
+
+	let mut stats = SyncStats { pages: 1, seen: packages.len(), from_dump: true, ..Default::default() };
+	apply_packages(db, packages, false, &SyncFilter::new(config), &mut stats)?;
+	let gone = gone_unless_seen(&stats);
+	link_renames(db, &mut stats, &gone)?;
+	db.flush();
+
+	Ok(stats)
+}
+
+/// whether each of the packages sharing a repository with one in
+/// `stats.possibly_renamed` is gone from the registry, or why that couldn't be
+/// told. the ones seen in this sync are still there
+type Gone = HashMap<String, std::result::Result<bool, String>>;
+
+/// for a dump (or the simulated registry), which is the whole registry: the
+/// packages that aren't in it are gone
+fn gone_unless_seen(stats: &SyncStats) -> Gone {
+	stats.possibly_renamed.iter()
+		.flat_map(|(_, sharing)| sharing)
+		.map(|other| (other.clone(), Ok(!stats.seen_names.contains(other))))
+		.collect()
+}
+
+/// links the packages in `stats.possibly_renamed` to the one they were renamed
+/// from: the one package sharing their repository that's `gone`. if there's
+/// more than one (or it couldn't be told), it's left to someone to work out
+fn link_renames(db: &DatabaseThing, stats: &mut SyncStats, gone: &Gone) -> Result {
+	for (name, sharing) in std::mem::take(&mut stats.possibly_renamed) {
+		let mut renamed_from = Vec::new();
+		let mut unknown = Vec::new();
+		for other in sharing.iter().filter(|other| !stats.seen_names.contains(*other)) {
+			match gone.get(other) {
+				Some(Ok(true)) => { renamed_from.push(other.as_str()) }
+				Some(Err(e)) => { unknown.push(format!("{other} ({e})")) }
+				_ => {}
+			}
+		}
+
+		let reason = match (renamed_from.as_slice(), unknown.is_empty()) {
+			([], true) => { continue }
+			([old], true) => {
+				info!("package {name} is {old} renamed on the registry, it carries on where {old} was");
+				db.rename_package(old, &name)?;
+				stats.renamed += 1;
+				continue
+			}
+			(_, true) => {
+				format!("it shares its repository with {}, which aren't on the registry any more, it might be one of them renamed", renamed_from.join(" and "))
+			}
+			(_, false) => {
+				format!("it shares its repository with {}, which couldn't be checked for still being on the registry, so it might be renamed from one of them", unknown.join(" and "))
+			}
+		};
+		warn!("package {name} needs review: {reason}");
+		db.set_state(&name, db::PackageState::NeedsReview(db::PackageNeedsReview { reason, flagged_at: Utc::now(), contact_failure: None }))?;
+	}
+	Ok(())
+}
+
+/// adds packages that aren't in the db yet, and updates the ones that
+/// changed. returns whether anything was added or updated
+fn apply_packages(
+	db: &DatabaseThing,
+	packages: Vec<RegistryPackage>,
+	themes: bool,
+	filter: &SyncFilter,
+	stats: &mut SyncStats
+) -> Result<bool> {
+	let mut changed = false;
+
+	for package in packages {
+		let (name, downloads) = (package.name.clone(), package.downloads);
+		stats.seen_names.insert(name.clone());
+		let package = match package.into_package(themes) {
+			Some(package) => {
+				db.set_without_repository(&name, None);
+				package
+			}
+			None => {
+				stats.no_repository += 1;
+				db.set_without_repository(&name, Some(downloads));
+				continue
+			}
+		};
+
+		if package.is_theme() && !filter.include_themes {
+			stats.themes_excluded += 1;
+			continue
+		}
+
+		let allowed = filter.allowlist.as_ref().map_or(true, |allowlist| allowlist.matches(&package));
+		if filter.blocklist.matches(&package) || !allowed {
+			stats.not_allowed += 1;
+			continue
+		}
+
+		match db.package_info(&package.name) {
+			Some(existing) => {
+				// a new version is the only reliable sign a package was updated
+				let updated = existing.metadata.version != package.metadata.version;
+				let newly_theme = package.theme && !existing.theme;
+				// counts change all the time, so they don't count as the package being updated
+				let counts_changed = existing.downloads != package.downloads
+					|| existing.stargazers_count != package.stargazers_count;
+				// packages stored before owners were kept track of
+				let missing_owner = existing.owner.is_none();
+				if updated || newly_theme || counts_changed || missing_owner {
+					let package = db::PackageNew { theme: existing.theme || package.theme, ..package };
+					db.update_package_info(&package)?;
+					if updated {
+						stats.updated += 1;
+						changed = true;
+					}
+					if counts_changed {
+						stats.counts_refreshed += 1;
+					}
+				}
+			}
+			None => {
+				let sharing = db.sharing_repository(&package);
+				db.add_package(&package)?;
+				if !sharing.is_empty() {
+					stats.possibly_renamed.push((package.name.clone(), sharing));
+				}
+				stats.added += 1;
+				changed = true;
+			}
+		}
+	}
+
+	Ok(changed)
+}
+
+/// url of the registry's detail endpoint for a package. the name is encoded
+/// as a single path segment, since package names can have all sorts of
+/// characters in them that would otherwise end up changing the url
+/// (scoped names with a `/`, `#`, `?`, `%`, spaces, non ascii...)
+fn package_url(base_url: &str, name: &str) -> Result<Url> {
+	let mut url = Url::parse(base_url)
+		.map_err(|e| format!("error parsing registry url {base_url}: {e}"))?;
+	url.path_segments_mut()
+		.map_err(|_| format!("registry url {base_url} can't have paths"))?
+		.pop_if_empty()
+		.push("packages")
+		.push(name);
+	Ok(url)
+}
+
+/// how long to wait before retrying, 2, 4, 8... seconds, up to 5 minutes
+fn backoff(attempt: u32) -> StdDuration {
+	StdDuration::from_secs(2u64.saturating_pow(attempt).min(300))
+}
+
+/// the `Retry-After` header, if it's there and in seconds
+fn retry_after(res: &Response) -> Option<StdDuration> {
+	res.headers()
+		.get(RETRY_AFTER)?
+		.to_str().ok()?
+		.trim()
+		.parse().ok()
+		.map(StdDuration::from_secs)
+}
+
+/// finds the url for `rel` (like `next`) in a `Link` header, like
+/// `<https://atom.io/api/packages?page=2>; rel="next", <https://atom.io/api/packages?page=300>; rel="last"`
+fn find_link(header: &str, rel: &str) -> Option<String> {
+	let rel = format!("rel=\"{rel}\"");
+	header.split(',').find_map(|link| {
+		let (url, params) = link.split_once(';')?;
+		let matches = params.split(';')
+			.any(|param| param.trim().replace(' ', "") == rel);
+		if !matches { return None }
+
+		let url = url.trim().strip_prefix('<')?.strip_suffix('>')?;
+		Some(url.into())
+	})
+}
+
+/// the `page` in a url's query
+fn page_number(url: &str) -> Option<usize> {
+	Url::parse(url).ok()?
+		.query_pairs()
+		.find(|(key, _)| key == "page")?
+		.1.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// names like the odd ones found in the registry, and what their detail url should end with
+	const NAMES: &[(&str, &str)] = &[
+		("linter-eslint", "/packages/linter-eslint"),
+		("Sublime-Style-Column-Selection", "/packages/Sublime-Style-Column-Selection"),
+		("atom.io-theme", "/packages/atom.io-theme"),
+		("under_score", "/packages/under_score"),
+		("language-c++", "/packages/language-c++"),
+		("@scope/package", "/packages/@scope%2Fpackage"),
+		("language-c#", "/packages/language-c%23"),
+		("why?", "/packages/why%3F"),
+		("100%-done", "/packages/100%25-done"),
+		("with space", "/packages/with%20space"),
+		("ümlaut-theme", "/packages/%C3%BCmlaut-theme"),
+		("日本語", "/packages/%E6%97%A5%E6%9C%AC%E8%AA%9E")
+	];
+
+	#[test]
+	fn package_urls_are_encoded() {
+		for (name, expected) in NAMES {
+			let url = package_url("https://atom.io/api", name).unwrap();
+			assert_eq!(url.as_str(), format!("https://atom.io/api{expected}"), "package name {name}");
+		}
+	}
+
+	#[test]
+	fn package_urls_round_trip() {
+		for (name, _) in NAMES {
+			let url = package_url("https://atom.io/api", name).unwrap();
+			let segment = url.path_segments().unwrap().next_back().unwrap();
+			let decoded = percent_encoding::percent_decode_str(segment).decode_utf8().unwrap();
+			assert_eq!(&decoded, name);
+		}
+	}
+
+	#[test]
+	fn trailing_slash_in_base_url() {
+		let url = package_url("https://atom.io/api/", "@scope/package").unwrap();
+		assert_eq!(url.as_str(), "https://atom.io/api/packages/@scope%2Fpackage");
+	}
+}
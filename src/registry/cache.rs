@@ -0,0 +1,64 @@
+//! on disk cache of registry responses, so repeated runs don't have to fetch everything again
+
+use crate::Result;
+use chrono::{ DateTime, Duration, Utc };
+use serde::{ Deserialize, Serialize };
+use std::path::PathBuf;
+use tokio::fs;
+
+pub struct RegistryCache {
+	dir: PathBuf,
+	/// how long a cached page is used as is, before checking with the registry if it's changed
+	max_age: Duration
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct CachedPage {
+	pub url: String,
+	pub etag: Option<String>,
+	pub next: Option<String>,
+	/// how many pages there are, if the registry said
+	#[serde(default)]
+	pub last_page: Option<usize>,
+	pub fetched_at: DateTime<Utc>,
+	pub body: String
+}
+
+impl CachedPage {
+	pub fn is_fresh(&self, max_age: Duration) -> bool {
+		Utc::now() - self.fetched_at < max_age
+	}
+}
+
+impl RegistryCache {
+	pub fn new(dir: impl Into<PathBuf>, max_age: Duration) -> Self {
+		Self { dir: dir.into(), max_age }
+	}
+
+	pub fn max_age(&self) -> Duration {
+		self.max_age
+	}
+
+	pub async fn get(&self, url: &str) -> Option<CachedPage> {
+		let data = fs::read(self.path_for(url)).await.ok()?;
+		let page = serde_json::from_slice::<CachedPage>(&data).ok()?;
+
+		// just in case two urls end up with the same filename
+		if page.url != url { return None }
+		Some(page)
+	}
+
+	pub async fn put(&self, page: &CachedPage) -> Result {
+		fs::create_dir_all(&self.dir).await?;
+		fs::write(self.path_for(&page.url), serde_json::to_vec(page)?).await?;
+		Ok(())
+	}
+
+	fn path_for(&self, url: &str) -> PathBuf {
+		let name = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+		let name = name.chars()
+			.map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+			.collect::<String>();
+		self.dir.join(format!("{name}.json"))
+	}
+}
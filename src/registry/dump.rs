@@ -0,0 +1,107 @@
+//! offline dumps of the registry, for when the live api is unreliable (or gone)
+
+use super::RegistryPackage;
+use crate::Result;
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use std::fs::{ self, File };
+use std::io::Read;
+use std::path::Path;
+
+/// a json document in a dump, which can have one package, a list of them,
+/// or a list of them under `packages` (like an api response saved as is)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DumpDocument {
+	Many(Vec<RegistryPackage>),
+	Wrapped { packages: Vec<RegistryPackage> },
+	One(Box<RegistryPackage>)
+}
+
+impl DumpDocument {
+	fn into_packages(self) -> Vec<RegistryPackage> {
+		match self {
+			Self::Many(packages) | Self::Wrapped { packages } => { packages }
+			Self::One(package) => { vec![*package] }
+		}
+	}
+}
+
+/// loads packages from a dump, which can be any of:
+///
+/// - a json file (see [`DumpDocument`] for what it can contain)
+/// - a directory of json files, searched recursively (like the `out/packages`
+///   directory of the community package collection)
+/// - a `.tar`, `.tar.gz`, or `.tgz` archive of json files
+pub fn load(path: &Path) -> Result<Vec<RegistryPackage>> {
+	let name = path.to_string_lossy().to_lowercase();
+
+	if path.is_dir() {
+		let mut packages = Vec::new();
+		load_dir(path, &mut packages)?;
+		Ok(packages)
+	} else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+		let file = File::open(path).map_err(|e| format!("error opening dump {}: {e}", path.display()))?;
+		load_tar(GzDecoder::new(file), path)
+	} else if name.ends_with(".tar") {
+		let file = File::open(path).map_err(|e| format!("error opening dump {}: {e}", path.display()))?;
+		load_tar(file, path)
+	} else {
+		let data = fs::read(path).map_err(|e| format!("error reading dump {}: {e}", path.display()))?;
+		parse_document(&data, &path.display().to_string())
+	}
+}
+
+fn load_dir(dir: &Path, packages: &mut Vec<RegistryPackage>) -> Result {
+	let mut entries = fs::read_dir(dir)
+		.map_err(|e| format!("error reading dump directory {}: {e}", dir.display()))?
+		.collect::<std::result::Result<Vec<_>, _>>()?;
+	entries.sort_by_key(|entry| entry.path());
+
+	for entry in entries {
+		let path = entry.path();
+		if path.is_dir() {
+			load_dir(&path, packages)?;
+		} else if is_json(&path) {
+			let data = fs::read(&path).map_err(|e| format!("error reading {}: {e}", path.display()))?;
+			match parse_document(&data, &path.display().to_string()) {
+				Ok(mut parsed) => { packages.append(&mut parsed) }
+				Err(e) => { error!("{e}") }
+			}
+		}
+	}
+
+	Ok(())
+}
+
+fn load_tar(reader: impl Read, path: &Path) -> Result<Vec<RegistryPackage>> {
+	let mut archive = tar::Archive::new(reader);
+	let mut packages = Vec::new();
+
+	let entries = archive.entries()
+		.map_err(|e| format!("error reading dump archive {}: {e}", path.display()))?;
+	for entry in entries {
+		let mut entry = entry.map_err(|e| format!("error reading dump archive {}: {e}", path.display()))?;
+		let entry_path = entry.path()?.into_owned();
+		if !entry.header().entry_type().is_file() || !is_json(&entry_path) { continue }
+
+		let mut data = Vec::new();
+		entry.read_to_end(&mut data)?;
+		match parse_document(&data, &format!("{}:{}", path.display(), entry_path.display())) {
+			Ok(mut parsed) => { packages.append(&mut parsed) }
+			Err(e) => { error!("{e}") }
+		}
+	}
+
+	Ok(packages)
+}
+
+fn parse_document(data: &[u8], name: &str) -> Result<Vec<RegistryPackage>> {
+	let document = serde_json::from_slice::<DumpDocument>(data)
+		.map_err(|e| format!("error parsing {name}: {e}"))?;
+	Ok(document.into_packages())
+}
+
+fn is_json(path: &Path) -> bool {
+	path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("json"))
+}
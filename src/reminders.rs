@@ -0,0 +1,165 @@
+//! following up on filed issues that nobody has replied to, and noticing the
+//! replies that say yes or no (see [`consent::answer_from_replies`])
+
+use crate::activity;
+use crate::config::Config;
+use crate::consent;
+use crate::db::{ DatabaseThing, PackageIssueFiled, PackageState, Reminder, ReminderKind };
+use crate::forge::{ self, Forges };
+use crate::templates::Vars;
+use crate::Result;
+use chrono::{ DateTime, Duration, Utc };
+use std::collections::BTreeMap;
+use tracing::instrument;
+
+
+/// reminders for an issue filed at `filed_at`
+pub fn schedule(filed_at: DateTime<Utc>, config: &Config) -> Vec<Reminder> {
+	[
+		(ReminderKind::FollowUp, config.follow_up_after),
+		(ReminderKind::FinalReminder, config.final_reminder_after)
+	]
+		.into_iter()
+		.filter(|(_, days)| *days > 0)
+		.map(|(kind, days)| Reminder {
+			kind,
+			due_at: filed_at + Duration::days(days as i64),
+			sent_at: None
+		})
+		.collect()
+}
+
+/// posts the reminders that are due, unless someone has replied to the issue
+/// (or closed it) since, in which case the rest of its reminders are cancelled
+pub async fn send_due(db: &DatabaseThing, forges: &mut Forges, dry_run: bool) -> Result {
+	let now = Utc::now();
+
+	// packages that share a repository share an issue, so they only get one reminder between them
+	let mut issues = BTreeMap::<String, (PackageIssueFiled, Vec<String>)>::new();
+	for (name, filed) in db.filed_issues() {
+		if filed.replied_at.is_some() { continue }
+		if !filed.reminders.iter().any(|reminder| reminder.sent_at.is_none() && reminder.due_at <= now) { continue }
+
+		issues.entry(filed.issue_url.clone())
+			.or_insert_with(|| (filed, Vec::new()))
+			.1.push(name);
+	}
+
+	for (issue_url, (filed, names)) in issues {
+		poll_issue(db, forges, &issue_url, &filed, &names, now, dry_run).await?;
+	}
+
+	Ok(())
+}
+
+/// checks one issue (filed for `names`) for replies, and posts its reminder if nobody has
+#[instrument(skip_all, fields(package = %names.join(", "), issue = %issue_url))]
+async fn poll_issue(
+	db: &DatabaseThing,
+	forges: &mut Forges,
+	issue_url: &str,
+	filed: &PackageIssueFiled,
+	names: &[String],
+	now: DateTime<Utc>,
+	dry_run: bool
+) -> Result {
+	// for after the forge's client has borrowed `forges`
+	let config = forges.config().clone();
+	let number = match forge::issue_number(issue_url) {
+		Some(number) => { number }
+		None => {
+			error!("can't send reminder on {issue_url}: can't tell what issue number it is");
+			return Ok(())
+		}
+	};
+	let client = match forges.client_for(&filed.repository) {
+		Ok(client) => { client }
+		Err(e) => {
+			error!("can't send reminder on {issue_url}: {e}");
+			return Ok(())
+		}
+	};
+
+	let activity = match client.issue_activity(&filed.repository, number).await {
+		Ok(activity) => { activity }
+		Err(e) => {
+			error!("error checking {issue_url} for replies: {e}");
+			return Ok(())
+		}
+	};
+	for name in names.iter() {
+		db.set_seen_comments(name, &activity.comments);
+	}
+	if !dry_run {
+		// the packages on an issue are answered for together
+		if consent::answer_from_replies(db, &config, &names[0], &activity.comments, activity.closed).await?.is_some() {
+			return Ok(())
+		}
+	}
+	if activity.replied || activity.closed {
+		info!("{issue_url} was {}, cancelling its reminders", if activity.replied { "replied to" } else { "closed" });
+		for name in names.iter() {
+			update_filed(db, name, None, |filed| filed.replied_at = Some(now))?;
+		}
+		db.flush();
+		return Ok(())
+	}
+
+	// if more than one is due (eg. the bot wasn't run for a while), only the latest one is sent
+	let reminder = filed.reminders.iter()
+		.filter(|reminder| reminder.sent_at.is_none() && reminder.due_at <= now)
+		.max_by_key(|reminder| reminder.due_at)
+		.expect("issues without due reminders were filtered out");
+	let packages = names.iter().filter_map(|name| db.package_info(name)).collect::<Vec<_>>();
+	let vars = Vars::issue(&filed.repository, &packages).with_declined(db.declined_packages_of(&filed.repository));
+	let templates = config.templates_for(filed.template_variant.as_deref(), filed.locale.as_deref());
+	let (kind, body) = match reminder.kind {
+		ReminderKind::FollowUp => { ("follow up", templates.follow_up(&vars)) }
+		ReminderKind::FinalReminder => { ("final reminder", templates.final_reminder(&vars)) }
+	};
+	let body = match body {
+		Ok(body) => { body }
+		Err(e) => {
+			error!("can't send {kind} on {issue_url}: {e}");
+			return Ok(())
+		}
+	};
+
+	if dry_run {
+		println!("would post {kind} on {issue_url}:\n{body}");
+		return Ok(())
+	}
+	if let Some(breaker) = db.breaker(&filed.repository.host) {
+		info!("not posting {kind} on {issue_url} yet: too much has failed on {} lately, nothing is posted on it until {}", filed.repository.host, breaker.until);
+		return Ok(())
+	}
+	if let Err(e) = client.comment_on_issue(&filed.repository, number, &body).await {
+		error!("error posting reminder on {issue_url}: {e}");
+		return Ok(())
+	}
+	info!("posted {kind} on {issue_url}");
+	activity::record(db, &format!("posted {kind} on {issue_url} for {}", activity::packages(names)));
+
+	// noted, so the history still says it was sent once the package has moved on
+	for name in names.iter() {
+		update_filed(db, name, Some(format!("posted {kind}")), |filed| {
+			filed.reminders.iter_mut()
+				.filter(|reminder| reminder.sent_at.is_none() && reminder.due_at <= now)
+				.for_each(|reminder| reminder.sent_at = Some(now));
+		})?;
+	}
+	db.flush();
+
+	Ok(())
+}
+
+/// changes what's kept about `name`'s issue, if it's still filed
+pub fn update_filed(db: &DatabaseThing, name: &str, note: Option<String>, f: impl FnOnce(&mut PackageIssueFiled)) -> Result {
+	let mut filed = match db.filed_issues().into_iter().find(|(filed_name, _)| filed_name == name) {
+		Some((_, filed)) => { filed }
+		None => { return Ok(()) }
+	};
+	f(&mut filed);
+	db.set_state_noted(name, PackageState::IssueFiled(filed), note)?;
+	Ok(())
+}
@@ -0,0 +1,71 @@
+//! the repository urls in package.json files, which are written every which
+//! way. it's on its own (it only needs the `url` crate) so the fuzz target in
+//! `fuzz/` can build it without the rest of the bot
+
+/// the host (lowercased), owner, and name of the repository `url` points to, for
+/// the kinds of urls found in package.json files, like `https://github.com/owner/repo`,
+/// `git+https://github.com/owner/repo.git`, or `git@github.com:owner/repo.git`.
+/// only gitlab has owners with a `/` in them (groups with subgroups), everywhere
+/// else what's after the owner and name is a page in the repository
+pub fn parse(url: &str) -> Option<(String, String, String)> {
+	let url = url.trim();
+	let url = url.strip_prefix("git+").unwrap_or(url);
+
+	let (host, path) = if let Some(rest) = url.strip_prefix("git@") {
+		// scp-like syntax
+		let (host, path) = rest.split_once(':')?;
+		(host.to_string(), path.to_string())
+	} else {
+		let parsed = url::Url::parse(url).ok()?;
+		(parsed.host_str()?.to_string(), parsed.path().to_string())
+	};
+
+	let host = host.to_lowercase();
+	let segments = path.split('/').filter(|segment| !segment.is_empty()).collect::<Vec<_>>();
+	let (owner, name) = if is_gitlab(&host) {
+		// pages in the repository are after a `-`, like `/group/repo/-/tree/main`
+		let end = segments.iter().position(|segment| *segment == "-").unwrap_or(segments.len());
+		let (name, owner) = segments[..end].split_last()?;
+		(owner.join("/"), *name)
+	} else {
+		match segments.as_slice() {
+			[owner, name, ..] => { (owner.to_string(), *name) }
+			_ => { return None }
+		}
+	};
+	let name = name.strip_suffix(".git").unwrap_or(name);
+
+	if host.is_empty() || owner.is_empty() || name.is_empty() { return None }
+
+	Some((host, owner, name.into()))
+}
+
+/// gitlab.com, or a self hosted gitlab, which are nearly always on a gitlab.*
+/// subdomain, or at least have gitlab somewhere in the name (gitlab.gnome.org, etc)
+pub fn is_gitlab(host: &str) -> bool {
+	host.split('.').any(|part| part.contains("gitlab"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn repo(host: &str, owner: &str, name: &str) -> Option<(String, String, String)> {
+		Some((host.into(), owner.into(), name.into()))
+	}
+
+	#[test]
+	fn pages_in_the_repository_are_left_off() {
+		assert_eq!(parse("https://github.com/atom/atom/tree/master/packages/one-light-ui"), repo("github.com", "atom", "atom"));
+		assert_eq!(parse("https://github.com/atom/one-dark-ui.git/"), repo("github.com", "atom", "one-dark-ui"));
+		assert_eq!(parse("https://gitlab.com/group/subgroup/package/-/tree/main"), repo("gitlab.com", "group/subgroup", "package"));
+	}
+
+	#[test]
+	fn only_gitlab_owners_have_subgroups() {
+		assert_eq!(parse("https://gitlab.com/group/subgroup/atom-package"), repo("gitlab.com", "group/subgroup", "atom-package"));
+		assert_eq!(parse("git@gitlab.gnome.org:someone/atom-theme.git"), repo("gitlab.gnome.org", "someone", "atom-theme"));
+		assert_eq!(parse("https://bitbucket.org/someone/atom-package/src/master"), repo("bitbucket.org", "someone", "atom-package"));
+		assert_eq!(parse("https://github.com/someone"), None);
+	}
+}
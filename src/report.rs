@@ -0,0 +1,247 @@
+//! the report at the end of a run: what happened since the last one (issues
+//! filed, approvals, replies, failures, what was skipped and why, and how many
+//! api calls each stage made), written as markdown to `reports.dir`, and
+//! printed shorter, for pasting in the team chat. what was done with packages
+//! comes from the [`RunReport`]s of the runs since, everything else (approvals,
+//! replies, being skipped before contacting) from the database
+
+use crate::cli::RunSummary;
+use crate::config::Config;
+use crate::db::{ DatabaseThing, PackageState };
+use crate::error::Class;
+use crate::notify;
+use crate::runs::{ self, Action, RunReport };
+use crate::usage::{ self, Usage };
+use crate::Result;
+use chrono::{ DateTime, Utc };
+use std::collections::{ BTreeMap, HashSet };
+use std::fmt::Write as _;
+use std::path::Path;
+use tokio::fs;
+
+/// how many names are listed for each reason packages were skipped for
+const SKIPPED_NAMES: usize = 10;
+
+/// what's in a report
+#[derive(Default)]
+struct Report {
+	filed: Vec<String>,
+	emailed: Vec<String>,
+	approved: Vec<String>,
+	needs_review: Vec<String>,
+	failed: Vec<String>,
+	/// failed in a way that's never going to work, so not retried
+	given_up: Vec<String>,
+	/// names, by what they were skipped for
+	skipped: BTreeMap<String, Vec<String>>,
+	replies: Vec<String>
+}
+
+/// writes the report for a run that started at `started_at` (or the last report,
+/// if there's been one), and prints the short version of it, which is returned
+/// too. for dry runs, it's only printed, since nothing was really done
+pub async fn finish(db: &DatabaseThing, config: &Config, summary: &RunSummary, results: &RunReport, started_at: DateTime<Utc>) -> Result<String> {
+	let since = db.last_report_at().unwrap_or(started_at);
+	let now = Utc::now();
+	// the runs since the last report that weren't reported on, for dry runs only this one
+	let mut runs = match summary.dry_run {
+		true => { Vec::new() }
+		false => {
+			runs::load(&config.db_path).await
+				.unwrap_or_else(|e| {
+					warn!("{e}");
+					Vec::new()
+				})
+				.into_iter()
+				.filter(|run| run.started_at >= since && !run.dry_run)
+				.collect()
+		}
+	};
+	runs.push(results.clone());
+	let report = gather(db, &runs, since);
+	let calls = usage::take();
+	for line in calls.summary() {
+		info!("api calls: {line}");
+	}
+
+	let path = match (&config.report_dir, summary.dry_run) {
+		(Some(dir), false) => {
+			fs::create_dir_all(dir).await
+				.map_err(|e| format!("error creating report directory {dir}: {e}"))?;
+			let path = Path::new(dir).join(format!("run-{}.md", now.format("%Y-%m-%dT%H-%M-%S")));
+			fs::write(&path, markdown(&report, summary, &calls, since, now)?).await
+				.map_err(|e| format!("error writing report {}: {e}", path.display()))?;
+			db.set_last_report_at(now);
+			Some(path)
+		}
+		_ => { None }
+	};
+
+	let condensed = condensed(&report, summary, path.as_deref());
+	println!("{condensed}");
+	if !summary.dry_run {
+		notify::send(config, &condensed).await;
+	}
+	Ok(condensed)
+}
+
+/// everything packages went through since `since`: what `runs` did with them,
+/// and where the rest ended up
+fn gather(db: &DatabaseThing, runs: &[RunReport], since: DateTime<Utc>) -> Report {
+	let mut report = Report::default();
+	let mut seen_replies = HashSet::new();
+
+	for result in runs.iter().flat_map(|run| &run.packages) {
+		let name = &result.package;
+		let reason = result.reason.as_deref().unwrap_or_default();
+		match (result.action, &result.error) {
+			(Action::Filed, _) => {
+				match &result.issue_url {
+					Some(issue_url) => { report.filed.push(format!("`{name}`: {issue_url}")) }
+					None => { report.filed.push(format!("`{name}`")) }
+				}
+			}
+			(Action::AlreadyFiled, _) => { report.filed.push(format!("`{name}` (there already): {}", result.issue_url.as_deref().unwrap_or_default())) }
+			(Action::Emailed, _) => { report.emailed.push(format!("`{name}`")) }
+			(Action::NeedsReview, _) => { report.needs_review.push(format!("`{name}`: {reason}")) }
+			(Action::Failed, Some(error)) if error.class == Class::Terminal => { report.given_up.push(format!("`{name}`: {}", error.message)) }
+			(Action::Failed, Some(error)) if error.class == Class::NeedsHuman => { report.needs_review.push(format!("`{name}`: {}", error.message)) }
+			(Action::Failed, error) => {
+				let message = error.as_ref().map(|error| error.message.as_str()).unwrap_or_default();
+				match db.package_state(name) {
+					Some(PackageState::RetryPending(retry)) => {
+						report.failed.push(format!("`{name}`: {message} (trying again after {})", retry.retry_at.format("%Y-%m-%d %H:%M")))
+					}
+					_ => { report.failed.push(format!("`{name}`: {message}")) }
+				}
+			}
+			(Action::Skipped, _) => { report.skipped.entry(format!("skipped: {reason}")).or_default().push(name.clone()) }
+		}
+	}
+
+	for package in db.packages() {
+		let name = package.info.name;
+		if let Some(transition) = package.history.iter().rev().find(|transition| transition.at >= since) {
+			match &transition.state {
+				PackageState::MaintainerApproved(approved) => { report.approved.push(format!("`{name}`: {}", approved.approval_url)) }
+				PackageState::Migrated(migrated) => { report.approved.push(format!("`{name}` (and migrated): {}", migrated.approval.approval_url)) }
+				// what runs did, which is listed above
+				PackageState::IssueFiled(_) | PackageState::ContactedByEmail(_) | PackageState::NeedsReview(_) | PackageState::RetryPending(_) | PackageState::Dormant(_) => {}
+				PackageState::Blocked(skipped) if skipped.contact_failure.is_some() => {}
+				PackageState::Deferred(skipped) => { report.skipped.entry(format!("deferred: {}", without_count(&skipped.reason))).or_default().push(name.clone()) }
+				PackageState::SkippedDeprecated(skipped) | PackageState::Blocked(skipped) | PackageState::OptedOut(skipped) | PackageState::DeclinedByOwnerPolicy(skipped) => {
+					report.skipped.entry(format!("{}: {}", transition.state.name(), skipped.reason)).or_default().push(name.clone())
+				}
+				PackageState::AlreadyMigrated(_) | PackageState::ConsentRevoked(_) => {
+					report.skipped.entry(transition.state.name().to_string()).or_default().push(name.clone())
+				}
+				PackageState::New | PackageState::IssueFilingInProgress(_) => {}
+			}
+		}
+
+		for comment in package.comments.iter().flat_map(|seen| &seen.comments) {
+			// packages filed together share their issue, and its comments
+			if comment.created_at < since || !seen_replies.insert(comment.url.clone()) { continue }
+			let first_line = comment.body.lines().find(|line| !line.trim().is_empty()).unwrap_or_default();
+			let excerpt = match first_line.char_indices().nth(100) {
+				Some((end, _)) => { format!("{}…", &first_line[..end]) }
+				None => { first_line.to_string() }
+			};
+			report.replies.push(format!("`{name}`: \"{excerpt}\" ({})", comment.url));
+		}
+	}
+
+	report
+}
+
+/// `downloads is below the minimum of 100` for `12 downloads is below the minimum of 100`,
+/// so packages deferred for the same thing are together
+fn without_count(reason: &str) -> &str {
+	match reason.split_once(' ') {
+		Some((count, rest)) if count.parse::<u64>().is_ok() => { rest }
+		_ => { reason }
+	}
+}
+
+fn markdown(report: &Report, summary: &RunSummary, calls: &Usage, since: DateTime<Utc>, now: DateTime<Utc>) -> std::result::Result<String, std::fmt::Error> {
+	let mut out = String::new();
+	writeln!(out, "# Run report, {} to {}", since.format("%Y-%m-%d %H:%M UTC"), now.format("%Y-%m-%d %H:%M UTC"))?;
+	writeln!(out)?;
+	if summary.stopped_early {
+		writeln!(out, "The run stopped early.")?;
+		writeln!(out)?;
+	}
+	writeln!(out, "- packages gone through: {}", summary.processed)?;
+	writeln!(out, "- issues filed: {}", summary.issues_filed)?;
+	writeln!(out, "- packages emailed: {}", summary.emailed)?;
+	writeln!(out, "- failed: {}", summary.failed)?;
+	writeln!(out, "- skipped: {}", report.skipped.values().map(Vec::len).sum::<usize>())?;
+
+	let sections = [
+		("Issues filed", &report.filed),
+		("Emailed", &report.emailed),
+		("Approved", &report.approved),
+		("Maintainer replies", &report.replies),
+		("Needs review", &report.needs_review),
+		("Failed", &report.failed),
+		("Given up on", &report.given_up)
+	];
+	for (title, lines) in sections {
+		if lines.is_empty() { continue }
+		writeln!(out)?;
+		writeln!(out, "## {title} ({})", lines.len())?;
+		writeln!(out)?;
+		for line in lines {
+			writeln!(out, "- {line}")?;
+		}
+	}
+
+	if !report.skipped.is_empty() {
+		writeln!(out)?;
+		writeln!(out, "## Skipped")?;
+		writeln!(out)?;
+		for (reason, names) in &report.skipped {
+			let mut listed = names.iter().take(SKIPPED_NAMES).map(|name| format!("`{name}`")).collect::<Vec<_>>();
+			if names.len() > SKIPPED_NAMES {
+				listed.push(format!("and {} more", names.len() - SKIPPED_NAMES));
+			}
+			writeln!(out, "- {reason} ({}): {}", names.len(), listed.join(", "))?;
+		}
+	}
+
+	if !calls.0.is_empty() {
+		writeln!(out)?;
+		writeln!(out, "## API calls")?;
+		writeln!(out)?;
+		writeln!(out, "| API | Stage | Endpoint | Calls |")?;
+		writeln!(out, "| --- | --- | --- | ---: |")?;
+		for ((api, stage, endpoint), count) in &calls.0 {
+			writeln!(out, "| {api} | {} | `{endpoint}` | {count} |", stage.name())?;
+		}
+	}
+
+	Ok(out)
+}
+
+/// a few lines, with only what someone would want to hear about
+fn condensed(report: &Report, summary: &RunSummary, path: Option<&Path>) -> String {
+	let mut lines = vec![format!(
+		"**run {}{}**: {} packages gone through, {} issues {}, {} emailed, {} failed, {} skipped",
+		if summary.dry_run { "(dry run)" } else { "finished" },
+		if summary.stopped_early { ", stopped early" } else { "" },
+		summary.processed,
+		summary.issues_filed,
+		if summary.dry_run { "would be filed" } else { "filed" },
+		summary.emailed,
+		summary.failed,
+		report.skipped.values().map(Vec::len).sum::<usize>()
+	)];
+	for (what, listed) in [("approved", &report.approved), ("new replies", &report.replies), ("needs review", &report.needs_review)] {
+		if listed.is_empty() { continue }
+		lines.push(format!("- {what} ({}): {}", listed.len(), listed.join("; ")));
+	}
+	if let Some(path) = path {
+		lines.push(format!("full report: {}", path.display()));
+	}
+	lines.join("\n")
+}
@@ -0,0 +1,104 @@
+//! sending errors and panics to sentry (or anything that takes sentry's api,
+//! like glitchtip), if `sentry.dsn_env` is set, so whoever runs the bot finds out
+//! about what went wrong in unattended runs. errors that are logged are sent,
+//! tagged with the package and so on from the spans they happened in, with
+//! what was logged before them (state changes, requests that failed) as
+//! breadcrumbs
+
+use crate::config::Config;
+use sentry::integrations::tracing::{ self as sentry_tracing, EventFilter, EventMapping };
+use sentry::protocol::Context;
+use sentry::{ ClientInitGuard, ClientOptions };
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use tracing::field::{ Field, Visit };
+use tracing::span::{ Attributes, Id, Record };
+use tracing::{ Level, Subscriber };
+use tracing_subscriber::layer;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// starts sending to sentry, until what's returned is dropped (which waits a bit
+/// for what's been captured to be sent)
+pub fn init(config: &Config) -> Option<ClientInitGuard> {
+	let dsn = config.sentry_dsn.as_ref()?;
+	let guard = sentry::init((dsn.expose(), ClientOptions {
+		release: sentry::release_name!(),
+		environment: config.sentry_environment.clone().or_else(|| config.profile.clone()).map(Into::into),
+		..Default::default()
+	}));
+	info!("sending errors to sentry");
+	Some(guard)
+}
+
+/// for [`crate::logging::init`]. it doesn't do anything until [`init`] is called
+pub fn layer<S>() -> impl Layer<S>
+where
+	S: Subscriber + for<'a> LookupSpan<'a>
+{
+	let sentry = sentry_tracing::layer()
+		.event_filter(|metadata| match *metadata.level() {
+			Level::TRACE => { EventFilter::Ignore }
+			_ => { EventFilter::Breadcrumb }
+		})
+		.event_mapper(|event, ctx| match *event.metadata().level() {
+			Level::ERROR => {
+				let mut tags = BTreeMap::new();
+				for span in ctx.event_scope(event).into_iter().flat_map(|scope| scope.from_root()) {
+					if let Some(fields) = span.extensions().get::<SpanFields>() {
+						tags.extend(fields.0.iter().map(|(name, value)| (name.to_string(), value.clone())));
+					}
+				}
+				let mut sentry_event = sentry_tracing::event_from_event(event, ctx);
+				// the event's own fields are only context, they're tags too so they can be searched for
+				if let Some(Context::Other(fields)) = sentry_event.contexts.get("Rust Tracing Tags") {
+					for (name, value) in fields {
+						tags.insert(name.clone(), value.as_str().map_or_else(|| value.to_string(), String::from));
+					}
+				}
+				sentry_event.tags = tags;
+				EventMapping::Event(sentry_event)
+			}
+			_ => { EventMapping::Breadcrumb(sentry_tracing::breadcrumb_from_event(event)) }
+		})
+		// spans are only used for their fields, not sent as performance data
+		.span_filter(|_| false);
+	RecordSpanFields.and_then(sentry)
+}
+
+/// what a span's fields were set to, like `package`
+struct SpanFields(BTreeMap<&'static str, String>);
+
+impl Visit for SpanFields {
+	fn record_str(&mut self, field: &Field, value: &str) {
+		self.0.insert(field.name(), value.to_string());
+	}
+
+	fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+		self.0.insert(field.name(), format!("{value:?}"));
+	}
+}
+
+/// keeps every span's [`SpanFields`], since sentry's layer doesn't
+struct RecordSpanFields;
+
+impl<S> Layer<S> for RecordSpanFields
+where
+	S: Subscriber + for<'a> LookupSpan<'a>
+{
+	fn on_new_span(&self, attrs: &Attributes, id: &Id, ctx: layer::Context<S>) {
+		let mut fields = SpanFields(BTreeMap::new());
+		attrs.record(&mut fields);
+		if let Some(span) = ctx.span(id) {
+			span.extensions_mut().insert(fields);
+		}
+	}
+
+	fn on_record(&self, id: &Id, values: &Record, ctx: layer::Context<S>) {
+		if let Some(span) = ctx.span(id) {
+			if let Some(fields) = span.extensions_mut().get_mut::<SpanFields>() {
+				values.record(fields);
+			}
+		}
+	}
+}
@@ -0,0 +1,123 @@
+//! collecting replies on the issues that were filed, for `poll-responses`:
+//! recording yeses and noes, and when someone replied, without posting
+//! anything, so it can run far more often than filing does. each run checks up
+//! to `responses.max_issues` of them (the ones checked longest ago first), and
+//! stops checking the ones on a host once it's down to `responses.min_rate_limit`
+//! api requests, leaving the rest for filing
+
+use crate::consent;
+use crate::db::{ DatabaseThing, PackageIssueFiled, PackageState };
+use crate::forge::{ self, Forges };
+use crate::usage::{ self, Stage };
+use crate::Result;
+use chrono::Utc;
+use std::collections::{ BTreeMap, HashMap };
+use tracing::instrument;
+
+/// what checking an issue takes: the issue, and its comments
+const REQUESTS_PER_ISSUE: u32 = 2;
+
+pub async fn poll(db: &DatabaseThing, forges: &mut Forges) -> Result {
+	usage::in_stage(Stage::Polling, check_issues(db, forges)).await
+}
+
+async fn check_issues(db: &DatabaseThing, forges: &mut Forges) -> Result {
+	let config = forges.config().clone();
+
+	// packages that share a repository share an issue, so it's only checked once
+	let mut issues = BTreeMap::<String, (PackageIssueFiled, Vec<String>)>::new();
+	for (name, filed) in db.filed_issues() {
+		issues.entry(filed.issue_url.clone())
+			.or_insert_with(|| (filed, Vec::new()))
+			.1.push(name);
+	}
+	let mut issues = issues.into_iter()
+		.map(|(issue_url, (filed, names))| {
+			let checked_at = db.package(&names[0]).and_then(|package| package.comments).map(|seen| seen.checked_at);
+			(checked_at, issue_url, filed, names)
+		})
+		.collect::<Vec<_>>();
+	// never checked first
+	issues.sort_by_key(|(checked_at, ..)| *checked_at);
+	let total = issues.len();
+	if config.responses_max_issues > 0 {
+		issues.truncate(config.responses_max_issues);
+	}
+
+	// api requests that can still be used on each host, for hosts that say
+	let mut budgets = HashMap::<String, Option<u32>>::new();
+	let (mut checked, mut answered, mut over_budget) = (0, 0, 0);
+	for (_, issue_url, filed, names) in issues {
+		let host = filed.repository.host.clone();
+		if !budgets.contains_key(&host) {
+			let remaining = match forges.client_for(&filed.repository) {
+				Ok(client) => { client.rate_limit().await.ok().flatten().map(|limit| limit.remaining) }
+				Err(_) => { None }
+			};
+			budgets.insert(host.clone(), remaining.map(|remaining| remaining.saturating_sub(config.responses_min_rate_limit)));
+		}
+		if let Some(budget) = budgets.get_mut(&host).unwrap() {
+			if *budget < REQUESTS_PER_ISSUE {
+				over_budget += 1;
+				continue
+			}
+			*budget -= REQUESTS_PER_ISSUE;
+		}
+
+		if check(db, forges, &issue_url, &filed, &names).await? {
+			answered += 1;
+		}
+		checked += 1;
+		db.flush();
+	}
+
+	info!("checked {checked} of {total} issues for replies, {answered} answered");
+	if over_budget > 0 {
+		info!("{over_budget} were left for next time, to leave responses.min_rate_limit api requests for filing");
+	}
+
+	Ok(())
+}
+
+/// records what's been said on `issue_url`, returning whether it was answered
+#[instrument(skip_all, fields(package = %names.join(", "), issue = %issue_url))]
+async fn check(db: &DatabaseThing, forges: &mut Forges, issue_url: &str, filed: &PackageIssueFiled, names: &[String]) -> Result<bool> {
+	let config = forges.config().clone();
+	let number = match forge::issue_number(issue_url) {
+		Some(number) => { number }
+		None => {
+			error!("can't check {issue_url} for replies: can't tell what issue number it is");
+			return Ok(false)
+		}
+	};
+	let activity = match forges.client_for(&filed.repository) {
+		Ok(client) => { client.issue_activity(&filed.repository, number).await }
+		Err(e) => { Err(e) }
+	};
+	let activity = match activity {
+		Ok(activity) => { activity }
+		Err(e) => {
+			error!("error checking {issue_url} for replies: {e}");
+			return Ok(false)
+		}
+	};
+
+	for name in names.iter() {
+		db.set_seen_comments(name, &activity.comments);
+	}
+	// the packages on an issue are answered for together
+	if let Some(answer) = consent::answer_from_replies(db, &config, &names[0], &activity.comments, activity.closed).await? {
+		info!("{issue_url}: {answer}");
+		return Ok(true)
+	}
+	if activity.replied && filed.replied_at.is_none() {
+		info!("{issue_url} was replied to, cancelling its reminders");
+		for name in names.iter() {
+			if let Some(PackageState::IssueFiled(mut filed)) = db.package_state(name) {
+				filed.replied_at = Some(Utc::now());
+				db.set_state(name, PackageState::IssueFiled(filed))?;
+			}
+		}
+	}
+	Ok(false)
+}
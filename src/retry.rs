@@ -0,0 +1,164 @@
+//! retrying packages that couldn't be contacted because something went wrong,
+//! like the network or the forge having a bad day
+
+use crate::config::Config;
+use crate::db::{ self, DatabaseThing, PackageState };
+use crate::forge::{ ForgeClient, RepoLocation };
+use crate::error::{ Class, Failure };
+use crate::metrics;
+use crate::{ Error, Result };
+use chrono::{ DateTime, Duration, Utc };
+
+/// marks packages that failed to be contacted to be retried later, going by the
+/// error's [`Class`]: retryable ones are retried (until they've been retried too
+/// many times, when they need review), ones that need a human need review
+/// straight away, and terminal ones are blocked
+pub fn record_failure(db: &DatabaseThing, config: &Config, names: &[String], error: &Error) -> Result {
+	let class = error.class();
+	metrics::failed(error.failure(), class);
+	let contact_failure = Some(db::ContactFailure { failure: error.failure(), class });
+	for name in names {
+		let attempts = match db.package_state(name) {
+			Some(PackageState::RetryPending(retry)) => { retry.attempts + 1 }
+			Some(PackageState::IssueFilingInProgress(filing)) => { filing.attempts + 1 }
+			_ => { 1 }
+		};
+
+		let state = if class == Class::Terminal {
+			info!("package {name} won't be retried, it's never going to work: {error}");
+			PackageState::Blocked(db::PackageSkipped {
+				reason: format!("contacting failed: {error}"),
+				skipped_at: Utc::now(),
+				contact_failure
+			})
+		} else if class == Class::NeedsHuman {
+			PackageState::NeedsReview(db::PackageNeedsReview {
+				reason: format!("contacting failed: {error}"),
+				flagged_at: Utc::now(),
+				contact_failure
+			})
+		} else if attempts >= config.retry_max_attempts {
+			PackageState::NeedsReview(db::PackageNeedsReview {
+				reason: format!("contacting failed {attempts} times, last error: {error}"),
+				flagged_at: Utc::now(),
+				contact_failure
+			})
+		} else {
+			let retry_at = Utc::now() + backoff(attempts);
+			info!("package {name} will be retried after {retry_at} (attempt {attempts}/{})", config.retry_max_attempts);
+			PackageState::RetryPending(db::PackageRetryPending {
+				attempts,
+				last_error: error.to_string(),
+				retry_at
+			})
+		};
+		db.set_state(name, state)?;
+	}
+	db.flush();
+
+	Ok(())
+}
+
+/// stops anything being tried on a repository for a while after something failed on it,
+/// so a misbehaving one doesn't get hammered every run. if it was the forge rate
+/// limiting the bot, it's the whole host that's stopped for a bit instead, see
+/// [`RATE_LIMIT_COOLDOWN`]. terminal failures aren't cooled down for, since
+/// nothing's tried again after them
+pub fn cool_down(db: &DatabaseThing, config: &Config, repo: &RepoLocation, error: &Error) {
+	if error.class() == Class::Terminal { return }
+	if error.failure() == Failure::RateLimited {
+		let until = Utc::now() + Duration::minutes(RATE_LIMIT_COOLDOWN);
+		warn!("{} is rate limiting the bot, not filing anything on it until {until}", repo.host);
+		db.set_host_cooldown(&repo.host, until);
+		return
+	}
+	if config.repo_cooldown == 0 { return }
+
+	let until = Utc::now() + Duration::hours(config.repo_cooldown as i64);
+	info!("not trying anything on {repo} again until {until}");
+	db.set_repo_cooldown(repo, Some(until));
+}
+
+/// minutes a host that rate limited the bot is left alone for. github asks for at
+/// least a minute after its secondary limit, which is what this is usually for
+const RATE_LIMIT_COOLDOWN: i64 = 5;
+
+/// a host that rate limited the bot, see [`host_cooldown`]
+pub struct HostCooldown {
+	pub until: DateTime<Utc>,
+	/// it's the hourly limit that ran out, so `until` is when it resets
+	pub exhausted: bool
+}
+
+/// when `host` can be used again, if it rate limited the bot. if it's the hourly
+/// limit that ran out, that's when it resets, since there's no point trying
+/// before. that's saved as the host's cooldown, so other runs know to wait too
+pub async fn host_cooldown(db: &DatabaseThing, client: &ForgeClient, host: &str) -> Option<HostCooldown> {
+	let until = db.host_cooldown(host)?;
+	match client.rate_limit().await {
+		Ok(Some(rate_limit)) if rate_limit.remaining == 0 => {
+			if rate_limit.resets_at > until {
+				db.set_host_cooldown(host, rate_limit.resets_at);
+			}
+			Some(HostCooldown { until: until.max(rate_limit.resets_at), exhausted: true })
+		}
+		_ => { Some(HostCooldown { until, exhausted: false }) }
+	}
+}
+
+/// half an hour after the first failure, doubling every time after, up to a day
+fn backoff(attempts: u32) -> Duration {
+	let minutes = 30i64.saturating_mul(1 << attempts.saturating_sub(1).min(10));
+	Duration::minutes(minutes.min(24 * 60))
+}
+
+/// puts packages that failed to be contacted back to new, forgetting their
+/// attempts and their repositories' cooldowns, so they're tried again straight
+/// away. `state` is `RetryPending`, or `NeedsReview` or `Blocked` (only the ones
+/// there because contacting failed), and with `names`, only those packages are reset.
+/// returns the names of the packages that were reset
+pub fn reset(db: &DatabaseThing, state: &str, names: &[String]) -> Result<Vec<String>> {
+	let kind = ["RetryPending", "NeedsReview", "Blocked"].into_iter()
+		.find(|kind| kind.eq_ignore_ascii_case(state))
+		.ok_or_else(|| format!("can only retry packages that are RetryPending, NeedsReview, or Blocked, not {state}"))?;
+
+	let mut packages = db.packages();
+	if !names.is_empty() {
+		for name in names {
+			if !packages.iter().any(|package| package.info.name.eq_ignore_ascii_case(name)) {
+				info!("skipping package {name}: not in the database");
+			}
+		}
+		packages.retain(|package| names.iter().any(|name| package.info.name.eq_ignore_ascii_case(name)));
+	}
+
+	let mut reset = Vec::new();
+	for package in packages {
+		let name = package.info.name;
+		let failure = match &package.state {
+			PackageState::RetryPending(retry) if kind == "RetryPending" => {
+				format!("{} attempts, last error: {}", retry.attempts, retry.last_error)
+			}
+			PackageState::NeedsReview(review) if kind == "NeedsReview" && review.contact_failure.is_some() => {
+				review.reason.clone()
+			}
+			PackageState::Blocked(blocked) if kind == "Blocked" && blocked.contact_failure.is_some() => {
+				blocked.reason.clone()
+			}
+			state => {
+				if !names.is_empty() { info!("skipping package {name}: it's {}", state.name()) }
+				continue
+			}
+		};
+
+		info!("resetting package {name} ({failure})");
+		if let Some(repo) = RepoLocation::parse(&package.info.repository.url) {
+			db.set_repo_cooldown(&repo, None);
+		}
+		db.set_state(&name, PackageState::New)?;
+		reset.push(name);
+	}
+	db.flush();
+
+	Ok(reset)
+}
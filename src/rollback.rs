@@ -0,0 +1,87 @@
+//! undoing the issues filed in a window of time, for when a bad template
+//! or filter makes it into a run
+
+use crate::activity;
+use crate::db::{ DatabaseThing, PackageIssueFiled, PackageState };
+use crate::forge::{ self, Forges };
+use crate::templates::Vars;
+use crate::Result;
+use chrono::{ DateTime, Utc };
+use std::collections::BTreeMap;
+
+
+/// posts an apology on and closes every issue filed since `since`, and puts
+/// the packages they were filed for back to new
+pub async fn run(db: &DatabaseThing, forges: &mut Forges, since: DateTime<Utc>, dry_run: bool) -> Result {
+	// grouped packages share an issue, so it's only closed once
+	let mut issues = BTreeMap::<String, (PackageIssueFiled, Vec<String>)>::new();
+	for (name, filed) in db.filed_issues() {
+		if filed.filed_at < since { continue }
+
+		issues.entry(filed.issue_url.clone())
+			.or_insert_with(|| (filed, Vec::new()))
+			.1.push(name);
+	}
+
+	info!("{} issues filed since {since}", issues.len());
+
+	let mut rolled_back = 0;
+	let mut failed = 0;
+	for (issue_url, (filed, names)) in issues {
+		if dry_run {
+			println!("would close {issue_url} (for {})", names.join(", "));
+			continue
+		}
+
+		let number = match forge::issue_number(&issue_url) {
+			Some(number) => { number }
+			None => {
+				error!("can't close {issue_url}: can't tell what issue number it is");
+				failed += 1;
+				continue
+			}
+		};
+		let packages = names.iter().filter_map(|name| db.package_info(name)).collect::<Vec<_>>();
+		let apology = match forges.config().templates_for(filed.template_variant.as_deref(), filed.locale.as_deref()).rollback(&Vars::issue(&filed.repository, &packages)) {
+			Ok(apology) => { apology }
+			Err(e) => {
+				error!("can't close {issue_url}: {e}");
+				failed += 1;
+				continue
+			}
+		};
+		let client = match forges.client_for(&filed.repository) {
+			Ok(client) => { client }
+			Err(e) => {
+				error!("can't close {issue_url}: {e}");
+				failed += 1;
+				continue
+			}
+		};
+
+		if let Err(e) = client.comment_on_issue(&filed.repository, number, &apology).await {
+			error!("error posting apology on {issue_url}: {e}");
+			failed += 1;
+			continue
+		}
+		if let Err(e) = client.close_issue(&filed.repository, number).await {
+			error!("error closing {issue_url}: {e}");
+			failed += 1;
+			continue
+		}
+		info!("closed {issue_url}");
+		activity::record(db, &format!("closed {issue_url} with an apology, putting {} back to new", activity::packages(&names)));
+
+		for name in names.iter() {
+			db.set_state(name, PackageState::New)?;
+		}
+		db.flush();
+		rolled_back += 1;
+	}
+
+	if !dry_run {
+		info!("rolled back {rolled_back} issues, {failed} failed");
+	}
+
+	Ok(())
+}
@@ -0,0 +1,215 @@
+//! what a run did with each package it went through: what was done, how long it
+//! took, and what went wrong, if anything did. the last [`RUNS_KEPT`] are kept
+//! next to the database, in `<db_path>.runs.json`, for the run report,
+//! `last-run`, and `analytics` to go by
+
+use crate::error::{ Class, Failure };
+use crate::status::{ self, OutputFormat };
+use crate::{ Error, Result };
+use chrono::{ DateTime, Utc };
+use serde::{ Deserialize, Serialize };
+use std::path::Path;
+use std::time::Duration;
+use tokio::fs;
+
+/// how many runs are kept
+const RUNS_KEPT: usize = 20;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct RunReport {
+	pub started_at: DateTime<Utc>,
+	pub finished_at: Option<DateTime<Utc>>,
+	pub dry_run: bool,
+	pub stopped_early: bool,
+	/// in the order they were gone through
+	pub packages: Vec<PackageResult>
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct PackageResult {
+	pub package: String,
+	pub action: Action,
+	/// milliseconds spent on it, not counting waiting between filing issues
+	pub took_ms: u64,
+	/// the issue that was filed, or was there already
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub issue_url: Option<String>,
+	/// why it needs review, or was skipped
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub reason: Option<String>,
+	/// what went wrong, for failures
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub error: Option<PackageError>
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct PackageError {
+	pub message: String,
+	pub failure: Failure,
+	pub class: Class
+}
+
+/// what was done with a package
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+	/// an issue was filed, or would have been for dry runs
+	Filed,
+	/// there was an issue already, so it was recorded rather than filing another
+	AlreadyFiled,
+	Emailed,
+	NeedsReview,
+	Failed,
+	Skipped
+}
+
+impl Action {
+	pub const ALL: [Action; 6] = [Self::Filed, Self::AlreadyFiled, Self::Emailed, Self::NeedsReview, Self::Failed, Self::Skipped];
+
+	/// for printing
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Filed => { "filed" }
+			Self::AlreadyFiled => { "already filed" }
+			Self::Emailed => { "emailed" }
+			Self::NeedsReview => { "needs review" }
+			Self::Failed => { "failed" }
+			Self::Skipped => { "skipped" }
+		}
+	}
+}
+
+impl RunReport {
+	pub fn new(dry_run: bool) -> Self {
+		Self { started_at: Utc::now(), finished_at: None, dry_run, stopped_early: false, packages: Vec::new() }
+	}
+
+	/// records `action` for each of `packages`, which were done together (like
+	/// packages sharing an issue), so they're each counted as taking `took`
+	pub fn record(&mut self, packages: &[String], action: Action, took: Duration) {
+		self.push(packages, action, took, None, None, None);
+	}
+
+	pub fn filed(&mut self, packages: &[String], issue_url: &str, already_filed: bool, took: Duration) {
+		let action = if already_filed { Action::AlreadyFiled } else { Action::Filed };
+		self.push(packages, action, took, Some(issue_url), None, None);
+	}
+
+	pub fn failed(&mut self, packages: &[String], error: &Error, took: Duration) {
+		let error = PackageError { message: error.to_string(), failure: error.failure(), class: error.class() };
+		self.push(packages, Action::Failed, took, None, None, Some(error));
+	}
+
+	/// for [`Action::NeedsReview`] and [`Action::Skipped`]
+	pub fn with_reason(&mut self, packages: &[String], action: Action, reason: &str, took: Duration) {
+		self.push(packages, action, took, None, Some(reason), None);
+	}
+
+	fn push(&mut self, packages: &[String], action: Action, took: Duration, issue_url: Option<&str>, reason: Option<&str>, error: Option<PackageError>) {
+		for package in packages {
+			self.packages.push(PackageResult {
+				package: package.clone(),
+				action,
+				took_ms: took.as_millis() as u64,
+				issue_url: issue_url.map(String::from),
+				reason: reason.map(String::from),
+				error: error.clone()
+			});
+		}
+	}
+
+	pub fn count(&self, action: Action) -> usize {
+		self.packages.iter().filter(|result| result.action == action).count()
+	}
+
+	pub fn finish(&mut self, stopped_early: bool) {
+		self.finished_at = Some(Utc::now());
+		self.stopped_early = stopped_early;
+	}
+
+	/// adds it to the runs kept next to the database at `db_path`
+	pub async fn save(&self, db_path: &str) -> Result {
+		let mut runs = load(db_path).await?;
+		runs.push(self.clone());
+		let drop = runs.len().saturating_sub(RUNS_KEPT);
+		runs.drain(..drop);
+
+		let path = path(db_path);
+		fs::write(&path, serde_json::to_string_pretty(&runs)?).await
+			.map_err(|e| format!("error writing run reports {path}: {e}"))?;
+		Ok(())
+	}
+}
+
+/// where the runs are kept for the database at `db_path`
+pub fn path(db_path: &str) -> String {
+	format!("{db_path}.runs.json")
+}
+
+/// the runs kept next to the database at `db_path`, oldest first
+pub async fn load(db_path: &str) -> Result<Vec<RunReport>> {
+	let path = path(db_path);
+	if !Path::new(&path).exists() { return Ok(Vec::new()) }
+
+	let json = fs::read_to_string(&path).await
+		.map_err(|e| format!("error reading run reports {path}: {e}"))?;
+	Ok(serde_json::from_str(&json).map_err(|e| format!("error parsing run reports {path}: {e}"))?)
+}
+
+/// for `last-run`
+pub async fn print_last(db_path: &str, format: OutputFormat) -> Result {
+	let run = match load(db_path).await?.pop() {
+		Some(run) => { run }
+		None => {
+			match format {
+				OutputFormat::Json => { println!("null") }
+				OutputFormat::Text => { println!("there haven't been any runs yet") }
+			}
+			return Ok(())
+		}
+	};
+	if format == OutputFormat::Json {
+		println!("{}", serde_json::to_string_pretty(&run)?);
+		return Ok(())
+	}
+
+	let finished = match run.finished_at {
+		Some(finished_at) => { format!("finished {}", finished_at.format("%Y-%m-%d %H:%M UTC")) }
+		None => { "didn't finish".into() }
+	};
+	println!(
+		"run started {}, {finished}{}",
+		run.started_at.format("%Y-%m-%d %H:%M UTC"),
+		if run.stopped_early { ", stopped early" } else { "" }
+	);
+	let counts = Action::ALL.iter()
+		.map(|action| (action, run.count(*action)))
+		.filter(|(_, count)| *count > 0)
+		.map(|(action, count)| format!("{count} {}", action.name()))
+		.collect::<Vec<_>>();
+	match counts.is_empty() {
+		true => { println!("no packages were gone through") }
+		false => { println!("{}", counts.join(", ")) }
+	}
+	if run.packages.is_empty() { return Ok(()) }
+
+	println!();
+	let rows = run.packages.iter()
+		.map(|result| {
+			let detail = match (&result.error, &result.issue_url, &result.reason) {
+				(Some(error), _, _) => { format!("{} ({}): {}", error.failure.name(), error.class.name(), error.message) }
+				(None, Some(issue_url), _) => { issue_url.clone() }
+				(None, None, Some(reason)) => { reason.clone() }
+				(None, None, None) => { String::new() }
+			};
+			[
+				result.package.clone(),
+				result.action.name().to_string(),
+				format!("{:.1}s", result.took_ms as f64 / 1000.0),
+				detail
+			]
+		})
+		.collect::<Vec<_>>();
+	status::print_table(["package", "action", "took", ""], &rows);
+	Ok(())
+}
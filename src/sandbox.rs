@@ -0,0 +1,52 @@
+//! `--sandbox <org>`: every issue is filed in a repository of its own in a github
+//! organisation that's only for trying the bot out (`<org>/<owner>-<name>`, made
+//! the first time it's needed), rather than in the package's repository.
+//! everything else is done for real, pre-flight checks on the real repositories
+//! included, so a whole run can be gone through (and the issues replied to by
+//! hand, to try out polling) before any maintainer sees it. the database is a
+//! separate one (`<db_path>.sandbox`), emails are only written to the outbox,
+//! approved packages aren't registered with the pulsar backend, and claims
+//! aren't made, see [`Config::use_sandbox`]
+
+use crate::config::Config;
+use crate::forge::{ ForgeClient, Forges, RepoLocation };
+use crate::Result;
+
+/// where the organisation is
+pub const HOST: &str = "github.com";
+
+/// github repository names can be this long
+const MAX_NAME_LENGTH: usize = 100;
+
+/// the repository in `org` that `repo`'s issue is filed in instead
+pub fn repo_for(org: &str, repo: &RepoLocation) -> RepoLocation {
+	let name = format!("{}-{}", repo.owner, repo.name)
+		.chars()
+		.map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '-' })
+		.take(MAX_NAME_LENGTH)
+		.collect();
+	RepoLocation { host: HOST.into(), owner: org.into(), name }
+}
+
+/// where to file `repo`'s issue, and what with: its repository in the sandbox
+/// organisation with `--sandbox`, or itself. the client for [`HOST`] needs to
+/// have been created already
+pub fn redirect<'a>(forges: &'a Forges, repo: RepoLocation, client: &'a ForgeClient) -> Result<(RepoLocation, &'a ForgeClient)> {
+	match &forges.config().sandbox {
+		Some(org) => {
+			let sandbox = repo_for(org, &repo);
+			let client = forges.client(&sandbox)?;
+			Ok((sandbox, client))
+		}
+		None => { Ok((repo, client)) }
+	}
+}
+
+/// makes `sandbox` if it doesn't exist yet, when filing with `--sandbox`
+pub async fn prepare(config: &Config, client: &ForgeClient, sandbox: &RepoLocation) -> Result {
+	if config.sandbox.is_none() { return Ok(()) }
+	if client.create_sandbox_repo(sandbox).await? {
+		info!("made sandbox repository {sandbox}");
+	}
+	Ok(())
+}
@@ -0,0 +1,103 @@
+//! tokens and passwords, which can come from a file (like docker and systemd
+//! secrets in `/run/secrets`), the os keyring, or an environment variable.
+//! they're kept in [`Secret`]s so they don't end up in logs or error messages
+
+use std::fmt;
+use std::fs;
+use std::process::Command;
+
+/// a token or password. it can't be printed, [`Secret::expose`] gets it for
+/// the places that actually need it
+#[derive(Clone, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+	pub fn expose(&self) -> &str {
+		&self.0
+	}
+
+	/// `text` with the secret taken out, for things that might echo it back,
+	/// like an error response
+	pub fn redact(&self, text: &str) -> String {
+		text.replace(&self.0, "[redacted]")
+	}
+
+	/// for secrets that are a few tokens, separated by whitespace or commas
+	/// (like one per line in a file)
+	pub fn split(&self) -> Vec<Secret> {
+		self.0.split(|c: char| c.is_whitespace() || c == ',')
+			.filter(|token| !token.is_empty())
+			.map(|token| Secret(token.into()))
+			.collect()
+	}
+}
+
+impl fmt::Debug for Secret {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("Secret([redacted])")
+	}
+}
+
+/// where to find a secret. the file is used if it's set, then the keyring, then
+/// the environment variable
+pub struct SecretSource<'a> {
+	/// what it's for, like `forges.github_token`, for errors
+	pub key: &'a str,
+	pub env: Option<&'a str>,
+	pub file: Option<&'a str>,
+	pub keyring: Option<&'a str>
+}
+
+impl SecretSource<'_> {
+	/// the secret, or `None` if there's no file or keyring entry set and the
+	/// environment variable is empty or not set. a file or keyring entry that
+	/// is set but can't be read is an error, since it was asked for
+	pub fn load(&self) -> Result<Option<Secret>, String> {
+		let key = self.key;
+
+		if let Some(path) = self.file.filter(|path| !path.is_empty()) {
+			let contents = fs::read_to_string(path)
+				.map_err(|e| format!("{key}: error reading {path}: {e}"))?;
+			return secret(contents)
+				.map(Some)
+				.ok_or_else(|| format!("{key}: {path} is empty"))
+		}
+
+		if let Some(name) = self.keyring.filter(|name| !name.is_empty()) {
+			let contents = from_keyring(name).map_err(|e| format!("{key}: {e}"))?;
+			return secret(contents)
+				.map(Some)
+				.ok_or_else(|| format!("{key}: keyring entry {name} is empty"))
+		}
+
+		Ok(self.env
+			.and_then(|name| std::env::var(name).ok())
+			.and_then(secret))
+	}
+}
+
+/// files (and keyring tools) usually end with a newline, which isn't part of the token
+fn secret(contents: String) -> Option<Secret> {
+	let trimmed = contents.trim();
+	(!trimmed.is_empty()).then(|| Secret(trimmed.into()))
+}
+
+/// looks up a password saved in the os keyring under the service `name`. on macOS
+/// that's the login keychain (`security add-generic-password -s <name> -a <anything> -w`),
+/// anywhere else it's the secret service (`secret-tool store --label <label> service <name>`)
+fn from_keyring(name: &str) -> Result<String, String> {
+	let (program, output) = if cfg!(target_os = "macos") {
+		("security", Command::new("security").args(["find-generic-password", "-s", name, "-w"]).output())
+	} else {
+		("secret-tool", Command::new("secret-tool").args(["lookup", "service", name]).output())
+	};
+
+	let output = output.map_err(|e| format!("error running {program} to look up {name} in the keyring: {e}"))?;
+	if !output.status.success() {
+		// neither tool prints the secret when it fails, only why
+		let stderr = String::from_utf8_lossy(&output.stderr);
+		return Err(format!("error looking up {name} in the keyring ({program} {}): {}", output.status, stderr.trim()))
+	}
+
+	String::from_utf8(output.stdout).map_err(|_| format!("keyring entry {name} isn't valid utf-8"))
+}
@@ -0,0 +1,220 @@
+//! a pretend forge that keeps its issues in memory, and fails at random, for
+//! testing how retries and the package states hold up without touching a real one.
+//! with `[simulation]`, there's a pretend registry too, of made up packages, and
+//! pretend maintainers who answer some of the issues, so everything from syncing
+//! to following up can be load tested without any network
+
+use crate::db::{ PackageMetadata, Releases, Repository };
+use crate::error::Failure;
+use crate::forge::{ IssueActivity, IssueComment };
+use crate::random;
+use crate::registry::RegistryPackage;
+use crate::templates::IssueTitles;
+use crate::Error;
+use chrono::{ DateTime, Utc };
+use rand::rngs::StdRng;
+use rand::{ Rng, SeedableRng };
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{ Hash, Hasher };
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Clone, PartialEq)]
+pub struct SimulationConfig {
+	/// made up packages `sync` adds instead of fetching the registry, if any
+	pub packages: u32,
+	pub popularity: Popularity,
+	/// of the most popular package
+	pub max_downloads: u32,
+	/// chance (0 to 1) of a maintainer answering an issue
+	pub reply_rate: f64,
+	/// chance of an answer being yes
+	pub approval_rate: f64,
+	/// so runs can be compared, it's the same packages and answers for the same seed
+	pub seed: u64
+}
+
+/// how downloads are spread between the made up packages
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Popularity {
+	/// a few very popular ones and lots hardly used, like the real registry
+	Zipf,
+	Uniform
+}
+
+impl FromStr for Popularity {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"zipf" => { Ok(Self::Zipf) }
+			"uniform" => { Ok(Self::Uniform) }
+			_ => { Err(format!("unknown popularity {s} (expected zipf or uniform)")) }
+		}
+	}
+}
+
+/// the made up packages. a few owners have more than one, a few repositories
+/// have more than one package in them, and a tenth are on gitlab
+pub fn registry(config: &SimulationConfig) -> Vec<RegistryPackage> {
+	let mut rng = StdRng::seed_from_u64(config.seed);
+	let owners = (config.packages / 3).max(1);
+
+	let mut previous_repo = None::<String>;
+	(1..=config.packages)
+		.map(|i| {
+			let name = format!("simulated-package-{i}");
+			let downloads = match config.popularity {
+				// the most popular has max_downloads, the next half that, then a third, and so on
+				Popularity::Zipf => { config.max_downloads / i }
+				Popularity::Uniform => { rng.gen_range(0..=config.max_downloads) }
+			};
+			let host = if rng.gen_bool(0.1) { "gitlab.com" } else { "github.com" };
+			let repo = match previous_repo.take() {
+				Some(previous) if rng.gen_bool(0.05) => { previous }
+				_ => { format!("https://{host}/simulated-owner-{}/{name}", rng.gen_range(1..=owners)) }
+			};
+			previous_repo = Some(repo.clone());
+
+			RegistryPackage {
+				name,
+				repository: Some(Repository { r#type: "git".into(), url: repo }),
+				downloads,
+				stargazers_count: downloads / 100,
+				metadata: PackageMetadata {
+					version: Some("1.0.0".into()),
+					description: Some("a made up package, for load testing".into()),
+					..Default::default()
+				},
+				releases: Releases { latest: Some("1.0.0".into()) },
+				versions: vec!["1.0.0".into()],
+				owner: None,
+				readme: None
+			}
+		})
+		.collect()
+}
+
+pub struct SimulatedClient {
+	host: String,
+	/// chance (0 to 1) of any request failing
+	failure_rate: f64,
+	/// chances of maintainers answering, and saying yes
+	reply_rate: f64,
+	approval_rate: f64,
+	seed: u64,
+	issues: Mutex<Vec<SimulatedIssue>>
+}
+
+struct SimulatedIssue {
+	/// `owner/repo`
+	repo: String,
+	title: String,
+	url: String,
+	opened_at: DateTime<Utc>,
+	closed: bool
+}
+
+impl SimulatedClient {
+	pub fn new(host: &str, failure_rate: f64, simulation: &SimulationConfig) -> Self {
+		Self {
+			host: host.into(),
+			failure_rate: failure_rate.clamp(0.0, 1.0),
+			reply_rate: simulation.reply_rate,
+			approval_rate: simulation.approval_rate,
+			seed: simulation.seed,
+			issues: Mutex::new(Vec::new())
+		}
+	}
+
+	/// fails the way a real forge might, `failure_rate` of the time
+	pub async fn maybe_fail(&self) -> crate::Result {
+		let failure = random::with(|rng| rng.gen_bool(self.failure_rate).then(|| rng.gen_range(0..4)));
+		let failure = match failure {
+			Some(failure) => { failure }
+			None => { return Ok(()) }
+		};
+
+		let (failure, message) = match failure {
+			0 => { (Failure::RateLimited, "simulated failure: 429 Too Many Requests") }
+			1 => { (Failure::Server, "simulated failure: 500 Internal Server Error") }
+			2 => { (Failure::Other, "simulated failure: unexpected response") }
+			_ => {
+				tokio::time::sleep(Duration::from_secs(1)).await;
+				(Failure::Network, "simulated failure: operation timed out")
+			}
+		};
+		Err(Error::Forge { failure, source: message.into() })
+	}
+
+	pub async fn create_permission_request_issue(
+		&self,
+		owner: &str,
+		repo: &str,
+		title: &str,
+		_body: &str
+	) -> crate::Result<String> {
+		self.maybe_fail().await?;
+
+		let mut issues = self.issues.lock().unwrap();
+		let number = issues.iter().filter(|issue| issue.repo == format!("{owner}/{repo}")).count() + 1;
+		// .invalid so these can never be mistaken for real ones
+		let url = format!("https://simulated.invalid/{}/{owner}/{repo}/issues/{number}", self.host);
+		issues.push(SimulatedIssue {
+			repo: format!("{owner}/{repo}"),
+			title: title.into(),
+			url: url.clone(),
+			opened_at: Utc::now(),
+			closed: false
+		});
+		info!("(simulated) filed {url}");
+		Ok(url)
+	}
+
+	pub async fn comment_on_issue(&self, owner: &str, repo: &str, number: u64, _body: &str) -> crate::Result {
+		self.maybe_fail().await?;
+		info!("(simulated) commented on {owner}/{repo}#{number}");
+		Ok(())
+	}
+
+	pub async fn close_issue(&self, owner: &str, repo: &str, number: u64) -> crate::Result {
+		self.maybe_fail().await?;
+		let mut issues = self.issues.lock().unwrap();
+		if let Some(issue) = issues.iter_mut().find(|issue| issue.url.ends_with(&format!("/{owner}/{repo}/issues/{number}"))) {
+			issue.closed = true;
+		}
+		Ok(())
+	}
+
+	/// `simulation.reply_rate` of the issues are answered, the same way every
+	/// time they're looked at (even by another run), as of when they're looked at
+	pub async fn issue_activity(&self, owner: &str, repo: &str, number: u64) -> crate::Result<IssueActivity> {
+		self.maybe_fail().await?;
+		let url = format!("https://simulated.invalid/{}/{owner}/{repo}/issues/{number}", self.host);
+		let (opened_at, closed) = self.issues.lock().unwrap().iter()
+			.find(|issue| issue.url == url)
+			.map_or((Utc::now(), false), |issue| (issue.opened_at, issue.closed));
+
+		let mut hasher = DefaultHasher::new();
+		(self.seed, &url).hash(&mut hasher);
+		let mut rng = StdRng::seed_from_u64(hasher.finish());
+		let comments = match rng.gen_bool(self.reply_rate) {
+			true => {
+				let body = if rng.gen_bool(self.approval_rate) { "yes, go ahead" } else { "no thanks" };
+				vec![IssueComment { url: format!("{url}#issuecomment-1"), body: body.into(), created_at: Utc::now() }]
+			}
+			false => { Vec::new() }
+		};
+		Ok(IssueActivity { opened_at, closed, replied: !comments.is_empty(), comments })
+	}
+
+	pub async fn find_issue(&self, owner: &str, repo: &str, titles: &IssueTitles) -> crate::Result<Option<String>> {
+		self.maybe_fail().await?;
+		let issues = self.issues.lock().unwrap();
+		Ok(issues.iter()
+			.rev()
+			.find(|issue| issue.repo == format!("{owner}/{repo}") && titles.matches(&issue.title))
+			.map(|issue| issue.url.clone()))
+	}
+}
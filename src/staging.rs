@@ -0,0 +1,150 @@
+//! splitting a run in two: one person drafts the issues that would be filed
+//! into a file, someone else reviews it, and then the approved ones are published
+
+use crate::db::{ DatabaseThing, PackageState };
+use crate::filing;
+use crate::forge::{ Forges, RepoLocation };
+use crate::retry;
+use crate::sandbox;
+use crate::usage::{ self, Stage };
+use crate::Result;
+use chrono::{ DateTime, Utc };
+use serde::{ Deserialize, Serialize };
+use tokio::fs;
+use tokio::time::Instant;
+
+#[derive(Deserialize, Serialize)]
+pub struct Draft {
+	pub created_at: DateTime<Utc>,
+	pub proposals: Vec<Proposal>
+}
+
+/// an issue that would be filed
+#[derive(Deserialize, Serialize)]
+pub struct Proposal {
+	pub repository: RepoLocation,
+	pub packages: Vec<String>,
+	/// can be edited before publishing, but keep the title matching the
+	/// template's, since it's how issues that were already filed are found
+	pub title: String,
+	pub body: String,
+	/// which of `template_variants` it's from, if there are any
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub template_variant: Option<String>,
+	/// which of `locales.templates` it's from, if it is
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub locale: Option<String>,
+	/// set to true by the reviewer for the ones that should be published
+	#[serde(default)]
+	pub approved: bool
+}
+
+impl Draft {
+	pub fn new() -> Self {
+		Self { created_at: Utc::now(), proposals: Vec::new() }
+	}
+
+	pub async fn write(&self, path: &str) -> Result {
+		let json = serde_json::to_string_pretty(self)?;
+		fs::write(path, json).await
+			.map_err(|e| format!("error writing draft {path}: {e}"))?;
+		info!("wrote {} proposed issues to {path}, set `approved` to true on the ones to file, then run `publish {path}`", self.proposals.len());
+		Ok(())
+	}
+}
+
+/// files the approved issues in a draft, skipping packages that have been
+/// contacted (or otherwise dealt with) since it was made
+pub async fn publish(db: &DatabaseThing, forges: &mut Forges, path: &str) -> Result {
+	let draft = fs::read_to_string(path).await
+		.map_err(|e| format!("error reading draft {path}: {e}"))?;
+	let draft = serde_json::from_str::<Draft>(&draft)
+		.map_err(|e| format!("error parsing draft {path}: {e}"))?;
+
+	let approved = draft.proposals.iter().filter(|proposal| proposal.approved).count();
+	info!("draft from {} has {approved} approved issues (of {})", draft.created_at, draft.proposals.len());
+
+	let mut filed = 0;
+	let mut failed = 0;
+	let mut next_filing = None::<Instant>;
+	for proposal in draft.proposals.iter().filter(|proposal| proposal.approved) {
+		let config = forges.config();
+		if !config.filing_window.contains(Utc::now()) {
+			info!("outside the filing window, stopping");
+			break
+		}
+		if config.daily_issue_limit > 0 && db.issues_filed_last_day() >= config.daily_issue_limit {
+			info!("filed {} issues in the last 24 hours, stopping (filing.daily_limit)", db.issues_filed_last_day());
+			break
+		}
+
+		let repo = &proposal.repository;
+		let names = proposal.packages.iter()
+			.filter(|name| match db.package_state(name) {
+				Some(PackageState::New | PackageState::Deferred(_) | PackageState::RetryPending(_)) => { true }
+				Some(state) => {
+					info!("skipping package {name}: it's {} now", state.name());
+					false
+				}
+				None => {
+					info!("skipping package {name}: not in the database");
+					false
+				}
+			})
+			.cloned()
+			.collect::<Vec<_>>();
+		if names.is_empty() { continue }
+		let names_str = names.join(", ");
+		if let Some(breaker) = db.breaker(&repo.host) {
+			info!("skipping package {names_str}: too much has failed on {} lately, nothing is filed on it until {}", repo.host, breaker.until);
+			continue
+		}
+
+		if let Err(e) = forges.client_for(repo) {
+			error!("skipping package {names_str}: {e}");
+			failed += 1;
+			continue
+		}
+		if let Some(org) = &forges.config().sandbox {
+			if let Err(e) = forges.client_for(&sandbox::repo_for(org, repo)) {
+				error!("skipping package {names_str}: {e}");
+				failed += 1;
+				continue
+			}
+		}
+		let (repo, client) = sandbox::redirect(forges, repo.clone(), forges.client(repo)?)?;
+		let repo = &repo;
+
+		let host_cooldown = retry::host_cooldown(db, client, &repo.host).await.map(|cooldown| {
+			info!("{} is rate limiting the bot, waiting until {}", repo.host, cooldown.until);
+			Instant::now() + (cooldown.until - Utc::now()).to_std().unwrap_or_default()
+		});
+		if let Some(wait_until) = next_filing.into_iter().chain(host_cooldown).max().filter(|wait_until| *wait_until > Instant::now()) {
+			debug!("waiting {}s before filing the next issue", wait_until.saturating_duration_since(Instant::now()).as_secs());
+			tokio::time::sleep_until(wait_until).await;
+		}
+
+		match usage::in_stage(Stage::Filing, filing::file_issue(db, forges.config(), client, repo, &names, filing::Issue {
+			title: &proposal.title,
+			body: &proposal.body,
+			template_variant: proposal.template_variant.as_deref(),
+			locale: proposal.locale.as_deref()
+		})).await {
+			Ok(result) => {
+				if !result.already_filed {
+					filed += 1;
+					next_filing = Some(Instant::now() + filing::next_delay(forges.config()));
+				}
+			}
+			Err(e) => {
+				error!("error filing issue for package {names_str} on {repo}: {e}");
+				failed += 1;
+				retry::cool_down(db, forges.config(), repo, &e);
+				retry::record_failure(db, forges.config(), &names, &e)?;
+			}
+		}
+	}
+
+	info!("published {filed} issues, {failed} failed");
+	Ok(())
+}
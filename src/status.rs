@@ -0,0 +1,660 @@
+//! looking at what's in the database, without changing anything
+
+use crate::config::Config;
+use crate::control::ControlHandle;
+use crate::db::{ DatabaseThing, Package, PackageState, ReminderKind, RepoRelease, RunRecord, TrippedBreaker };
+use crate::forge::{ self, ForgeKind, Forges, RateLimit, RepoLocation };
+use crate::github::GithubClient;
+use crate::queue::{ self, QueueOrder };
+use crate::Result;
+use chrono::{ DateTime, Duration, Utc };
+use serde::Serialize;
+use std::collections::{ BTreeMap, HashMap };
+use std::str::FromStr;
+
+/// how many of the recent runs the text status shows
+const RUNS_SHOWN: usize = 5;
+
+/// how `status`, `list`, and `show` print what they find
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+	/// for people
+	Text,
+	/// for scripts, one json value
+	Json
+}
+
+impl FromStr for OutputFormat {
+	type Err = String;
+
+	fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+		match &*s.trim().to_lowercase() {
+			"text" => { Ok(Self::Text) }
+			"json" => { Ok(Self::Json) }
+			_ => { Err(format!("unknown format {s} (expected text or json)")) }
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct StatusJson {
+	packages: usize,
+	/// by [`PackageState::kind`]
+	states: BTreeMap<&'static str, usize>,
+	issues_filed_last_day: usize,
+	issues_filed_last_week: usize,
+	/// `None` if there's no limit
+	daily_limit: Option<usize>,
+	daily_limit_left: Option<usize>,
+	rate_limits: Vec<RateLimitJson>,
+	/// hosts that rate limited the bot, and when they're used again
+	host_cooldowns: BTreeMap<String, DateTime<Utc>>,
+	/// hosts nothing's filed on for now, because too much failed on them
+	breakers: BTreeMap<String, TrippedBreaker>,
+	/// repositories cooling down after something failed on them
+	repo_cooldowns: usize,
+	repo_cooldowns_next_ends_at: Option<DateTime<Utc>>,
+	/// seconds between issues, the least and most
+	filing_delay: [u64; 2],
+	/// in the last week
+	last_filed_at: Option<DateTime<Utc>>,
+	last_sync: Option<DateTime<Utc>>,
+	counts_refreshed_at: Option<DateTime<Utc>>,
+	/// a sync didn't finish, and the next one carries on from where it stopped
+	sync_unfinished: bool,
+	database_size_bytes: usize,
+	database_serialize_ms: u128,
+	/// oldest first
+	recent_runs: Vec<RunJson>
+}
+
+#[derive(Serialize)]
+struct RunJson {
+	#[serde(flatten)]
+	run: RunRecord,
+	packages_per_hour: f64,
+	error_rate: f64
+}
+
+#[derive(Serialize)]
+struct RateLimitJson {
+	host: String,
+	limit: Option<u32>,
+	remaining: Option<u32>,
+	resets_at: Option<DateTime<Utc>>,
+	/// why it couldn't be checked
+	error: Option<String>
+}
+
+/// a package in `list --format json` (and [`crate::api`]'s `/packages`)
+#[derive(Serialize)]
+pub struct ListJson {
+	name: String,
+	/// a [`PackageState::kind`]
+	state: &'static str,
+	repository: String,
+	downloads: u32,
+	stars: u32,
+	details: Option<String>,
+	/// when its repository was last pushed to, if that's been checked
+	pushed_at: Option<DateTime<Utc>>,
+	/// the tag of its repository's latest release
+	latest_release: Option<String>
+}
+
+impl ListJson {
+	pub fn new(package: &Package) -> Self {
+		Self {
+			name: package.info.name.clone(),
+			state: package.state.kind(),
+			repository: package.info.repository.url.clone(),
+			downloads: package.info.downloads,
+			stars: package.info.stargazers_count,
+			details: details(&package.state),
+			pushed_at: package.activity.as_ref().and_then(|activity| activity.pushed_at),
+			latest_release: package.activity.as_ref().and_then(|activity| activity.latest_release.as_ref()).map(|release| release.tag.clone())
+		}
+	}
+}
+
+/// a package in `show --format json` (and [`crate::api`]'s `/packages/{name}`)
+#[derive(Serialize)]
+pub struct ShowJson<'a> {
+	#[serde(flatten)]
+	package: &'a Package,
+	issue_url: Option<String>
+}
+
+impl<'a> ShowJson<'a> {
+	pub fn new(package: &'a Package) -> Self {
+		Self { package, issue_url: issue_url(package) }
+	}
+}
+
+/// prints how many packages are in each state, how many issues have been filed
+/// lately, how much more can be filed, and when the registry was last synced
+pub async fn print(db: &DatabaseThing, forges: &mut Forges, format: OutputFormat) -> Result {
+	let packages = db.packages();
+
+	let mut counts = BTreeMap::<&str, usize>::new();
+	let mut kinds = BTreeMap::<&'static str, usize>::new();
+	for package in packages.iter() {
+		*counts.entry(package.state.name()).or_default() += 1;
+		*kinds.entry(package.state.kind()).or_default() += 1;
+	}
+
+	let last_day = db.issues_filed_last_day();
+	let last_week = db.issues_filed_since(Utc::now() - Duration::days(7));
+	let daily_limit = forges.config().daily_issue_limit;
+	let (delay_min, delay_max) = forges.config().filing_delay;
+	let last_filing = db.last_filing();
+	let sync = db.registry_sync_state();
+	prepare_budget(forges);
+	let rate_limits = rate_limits(forges).await;
+	let host_cooldowns = db.host_cooldowns();
+	let breakers = db.breakers();
+	let repo_cooldowns = db.repo_cooldowns();
+	let (database_size, serialize_took) = db.measure_save()?;
+	let runs = db.recent_runs();
+
+	if format == OutputFormat::Json {
+		let status = StatusJson {
+			packages: packages.len(),
+			states: kinds,
+			issues_filed_last_day: last_day,
+			issues_filed_last_week: last_week,
+			daily_limit: Some(daily_limit).filter(|&limit| limit > 0),
+			daily_limit_left: (daily_limit > 0).then(|| daily_limit.saturating_sub(last_day)),
+			rate_limits: rate_limits.into_iter()
+				.filter_map(|(host, rate_limit)| match rate_limit {
+					Ok(Some(rate_limit)) => { Some(RateLimitJson { host, limit: Some(rate_limit.limit), remaining: Some(rate_limit.remaining), resets_at: Some(rate_limit.resets_at), error: None }) }
+					Ok(None) => { None }
+					Err(e) => { Some(RateLimitJson { host, limit: None, remaining: None, resets_at: None, error: Some(e.to_string()) }) }
+				})
+				.collect(),
+			host_cooldowns,
+			breakers,
+			repo_cooldowns: repo_cooldowns.len(),
+			repo_cooldowns_next_ends_at: repo_cooldowns.values().min().copied(),
+			filing_delay: [delay_min, delay_max],
+			last_filed_at: last_filing,
+			last_sync: sync.last_sync,
+			counts_refreshed_at: sync.counts_refreshed_at,
+			sync_unfinished: sync.cursor.is_some(),
+			database_size_bytes: database_size,
+			database_serialize_ms: serialize_took.as_millis(),
+			recent_runs: runs.into_iter()
+				.map(|run| RunJson { packages_per_hour: run.throughput(), error_rate: run.error_rate(), run })
+				.collect()
+		};
+		println!("{}", serde_json::to_string_pretty(&status)?);
+		return Ok(())
+	}
+
+	println!("{} packages", packages.len());
+	for (state, count) in counts.iter() {
+		println!("   {state}: {count}");
+	}
+
+	println!("issues filed in the last 24 hours: {last_day}, last 7 days: {last_week}");
+
+	if daily_limit > 0 {
+		println!("daily issue limit: {} of {daily_limit} left", daily_limit.saturating_sub(last_day));
+	} else {
+		println!("daily issue limit: none (filing.daily_limit)");
+	}
+	match last_filing {
+		Some(last_filing) => { println!("{delay_min} to {delay_max}s between issues (filing.delay_min and delay_max), the last one was filed at {last_filing}") }
+		None => { println!("{delay_min} to {delay_max}s between issues (filing.delay_min and delay_max)") }
+	}
+
+	for (host, rate_limit) in rate_limits {
+		match rate_limit {
+			Ok(Some(rate_limit)) => { println!("{host} api: {} of {} requests left, resets at {}", rate_limit.remaining, rate_limit.limit, rate_limit.resets_at) }
+			Ok(None) => {}
+			Err(e) => { println!("{host} api: error checking the rate limit: {e}") }
+		}
+	}
+	for (host, until) in &host_cooldowns {
+		println!("{host} rate limited the bot, nothing is filed on it until {until}");
+	}
+	for (host, breaker) in &breakers {
+		println!(
+			"{} things tried on {host} failed in a row at {}, nothing is filed on it until {} (`retry` carries on sooner). the last error: {}",
+			breaker.failures,
+			breaker.tripped_at,
+			breaker.until,
+			breaker.last_error
+		);
+	}
+	if let Some(next_ends_at) = repo_cooldowns.values().min() {
+		println!("repositories cooling down after something failed on them: {}, the next one ends at {next_ends_at}", repo_cooldowns.len());
+	}
+
+	match sync.last_sync {
+		Some(last_sync) => { println!("last registry sync: {last_sync}") }
+		None => { println!("last registry sync: never") }
+	}
+	if let Some(counts_refreshed_at) = sync.counts_refreshed_at {
+		println!("download and star counts last refreshed: {counts_refreshed_at}");
+	}
+	if sync.cursor.is_some() {
+		println!("a registry sync didn't finish, the next one carries on from where it stopped");
+	}
+
+	let database_size = match database_size {
+		size if size >= 1024 * 1024 => { format!("{:.1} MB", size as f64 / (1024.0 * 1024.0)) }
+		size => { format!("{:.0} KB", size as f64 / 1024.0) }
+	};
+	println!("database: {database_size}, saving it takes {}ms", serialize_took.as_millis());
+	if !runs.is_empty() {
+		println!("the last {} runs:", runs.len().min(RUNS_SHOWN));
+		let rows = runs.iter().rev().take(RUNS_SHOWN).rev()
+			.map(|run| [
+				format!("   {}", run.started_at.format("%Y-%m-%d %H:%M")),
+				match run.finished_at - run.started_at {
+					took if took.num_minutes() > 0 => { format!("{}m", took.num_minutes()) }
+					took => { format!("{}s", took.num_seconds()) }
+				},
+				run.processed.to_string(),
+				format!("{:.0}", run.throughput()),
+				format!("{:.0}%", run.error_rate() * 100.0)
+			])
+			.collect::<Vec<_>>();
+		print_table(["   started", "took", "packages", "per hour", "failed"], &rows);
+		if let Some(all) = RunRecord::combined(&runs).filter(|_| runs.len() > RUNS_SHOWN) {
+			println!("   {:.0}% failed over all {} runs kept", all.error_rate() * 100.0, runs.len());
+		}
+	}
+
+	Ok(())
+}
+
+/// github hosts there's a token for, since that's what the rate limit is counted against
+fn github_hosts(config: &Config) -> Vec<String> {
+	let mut hosts = Vec::new();
+	if config.github_token.is_some() {
+		hosts.push("github.com".to_string());
+	}
+	hosts.extend(config.forge_tokens.keys()
+		.filter(|host| forge::forge_for_host(host, config) == Some(ForgeKind::GitHub))
+		.cloned());
+	hosts
+}
+
+/// creates the clients [`budget_line`] checks rate limits with
+pub fn prepare_budget(forges: &mut Forges) {
+	for host in github_hosts(forges.config()) {
+		let _ = forges.client_for_host(&host);
+	}
+}
+
+/// github's api rate limits for every host there's a token for. checking doesn't use any of it up
+async fn rate_limits(forges: &Forges) -> Vec<(String, Result<Option<RateLimit>>)> {
+	let mut rate_limits = Vec::new();
+	for host in github_hosts(forges.config()) {
+		let rate_limit = match forges.created_client_for_host(&host) {
+			Ok(client) => { client.rate_limit().await }
+			Err(e) => { Err(e) }
+		};
+		rate_limits.push((host, rate_limit));
+	}
+	rate_limits
+}
+
+/// one line with the budgets that might be keeping the bot waiting (api rate
+/// limits, cooldowns, and the daily issue limit), for logging every so often.
+/// [`prepare_budget`] has to be called first
+pub async fn budget_line(db: &DatabaseThing, forges: &Forges) -> String {
+	budget_from(db, forges.config().daily_issue_limit, rate_limits(forges).await)
+}
+
+/// [`budget_line`], with the rate limits checked already
+fn budget_from(db: &DatabaseThing, daily_limit: usize, rate_limits: Vec<(String, Result<Option<RateLimit>>)>) -> String {
+	let mut parts = Vec::new();
+	for (host, rate_limit) in rate_limits {
+		match rate_limit {
+			Ok(Some(rate_limit)) => { parts.push(format!("{host} api {} of {} left", rate_limit.remaining, rate_limit.limit)) }
+			Ok(None) => {}
+			Err(e) => { parts.push(format!("{host} api unknown ({e})")) }
+		}
+	}
+	for (host, until) in db.host_cooldowns() {
+		parts.push(format!("{host} rate limited until {}", until.format("%H:%M:%S")));
+	}
+	for (host, breaker) in db.breakers() {
+		parts.push(format!("{host} stopped until {}", breaker.until.format("%H:%M:%S")));
+	}
+	let repo_cooldowns = db.repo_cooldowns().len();
+	if repo_cooldowns > 0 {
+		parts.push(format!("{repo_cooldowns} repositories cooling down"));
+	}
+	if daily_limit > 0 {
+		parts.push(format!("{} of {daily_limit} issues left today", daily_limit.saturating_sub(db.issues_filed_last_day())));
+	}
+
+	match parts.is_empty() {
+		true => { "budget: no limits in effect".into() }
+		false => { format!("budget: {}", parts.join(", ")) }
+	}
+}
+
+/// what the bot's up to right now, a line at a time, for SIGUSR2 (see
+/// [`crate::control::snapshot_on_signal`]): the run's progress if there is one,
+/// how many packages are in each state, and the budget. only github.com's rate
+/// limit is checked, with `github`, to not get in the way of the run
+pub async fn snapshot(db: &DatabaseThing, run: Option<ControlHandle>, github: Option<&GithubClient>, daily_limit: usize) -> Vec<String> {
+	let mut lines = Vec::new();
+	let progress = run.as_ref().map(ControlHandle::progress);
+	match progress {
+		Some(progress) if progress.total > 0 && progress.done < progress.total => {
+			let mut line = format!(
+				"{} of {} packages gone through ({} left), {} filed, {} failed",
+				progress.done, progress.total, progress.total - progress.done, progress.issues_filed, progress.failed
+			);
+			if let Some(current) = &progress.current {
+				line += &format!(", on {current}");
+			}
+			if run.as_ref().map_or(false, ControlHandle::is_paused) {
+				line += ", paused";
+			}
+			if let Some(until) = progress.waiting_until {
+				line += &format!(", filing the next issue in {}s", until.saturating_duration_since(tokio::time::Instant::now()).as_secs());
+			}
+			lines.push(line);
+		}
+		Some(_) => { lines.push("not going through packages right now".into()) }
+		None => { lines.push("not running".into()) }
+	}
+
+	let mut counts = BTreeMap::<&str, usize>::new();
+	for package in db.packages().iter() {
+		*counts.entry(package.state.name()).or_default() += 1;
+	}
+	let counts = counts.iter().map(|(state, count)| format!("{state} {count}")).collect::<Vec<_>>();
+	lines.push(format!("{} packages: {}", db.package_count(), counts.join(", ")));
+
+	let rate_limits = match github {
+		Some(github) => { vec![("github.com".to_string(), github.rate_limit().await.map(Some))] }
+		None => { Vec::new() }
+	};
+	lines.push(budget_from(db, daily_limit, rate_limits));
+	lines
+}
+
+/// which packages `list` prints, and in what order
+pub struct ListFilter {
+	/// a [`PackageState::kind`], in any case
+	pub state: Option<String>,
+	pub min_downloads: u32,
+	pub min_stars: u32,
+	/// only ones with this in their name, in any case
+	pub search: Option<String>,
+	/// only ones whose repository's owner is this, in any case
+	pub owner: Option<String>,
+	/// the order they're in in the database if not set
+	pub sort: Option<QueueOrder>,
+	pub limit: Option<usize>,
+	pub format: OutputFormat
+}
+
+/// prints the packages matching `filter` as a table
+pub fn list(db: &DatabaseThing, filter: &ListFilter) -> Result {
+	let mut packages = matching(db, filter)?;
+	let matching = packages.len();
+	if let Some(limit) = filter.limit {
+		packages.truncate(limit);
+	}
+
+	if filter.format == OutputFormat::Json {
+		let rows = packages.iter().map(ListJson::new).collect::<Vec<_>>();
+		println!("{}", serde_json::to_string_pretty(&rows)?);
+		return Ok(())
+	}
+
+	let rows = packages.iter()
+		.map(|package| [
+			package.info.name.clone(),
+			package.state.name().to_string(),
+			package.info.downloads.to_string(),
+			package.info.stargazers_count.to_string(),
+			package.activity.as_ref().and_then(|activity| activity.pushed_at).map(|at| at.format("%Y-%m-%d").to_string()).unwrap_or_default(),
+			details(&package.state).unwrap_or_default()
+		])
+		.collect::<Vec<_>>();
+	print_table(["name", "state", "downloads", "stars", "last push", "details"], &rows);
+
+	if packages.len() < matching {
+		println!("{} of {matching} matching packages", packages.len());
+	} else {
+		println!("{matching} packages");
+	}
+
+	Ok(())
+}
+
+/// every package matching `filter` (not cut down to its limit), in its order
+pub fn matching(db: &DatabaseThing, filter: &ListFilter) -> Result<Vec<Package>> {
+	if let Some(state) = &filter.state {
+		if !STATE_KINDS.iter().any(|kind| kind.eq_ignore_ascii_case(state)) {
+			return Err(format!("unknown state {state} (expected one of {})", STATE_KINDS.join(", ")).into())
+		}
+	}
+
+	let search = filter.search.as_ref().map(|search| search.trim().to_lowercase());
+	let mut packages = db.packages().into_iter()
+		.filter(|package| filter.state.as_ref().map_or(true, |state| package.state.kind().eq_ignore_ascii_case(state)))
+		.filter(|package| package.info.downloads >= filter.min_downloads && package.info.stargazers_count >= filter.min_stars)
+		.filter(|package| search.as_ref().map_or(true, |search| package.info.name.to_lowercase().contains(search)))
+		.filter(|package| filter.owner.as_ref().map_or(true, |owner| {
+			RepoLocation::parse(&package.info.repository.url).map_or(false, |repo| repo.owner.eq_ignore_ascii_case(owner.trim()))
+		}))
+		.collect::<Vec<_>>();
+
+	if let Some(order) = filter.sort {
+		let mut infos = packages.iter().map(|package| package.info.clone()).collect::<Vec<_>>();
+		queue::sort(&mut infos, order);
+		let mut by_name = packages.into_iter()
+			.map(|package| (package.info.name.clone(), package))
+			.collect::<HashMap<_, _>>();
+		packages = infos.iter().filter_map(|info| by_name.remove(&info.name)).collect();
+	}
+
+	Ok(packages)
+}
+
+/// prints everything known about one package: its state and how it got there,
+/// its repository and issue, the last comments seen on the issue, and what's
+/// scheduled to happen next
+pub fn show(db: &DatabaseThing, name: &str, format: OutputFormat) -> Result {
+	let package = db.package(name)
+		.ok_or_else(|| format!("package {name} is not in the database"))?;
+
+	if format == OutputFormat::Json {
+		// everything in the database, which has the follow-ups already
+		let show = ShowJson::new(&package);
+		println!("{}", serde_json::to_string_pretty(&show)?);
+		return Ok(())
+	}
+
+	println!("{}", package.info.name);
+	if !package.previous_names.is_empty() {
+		println!("   renamed from: {}", package.previous_names.join(", "));
+	}
+	match package.history.last() {
+		Some(last) => { println!("   state: {} (since {})", package.state.name(), last.at) }
+		None => { println!("   state: {}", package.state.name()) }
+	}
+	if let Some(details) = details(&package.state) {
+		println!("   {details}");
+	}
+	println!("   repository: {}", package.info.repository.url);
+	println!("   downloads: {}, stars: {}", package.info.downloads, package.info.stargazers_count);
+	if let Some(license) = &package.license {
+		println!("   license: {} (checked {})", license.license.as_deref().unwrap_or("none"), license.checked_at);
+	}
+	if let Some(activity) = &package.activity {
+		let pushed = activity.pushed_at.map_or_else(|| "never".into(), |at| at.to_string());
+		let release = match &activity.latest_release {
+			Some(RepoRelease { tag, released_at: Some(released_at) }) => { format!("{tag} ({released_at})") }
+			Some(RepoRelease { tag, released_at: None }) => { tag.clone() }
+			None => { "none".into() }
+		};
+		println!("   last push: {pushed}, latest release: {release} (checked {})", activity.checked_at);
+	}
+	match issue_url(&package) {
+		Some(issue_url) => { println!("   issue: {issue_url}") }
+		None => { println!("   issue: none filed") }
+	}
+
+	println!("history:");
+	if package.history.is_empty() {
+		println!("   nothing recorded");
+	}
+	for transition in package.history.iter() {
+		match details(&transition.state) {
+			Some(details) => { println!("   {}: {} -> {} ({details})", transition.at, transition.from, transition.state.name()) }
+			None => { println!("   {}: {} -> {}", transition.at, transition.from, transition.state.name()) }
+		}
+		if let Some(note) = &transition.note {
+			println!("      note: {note}");
+		}
+	}
+
+	match &package.comments {
+		Some(seen) => {
+			println!("last comments seen (checked {}):", seen.checked_at);
+			if seen.comments.is_empty() {
+				println!("   none");
+			}
+			for comment in seen.comments.iter() {
+				println!("   {} {}", comment.created_at, comment.url);
+				for line in comment.body.lines() {
+					println!("      {line}");
+				}
+			}
+		}
+		None => { println!("last comments seen: never checked") }
+	}
+
+	println!("scheduled:");
+	let mut scheduled = false;
+	match &package.state {
+		PackageState::IssueFiled(filed) => {
+			for reminder in filed.reminders.iter() {
+				let kind = match reminder.kind {
+					ReminderKind::FollowUp => { "follow-up" }
+					ReminderKind::FinalReminder => { "final reminder" }
+				};
+				match (reminder.sent_at, filed.replied_at) {
+					(Some(sent_at), _) => { println!("   {kind} due {}, sent {sent_at}", reminder.due_at) }
+					(None, Some(replied_at)) => { println!("   {kind} due {}, cancelled since someone replied at {replied_at}", reminder.due_at) }
+					(None, None) => { println!("   {kind} due {}", reminder.due_at) }
+				}
+				scheduled = true;
+			}
+		}
+		PackageState::RetryPending(retry) => {
+			println!("   contacting again after {}", retry.retry_at);
+			scheduled = true;
+		}
+		_ => {}
+	}
+	if !scheduled {
+		println!("   nothing");
+	}
+
+	Ok(())
+}
+
+/// the issue permission was asked for on, if there is one
+pub fn issue_url(package: &Package) -> Option<String> {
+	let from_state = match &package.state {
+		PackageState::IssueFiled(filed) => { Some(filed.issue_url.clone()) }
+		PackageState::MaintainerApproved(approved) => { approved.issue_url.clone() }
+		PackageState::Migrated(migrated) => { migrated.approval.issue_url.clone() }
+		PackageState::ConsentRevoked(revoked) => { revoked.approval.issue_url.clone() }
+		_ => { None }
+	};
+	from_state.or_else(|| package.history.iter().rev().find_map(|transition| match &transition.state {
+		PackageState::IssueFiled(filed) => { Some(filed.issue_url.clone()) }
+		_ => { None }
+	}))
+}
+
+/// prints rows lined up in columns, with columns of numbers (and percentages)
+/// on the right. the last column isn't padded, since it's usually the long one
+pub fn print_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+	print!("{}", table(header, rows));
+}
+
+/// what [`print_table`] prints
+pub fn table<const N: usize>(header: [&str; N], rows: &[[String; N]]) -> String {
+	let mut widths = header.map(str::len);
+	let mut numeric = [!rows.is_empty(); N];
+	for row in rows {
+		for (i, cell) in row.iter().enumerate() {
+			widths[i] = widths[i].max(cell.chars().count());
+			numeric[i] &= !cell.is_empty() && cell.chars().all(|c| c.is_ascii_digit() || c == '%');
+		}
+	}
+
+	let mut out = String::new();
+	let mut print_row = |row: &[&str]| {
+		let mut line = String::new();
+		for (i, cell) in row.iter().enumerate() {
+			if i == N - 1 {
+				line.push_str(cell);
+			} else if numeric[i] {
+				line.push_str(&format!("{cell:>width$}  ", width = widths[i]));
+			} else {
+				line.push_str(&format!("{cell:<width$}  ", width = widths[i]));
+			}
+		}
+		out.push_str(line.trim_end());
+		out.push('\n');
+	};
+
+	print_row(&header);
+	for row in rows {
+		print_row(&row.iter().map(String::as_str).collect::<Vec<_>>());
+	}
+	out
+}
+
+const STATE_KINDS: &[&str] = &[
+	"New",
+	"IssueFiled",
+	"ContactedByEmail",
+	"Deferred",
+	"SkippedDeprecated",
+	"NeedsReview",
+	"AlreadyMigrated",
+	"Blocked",
+	"OptedOut",
+	"DeclinedByOwnerPolicy",
+	"Dormant",
+	"RetryPending",
+	"IssueFilingInProgress",
+	"MaintainerApproved",
+	"Migrated",
+	"ConsentRevoked"
+];
+
+/// the most useful thing to know about a package in a state
+fn details(state: &PackageState) -> Option<String> {
+	Some(match state {
+		PackageState::New => { return None }
+		PackageState::IssueFiled(filed) => { filed.issue_url.clone() }
+		PackageState::ContactedByEmail(contacted) => { contacted.email.clone() }
+		PackageState::Deferred(deferred) => { deferred.reason.clone() }
+		PackageState::SkippedDeprecated(skipped) | PackageState::Blocked(skipped) | PackageState::OptedOut(skipped) | PackageState::DeclinedByOwnerPolicy(skipped) | PackageState::Dormant(skipped) => { skipped.reason.clone() }
+		PackageState::NeedsReview(review) => { review.reason.clone() }
+		PackageState::AlreadyMigrated(migrated) => { format!("since {}", migrated.found_at) }
+		PackageState::RetryPending(retry) => { format!("attempt {} failed ({}), trying again after {}", retry.attempts, retry.last_error, retry.retry_at) }
+		PackageState::IssueFilingInProgress(filing) => { format!("on {} since {}", filing.repository, filing.started_at) }
+		PackageState::MaintainerApproved(approved) => { approved.approval_url.clone() }
+		PackageState::Migrated(migrated) => { format!("{} (at {})", migrated.approval.approval_url, migrated.migrated_at) }
+		PackageState::ConsentRevoked(revoked) => { revoked.revocation_url.clone() }
+	})
+}
@@ -0,0 +1,157 @@
+//! telling systemd how the daemon's doing, when it's run as a `Type=notify`
+//! service: that it's started (`READY=1`), what it's up to (the status line in
+//! `systemctl status`), and, with `WatchdogSec=`, that it's still alive, so
+//! systemd restarts it if it hangs. it's all sent to `NOTIFY_SOCKET`, so
+//! outside systemd (or off linux) none of it does anything
+
+use crate::control::ControlHandle;
+use crate::db::DatabaseThing;
+use chrono::{ DateTime, Local };
+use std::sync::{ Arc, Mutex };
+use std::time::Duration;
+
+/// how often the status line is updated (for how far a batch has got), if
+/// there's no watchdog to ping more often than this
+const STATUS_EVERY: Duration = Duration::from_secs(30);
+
+/// what the daemon's up to
+#[derive(Clone)]
+pub enum Activity {
+	Syncing,
+	/// checking filed issues
+	Polling,
+	Contacting,
+	/// until the next sync, poll, or batch
+	Sleeping(DateTime<Local>),
+	Stopping
+}
+
+/// for keeping systemd up to date, see the module docs
+#[derive(Clone)]
+pub struct Notifier {
+	activity: Arc<Mutex<Activity>>,
+	control: ControlHandle
+}
+
+impl Notifier {
+	/// tells systemd the daemon's started, and keeps pinging its watchdog (if
+	/// it has one) for as long as the database isn't stuck
+	pub fn start(db: &DatabaseThing, control: ControlHandle) -> Self {
+		let notifier = Self { activity: Arc::new(Mutex::new(Activity::Syncing)), control };
+		if std::env::var_os("NOTIFY_SOCKET").is_none() { return notifier }
+
+		if let Err(e) = send(&format!("READY=1\nSTATUS={}", notifier.status_line())) {
+			warn!("error telling systemd the daemon's started: {e}");
+		}
+
+		let watchdog = watchdog_interval();
+		if let Some(interval) = watchdog {
+			info!("pinging systemd's watchdog every {}s", interval.as_secs());
+		}
+		let every = watchdog.map_or(STATUS_EVERY, |interval| interval.min(STATUS_EVERY));
+		let (db, pinger) = (db.clone(), notifier.clone());
+		tokio::spawn(async move {
+			loop {
+				tokio::time::sleep(every).await;
+				// takes the database's lock, so the pings stop (and systemd restarts
+				// it) if something's holding on to it, like `/healthz`
+				db.package_count();
+				let ping = match watchdog {
+					Some(_) => { "WATCHDOG=1\n" }
+					None => { "" }
+				};
+				if let Err(e) = send(&format!("{ping}STATUS={}", pinger.status_line())) {
+					debug!("error pinging systemd: {e}");
+				}
+			}
+		});
+		notifier
+	}
+
+	/// changes what the daemon says it's up to
+	pub fn set(&self, activity: Activity) {
+		let stopping = matches!(activity, Activity::Stopping);
+		*self.activity.lock().unwrap() = activity;
+		if std::env::var_os("NOTIFY_SOCKET").is_none() { return }
+
+		let stopping = if stopping { "STOPPING=1\n" } else { "" };
+		if let Err(e) = send(&format!("{stopping}STATUS={}", self.status_line())) {
+			debug!("error updating systemd's status line: {e}");
+		}
+	}
+
+	fn status_line(&self) -> String {
+		match &*self.activity.lock().unwrap() {
+			Activity::Syncing => { "syncing the registry".into() }
+			Activity::Polling => { "checking filed issues".into() }
+			Activity::Contacting => {
+				let progress = self.control.progress();
+				let current = progress.current.map(|current| format!(", on {current}")).unwrap_or_default();
+				format!(
+					"contacting packages, {}/{} gone through, {} filed, {} failed{current}",
+					progress.done, progress.total, progress.issues_filed, progress.failed
+				)
+			}
+			Activity::Sleeping(until) => { format!("sleeping until {}", until.format("%Y-%m-%d %H:%M")) }
+			Activity::Stopping => { "stopping".into() }
+		}
+	}
+}
+
+/// half of `WatchdogSec=`, if systemd's watching this process
+fn watchdog_interval() -> Option<Duration> {
+	let usec = std::env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+	// it's for another process, like the shell that started this one
+	if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+		if pid.parse::<u32>().ok()? != std::process::id() { return None }
+	}
+	Some(Duration::from_micros(usec / 2)).filter(|interval| !interval.is_zero())
+}
+
+/// `message` to `NOTIFY_SOCKET`, which can be a path, or (starting with `@`) an
+/// abstract socket, which std can't send to on every supported rust version
+#[cfg(target_os = "linux")]
+fn send(message: &str) -> std::io::Result<()> {
+	use std::io::{ Error, ErrorKind };
+	use std::os::unix::ffi::OsStrExt;
+
+	let socket = match std::env::var_os("NOTIFY_SOCKET") {
+		Some(socket) => { socket }
+		None => { return Ok(()) }
+	};
+	let path = socket.as_bytes();
+	let mut addr = unsafe { std::mem::zeroed::<libc::sockaddr_un>() };
+	addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+	if path.is_empty() || path.len() >= addr.sun_path.len() {
+		return Err(Error::new(ErrorKind::InvalidInput, "NOTIFY_SOCKET isn't a socket's path"))
+	}
+	for (to, from) in addr.sun_path.iter_mut().zip(path) {
+		*to = *from as std::os::raw::c_char;
+	}
+	if path[0] == b'@' {
+		addr.sun_path[0] = 0;
+	}
+	let len = std::mem::size_of::<libc::sa_family_t>() + path.len();
+
+	unsafe {
+		let fd = libc::socket(libc::AF_UNIX, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+		if fd < 0 { return Err(Error::last_os_error()) }
+		let sent = libc::sendto(
+			fd,
+			message.as_ptr() as *const libc::c_void,
+			message.len(),
+			libc::MSG_NOSIGNAL,
+			&addr as *const libc::sockaddr_un as *const libc::sockaddr,
+			len as libc::socklen_t
+		);
+		let e = Error::last_os_error();
+		libc::close(fd);
+		if sent < 0 { return Err(e) }
+	}
+	Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send(_message: &str) -> std::io::Result<()> {
+	Ok(())
+}
@@ -0,0 +1,703 @@
+//! the text of the issues, emails, and comments that get posted. the built in
+//! ones are in `resources/`, and any of them can be swapped out by putting a
+//! file with the same name in `templates_dir`. they're handlebars templates,
+//! filled in with [`Vars`] (like `{{package}}`, or `{{#each packages}}`), and
+//! the pulsar backend's url as `{{pulsar_backend_url}}`. `{{short downloads}}`
+//! makes numbers short, like 120k, and `{{#if theme}}`, `{{#if grouped}}`, and
+//! `{{#if declined_other_packages}}` cover the variations in one template. a
+//! variable that isn't one of those is an error when the config's loaded,
+//! rather than left empty in an issue
+
+use crate::db::{ PackageMetadata, PackageNew, Releases, Repository };
+use crate::forge::RepoLocation;
+use crate::{ Error, Result };
+use handlebars::{ handlebars_helper, Handlebars };
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{ Path, PathBuf };
+
+/// the templates, by file, and what they're registered as
+pub const FILES: &[(&str, &str, &str)] = &[
+	("issue_template.md", "issue", include_str!("../resources/issue_template.md")),
+	("email_template.md", "email", include_str!("../resources/email_template.md")),
+	("follow_up_template.md", "follow_up", include_str!("../resources/follow_up_template.md")),
+	("final_reminder_template.md", "final_reminder", include_str!("../resources/final_reminder_template.md")),
+	("rollback_template.md", "rollback", include_str!("../resources/rollback_template.md"))
+];
+
+/// characters, in an issue's body or a comment. github's limit, which is the
+/// lowest of the forges'
+pub const MAX_BODY: usize = 65_536;
+
+/// characters, in an issue's title, which is github's limit
+pub const MAX_TITLE: usize = 256;
+
+/// what the templates were filled in with before they were handlebars templates
+const OLD_PLACEHOLDERS: &[&str] = &["package", "repository"];
+
+#[derive(Clone)]
+pub struct Templates {
+	/// the issue's title, as it is in the template, see [`IssueTitles`]
+	pub issue_title: String,
+	/// the issue's title and body, and the email's subject and body, are `_title` and `_body`
+	handlebars: Handlebars<'static>,
+	pulsar_backend_url: String,
+	/// the `@` mentions written in the templates, which are meant, unlike ones
+	/// from what they're filled in with (lowercase, without the `@`)
+	mentions: HashSet<String>,
+	/// every file it could've been loaded from, whether it's there or not, so
+	/// the daemon notices new ones too
+	files: Vec<PathBuf>
+}
+
+/// what the templates can use
+#[derive(Clone, Serialize)]
+pub struct Vars {
+	/// the package, or the first one for an issue for several
+	pub package: String,
+	pub downloads: u32,
+	pub stars: u32,
+	pub theme: bool,
+	/// every package it's for, in the order they're listed in
+	pub packages: Vec<String>,
+	/// whether it's for more than one package, like the packages in a monorepo
+	pub grouped: bool,
+	/// the other packages it's for, with their own downloads and so on
+	pub siblings: Vec<PackageVars>,
+	/// other packages of the same owner's that they said no to, for
+	/// `{{#if declined_other_packages}}`
+	pub declined_other_packages: Vec<String>,
+	/// url of the repository
+	pub repository: String
+}
+
+/// a package in [`Vars::siblings`]
+#[derive(Clone, Serialize)]
+pub struct PackageVars {
+	pub name: String,
+	pub downloads: u32,
+	pub stars: u32,
+	pub theme: bool
+}
+
+impl PackageVars {
+	fn new(package: &PackageNew) -> Self {
+		Self {
+			name: package.name.clone(),
+			downloads: package.downloads,
+			stars: package.stargazers_count,
+			theme: package.is_theme()
+		}
+	}
+}
+
+impl Vars {
+	/// for an issue (or a comment on one) on `repo`, for `packages`
+	pub fn issue(repo: &RepoLocation, packages: &[PackageNew]) -> Self {
+		let mut packages = packages.iter().map(PackageVars::new);
+		let first = packages.next().unwrap_or(PackageVars { name: String::new(), downloads: 0, stars: 0, theme: false });
+		let siblings = packages.collect::<Vec<_>>();
+		Self {
+			packages: std::iter::once(first.name.clone()).chain(siblings.iter().map(|sibling| sibling.name.clone())).collect(),
+			grouped: !siblings.is_empty(),
+			package: first.name,
+			downloads: first.downloads,
+			stars: first.stars,
+			theme: first.theme,
+			siblings,
+			declined_other_packages: Vec::new(),
+			repository: format!("https://{repo}")
+		}
+	}
+
+	/// with `declined` as [`Self::declined_other_packages`], leaving out the ones it's for
+	pub fn with_declined(mut self, declined: Vec<String>) -> Self {
+		self.declined_other_packages = declined.into_iter().filter(|name| !self.packages.contains(name)).collect();
+		self
+	}
+
+	/// for an email about `package`
+	pub fn email(package: &PackageNew) -> Self {
+		let PackageVars { name, downloads, stars, theme } = PackageVars::new(package);
+		Self {
+			packages: vec![name.clone()],
+			package: name,
+			downloads,
+			stars,
+			theme,
+			grouped: false,
+			siblings: Vec::new(),
+			declined_other_packages: Vec::new(),
+			repository: package.repository.url.clone()
+		}
+	}
+}
+
+impl Templates {
+	/// each template is the first of `dirs` that has it, or the built in one
+	pub fn load(dirs: &[&str], pulsar_backend_url: &str) -> Result<Self> {
+		let read = |file: &str, built_in: &str| -> Result<String> {
+			for dir in dirs {
+				let path = Path::new(dir).join(file);
+				match fs::read_to_string(&path) {
+					Ok(contents) => { return Ok(contents) }
+					Err(e) if e.kind() == ErrorKind::NotFound => { continue }
+					Err(e) => { return Err(format!("error reading template {}: {e}", path.display()).into()) }
+				}
+			}
+			Ok(built_in.into())
+		};
+
+		let mut handlebars = Handlebars::new();
+		// they're markdown and plain text, not html
+		handlebars.register_escape_fn(handlebars::no_escape);
+		handlebars.set_strict_mode(true);
+		handlebars.register_helper("short", Box::new(short));
+		let mut issue_title = String::new();
+		let mut mentions = HashSet::new();
+		for (file, name, built_in) in FILES {
+			let contents = read(file, built_in)?;
+			mentions.extend(mentions_in(&contents));
+			if let Some(old) = OLD_PLACEHOLDERS.iter().find(|old| has_old_placeholder(&contents, old)) {
+				return Err(format!("template {file} has {{{old}}} in it, which is {{{{{old}}}}} now").into())
+			}
+
+			let mut register = |name: String, template: &str| {
+				handlebars.register_template_string(&name, template)
+					.map_err(|e| format!("template {file}: {e}"))
+			};
+			match *name {
+				// the first line is the title (or subject), then there's an empty line before the body
+				"issue" | "email" => {
+					let (title, body) = contents.split_once("\n\n")
+						.ok_or_else(|| format!("template {file} needs a title on the first line, then an empty line, then the body"))?;
+					if *name == "issue" {
+						issue_title = title.into();
+					}
+					register(format!("{name}_title"), title)?;
+					register(format!("{name}_body"), body)?;
+				}
+				name => { register(name.into(), &contents)? }
+			}
+		}
+
+		let files = dirs.iter()
+			.flat_map(|dir| FILES.iter().map(move |(file, _, _)| Path::new(dir).join(file)))
+			.collect();
+		let templates = Self { issue_title, handlebars, pulsar_backend_url: pulsar_backend_url.into(), mentions, files };
+		templates.check()?;
+		Ok(templates)
+	}
+
+	/// every file it could've been loaded from, for reloading them when they change
+	pub fn files(&self) -> &[PathBuf] {
+		&self.files
+	}
+
+	/// the title and body of the issue
+	pub fn issue(&self, vars: &Vars) -> Result<(String, String)> {
+		let title = self.render("issue_title", vars)?;
+		check_title(&title).map_err(|e| Error::Content(format!("template issue_title filled in for {} {e}", vars.packages.join(", "))))?;
+		Ok((title, self.render_markdown("issue_body", vars)?))
+	}
+
+	/// turns down an issue that's about to be filed on `repo` if it isn't right
+	/// (see [`Self::render_markdown`]), for ones that could've been edited since
+	/// they were filled in, like drafts. the repository's owner can be mentioned
+	/// in those
+	pub fn check_issue(&self, repo: &RepoLocation, title: &str, body: &str) -> Result {
+		check_title(title).map_err(|e| Error::Content(format!("the issue's title {e}")))?;
+		self.check_body(body, Some(&repo.owner)).map_err(|e| Error::Content(format!("the issue's body {e}")))
+	}
+
+	/// the subject and body of the email sent instead when there's no forge to file on
+	pub fn email(&self, vars: &Vars) -> Result<(String, String)> {
+		Ok((self.render("email_title", vars)?, self.render("email_body", vars)?))
+	}
+
+	pub fn follow_up(&self, vars: &Vars) -> Result<String> {
+		self.render_markdown("follow_up", vars)
+	}
+
+	pub fn final_reminder(&self, vars: &Vars) -> Result<String> {
+		self.render_markdown("final_reminder", vars)
+	}
+
+	/// posted on issues before closing them with `rollback`
+	pub fn rollback(&self, vars: &Vars) -> Result<String> {
+		self.render_markdown("rollback", vars)
+	}
+
+	/// for what's posted on a forge, which turns down ones that are empty, too
+	/// long, have something that wasn't filled in, would mention someone the
+	/// templates don't, or have a code block that's never closed (which would
+	/// show the rest of it as code)
+	fn render_markdown(&self, template: &str, vars: &Vars) -> Result<String> {
+		let markdown = self.render(template, vars)?;
+		self.check_body(&markdown, None)
+			.map_err(|e| Error::Content(format!("template {template} filled in for {} {e}", vars.packages.join(", "))))?;
+		Ok(markdown)
+	}
+
+	/// see [`Self::render_markdown`], with what's wrong to go after what it's about
+	fn check_body(&self, markdown: &str, owner: Option<&str>) -> std::result::Result<(), String> {
+		if markdown.trim().is_empty() {
+			return Err("is empty".into())
+		}
+		let length = markdown.chars().count();
+		if length > MAX_BODY {
+			return Err(format!("is {length} characters, which is more than github allows ({MAX_BODY})"))
+		}
+		let fences = markdown.lines().filter(|line| line.trim_start().starts_with("```")).count();
+		if fences % 2 == 1 {
+			return Err("has a ``` code block that's never closed".into())
+		}
+		let text = without_code(markdown);
+		if text.contains("{{") || text.contains("}}") {
+			return Err("has {{ or }} left in it, so something wasn't filled in".into())
+		}
+		let owner = owner.map(str::to_lowercase);
+		let unexpected = mentions_in(&text)
+			.filter(|mention| !self.mentions.contains(mention) && Some(mention) != owner.as_ref())
+			.map(|mention| format!("@{mention}"))
+			.collect::<Vec<_>>();
+		if !unexpected.is_empty() {
+			return Err(format!("would mention {} (put it in `backticks` if it's not meant to)", unexpected.join(", ")))
+		}
+		Ok(())
+	}
+
+	fn render(&self, template: &str, vars: &Vars) -> Result<String> {
+		let mut data = serde_json::to_value(vars)?;
+		data["pulsar_backend_url"] = self.pulsar_backend_url.clone().into();
+		self.handlebars.render(template, &data)
+			.map_err(|e| format!("error filling in template {template}: {e}").into())
+	}
+
+	/// fills every template in once, so one that uses something that isn't there
+	/// (or is too long, or doesn't say which package it's about) fails now,
+	/// rather than on the first package it's used for
+	fn check(&self) -> Result {
+		let repo = RepoLocation { host: "github.com".into(), owner: "owner".into(), name: "repo".into() };
+		let package = |name: &str| PackageNew {
+			name: name.into(),
+			repository: Repository { r#type: "git".into(), url: format!("https://{repo}") },
+			downloads: 1000,
+			stargazers_count: 10,
+			metadata: PackageMetadata::default(),
+			theme: false,
+			releases: Releases::default(),
+			versions: Vec::new(),
+			owner: None,
+			readme_locale: None
+		};
+		// names that aren't going to be in a template by chance, and that aren't
+		// part of each other, so one being there doesn't look like another is
+		for (packages, declined) in [
+			(vec![package("alpha-package")], Vec::new()),
+			(vec![package("alpha-package"), package("beta-package")], vec!["gamma-package".into()])
+		] {
+			let vars = Vars::issue(&repo, &packages).with_declined(declined);
+			// it's on the package's repository, so it only has to say for ones for several
+			let (title, body) = self.issue(&vars)?;
+			if !IssueTitles::new([self.issue_title.as_str()]).matches(&title) {
+				return Err(format!("issue_template.md's title filled in as {title:?}, which wouldn't be found again to not file it twice").into())
+			}
+			if let Some(missing) = packages.iter().filter(|_| vars.grouped).find(|package| !body.contains(&package.name)) {
+				return Err(format!("issue_template.md doesn't say an issue for several packages is for {} too, it needs {{{{#each packages}}}}", missing.name).into())
+			}
+			let (subject, body) = self.email(&Vars::email(&packages[0]))?;
+			if !subject.contains(&packages[0].name) && !body.contains(&packages[0].name) {
+				return Err("email_template.md doesn't say which package it's about, it needs {{package}}".into())
+			}
+			if !body.contains(&packages[0].repository.url) {
+				return Err("email_template.md doesn't say which repository it's about, it needs {{repository}}".into())
+			}
+			self.follow_up(&vars)?;
+			self.final_reminder(&vars)?;
+			self.rollback(&vars)?;
+		}
+		Ok(())
+	}
+}
+
+/// the titles issues the bot filed could have: what each of the templates
+/// could fill theirs in as, and `previous_issue_titles`, so changing the title
+/// doesn't mean a second issue for ones that were filed with the old one. the
+/// text that's in every title the template could make has to be the same, and
+/// anything filled in (or in an `{{#if}}` or the like) can be anything
+#[derive(Clone, Default)]
+pub struct IssueTitles {
+	patterns: Vec<Vec<TitlePiece>>
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum TitlePiece {
+	Text(String),
+	/// anything, even nothing
+	Anything
+}
+
+impl IssueTitles {
+	pub fn new<'a>(templates: impl IntoIterator<Item = &'a str>) -> Self {
+		Self::default().with(templates)
+	}
+
+	/// these, and the titles `templates` could fill in as too
+	pub fn with<'a>(mut self, templates: impl IntoIterator<Item = &'a str>) -> Self {
+		for template in templates {
+			let pattern = title_pattern(template);
+			if !self.patterns.contains(&pattern) {
+				self.patterns.push(pattern);
+			}
+		}
+		self
+	}
+
+	/// whether it's the title of an issue the bot filed
+	pub fn matches(&self, title: &str) -> bool {
+		self.patterns.iter().any(|pattern| title_matches(pattern, title))
+	}
+
+	/// for forges that can only search for issues: the longest bit of text
+	/// that's in every title for each pattern, which is empty for ones that
+	/// are only filled in
+	pub fn search_terms(&self) -> Vec<&str> {
+		let mut terms = Vec::new();
+		for pattern in &self.patterns {
+			let term = pattern.iter()
+				.filter_map(|piece| match piece {
+					TitlePiece::Text(text) => { Some(text.trim()) }
+					TitlePiece::Anything => { None }
+				})
+				.max_by_key(|text| text.len())
+				.unwrap_or_default();
+			if !terms.contains(&term) {
+				terms.push(term);
+			}
+		}
+		terms
+	}
+}
+
+/// `template`, as text that's always there and bits that can be anything
+fn title_pattern(template: &str) -> Vec<TitlePiece> {
+	let mut pattern = Vec::new();
+	let mut push = |piece: TitlePiece| {
+		match (pattern.last_mut(), piece) {
+			(Some(TitlePiece::Anything), TitlePiece::Anything) => {}
+			(Some(TitlePiece::Text(last)), TitlePiece::Text(text)) => { last.push_str(&text) }
+			(_, piece) => { pattern.push(piece) }
+		}
+	};
+	// inside `{{#if}}`s and the like, nothing's always there
+	let mut depth = 0usize;
+	let mut rest = template;
+	while let Some(start) = rest.find("{{") {
+		if depth == 0 && start > 0 {
+			push(TitlePiece::Text(rest[..start].into()));
+		}
+		let tag = &rest[start + 2..];
+		let end = match tag.find("}}") {
+			Some(end) => { end }
+			// an error when it's loaded anyway
+			None => { break }
+		};
+		match tag.trim_start_matches(['{', '~']).chars().next() {
+			Some('#') => { depth += 1 }
+			Some('/') => { depth = depth.saturating_sub(1) }
+			_ => {}
+		}
+		push(TitlePiece::Anything);
+		rest = tag[end + 2..].trim_start_matches('}');
+	}
+	if depth == 0 && !rest.is_empty() {
+		push(TitlePiece::Text(rest.into()));
+	}
+	pattern
+}
+
+fn title_matches(pattern: &[TitlePiece], title: &str) -> bool {
+	let mut rest = title;
+	// whether the last piece was text, so this one has to come straight after it
+	let mut anchored = true;
+	for (i, piece) in pattern.iter().enumerate() {
+		let text = match piece {
+			TitlePiece::Text(text) => { text }
+			TitlePiece::Anything => {
+				anchored = false;
+				continue
+			}
+		};
+		if anchored {
+			match rest.strip_prefix(text.as_str()) {
+				Some(after) => { rest = after }
+				None => { return false }
+			}
+		} else if i == pattern.len() - 1 {
+			return rest.ends_with(text.as_str())
+		} else {
+			match rest.find(text.as_str()) {
+				// the first place it's in leaves the most for the rest to match
+				Some(at) => { rest = &rest[at + text.len()..] }
+				None => { return false }
+			}
+		}
+		anchored = true;
+	}
+	!anchored || rest.is_empty()
+}
+
+// `{{short downloads}}`, like 950, 1.2k, 120k, or 3.4M
+handlebars_helper!(short: |n: u64| match n {
+	0..=999 => { n.to_string() }
+	1_000..=99_999 => { format!("{:.1}k", n as f64 / 1_000.0).replace(".0k", "k") }
+	100_000..=999_999 => { format!("{}k", n / 1_000) }
+	_ => { format!("{:.1}M", n as f64 / 1_000_000.0).replace(".0M", "M") }
+});
+
+/// `title` being empty, on more than one line, too long, or with something that
+/// wasn't filled in, as what's wrong with it
+fn check_title(title: &str) -> std::result::Result<(), String> {
+	let length = title.chars().count();
+	if title.trim().is_empty() {
+		Err("is empty".into())
+	} else if title.contains('\n') {
+		Err("is on more than one line".into())
+	} else if length > MAX_TITLE {
+		Err(format!("is {length} characters, which is more than github allows ({MAX_TITLE})"))
+	} else if title.contains("{{") || title.contains("}}") {
+		Err("has {{ or }} left in it, so something wasn't filled in".into())
+	} else {
+		Ok(())
+	}
+}
+
+/// `markdown` without its code blocks and `code spans`, where `@` mentions and
+/// braces don't mean anything
+fn without_code(markdown: &str) -> String {
+	let mut text = String::new();
+	let mut in_block = false;
+	for line in markdown.lines() {
+		if line.trim_start().starts_with("```") {
+			in_block = !in_block;
+			continue
+		}
+		if in_block { continue }
+		// every other piece between backticks is code, if they're paired up
+		let pieces = line.split('`').collect::<Vec<_>>();
+		match pieces.len() % 2 {
+			1 => { text.extend(pieces.iter().step_by(2).copied()) }
+			_ => { text.push_str(line) }
+		}
+		text.push('\n');
+	}
+	text
+}
+
+/// who `text` mentions, like github finds them: `@` and a username (or
+/// `org/team`), but not in the middle of a word, like an email address.
+/// lowercase, without the `@`
+fn mentions_in(text: &str) -> impl Iterator<Item = String> + '_ {
+	text.match_indices('@').filter_map(|(i, _)| {
+		let before = text[..i].chars().next_back();
+		if before.map_or(false, |c| c.is_alphanumeric() || "_.-/@`".contains(c)) { return None }
+		let name = text[i + 1..].split(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '/')).next()?;
+		let name = name.trim_end_matches(['-', '/']);
+		name.starts_with(|c: char| c.is_ascii_alphanumeric()).then(|| name.to_lowercase())
+	})
+}
+
+/// whether `template` has `{name}` in it, but not as part of `{{name}}`
+fn has_old_placeholder(template: &str, name: &str) -> bool {
+	let old = format!("{{{name}}}");
+	template.match_indices(&old).any(|(i, _)| {
+		!template[..i].ends_with('{') && !template[i + old.len()..].starts_with('}')
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// packages like the ones issues get filed for, grouped by repository, with
+	/// what their snapshots are called
+	const PACKAGES: &[(&str, &str, &[&str], bool)] = &[
+		("single", "https://github.com/AtomLinter/linter-eslint", &["linter-eslint"], false),
+		("theme", "https://github.com/someone/one-dark-ui-plus", &["one-dark-ui-plus"], true),
+		("unicode", "https://github.com/ümlaut/日本語-syntax", &["日本語-syntax"], true),
+		("monorepo", "https://github.com/atom-community/languages", &["language-c++", "language-c#", "language-`backticks`"], false),
+		("gitlab", "git+https://gitlab.com/group/subgroup/repo.git", &["@scope/package", "with *markdown* in it"], false)
+	];
+
+	fn package(name: &str, url: &str, theme: bool) -> PackageNew {
+		PackageNew {
+			name: name.into(),
+			repository: Repository { r#type: "git".into(), url: url.into() },
+			downloads: 1000,
+			stargazers_count: 10,
+			metadata: PackageMetadata::default(),
+			theme,
+			releases: Releases::default(),
+			versions: Vec::new(),
+			owner: None,
+			readme_locale: None
+		}
+	}
+
+	/// the built in issue, for a repository's packages, as it would be filed.
+	/// `cargo insta review` to look through what changed
+	#[test]
+	fn issues() {
+		let templates = Templates::load(&[], "https://api.pulsar-edit.dev").unwrap();
+		for (snapshot, url, names, theme) in PACKAGES {
+			let packages = names.iter().map(|name| package(name, url, *theme)).collect::<Vec<_>>();
+			let (title, body) = templates.issue(&Vars::issue(&RepoLocation::parse(url).unwrap(), &packages)).unwrap();
+			insta::assert_snapshot!(format!("issue_{snapshot}"), format!("{title}\n\n{body}"), &format!("{url}: {}", names.join(", ")));
+		}
+	}
+
+	/// the built in email, for each package
+	#[test]
+	fn emails() {
+		let templates = Templates::load(&[], "https://api.pulsar-edit.dev").unwrap();
+		for (snapshot, url, names, theme) in PACKAGES {
+			for (i, name) in names.iter().enumerate() {
+				let (subject, body) = templates.email(&Vars::email(&package(name, url, *theme))).unwrap();
+				insta::assert_snapshot!(format!("email_{snapshot}_{}", i + 1), format!("{subject}\n\n{body}"), &format!("{url}: {name}"));
+			}
+		}
+	}
+
+	/// the reminders that are posted on issues nobody's replied to
+	#[test]
+	fn reminders() {
+		let templates = Templates::load(&[], "https://api.pulsar-edit.dev").unwrap();
+		let (_, url, names, theme) = PACKAGES[0];
+		let packages = names.iter().map(|name| package(name, url, theme)).collect::<Vec<_>>();
+		let vars = Vars::issue(&RepoLocation::parse(url).unwrap(), &packages);
+		insta::assert_snapshot!("follow_up", templates.follow_up(&vars).unwrap());
+		insta::assert_snapshot!("final_reminder", templates.final_reminder(&vars).unwrap());
+		insta::assert_snapshot!("rollback", templates.rollback(&vars).unwrap());
+	}
+
+	/// templates that would be filled in wrong are found when the config's loaded
+	#[test]
+	fn mistakes_fail_loading() {
+		let dir = std::env::temp_dir().join(format!("pulsar-bot-templates-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let too_long = "a".repeat(MAX_BODY + 1);
+		for (file, template, error) in [
+			("follow_up_template.md", "Hello, {{pakage}}", "pakage"),
+			("follow_up_template.md", "Hello, {package}", "{{package}} now"),
+			("follow_up_template.md", "Hello, {{#if grouped}}", "follow_up_template.md"),
+			("follow_up_template.md", "```\n{{package}}", "never closed"),
+			("follow_up_template.md", &too_long, "more than github allows"),
+			("follow_up_template.md", "Hello, \\{{package}}", "wasn't filled in"),
+			("follow_up_template.md", "{{#if theme}}Hello!{{/if}}", "is empty"),
+			("issue_template.md", &format!("{}{{{{package}}}}\n\n{{{{#each packages}}}}{{{{this}}}}{{{{/each}}}}", "a".repeat(MAX_TITLE)), "more than github allows (256)"),
+			("issue_template.md", "Title\n\nHello, {{package}}", "needs {{#each packages}}"),
+			("email_template.md", "About {{package}}\n\nHello!", "needs {{repository}}")
+		] {
+			for (file, _, _) in FILES {
+				let _ = fs::remove_file(dir.join(file));
+			}
+			fs::write(dir.join(file), template).unwrap();
+			let e = Templates::load(&[dir.to_str().unwrap()], "https://api.pulsar-edit.dev").err().expect(template).to_string();
+			assert!(e.contains(error), "{template}: {e}");
+		}
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn package_variables() {
+		let dir = std::env::temp_dir().join(format!("pulsar-bot-variables-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		fs::write(
+			dir.join("follow_up_template.md"),
+			"your package `{{package}}` with {{short downloads}} downloads{{#each siblings}}, and `{{name}}` with {{short downloads}}{{/each}}"
+		).unwrap();
+		let templates = Templates::load(&[dir.to_str().unwrap()], "https://api.pulsar-edit.dev").unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+
+		let url = "https://github.com/owner/repo";
+		let mut packages = vec![package("foo", url, false), package("bar", url, false)];
+		packages[0].downloads = 120_400;
+		packages[1].downloads = 1_530_000;
+		assert_eq!(
+			templates.follow_up(&Vars::issue(&RepoLocation::parse(url).unwrap(), &packages)).unwrap(),
+			"your package `foo` with 120k downloads, and `bar` with 1.5M"
+		);
+	}
+
+	/// someone that's only mentioned because of what a template was filled in
+	/// with isn't, but ones the templates mention (and the owner, in drafts) are
+	#[test]
+	fn unexpected_mentions() {
+		let dir = std::env::temp_dir().join(format!("pulsar-bot-mentions-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		fs::write(dir.join("follow_up_template.md"), "Hello again about {{package}}! cc @pulsar-edit/packages").unwrap();
+		let templates = Templates::load(&[dir.to_str().unwrap()], "https://api.pulsar-edit.dev").unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+
+		let url = "https://github.com/owner/repo";
+		let repo = RepoLocation::parse(url).unwrap();
+		let follow_up = |name: &str| templates.follow_up(&Vars::issue(&repo, &[package(name, url, false)]));
+		assert_eq!(follow_up("some-package").unwrap(), "Hello again about some-package! cc @pulsar-edit/packages");
+		let e = follow_up("@someone/package").unwrap_err().to_string();
+		assert!(e.contains("would mention @someone/package"), "{e}");
+
+		assert!(templates.check_issue(&repo, "Title", "thanks @owner, and someone@example.com").is_ok());
+		let e = templates.check_issue(&repo, "Title", "thanks @owner and @Someone-Else, but not `@code`").unwrap_err().to_string();
+		assert!(e.ends_with("would mention @someone-else (put it in `backticks` if it's not meant to)"), "{e}");
+	}
+
+	#[test]
+	fn conditional_sections() {
+		let dir = std::env::temp_dir().join(format!("pulsar-bot-conditionals-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		fs::write(
+			dir.join("follow_up_template.md"),
+			"{{#if theme}}your theme{{else}}your package{{/if}}{{#if grouped}}s{{/if}}\
+			{{#if declined_other_packages}} (we won't ask about {{#each declined_other_packages}}`{{this}}` {{/each}}again){{/if}}"
+		).unwrap();
+		let templates = Templates::load(&[dir.to_str().unwrap()], "https://api.pulsar-edit.dev").unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+
+		let url = "https://github.com/owner/repo";
+		let repo = RepoLocation::parse(url).unwrap();
+		let follow_up = |packages: &[PackageNew], declined: &[&str]| {
+			let declined = declined.iter().map(|name| name.to_string()).collect();
+			templates.follow_up(&Vars::issue(&repo, packages).with_declined(declined)).unwrap()
+		};
+		assert_eq!(follow_up(&[package("a", url, false)], &[]), "your package");
+		assert_eq!(follow_up(&[package("a", url, true)], &[]), "your theme");
+		assert_eq!(follow_up(&[package("a", url, false), package("b", url, false)], &["a"]), "your packages");
+		assert_eq!(follow_up(&[package("a", url, false)], &["c"]), "your package (we won't ask about `c` again)");
+	}
+
+	#[test]
+	fn titles_are_found_again() {
+		let titles = IssueTitles::new([
+			"Request to use {{package}} for Pulsar",
+			"{{#if grouped}}Packages{{else}}Package{{/if}} on Pulsar: {{package}}",
+			"Request to use package for Pulsar"
+		]);
+		for (title, matches) in [
+			("Request to use linter-eslint for Pulsar", true),
+			("Request to use @scope/package for Pulsar", true),
+			("Request to use package for Pulsar", true),
+			("Request to use linter-eslint for Pulsar, please", false),
+			("Re: Request to use linter-eslint for Pulsar", false),
+			("Packages on Pulsar: language-c", true),
+			("Package on Pulsar: a", true),
+			("Pulsar: a", false),
+			("Something else entirely", false)
+		] {
+			assert_eq!(titles.matches(title), matches, "{title}");
+		}
+		assert_eq!(titles.search_terms(), ["Request to use", "on Pulsar:", "Request to use package for Pulsar"]);
+	}
+}
@@ -0,0 +1,76 @@
+//! sending spans to an opentelemetry collector over otlp/http, if
+//! `otlp.endpoint` is set, so runs on shared infrastructure show up in the same
+//! tracing backend as everything else. there's a span for syncing, and for
+//! checking a repository, filing an issue, and polling it for each package,
+//! with what was logged in them as events
+
+use crate::config::Config;
+use opentelemetry::sdk::trace::{ self, Tracer };
+use opentelemetry::sdk::Resource;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use std::sync::Mutex;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::{ reload, EnvFilter, Layer, Registry };
+
+type OtlpLayer = OpenTelemetryLayer<Registry, Tracer>;
+
+lazy_static! {
+	/// for putting the real layer in once the config's been loaded
+	static ref HANDLE: Mutex<Option<reload::Handle<Option<OtlpLayer>, Registry>>> = Mutex::new(None);
+}
+
+/// for [`crate::logging::init`]. it doesn't do anything until [`init`] is called
+pub fn layer() -> Box<dyn Layer<Registry> + Send + Sync> {
+	let (layer, handle) = reload::Layer::new(None);
+	*HANDLE.lock().unwrap() = Some(handle);
+	layer
+		.with_filter(EnvFilter::new("warn,pulsar_migrator_issue_bot=info"))
+		.boxed()
+}
+
+/// sends what's left when it's dropped
+pub struct TracesGuard;
+
+impl Drop for TracesGuard {
+	fn drop(&mut self) {
+		opentelemetry::global::shutdown_tracer_provider();
+	}
+}
+
+/// starts sending spans, until what's returned is dropped
+pub fn init(config: &Config) -> crate::Result<Option<TracesGuard>> {
+	let endpoint = match &config.otlp_endpoint {
+		Some(endpoint) => { endpoint }
+		None => { return Ok(None) }
+	};
+	let traces_url = match endpoint.trim_end_matches('/') {
+		endpoint if endpoint.ends_with("/v1/traces") => { endpoint.to_string() }
+		endpoint => { format!("{endpoint}/v1/traces") }
+	};
+
+	let mut resource = vec![
+		KeyValue::new("service.name", env!("CARGO_PKG_NAME")),
+		KeyValue::new("service.version", env!("CARGO_PKG_VERSION"))
+	];
+	if let Some(environment) = config.sentry_environment.clone().or_else(|| config.profile.clone()) {
+		resource.push(KeyValue::new("deployment.environment", environment));
+	}
+
+	// otherwise they're printed straight to stderr
+	opentelemetry::global::set_error_handler(|e| warn!("error sending traces: {e}"))
+		.map_err(|e| format!("error setting up sending traces: {e}"))?;
+	let tracer = opentelemetry_otlp::new_pipeline()
+		.tracing()
+		.with_exporter(opentelemetry_otlp::new_exporter().http().with_endpoint(traces_url))
+		.with_trace_config(trace::config().with_resource(Resource::new(resource)))
+		.install_batch(opentelemetry::runtime::Tokio)
+		.map_err(|e| format!("error setting up sending traces to {endpoint}: {e}"))?;
+
+	if let Some(handle) = HANDLE.lock().unwrap().as_ref() {
+		handle.reload(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+			.map_err(|e| format!("error setting up sending traces: {e}"))?;
+	}
+	info!("sending traces to {endpoint}");
+	Ok(Some(TracesGuard))
+}
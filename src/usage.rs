@@ -0,0 +1,72 @@
+//! how many forge api calls each stage of a run (checking repositories before
+//! filing, filing, and keeping up with filed issues) makes, for the run report,
+//! so it's clear which one to make cheaper when rate limits get in the way
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+lazy_static! {
+	static ref CALLS: Mutex<Usage> = Mutex::new(Usage::default());
+}
+
+tokio::task_local! {
+	static STAGE: Stage;
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Stage {
+	Preflight,
+	Filing,
+	/// reminders, and checking for replies and permission being taken back
+	Polling,
+	/// anything not in one of the others, like `reconcile`
+	Other
+}
+
+impl Stage {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Preflight => { "preflight" }
+			Self::Filing => { "filing" }
+			Self::Polling => { "polling" }
+			Self::Other => { "other" }
+		}
+	}
+}
+
+/// calls, by api (`github`, `gitlab`, ...), stage, and what was asked for
+#[derive(Default)]
+pub struct Usage(pub BTreeMap<(&'static str, Stage, &'static str), u64>);
+
+impl Usage {
+	/// like `github: preflight 12, filing 8, polling 30`, a line for each api
+	pub fn summary(&self) -> Vec<String> {
+		let mut by_api = BTreeMap::<_, BTreeMap<_, u64>>::new();
+		for ((api, stage, _), calls) in &self.0 {
+			*by_api.entry(*api).or_default().entry(*stage).or_default() += calls;
+		}
+		by_api.into_iter()
+			.map(|(api, stages)| {
+				let stages = stages.into_iter().map(|(stage, calls)| format!("{} {calls}", stage.name())).collect::<Vec<_>>();
+				format!("{api}: {}", stages.join(", "))
+			})
+			.collect()
+	}
+}
+
+/// runs `f` with the api calls it makes counted for `stage`
+pub async fn in_stage<T>(stage: Stage, f: impl Future<Output = T>) -> T {
+	STAGE.scope(stage, f).await
+}
+
+/// counts a call to `api` for `endpoint`, from [`crate::forge::ForgeClient`]
+pub fn count(api: &'static str, endpoint: &'static str) {
+	let stage = STAGE.try_with(|stage| *stage).unwrap_or(Stage::Other);
+	*CALLS.lock().unwrap().0.entry((api, stage, endpoint)).or_default() += 1;
+}
+
+/// the calls made since the last time this was called
+pub fn take() -> Usage {
+	std::mem::take(&mut *CALLS.lock().unwrap())
+}
@@ -0,0 +1,89 @@
+//! `validate-config`, which loads the config and checks it without running
+//! anything, for deploy pipelines to run before the bot starts
+
+use crate::config::{ self, Config, ENV_PREFIX };
+use crate::Result;
+use std::env::var;
+use std::fs;
+
+/// prints everything wrong with the config at `path` (or [`config::DEFAULT_PATH`])
+/// with `profile`, with where it is, and fails if any of it stops the bot from running
+pub fn run(path: Option<&str>, profile: Option<&str>) -> Result {
+	let shown_path = path.unwrap_or(config::DEFAULT_PATH);
+	let config = match Config::load_unchecked(path, profile) {
+		Ok(config) => { config }
+		Err(e) => {
+			// these already say where (with the line, if it's in the file)
+			println!("{e}");
+			return Err(format!("{shown_path} isn't valid").into())
+		}
+	};
+
+	let contents = fs::read_to_string(shown_path).unwrap_or_default();
+	let problems = config.check();
+	let errors = problems.iter().filter(|problem| problem.fatal).count();
+	for problem in &problems {
+		println!(
+			"{}: {}: {}: {}",
+			locate(shown_path, &contents, config.profile.as_deref(), problem.key),
+			if problem.fatal { "error" } else { "warning" },
+			problem.key,
+			problem.message
+		);
+	}
+
+	match errors {
+		0 => {
+			println!("{} is valid ({} warnings)", config.source, problems.len());
+			Ok(())
+		}
+		errors => { Err(format!("{shown_path} isn't valid, it has {errors} errors").into()) }
+	}
+}
+
+/// where `key` is set: the environment variable overriding it, the line in the
+/// file (in the profile's section, if it's set there too), or just the file if
+/// it isn't in it at all (so it's the default)
+fn locate(path: &str, contents: &str, profile: Option<&str>, key: &str) -> String {
+	let env_name = format!("{ENV_PREFIX}{}", key.replace('.', "__").to_uppercase());
+	if var(&env_name).is_ok() {
+		return env_name
+	}
+
+	let (section, name) = key.rsplit_once('.').unwrap_or(("", key));
+	let profile_section = match (profile, section) {
+		(Some(profile), "") => { Some(format!("profiles.{profile}")) }
+		(Some(profile), section) => { Some(format!("profiles.{profile}.{section}")) }
+		(None, _) => { None }
+	};
+
+	let mut current = String::new();
+	let (mut in_section, mut in_profile) = (None, None);
+	for (i, line) in contents.lines().enumerate() {
+		let line = line.trim();
+		if let Some(header) = line.strip_prefix('[') {
+			current = header.trim_end_matches(']').trim().to_string();
+			// it's a section itself, like email.smtp
+			if current == key {
+				in_section = Some(i + 1);
+			} else if profile.map(|profile| format!("profiles.{profile}.{key}")) == Some(current.clone()) {
+				in_profile = Some(i + 1);
+			}
+			continue
+		}
+		let sets_key = line.strip_prefix(name)
+			.map(|rest| rest.trim_start().starts_with('='))
+			.unwrap_or(false);
+		if sets_key && current == section {
+			in_section = Some(i + 1);
+		} else if sets_key && Some(&current) == profile_section.as_ref() {
+			in_profile = Some(i + 1);
+		}
+	}
+
+	// the profile's is the one that's used
+	match in_profile.or(in_section) {
+		Some(line) => { format!("{path}:{line}") }
+		None => { path.into() }
+	}
+}
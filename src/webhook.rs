@@ -0,0 +1,34 @@
+//! telling other things about packages changing state, by posting them to a
+//! webhook, so they don't need to poll the database
+
+use crate::db::{ DatabaseThing, StateChange };
+use reqwest::Client;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// starts posting every state change `db` makes to `url`, in the background
+pub fn spawn(db: &DatabaseThing, url: String) -> crate::Result<JoinHandle<()>> {
+	let client = Client::builder()
+		.user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+		.build()?;
+	let (tx, mut rx) = mpsc::unbounded_channel::<StateChange>();
+	db.add_state_changes(tx);
+
+	Ok(tokio::spawn(async move {
+		while let Some(change) = rx.recv().await {
+			let res = client.post(&url)
+				.json(&change)
+				.send().await
+				.and_then(|res| res.error_for_status());
+			if let Err(e) = res {
+				error!("error posting state change of package {} ({} -> {}) to webhook: {e}", change.package, change.from, change.to);
+			}
+		}
+	}))
+}
+
+/// waits for the state changes already made to be posted, once
+/// [`DatabaseThing::stop_state_changes`] has been called
+pub async fn finish(handle: JoinHandle<()>) {
+	let _ = handle.await;
+}
@@ -0,0 +1,68 @@
+//! the days and hours issues are allowed to be filed in, since issues filed
+//! at 3am look a lot more like spam (and get answered less)
+
+use chrono::{ DateTime, Datelike, NaiveTime, Timelike, Utc, Weekday };
+
+/// when contacting is allowed, in utc. anything left unset is always allowed
+#[derive(Clone, Debug, Default)]
+pub struct FilingWindow {
+	pub days: Option<Vec<Weekday>>,
+	/// start (inclusive) and end (exclusive). if the end is before the start, it goes past midnight
+	pub hours: Option<(NaiveTime, NaiveTime)>
+}
+
+impl FilingWindow {
+	pub fn contains(&self, time: DateTime<Utc>) -> bool {
+		if let Some(days) = &self.days {
+			if !days.contains(&time.weekday()) { return false }
+		}
+		if let Some((start, end)) = self.hours {
+			let now = NaiveTime::from_hms(time.hour(), time.minute(), time.second());
+			let inside = if start <= end {
+				start <= now && now < end
+			} else {
+				now >= start || now < end
+			};
+			if !inside { return false }
+		}
+		true
+	}
+}
+
+/// parses days like `mon-fri`, `sat,sun`, or `mon-wed,fri`
+pub fn parse_days(s: &str) -> Result<Vec<Weekday>, String> {
+	let parse_day = |day: &str| day.trim().parse::<Weekday>()
+		.map_err(|_| format!("unknown day {day} (expected mon, tue, etc)"));
+
+	let mut days = Vec::new();
+	for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+		match part.split_once('-') {
+			Some((start, end)) => {
+				let (mut day, end) = (parse_day(start)?, parse_day(end)?);
+				days.push(day);
+				while day != end {
+					day = day.succ();
+					days.push(day);
+				}
+			}
+			None => { days.push(parse_day(part)?) }
+		}
+	}
+
+	if days.is_empty() { return Err("no days given".into()) }
+	Ok(days)
+}
+
+/// parses hours like `09:00-18:00`, or `9-18`
+pub fn parse_hours(s: &str) -> Result<(NaiveTime, NaiveTime), String> {
+	let parse_time = |time: &str| {
+		let time = time.trim();
+		NaiveTime::parse_from_str(time, "%H:%M")
+			.or_else(|_| NaiveTime::parse_from_str(&format!("{time}:00"), "%H:%M"))
+			.map_err(|_| format!("invalid time {time} (expected something like 09:00)"))
+	};
+
+	let (start, end) = s.split_once('-')
+		.ok_or_else(|| format!("expected a range like 09:00-18:00, got `{s}`"))?;
+	Ok((parse_time(start)?, parse_time(end)?))
+}
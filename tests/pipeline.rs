@@ -0,0 +1,844 @@
+//! the whole pipeline (sync, pre-flight, filing, polling) run by the actual
+//! binary, against a pretend registry and github that answer like the real ones
+//! do, including when they're rate limiting the bot. so the run loop can be
+//! moved around without finding out it broke on the real thing
+
+use serde_json::{ json, Value };
+use std::path::PathBuf;
+use std::process::Output;
+use tokio::process::Command;
+use wiremock::matchers::{ body_string_contains, method, path, path_regex };
+use wiremock::{ Mock, MockServer, ResponseTemplate };
+
+const OWNER: &str = "someone";
+
+/// a directory for the bot to keep its database (and reports) in, with a
+/// pretend registry and github that the config points it at
+struct Bot {
+	dir: PathBuf,
+	registry: MockServer,
+	github: MockServer
+}
+
+impl Bot {
+	/// with `packages` in the registry, each with their own repository (see
+	/// [`mount_registry`]), and the github endpoints that are the same whatever's
+	/// being tested. the pulsar backend isn't checked, unless `config` has a
+	/// `[pulsar]` of its own
+	async fn new(test: &str, packages: &[&str], config: &str) -> Self {
+		let dir = std::env::temp_dir().join(format!("pulsar-bot-{test}-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(&dir).unwrap();
+
+		let registry = MockServer::start().await;
+		let packages = packages.iter().map(|name| (*name, *name)).collect::<Vec<_>>();
+		mount_registry(&registry, &packages).await;
+
+		let github = MockServer::start().await;
+		Mock::given(method("GET")).and(path("/rate_limit"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(rate_limit(4999)))
+			.mount(&github).await;
+		Mock::given(method("GET")).and(path("/user"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(user("pulsar-bot")))
+			.mount(&github).await;
+
+		let pulsar = match config.contains("[pulsar]") {
+			true => { "" }
+			false => { "[pulsar]\ncheck_backend = false\n" }
+		};
+		std::fs::write(dir.join("config.toml"), format!(
+			"{config}\n\
+			[forges.api_urls]\n\
+			\"github.com\" = \"{}/\"\n\
+			{pulsar}\
+			[registry]\n\
+			url = \"{}\"\n\
+			max_retries = 1\n",
+			github.uri(),
+			registry.uri()
+		)).unwrap();
+
+		Self { dir, registry, github }
+	}
+
+	/// the registry having these packages instead, as (name, repository name)
+	async fn registry_has(&self, packages: &[(&str, &str)]) {
+		self.registry.reset().await;
+		mount_registry(&self.registry, packages).await;
+	}
+
+	/// runs the bot with `args`
+	async fn output(&self, args: &[&str]) -> Output {
+		Command::new(env!("CARGO_BIN_EXE_pulsar-migrator-issue-bot"))
+			.args(args)
+			.current_dir(&self.dir)
+			.env_clear()
+			.env("GITHUB_TOKEN", "not-a-real-token")
+			.output().await
+			.unwrap()
+	}
+
+	/// like [`Self::output`], failing the test if it fails
+	async fn run(&self, args: &[&str]) -> Output {
+		let output = self.output(args).await;
+		assert!(
+			output.status.success(),
+			"`{}` failed:\n{}",
+			args.join(" "),
+			String::from_utf8_lossy(&output.stderr)
+		);
+		output
+	}
+
+	/// like [`Self::run`], for the commands that print json
+	async fn json(&self, args: &[&str]) -> Value {
+		let output = self.run(args).await;
+		serde_json::from_slice(&output.stdout).unwrap()
+	}
+
+	/// the package's repository having a package.json for it
+	async fn has_package_json(&self, package: &str) {
+		let package_json = json!({ "name": package, "version": "1.0.0" }).to_string();
+		Mock::given(method("GET")).and(path(format!("/repos/{OWNER}/{package}/contents/package.json")))
+			.respond_with(ResponseTemplate::new(200).set_body_json(json!({
+				"name": "package.json",
+				"path": "package.json",
+				"sha": "0000000000000000000000000000000000000000",
+				"content": base64::encode(package_json),
+				"size": 40,
+				"url": format!("https://api.github.com/repos/{OWNER}/{package}/contents/package.json"),
+				"html_url": format!("https://github.com/{OWNER}/{package}/blob/master/package.json"),
+				"git_url": format!("https://api.github.com/repos/{OWNER}/{package}/git/blobs/0"),
+				"download_url": null,
+				"type": "file",
+				"_links": {
+					"self": format!("https://api.github.com/repos/{OWNER}/{package}/contents/package.json"),
+					"git": format!("https://api.github.com/repos/{OWNER}/{package}/git/blobs/0"),
+					"html": format!("https://github.com/{OWNER}/{package}/blob/master/package.json")
+				},
+				"license": null
+			})))
+			.mount(&self.github).await;
+	}
+
+	/// the package's repository having no issues yet, and filing one on it going like `response`
+	async fn files_issue(&self, package: &str, response: ResponseTemplate, times: u64) {
+		Mock::given(method("GET")).and(path(format!("/repos/{OWNER}/{package}/issues")))
+			.respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+			.mount(&self.github).await;
+		Mock::given(method("POST")).and(path(format!("/repos/{OWNER}/{package}/issues")))
+			.respond_with(response)
+			.expect(times)
+			.mount(&self.github).await;
+	}
+
+	/// starts over with no database, like on another machine
+	fn forget_db(&self) {
+		for entry in std::fs::read_dir(&self.dir).unwrap() {
+			let path = entry.unwrap().path();
+			if path.file_name().unwrap().to_string_lossy().starts_with("state.ron") {
+				std::fs::remove_file(path).unwrap();
+			}
+		}
+	}
+
+	/// the database, for changing what can't be got to by running the bot for a few weeks
+	fn edit_db(&self, edit: impl FnOnce(String) -> String) {
+		let db_path = self.dir.join("state.ron");
+		let db = std::fs::read_to_string(&db_path).unwrap();
+		std::fs::write(&db_path, edit(db)).unwrap();
+	}
+}
+
+impl Drop for Bot {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_dir_all(&self.dir);
+	}
+}
+
+/// `packages` (name, repository name) in the registry. the ones ending in
+/// `-without-repository` don't have one
+async fn mount_registry(registry: &MockServer, packages: &[(&str, &str)]) {
+	let items = packages.iter().enumerate()
+		.map(|(i, (name, repo))| json!({
+			"name": name,
+			"repository": match name.ends_with("-without-repository") {
+				true => { Value::Null }
+				false => { json!({ "type": "git", "url": format!("https://github.com/{OWNER}/{repo}") }) }
+			},
+			"downloads": 1000 - i,
+			"stargazers_count": 10,
+			"metadata": { "name": name, "author": format!("Someone <{OWNER}@example.com>") }
+		}))
+		.collect::<Vec<_>>();
+	Mock::given(method("GET")).and(path("/packages"))
+		.respond_with(ResponseTemplate::new(200).set_body_json(items))
+		.mount(registry).await;
+	Mock::given(method("GET")).and(path("/themes"))
+		.respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+		.mount(registry).await;
+}
+
+fn rate_limit(remaining: u32) -> Value {
+	let reset = chrono::Utc::now().timestamp() + 3600;
+	let limit = json!({ "limit": 5000, "remaining": remaining, "reset": reset, "used": 5000 - remaining });
+	json!({ "resources": { "core": limit, "search": limit }, "rate": limit })
+}
+
+fn user(login: &str) -> Value {
+	let url = format!("https://api.github.com/users/{login}");
+	json!({
+		"login": login,
+		"id": 1,
+		"node_id": "MDQ6VXNlcjE=",
+		"avatar_url": "https://avatars.githubusercontent.com/u/1",
+		"gravatar_id": "",
+		"url": url,
+		"html_url": format!("https://github.com/{login}"),
+		"followers_url": format!("{url}/followers"),
+		"following_url": format!("{url}/following"),
+		"gists_url": format!("{url}/gists"),
+		"starred_url": format!("{url}/starred"),
+		"subscriptions_url": format!("{url}/subscriptions"),
+		"organizations_url": format!("{url}/orgs"),
+		"repos_url": format!("{url}/repos"),
+		"events_url": format!("{url}/events"),
+		"received_events_url": format!("{url}/received_events"),
+		"type": "User",
+		"site_admin": false
+	})
+}
+
+fn issue(package: &str, number: u64, title: &str) -> Value {
+	let url = format!("https://api.github.com/repos/{OWNER}/{package}/issues/{number}");
+	json!({
+		"id": 1,
+		"node_id": "I_1",
+		"url": url,
+		"repository_url": format!("https://api.github.com/repos/{OWNER}/{package}"),
+		"labels_url": format!("{url}/labels{{/name}}"),
+		"comments_url": format!("{url}/comments"),
+		"events_url": format!("{url}/events"),
+		"html_url": format!("https://github.com/{OWNER}/{package}/issues/{number}"),
+		"number": number,
+		"state": "open",
+		"title": title,
+		"body": "",
+		"user": user("pulsar-bot"),
+		"labels": [],
+		"assignees": [],
+		"author_association": "NONE",
+		"locked": false,
+		"comments": 0,
+		"created_at": "2022-08-01T00:00:00Z",
+		"updated_at": "2022-08-01T00:00:00Z"
+	})
+}
+
+fn comment(package: &str, number: u64, login: &str, body: &str) -> Value {
+	json!({
+		"id": 2,
+		"node_id": "IC_2",
+		"url": format!("https://api.github.com/repos/{OWNER}/{package}/issues/comments/2"),
+		"html_url": format!("https://github.com/{OWNER}/{package}/issues/{number}#issuecomment-2"),
+		"body": body,
+		"user": user(login),
+		// after the issue was filed, whenever the test runs
+		"created_at": "2099-01-01T00:00:00Z"
+	})
+}
+
+/// a 403 from github with this message, which is how it says it's rate limiting
+fn forbidden(message: &str) -> ResponseTemplate {
+	ResponseTemplate::new(403)
+		.set_body_json(json!({ "message": message, "documentation_url": "https://docs.github.com/rest" }))
+}
+
+fn states(status: &Value) -> &Value {
+	&status["states"]
+}
+
+#[tokio::test]
+async fn files_issues_and_picks_up_approvals() {
+	let bot = Bot::new("pipeline", &["first-package", "second-package", "third-without-repository"], "").await;
+	for package in ["first-package", "second-package"] {
+		bot.has_package_json(package).await;
+		bot.files_issue(package, ResponseTemplate::new(201).set_body_json(issue(package, 1, "permission request")), 1).await;
+	}
+
+	Mock::given(method("GET")).and(path(format!("/repos/{OWNER}/first-package/license")))
+		.respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "LICENSE", "license": { "key": "mit", "spdx_id": "MIT" } })))
+		.mount(&bot.github).await;
+	Mock::given(method("GET")).and(path(format!("/repos/{OWNER}/first-package")))
+		.respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "first-package", "pushed_at": "2022-06-01T12:00:00Z" })))
+		.mount(&bot.github).await;
+	Mock::given(method("GET")).and(path(format!("/repos/{OWNER}/first-package/releases/latest")))
+		.respond_with(ResponseTemplate::new(200).set_body_json(json!({ "tag_name": "v1.2.0", "published_at": "2022-05-01T12:00:00Z" })))
+		.mount(&bot.github).await;
+
+	bot.run(&["sync"]).await;
+	assert_eq!(states(&bot.json(&["status", "--format", "json"]).await)["New"], 2);
+
+	bot.run(&["file"]).await;
+	let status = bot.json(&["status", "--format", "json"]).await;
+	assert_eq!(states(&status)["IssueFiled"], 2, "{status:#}");
+	let activity = std::fs::read_to_string(bot.dir.join("state.ron.activity.log")).unwrap();
+	assert!(activity.contains(&format!(" filed issue https://github.com/{OWNER}/first-package/issues/1 for package first-package\n")), "{activity}");
+	let last_run = bot.json(&["last-run", "--format", "json"]).await;
+	let actions = last_run["packages"].as_array().unwrap().iter()
+		.map(|result| result["action"].as_str().unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(actions, ["filed", "filed"], "{last_run:#}");
+
+	// a few weeks later, with one maintainer having said yes, and the other nothing yet
+	bot.edit_db(|db| {
+		let mut edited = String::new();
+		let mut rest = db.as_str();
+		while let Some(start) = rest.find("due_at: \"") {
+			let (before, after) = rest.split_at(start + "due_at: \"".len());
+			let end = after.find('"').unwrap();
+			edited.push_str(before);
+			edited.push_str("2000-01-01T00:00:00Z");
+			rest = &after[end..];
+		}
+		edited.push_str(rest);
+		edited
+	});
+	for (package, comments) in [
+		("first-package", json!([comment("first-package", 1, "maintainer", "yes, go ahead")])),
+		("second-package", json!([]))
+	] {
+		Mock::given(method("GET")).and(path(format!("/repos/{OWNER}/{package}/issues/1")))
+			.respond_with(ResponseTemplate::new(200).set_body_json(issue(package, 1, "permission request")))
+			.mount(&bot.github).await;
+		Mock::given(method("GET")).and(path(format!("/repos/{OWNER}/{package}/issues/1/comments")))
+			.respond_with(ResponseTemplate::new(200).set_body_json(comments))
+			.mount(&bot.github).await;
+	}
+	// the one nobody answered gets its follow-up
+	Mock::given(method("POST")).and(path(format!("/repos/{OWNER}/second-package/issues/1/comments")))
+		.respond_with(ResponseTemplate::new(201).set_body_json(comment("second-package", 1, "pulsar-bot", "reminder")))
+		.expect(1)
+		.mount(&bot.github).await;
+
+	bot.run(&["poll"]).await;
+	let status = bot.json(&["status", "--format", "json"]).await;
+	assert_eq!(states(&status)["MaintainerApproved"], 1, "{status:#}");
+	assert_eq!(states(&status)["IssueFiled"], 1, "{status:#}");
+	let activity = std::fs::read_to_string(bot.dir.join("state.ron.activity.log")).unwrap();
+	assert!(activity.contains(" package first-package approved in "), "{activity}");
+	assert!(activity.contains(&format!(" posted final reminder on https://github.com/{OWNER}/second-package/issues/1 for package second-package\n")), "{activity}");
+	let exported = bot.json(&["export", "--license", "mit"]).await;
+	assert_eq!(exported[0]["name"], "first-package", "{exported:#}");
+	assert_eq!(exported[0]["license"], "MIT", "{exported:#}");
+	assert_eq!(exported[0]["pushed_at"], "2022-06-01T12:00:00Z", "{exported:#}");
+	assert_eq!(exported[0]["latest_release"], "v1.2.0", "{exported:#}");
+	let listed = bot.json(&["list", "--format", "json", "--search", "first"]).await;
+	assert_eq!(listed[0]["pushed_at"], "2022-06-01T12:00:00Z", "{listed:#}");
+	assert_eq!(bot.json(&["export", "--license", "none"]).await, json!([]));
+	assert!(bot.json(&["show", "second-package", "--format", "json"]).await["license"]["license"].is_null());
+
+	// the one without a repository isn't stored, but still counts towards the downloads
+	let coverage = &bot.json(&["analytics", "--format", "json"]).await["coverage"];
+	assert_eq!(coverage["overall"]["packages"], 3, "{coverage:#}");
+	assert_eq!(coverage["overall"]["downloads"], 1000 + 999 + 998, "{coverage:#}");
+	assert_eq!(coverage["overall"]["approved"]["downloads"], 1000, "{coverage:#}");
+	let [thousands, hundreds] = [2, 1].map(|bucket| &coverage["by_downloads"][bucket]);
+	assert_eq!(thousands["approved"]["packages"], 1, "{coverage:#}");
+	assert_eq!(hundreds["filed"]["packages"], 1, "{coverage:#}");
+	assert_eq!(hundreds["no_repository"]["packages"], 1, "{coverage:#}");
+}
+
+#[tokio::test]
+async fn rate_limit_cools_the_host_down() {
+	let bot = Bot::new("rate-limit", &["limited-package"], "").await;
+	bot.has_package_json("limited-package").await;
+	bot.files_issue("limited-package", forbidden("API rate limit exceeded for user ID 1."), 1).await;
+
+	bot.run(&["sync"]).await;
+	bot.run(&["file"]).await;
+
+	let status = bot.json(&["status", "--format", "json"]).await;
+	assert_eq!(states(&status)["RetryPending"], 1, "{status:#}");
+	assert!(status["host_cooldowns"]["github.com"].is_string(), "{status:#}");
+	let last_run = bot.json(&["last-run", "--format", "json"]).await;
+	assert_eq!(last_run["packages"][0]["error"]["failure"], "rate_limited", "{last_run:#}");
+}
+
+#[tokio::test]
+async fn secondary_and_abuse_limits_cool_the_host_down() {
+	for (test, message) in [
+		("secondary-limit", "You have exceeded a secondary rate limit. Please wait a few minutes before you try again."),
+		("abuse-limit", "You have triggered an abuse detection mechanism. Please wait a few minutes before you try again.")
+	] {
+		let bot = Bot::new(test, &["busy-package"], "").await;
+		bot.has_package_json("busy-package").await;
+		bot.files_issue("busy-package", forbidden(message).insert_header("retry-after", "60"), 1).await;
+
+		bot.run(&["sync"]).await;
+		bot.run(&["file"]).await;
+
+		let status = bot.json(&["status", "--format", "json"]).await;
+		assert_eq!(states(&status)["RetryPending"], 1, "{test}: {status:#}");
+		assert!(status["host_cooldowns"]["github.com"].is_string(), "{test}: {status:#}");
+	}
+}
+
+#[tokio::test]
+async fn running_out_of_requests_stops_the_run_until_they_reset() {
+	let bot = Bot::new("rate-limit-exhausted", &["limited-package", "later-package"], "").await;
+	for package in ["limited-package", "later-package"] {
+		bot.has_package_json(package).await;
+	}
+	bot.files_issue("limited-package", forbidden("API rate limit exceeded for user ID 1."), 1).await;
+	bot.files_issue("later-package", ResponseTemplate::new(201).set_body_json(issue("later-package", 1, "permission request")), 0).await;
+	Mock::given(method("GET")).and(path("/rate_limit"))
+		.respond_with(ResponseTemplate::new(200).set_body_json(rate_limit(0)))
+		.with_priority(1)
+		.mount(&bot.github).await;
+
+	bot.run(&["sync"]).await;
+	bot.run(&["file"]).await;
+
+	let status = bot.json(&["status", "--format", "json"]).await;
+	assert_eq!(states(&status)["RetryPending"], 1, "{status:#}");
+	assert_eq!(states(&status)["New"], 1, "{status:#}");
+	// until the hourly limit resets, not the usual few minutes
+	let until = status["host_cooldowns"]["github.com"].as_str().unwrap().parse::<chrono::DateTime<chrono::Utc>>().unwrap();
+	assert!(until > chrono::Utc::now() + chrono::Duration::minutes(30), "{status:#}");
+	assert_eq!(bot.json(&["last-run", "--format", "json"]).await["stopped_early"], true);
+}
+
+#[tokio::test]
+async fn failing_host_trips_the_breaker() {
+	let packages = ["package-a", "package-b", "package-c", "package-d"];
+	let bot = Bot::new("breaker", &packages, "[filing]\nbreaker_failures = 2\nrepo_cooldown = 0").await;
+	// every request with the token failing, like it was revoked
+	Mock::given(method("GET")).and(path_regex(r"^/repos/[^/]+/[^/]+/contents/package\.json$"))
+		.respond_with(ResponseTemplate::new(401).set_body_json(json!({ "message": "Bad credentials", "documentation_url": "https://docs.github.com/rest" })))
+		.mount(&bot.github).await;
+
+	bot.run(&["sync"]).await;
+	bot.run(&["file"]).await;
+
+	let status = bot.json(&["status", "--format", "json"]).await;
+	assert!(status["breakers"]["github.com"].is_object(), "{status:#}");
+	// the two it didn't get to are left for after the breaker's reset
+	assert_eq!(states(&status)["New"], 2, "{status:#}");
+	let last_run = bot.json(&["last-run", "--format", "json"]).await;
+	let skipped = last_run["packages"].as_array().unwrap().iter()
+		.filter(|result| result["action"] == "skipped")
+		.count();
+	assert_eq!(skipped, 2, "{last_run:#}");
+}
+
+#[tokio::test]
+async fn failing_backend_check_is_a_failure() {
+	let backend = MockServer::start().await;
+	Mock::given(method("GET")).and(path("/api/packages/checked-package"))
+		.respond_with(ResponseTemplate::new(500))
+		.expect(1..)
+		.mount(&backend).await;
+	let bot = Bot::new("backend", &["checked-package"], &format!("[pulsar]\ncheck_backend = true\nbackend_url = \"{}/api\"", backend.uri())).await;
+	bot.has_package_json("checked-package").await;
+	bot.files_issue("checked-package", ResponseTemplate::new(500), 0).await;
+
+	bot.run(&["sync"]).await;
+	bot.run(&["file"]).await;
+
+	let last_run = bot.json(&["last-run", "--format", "json"]).await;
+	assert_eq!(last_run["packages"][0]["action"], "failed", "{last_run:#}");
+	assert_eq!(states(&bot.json(&["status", "--format", "json"]).await)["RetryPending"], 1);
+}
+
+#[tokio::test]
+async fn failing_canary_stops_the_run_and_gives_the_claim_back() {
+	let bot = Bot::new("canary", &["canaried-package"], "[filing]\ncanary_repo = \"https://github.com/someone/canary\"\n[claims]\ndir = \"claims\"").await;
+	bot.has_package_json("canaried-package").await;
+	Mock::given(method("POST")).and(path(format!("/repos/{OWNER}/canary/issues")))
+		.respond_with(ResponseTemplate::new(500).set_body_json(json!({ "message": "Server Error" })))
+		.expect(1..)
+		.mount(&bot.github).await;
+	bot.files_issue("canaried-package", ResponseTemplate::new(500), 0).await;
+
+	bot.run(&["sync"]).await;
+	let output = bot.output(&["file"]).await;
+	assert!(!output.status.success());
+	assert!(String::from_utf8_lossy(&output.stderr).contains("error filing canary issue for package canaried-package"));
+
+	// so another worker (or the next run) can have a go
+	let claims = std::fs::read_dir(bot.dir.join("claims")).unwrap().count();
+	assert_eq!(claims, 0);
+	let last_run = bot.json(&["last-run", "--format", "json"]).await;
+	assert_eq!(last_run["packages"][0]["action"], "failed", "{last_run:#}");
+	assert_eq!(last_run["stopped_early"], true, "{last_run:#}");
+}
+
+#[tokio::test]
+async fn looking_before_the_first_sync_doesnt_make_a_database() {
+	let bot = Bot::new("no-state", &[], "").await;
+	let output = bot.run(&["status"]).await;
+	assert!(String::from_utf8_lossy(&output.stdout).contains("no state yet"));
+	assert_eq!(bot.json(&["list", "--format", "json"]).await, json!([]));
+	assert!(!bot.dir.join("state.ron").exists());
+}
+
+#[tokio::test]
+async fn only_commands_that_save_the_database_move_a_corrupt_one_aside() {
+	let bot = Bot::new("corrupt", &["first-package"], "").await;
+	std::fs::write(bot.dir.join("state.ron"), "(packages: [oops").unwrap();
+	let corrupt = || std::fs::read_dir(&bot.dir).unwrap()
+		.filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().starts_with("state.ron.corrupt-"))
+		.count();
+
+	for args in [&["status"][..], &["list"], &["show", "first-package"], &["plan"]] {
+		let output = bot.output(args).await;
+		assert!(!output.status.success(), "`{}` worked on a corrupt database", args.join(" "));
+		assert!(String::from_utf8_lossy(&output.stderr).contains("error parsing ron in file state.ron"));
+	}
+	assert_eq!(std::fs::read_to_string(bot.dir.join("state.ron")).unwrap(), "(packages: [oops");
+	assert_eq!(corrupt(), 0);
+
+	bot.run(&["sync"]).await;
+	assert_eq!(corrupt(), 1);
+	assert_eq!(states(&bot.json(&["status", "--format", "json"]).await)["New"], 1);
+}
+
+#[tokio::test]
+async fn replays_recorded_responses() {
+	let bot = Bot::new("replay", &["replayed-package"], "").await;
+	bot.has_package_json("replayed-package").await;
+	// only the recording files it
+	bot.files_issue("replayed-package", ResponseTemplate::new(201).set_body_json(issue("replayed-package", 1, "permission request")), 1).await;
+
+	bot.run(&["--record", "sync-responses", "sync"]).await;
+	bot.run(&["--record", "file-responses", "file"]).await;
+	let recorded = bot.json(&["last-run", "--format", "json"]).await;
+
+	bot.forget_db();
+	bot.run(&["--replay", "sync-responses", "sync"]).await;
+	bot.run(&["--replay", "file-responses", "file"]).await;
+	let replayed = bot.json(&["last-run", "--format", "json"]).await;
+	assert_eq!(replayed["packages"][0]["action"], "filed", "{replayed:#}");
+	assert_eq!(replayed["packages"][0]["issue_url"], recorded["packages"][0]["issue_url"], "{replayed:#}");
+}
+
+#[tokio::test]
+async fn sandbox_files_in_the_org() {
+	let bot = Bot::new("sandbox", &["sandboxed-package"], "").await;
+	bot.has_package_json("sandboxed-package").await;
+	Mock::given(method("POST")).and(path(format!("/repos/{OWNER}/sandboxed-package/issues")))
+		.respond_with(ResponseTemplate::new(500))
+		.expect(0)
+		.mount(&bot.github).await;
+
+	let sandbox = "sandbox-org/someone-sandboxed-package";
+	Mock::given(method("GET")).and(path(format!("/repos/{sandbox}")))
+		.respond_with(ResponseTemplate::new(404).set_body_json(json!({ "message": "Not Found" })))
+		.mount(&bot.github).await;
+	Mock::given(method("POST")).and(path("/orgs/sandbox-org/repos"))
+		.respond_with(ResponseTemplate::new(201).set_body_json(json!({ "full_name": sandbox })))
+		.expect(1)
+		.mount(&bot.github).await;
+	Mock::given(method("GET")).and(path(format!("/repos/{sandbox}/issues")))
+		.respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+		.mount(&bot.github).await;
+	let mut filed = issue("sandboxed-package", 1, "permission request");
+	filed["html_url"] = json!(format!("https://github.com/{sandbox}/issues/1"));
+	Mock::given(method("POST")).and(path(format!("/repos/{sandbox}/issues")))
+		.respond_with(ResponseTemplate::new(201).set_body_json(filed))
+		.expect(1)
+		.mount(&bot.github).await;
+
+	bot.run(&["--sandbox", "sandbox-org", "sync"]).await;
+	bot.run(&["--sandbox", "sandbox-org", "file"]).await;
+
+	let last_run = bot.json(&["--sandbox", "sandbox-org", "last-run", "--format", "json"]).await;
+	assert_eq!(last_run["packages"][0]["issue_url"], format!("https://github.com/{sandbox}/issues/1"), "{last_run:#}");
+	// the real database is left alone
+	assert!(bot.dir.join("state.ron.sandbox").exists());
+	assert!(!bot.dir.join("state.ron").exists());
+}
+
+#[tokio::test]
+async fn template_variant_is_kept_with_the_issue() {
+	let bot = Bot::new("variants", &["varied-package"], "[template_variants]\nfriendly = \"friendly\"").await;
+	let title = include_str!("../resources/issue_template.md").lines().next().unwrap();
+	std::fs::create_dir_all(bot.dir.join("friendly")).unwrap();
+	std::fs::write(bot.dir.join("friendly/issue_template.md"), format!("{title}\n\nhello! could {{{{#each packages}}}}`{{{{this}}}}` {{{{/each}}}}be moved over?")).unwrap();
+	bot.has_package_json("varied-package").await;
+	Mock::given(method("GET")).and(path(format!("/repos/{OWNER}/varied-package/issues")))
+		.respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+		.mount(&bot.github).await;
+	Mock::given(method("POST")).and(path(format!("/repos/{OWNER}/varied-package/issues")))
+		.and(body_string_contains("could `varied-package` be moved over?"))
+		.respond_with(ResponseTemplate::new(201).set_body_json(issue("varied-package", 1, title)))
+		.expect(1)
+		.mount(&bot.github).await;
+
+	bot.run(&["sync"]).await;
+	bot.run(&["file"]).await;
+
+	let shown = bot.json(&["show", "varied-package", "--format", "json"]).await;
+	assert_eq!(shown["state"]["IssueFiled"]["template_variant"], "friendly", "{shown:#}");
+	let analytics = bot.json(&["analytics", "--format", "json"]).await;
+	assert_eq!(analytics["by_template_variant"][0]["bucket"], "template variant friendly", "{analytics:#}");
+	assert_eq!(analytics["by_template_variant"][0]["no_response"], 1, "{analytics:#}");
+}
+
+#[tokio::test]
+async fn forgotten_owners_stay_forgotten() {
+	let bot = Bot::new("forget", &["first-package"], "").await;
+	let email = format!("{OWNER}@example.com");
+	let db = || std::fs::read_to_string(bot.dir.join("state.ron")).unwrap();
+
+	bot.run(&["sync"]).await;
+	assert!(db().contains(&email));
+
+	let output = bot.run(&["forget", "--owner", &OWNER.to_uppercase()]).await;
+	assert!(String::from_utf8_lossy(&output.stdout).contains("first-package: package.json author, owner's name, owner's email"));
+	assert!(!db().contains(&email));
+	assert_eq!(states(&bot.json(&["status", "--format", "json"]).await)["New"], 1);
+
+	// the registry still has them, but they aren't stored again when the package is updated
+	bot.edit_db(|db| db.replace("downloads: 1000,", "downloads: 1,"));
+	bot.run(&["sync", "--full"]).await;
+	assert!(db().contains("downloads: 1000,"));
+	assert!(!db().contains(&email));
+}
+
+#[tokio::test]
+async fn imports_issues_filed_by_hand() {
+	let bot = Bot::new("import", &["hand-filed", "found-by-title"], "").await;
+	for (package, number, title, comments) in [
+		("hand-filed", 7, "can this go on pulsar?", json!([comment("hand-filed", 7, "maintainer", "yes, go ahead")])),
+		("found-by-title", 3, "Moving found-by-title to Pulsar", json!([]))
+	] {
+		Mock::given(method("GET")).and(path(format!("/repos/{OWNER}/{package}/issues/{number}")))
+			.respond_with(ResponseTemplate::new(200).set_body_json(issue(package, number, title)))
+			.mount(&bot.github).await;
+		Mock::given(method("GET")).and(path(format!("/repos/{OWNER}/{package}/issues/{number}/comments")))
+			.respond_with(ResponseTemplate::new(200).set_body_json(comments))
+			.mount(&bot.github).await;
+	}
+	Mock::given(method("GET")).and(path(format!("/repos/{OWNER}/found-by-title/issues")))
+		.respond_with(ResponseTemplate::new(200).set_body_json(json!([issue("found-by-title", 3, "Moving found-by-title to Pulsar")])))
+		.mount(&bot.github).await;
+
+	bot.run(&["sync"]).await;
+	bot.run(&["import", &format!("https://github.com/{OWNER}/hand-filed/issues/7#issuecomment-2")]).await;
+	let shown = bot.json(&["show", "hand-filed", "--format", "json"]).await;
+	assert!(shown["state"]["MaintainerApproved"].is_object(), "{shown:#}");
+
+	bot.run(&["import", "--owner", &OWNER.to_uppercase(), "--title", "Moving {{package}} to Pulsar"]).await;
+	let shown = bot.json(&["show", "found-by-title", "--format", "json"]).await;
+	let filed = &shown["state"]["IssueFiled"];
+	assert_eq!(filed["issue_url"], format!("https://github.com/{OWNER}/found-by-title/issues/3"), "{shown:#}");
+	// when it was opened, so its follow-up is due
+	assert_eq!(filed["filed_at"], "2022-08-01T00:00:00Z", "{shown:#}");
+	// the one that was approved already isn't looked at again
+	assert!(bot.json(&["show", "hand-filed", "--format", "json"]).await["state"]["MaintainerApproved"].is_object());
+}
+
+#[tokio::test]
+async fn renamed_packages_carry_on_where_they_were() {
+	let bot = Bot::new("rename", &["old-name", "sibling"], "").await;
+	bot.run(&["sync"]).await;
+	bot.run(&["mark", "old-name", "OptedOut", "--note", "said no by email"]).await;
+
+	// old-name is gone, and new-name is on its repository
+	bot.registry_has(&[("new-name", "old-name"), ("sibling", "sibling")]).await;
+	bot.run(&["sync", "--full"]).await;
+	let shown = bot.json(&["show", "new-name", "--format", "json"]).await;
+	assert!(shown["state"]["OptedOut"].is_object(), "{shown:#}");
+	assert_eq!(shown["previous_names"], json!(["old-name"]), "{shown:#}");
+	let status = bot.json(&["status", "--format", "json"]).await;
+	assert_eq!(states(&status)["OptedOut"], 1, "{status:#}");
+	assert_eq!(states(&status)["New"], 1, "{status:#}");
+}
+
+#[tokio::test]
+async fn responses_are_polled_on_their_own_budget() {
+	let packages = ["first-package", "second-package"];
+	let bot = Bot::new("responses", &packages, "[responses]\nmax_issues = 1").await;
+	for package in packages {
+		bot.has_package_json(package).await;
+		bot.files_issue(package, ResponseTemplate::new(201).set_body_json(issue(package, 1, "permission request")), 1).await;
+		Mock::given(method("GET")).and(path(format!("/repos/{OWNER}/{package}/issues/1")))
+			.respond_with(ResponseTemplate::new(200).set_body_json(issue(package, 1, "permission request")))
+			.mount(&bot.github).await;
+		Mock::given(method("GET")).and(path(format!("/repos/{OWNER}/{package}/issues/1/comments")))
+			.respond_with(ResponseTemplate::new(200).set_body_json(json!([comment(package, 1, "maintainer", "yes, go ahead")])))
+			.mount(&bot.github).await;
+	}
+	bot.run(&["sync"]).await;
+	bot.run(&["file"]).await;
+
+	// long before any reminders are due, one issue a run
+	bot.run(&["poll-responses"]).await;
+	let status = bot.json(&["status", "--format", "json"]).await;
+	assert_eq!(states(&status)["MaintainerApproved"], 1, "{status:#}");
+	bot.run(&["poll-responses"]).await;
+	let status = bot.json(&["status", "--format", "json"]).await;
+	assert_eq!(states(&status)["MaintainerApproved"], 2, "{status:#}");
+}
+
+#[tokio::test]
+async fn owners_that_declined_arent_asked_again() {
+	let bot = Bot::new("declined-owner", &["first-package", "second-package"], "[eligibility]\nskip_declined_owners = true").await;
+	bot.files_issue("second-package", ResponseTemplate::new(201).set_body_json(issue("second-package", 1, "permission request")), 0).await;
+	bot.run(&["sync"]).await;
+	bot.run(&["mark", "first-package", "OptedOut", "--note", "said no by email"]).await;
+
+	bot.run(&["file"]).await;
+	let shown = bot.json(&["show", "second-package", "--format", "json"]).await;
+	assert!(shown["state"]["DeclinedByOwnerPolicy"]["reason"].as_str().unwrap().contains("first-package"), "{shown:#}");
+}
+
+#[tokio::test]
+async fn bad_tokens_stop_runs_before_anything_is_filed() {
+	let bot = Bot::new("bad-token", &["first-package"], "").await;
+	bot.run(&["sync"]).await;
+	bot.github.reset().await;
+	Mock::given(method("GET")).and(path("/user"))
+		.respond_with(ResponseTemplate::new(401).set_body_json(json!({
+			"message": "Bad credentials",
+			"documentation_url": "https://docs.github.com/rest"
+		})))
+		.mount(&bot.github).await;
+	bot.files_issue("first-package", ResponseTemplate::new(201), 0).await;
+
+	let output = bot.output(&["file"]).await;
+	assert!(!output.status.success());
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	assert!(stderr.contains("the token for github.com (github) was turned down"), "{stderr}");
+	assert_eq!(states(&bot.json(&["status", "--format", "json"]).await)["New"], 1);
+}
+
+#[tokio::test]
+async fn graphs_owners_repositories_and_packages() {
+	let bot = Bot::new("graph", &[], "").await;
+	bot.registry_has(&[("mono-a", "monorepo"), ("mono-b", "monorepo"), ("solo", "solo")]).await;
+	bot.run(&["sync"]).await;
+
+	let graph = bot.json(&["graph", "--format", "json"]).await;
+	let owner = &graph["owners"][0];
+	assert_eq!(owner["owner"], OWNER, "{graph:#}");
+	assert_eq!(owner["packages"], 3, "{graph:#}");
+	let repositories = owner["repositories"].as_array().unwrap();
+	let monorepo = repositories.iter().find(|repo| repo["name"] == "monorepo").unwrap();
+	assert_eq!(monorepo["grouped"], true, "{graph:#}");
+	assert_eq!(monorepo["packages"].as_array().unwrap().len(), 2, "{graph:#}");
+	let solo = repositories.iter().find(|repo| repo["name"] == "solo").unwrap();
+	assert_eq!(solo["grouped"], false, "{graph:#}");
+	assert_eq!(bot.json(&["graph", "--format", "json", "--min-packages", "4"]).await["owners"], json!([]));
+
+	let dot = String::from_utf8(bot.run(&["graph"]).await.stdout).unwrap();
+	assert!(dot.starts_with("digraph packages {"), "{dot}");
+	assert!(dot.contains(&format!("subgraph \"cluster:github.com/{OWNER}/monorepo\"")), "{dot}");
+	assert!(dot.contains("\"package:solo\" [label=\"solo\\nNew, "), "{dot}");
+}
+
+#[tokio::test]
+async fn archives_everything_with_a_manifest() {
+	let bot = Bot::new("archive", &["archived-package"], "").await;
+	bot.has_package_json("archived-package").await;
+	bot.files_issue("archived-package", ResponseTemplate::new(201).set_body_json(issue("archived-package", 1, "permission request")), 1).await;
+	bot.run(&["sync"]).await;
+	bot.run(&["file"]).await;
+	bot.run(&["archive", "-o", "campaign.tar.gz"]).await;
+
+	let archive = std::fs::File::open(bot.dir.join("campaign.tar.gz")).unwrap();
+	let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+	let mut files = std::collections::BTreeMap::new();
+	for entry in archive.entries().unwrap() {
+		let mut entry = entry.unwrap();
+		let path = entry.path().unwrap().to_string_lossy().into_owned();
+		let mut contents = String::new();
+		std::io::Read::read_to_string(&mut entry, &mut contents).unwrap();
+		let (dir, path) = path.split_once('/').unwrap();
+		assert!(dir.starts_with("pulsar-migration-"), "{dir}");
+		files.insert(path.to_string(), contents);
+	}
+
+	let manifest = serde_json::from_str::<Value>(&files["manifest.json"]).unwrap();
+	assert_eq!(manifest["packages"]["IssueFiled"], 1, "{manifest:#}");
+	let listed = manifest["files"].as_array().unwrap().iter()
+		.map(|file| file["path"].as_str().unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(listed.len(), files.len() - 1, "{manifest:#}");
+	for path in ["state.ron", "runs.json", "activity.log", "analytics.txt", "analytics.json"] {
+		assert!(listed.contains(&path), "{path} isn't in {manifest:#}");
+	}
+	assert!(listed.iter().any(|path| path.starts_with("reports/run-")), "{manifest:#}");
+	assert!(files["activity.log"].contains("filed issue"), "{}", files["activity.log"]);
+	let analytics = serde_json::from_str::<Value>(&files["analytics.json"]).unwrap();
+	assert_eq!(analytics["overall"]["packages"], 1, "{analytics:#}");
+}
+
+#[tokio::test]
+async fn dormant_repositories_are_asked_last() {
+	let bot = Bot::new("dormant", &["active-package", "dormant-package"], "[eligibility]\ndormant_after_years = 2\ndormant = \"last\"").await;
+	let pushed = [("active-package", chrono::Utc::now().to_rfc3339()), ("dormant-package", "2015-01-01T00:00:00Z".into())];
+	for (package, pushed_at) in pushed {
+		bot.has_package_json(package).await;
+		bot.files_issue(package, ResponseTemplate::new(201).set_body_json(issue(package, 1, "permission request")), 1).await;
+		Mock::given(method("GET")).and(path(format!("/repos/{OWNER}/{package}")))
+			.respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": package, "pushed_at": pushed_at })))
+			.mount(&bot.github).await;
+	}
+
+	bot.run(&["sync"]).await;
+	bot.run(&["file"]).await;
+	let status = bot.json(&["status", "--format", "json"]).await;
+	assert_eq!(states(&status)["IssueFiled"], 1, "{status:#}");
+	assert_eq!(states(&status)["Dormant"], 1, "{status:#}");
+	let shown = bot.json(&["show", "dormant-package", "--format", "json"]).await;
+	assert!(shown["state"]["Dormant"]["reason"].as_str().unwrap().contains("since 2015-01-01"), "{shown:#}");
+
+	// once there's nothing else, it's asked after all
+	bot.run(&["file"]).await;
+	assert_eq!(states(&bot.json(&["status", "--format", "json"]).await)["IssueFiled"], 2);
+}
+
+#[tokio::test]
+async fn replays_webhook_deliveries_that_werent_handled() {
+	let bot = Bot::new("replay-webhooks", &["hooked-package"], "").await;
+	bot.has_package_json("hooked-package").await;
+	bot.files_issue("hooked-package", ResponseTemplate::new(201).set_body_json(issue("hooked-package", 1, "permission request")), 1).await;
+	bot.run(&["sync"]).await;
+	bot.run(&["file"]).await;
+
+	// received, then the bot was stopped before handling them
+	let issue_url = format!("https://github.com/{OWNER}/hooked-package/issues/1");
+	let maintainer = json!({ "login": OWNER, "type": "User" });
+	let event = json!({
+		"action": "created",
+		"issue": { "html_url": issue_url },
+		"comment": {
+			"html_url": format!("{issue_url}#issuecomment-1"),
+			"body": "yes, go ahead",
+			"created_at": chrono::Utc::now().to_rfc3339(),
+			"user": maintainer
+		},
+		"sender": maintainer
+	});
+	let deliveries = bot.dir.join("state.ron.webhooks");
+	std::fs::create_dir_all(&deliveries).unwrap();
+	for (id, body) in [("1-approval", event.to_string()), ("2-garbled", "{".to_string())] {
+		let delivery = json!({ "id": id, "kind": "issue_comment", "received_at": chrono::Utc::now().to_rfc3339(), "body": body });
+		std::fs::write(deliveries.join(format!("{id}.json")), delivery.to_string()).unwrap();
+	}
+
+	let listed = String::from_utf8(bot.run(&["replay-webhooks", "--dry-run"]).await.stdout).unwrap();
+	assert_eq!(listed.matches("issue_comment from").count(), 2, "{listed}");
+	assert_eq!(states(&bot.json(&["status", "--format", "json"]).await)["IssueFiled"], 1);
+
+	// the one that can't be handled is kept, and says so
+	assert!(!bot.output(&["replay-webhooks"]).await.status.success());
+	assert_eq!(states(&bot.json(&["status", "--format", "json"]).await)["MaintainerApproved"], 1);
+	assert!(!deliveries.join("1-approval.json").exists());
+	let garbled = serde_json::from_str::<Value>(&std::fs::read_to_string(deliveries.join("2-garbled.json")).unwrap()).unwrap();
+	assert_eq!(garbled["attempts"], 1, "{garbled:#}");
+	assert!(garbled["last_error"].as_str().unwrap().contains("can't read the event"), "{garbled:#}");
+}